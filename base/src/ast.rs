@@ -1,6 +1,7 @@
 //! Module containing the types which make up `gluon`'s AST (Abstract Syntax Tree)
 use std::fmt;
 use std::marker::PhantomData;
+use std::mem;
 use std::ops::{Deref, DerefMut};
 
 use pos::{self, BytePos, HasSpan, Span, Spanned};
@@ -205,6 +206,15 @@ pub struct PatternField<Id, P> {
     pub value: Option<P>,
 }
 
+/// The end of a string which is matched by a `Pattern::StringAffix`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Affix {
+    /// `"GET " ++ rest`
+    Prefix,
+    /// `rest ++ ".glu"`
+    Suffix,
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum Pattern<Id> {
     /// An as-pattern, eg. `option @ { monoid, functor }`
@@ -227,6 +237,16 @@ pub enum Pattern<Id> {
     },
     /// A literal pattern
     Literal(Literal),
+    /// String pattern which matches strings starting (or ending) with `literal` and binds the rest
+    /// of the string to `rest`, eg: `"GET " ++ path`
+    StringAffix {
+        affix: Affix,
+        literal: String,
+        rest: TypedIdent<Id>,
+    },
+    /// Or-pattern which matches if any of its alternatives match, eg: `Ok x | Missing x`. Every
+    /// alternative binds the same variables
+    Or(Vec<SpannedPattern<Id>>),
     /// An invalid pattern
     Error,
 }
@@ -234,6 +254,8 @@ pub enum Pattern<Id> {
 #[derive(Clone, PartialEq, Debug)]
 pub struct Alternative<Id> {
     pub pattern: SpannedPattern<Id>,
+    /// Boolean expression which must hold for the alternative to be taken, eg: `| x if x > 0 -> ..`
+    pub guard: Option<SpannedExpr<Id>>,
     pub expr: SpannedExpr<Id>,
 }
 
@@ -479,6 +501,9 @@ pub fn walk_mut_expr<'a, V: ?Sized + MutVisitor<'a>>(v: &mut V, e: &'a mut Spann
             v.visit_expr(expr);
             for alt in alts {
                 v.visit_pattern(&mut alt.pattern);
+                if let Some(ref mut guard) = alt.guard {
+                    v.visit_expr(guard);
+                }
                 v.visit_expr(&mut alt.expr);
             }
         }
@@ -589,7 +614,12 @@ pub fn walk_mut_pattern<'a, V: ?Sized + MutVisitor<'a>>(v: &mut V, p: &'a mut Pa
                 v.visit_pattern(elem);
             }
         }
-        Pattern::Ident(ref mut id) => v.visit_ident(id),
+        Pattern::Or(ref mut alternatives) => for alternative in alternatives {
+            v.visit_pattern(alternative);
+        },
+        Pattern::Ident(ref mut id) | Pattern::StringAffix { rest: ref mut id, .. } => {
+            v.visit_ident(id)
+        }
         Pattern::Literal(_) | Pattern::Error => (),
     }
 }
@@ -701,6 +731,9 @@ pub fn walk_expr<'a, V: ?Sized + Visitor<'a>>(v: &mut V, e: &'a SpannedExpr<V::I
             v.visit_expr(expr);
             for alt in alts {
                 v.visit_pattern(&alt.pattern);
+                if let Some(ref guard) = alt.guard {
+                    v.visit_expr(guard);
+                }
                 v.visit_expr(&alt.expr);
             }
         }
@@ -780,7 +813,12 @@ pub fn walk_pattern<'a, V: ?Sized + Visitor<'a>>(v: &mut V, p: &'a Pattern<V::Id
                 v.visit_pattern(elem);
             }
         }
-        Pattern::Ident(ref id) => v.visit_typ(&id.typ),
+        Pattern::Or(ref alternatives) => for alternative in alternatives {
+            v.visit_pattern(alternative);
+        },
+        Pattern::Ident(ref id) | Pattern::StringAffix { rest: ref id, .. } => {
+            v.visit_typ(&id.typ)
+        }
         Pattern::Literal(_) | Pattern::Error => (),
     }
 }
@@ -864,8 +902,10 @@ impl Typed for Pattern<Symbol> {
             Pattern::Record { ref typ, .. } => Ok(typ.clone()),
             Pattern::Tuple { ref typ, .. } => Ok(typ.clone()),
             Pattern::Constructor(ref id, ref args) => get_return_type(env, &id.typ, args.len()),
+            Pattern::Or(ref alternatives) => alternatives[0].try_type_of(env),
             Pattern::Error => Ok(Type::hole()),
             Pattern::Literal(ref l) => l.try_type_of(env),
+            Pattern::StringAffix { .. } => Ok(Type::string()),
         }
     }
 }
@@ -928,6 +968,210 @@ pub fn is_constructor(s: &str) -> bool {
         .starts_with(char::is_uppercase)
 }
 
+/// Returns `true` if the bindings of a `let` are allowed to refer to each other (and themselves).
+///
+/// Functions may always be recursive. Bindings without arguments only take part if they are
+/// constructed by a data constructor or a record literal and every reference to the group is
+/// under a lambda (or an application of `lazy`), as the recursive references are then not
+/// evaluated until the constructed value is inspected. Cycles such as `let x = x` or
+/// `let xs = Cons 1 xs` are never recursive.
+pub fn is_recursive_binding_group<Id>(bindings: &[ValueBinding<Id>]) -> bool
+where
+    Id: AsRef<str>,
+{
+    let names: Vec<&str> = bindings
+        .iter()
+        .filter_map(|bind| match bind.name.value {
+            Pattern::Ident(ref id) => Some(id.name.as_ref()),
+            _ => None,
+        })
+        .collect();
+    bindings.iter().all(|bind| {
+        !bind.args.is_empty() || match bind.name.value {
+            Pattern::Ident(ref id) => {
+                let references = references(&bind.expr, &names);
+                !is_constructor(id.name.as_ref()) && is_guarded_by_constructor(&bind.expr)
+                    && references.any && !references.unguarded
+            }
+            _ => false,
+        }
+    })
+}
+
+fn is_guarded_by_constructor<Id>(expr: &SpannedExpr<Id>) -> bool
+where
+    Id: AsRef<str>,
+{
+    match expr.value {
+        Expr::App { ref func, .. } => match func.value {
+            Expr::Ident(ref id) => is_constructor(id.name.as_ref()),
+            _ => false,
+        },
+        Expr::Record { .. } => true,
+        Expr::Tuple { ref elems, .. } if elems.len() == 1 => is_guarded_by_constructor(&elems[0]),
+        _ => false,
+    }
+}
+
+struct References {
+    /// `true` if any of the names are referred to
+    any: bool,
+    /// `true` if any of the names are referred to outside of a lambda or `lazy`, meaning that the
+    /// reference is evaluated along with the expression itself
+    unguarded: bool,
+}
+
+/// Finds the references to `names` in `expr` which are not shadowed by a binding inside `expr`
+fn references<Id>(expr: &SpannedExpr<Id>, names: &[&str]) -> References
+where
+    Id: AsRef<str>,
+{
+    struct RefersTo<'a, 'n, Id: 'a> {
+        names: &'n [&'n str],
+        shadowed: Vec<&'a str>,
+        guarded: bool,
+        references: References,
+        _marker: PhantomData<Id>,
+    }
+
+    impl<'a, 'n, Id> RefersTo<'a, 'n, Id>
+    where
+        Id: AsRef<str> + 'a,
+    {
+        fn refers_to(&mut self, name: &Id) {
+            let name = name.as_ref();
+            if self.names.contains(&name) && !self.shadowed.contains(&name) {
+                self.references.any = true;
+                self.references.unguarded |= !self.guarded;
+            }
+        }
+
+        fn visit_guarded<F>(&mut self, f: F)
+        where
+            F: FnOnce(&mut Self),
+        {
+            let guarded = self.guarded;
+            self.guarded = true;
+            f(self);
+            self.guarded = guarded;
+        }
+
+        fn visit_scoped<F>(&mut self, f: F)
+        where
+            F: FnOnce(&mut Self),
+        {
+            let len = self.shadowed.len();
+            f(self);
+            self.shadowed.truncate(len);
+        }
+    }
+
+    impl<'a, 'n, Id> Visitor<'a> for RefersTo<'a, 'n, Id>
+    where
+        Id: AsRef<str> + 'a,
+    {
+        type Ident = Id;
+
+        fn visit_expr(&mut self, expr: &'a SpannedExpr<Id>) {
+            match expr.value {
+                Expr::Ident(ref id) => self.refers_to(&id.name),
+                Expr::Record { ref exprs, .. } => {
+                    for field in exprs.iter().filter(|field| field.value.is_none()) {
+                        self.refers_to(&field.name.value);
+                    }
+                    walk_expr(self, expr)
+                }
+                Expr::Lambda(ref lambda) => self.visit_guarded(|this| {
+                    this.visit_scoped(|this| {
+                        this.shadowed
+                            .extend(lambda.args.iter().map(|arg| arg.name.value.name.as_ref()));
+                        this.visit_expr(&lambda.body);
+                    })
+                }),
+                Expr::App {
+                    ref func, ref args, ..
+                } => match func.value {
+                    Expr::Ident(ref id) if id.name.as_ref().rsplit('.').next() == Some("lazy") => {
+                        self.visit_expr(func);
+                        self.visit_guarded(|this| {
+                            for arg in args {
+                                this.visit_expr(arg);
+                            }
+                        });
+                    }
+                    _ => walk_expr(self, expr),
+                },
+                Expr::LetBindings(ref bindings, ref body) => self.visit_scoped(|this| {
+                    for bind in bindings {
+                        this.visit_pattern(&bind.name);
+                    }
+                    for bind in bindings {
+                        let guarded = this.guarded || !bind.args.is_empty();
+                        let outer_guarded = mem::replace(&mut this.guarded, guarded);
+                        this.visit_scoped(|this| {
+                            this.shadowed
+                                .extend(bind.args.iter().map(|arg| arg.name.value.name.as_ref()));
+                            this.visit_expr(&bind.expr);
+                        });
+                        this.guarded = outer_guarded;
+                    }
+                    this.visit_expr(body);
+                }),
+                Expr::Match(ref scrutinee, ref alts) => {
+                    self.visit_expr(scrutinee);
+                    for alt in alts {
+                        self.visit_scoped(|this| {
+                            this.visit_pattern(&alt.pattern);
+                            if let Some(ref guard) = alt.guard {
+                                this.visit_expr(guard);
+                            }
+                            this.visit_expr(&alt.expr);
+                        });
+                    }
+                }
+                Expr::Do(ref do_expr) => {
+                    self.visit_expr(&do_expr.bound);
+                    self.visit_scoped(|this| {
+                        this.shadowed.push(do_expr.id.value.name.as_ref());
+                        this.visit_expr(&do_expr.body);
+                    });
+                }
+                _ => walk_expr(self, expr),
+            }
+        }
+
+        fn visit_pattern(&mut self, pattern: &'a SpannedPattern<Id>) {
+            match pattern.value {
+                Pattern::As(ref id, _) => self.shadowed.push(id.as_ref()),
+                Pattern::Ident(ref id) | Pattern::StringAffix { rest: ref id, .. } => {
+                    self.shadowed.push(id.name.as_ref())
+                }
+                Pattern::Record { ref fields, .. } => self.shadowed.extend(
+                    fields
+                        .iter()
+                        .filter(|field| field.value.is_none())
+                        .map(|field| field.name.value.as_ref()),
+                ),
+                _ => (),
+            }
+            walk_pattern(self, &pattern.value)
+        }
+    }
+
+    let mut visitor = RefersTo {
+        names,
+        shadowed: Vec::new(),
+        guarded: false,
+        references: References {
+            any: false,
+            unguarded: false,
+        },
+        _marker: PhantomData,
+    };
+    visitor.visit_expr(expr);
+    visitor.references
+}
+
 pub fn expr_to_path(expr: &SpannedExpr<Symbol>, path: &mut String) -> Result<(), &'static str> {
     match expr.value {
         Expr::Ident(ref id) => {
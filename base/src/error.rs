@@ -113,7 +113,11 @@ impl<'a, T> IntoIterator for &'a mut Errors<T> {
 impl<T: fmt::Display> fmt::Display for Errors<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for (i, error) in self.errors.iter().enumerate() {
-            write!(f, "{}", error)?;
+            if f.alternate() {
+                write!(f, "{:#}", error)?;
+            } else {
+                write!(f, "{}", error)?;
+            }
             // Errors are assumed to not have a newline at the end so we add one to keep errors on
             // separate lines and one to space them out
             if i + 1 != self.errors.len() {
@@ -135,6 +139,8 @@ impl<T: fmt::Display + fmt::Debug + Any> StdError for Errors<T> {
 struct SourceContext<E> {
     line: String,
     error: Spanned<E, Location>,
+    /// Secondary labels pointing to other places in the source which are relevant to the error
+    notes: Vec<SourceContext<String>>,
 }
 
 impl<E> SourceContext<E>
@@ -156,8 +162,25 @@ where
         SourceContext {
             line: line.to_string(),
             error: spanned2(start, end, error.value),
+            notes: Vec::new(),
         }
     }
+
+    fn fmt_line(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Span { start, end, .. } = self.error.span;
+
+        writeln!(f, "{}", self.line)?;
+        for _ in 0..start.column.to_usize() {
+            write!(f, " ")?;
+        }
+
+        write!(f, "^")?;
+        for _ in (start.column.to_usize() + 1)..end.column.to_usize() {
+            write!(f, "~")?;
+        }
+
+        writeln!(f)
+    }
 }
 
 /// Error type which contains information of which file and where in the file the error occurred
@@ -192,23 +215,58 @@ impl<E: fmt::Display> InFile<E> {
     }
 }
 
+impl<E: fmt::Display, H: fmt::Display> InFile<Help<E, H>> {
+    /// Creates a new `InFile` error like `InFile::new` but which also shows the source of the
+    /// notes attached to each error
+    pub fn with_notes(
+        source_name: &str,
+        source: &str,
+        error: Errors<Spanned<Help<E, H>, BytePos>>,
+    ) -> InFile<Help<E, H>> {
+        let source = Source::new(source);
+
+        InFile {
+            source_name: source_name.to_string(),
+            error: Errors {
+                errors: error
+                    .errors
+                    .into_iter()
+                    .map(|error| {
+                        let notes = error
+                            .value
+                            .notes()
+                            .iter()
+                            .map(|note| SourceContext::new(&source, note.clone()))
+                            .collect();
+                        SourceContext {
+                            notes,
+                            ..SourceContext::new(&source, error)
+                        }
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
 impl<E: fmt::Display> fmt::Display for InFile<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for error in &self.error.errors {
-            let Span { start, end, .. } = error.error.span;
-
-            write!(f, "{}:{}\n{}\n", self.source_name, error.error, error.line)?;
-
-            for _ in 0..start.column.to_usize() {
-                write!(f, " ")?;
+            if f.alternate() {
+                writeln!(f, "{}:{:#}", self.source_name, error.error)?;
+            } else {
+                writeln!(f, "{}:{}", self.source_name, error.error)?;
             }
-
-            write!(f, "^")?;
-            for _ in (start.column.to_usize() + 1)..end.column.to_usize() {
-                write!(f, "~")?;
+            error.fmt_line(f)?;
+
+            for note in &error.notes {
+                writeln!(
+                    f,
+                    "{}:{}: note: {}",
+                    self.source_name, note.error.span.start, note.error.value
+                )?;
+                note.fmt_line(f)?;
             }
-
-            writeln!(f, "")?;
         }
         Ok(())
     }
@@ -220,10 +278,81 @@ impl<E: fmt::Display + fmt::Debug + Any> StdError for InFile<E> {
     }
 }
 
+/// A change to the source which resolves an error and which can be applied without any further
+/// input, for instance by an editor
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fix {
+    /// Short description of the fix, eg. "Rename to `length`"
+    pub title: String,
+    /// The spans to replace along with the text to replace them with. An empty span inserts the
+    /// text at its position
+    pub edits: Vec<(Span<BytePos>, String)>,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Help<E, H> {
     pub error: E,
     pub help: Option<H>,
+    /// Secondary labels pointing to other places which are relevant to the error. These are only
+    /// shown when the error is displayed with its source (see `InFile::with_notes`)
+    notes: Vec<Spanned<String, BytePos>>,
+    /// Fixes which resolve the error, if any could be found
+    fixes: Vec<Fix>,
+}
+
+impl<E, H> Help<E, H> {
+    /// Creates an error with an optional help message and without any notes or fixes
+    pub fn new(error: E, help: Option<H>) -> Help<E, H> {
+        Help {
+            error,
+            help,
+            notes: Vec::new(),
+            fixes: Vec::new(),
+        }
+    }
+
+    /// Adds a note which points at `span`
+    pub fn with_note(mut self, span: Span<BytePos>, note: String) -> Help<E, H> {
+        self.add_note(span, note);
+        self
+    }
+
+    /// Adds a note which points at `span`
+    pub fn add_note(&mut self, span: Span<BytePos>, note: String) {
+        self.notes.push(Spanned { span, value: note });
+    }
+
+    pub fn notes(&self) -> &[Spanned<String, BytePos>] {
+        &self.notes
+    }
+
+    /// Adds a fix which resolves the error
+    pub fn with_fix(mut self, fix: Fix) -> Help<E, H> {
+        self.add_fix(fix);
+        self
+    }
+
+    /// Adds a fix which resolves the error
+    pub fn add_fix(&mut self, fix: Fix) {
+        self.fixes.push(fix);
+    }
+
+    pub fn fixes(&self) -> &[Fix] {
+        &self.fixes
+    }
+
+    /// Converts the error while keeping the help, notes and fixes
+    pub fn map_error<F, U>(self, f: F) -> Help<U, H>
+    where
+        F: FnOnce(E) -> U,
+    {
+        Help {
+            error: f(self.error),
+            help: self.help,
+            notes: self.notes,
+            fixes: self.fixes,
+        }
+    }
 }
 
 impl<E, H> fmt::Display for Help<E, H>
@@ -232,7 +361,11 @@ where
     H: fmt::Display,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.error)?;
+        if f.alternate() {
+            write!(f, "{:#}", self.error)?;
+        } else {
+            write!(f, "{}", self.error)?;
+        }
         if let Some(ref help) = self.help {
             writeln!(f)?;
             write!(f, "help: {}", help)?;
@@ -243,6 +376,6 @@ where
 
 impl<E, H> From<E> for Help<E, H> {
     fn from(error: E) -> Help<E, H> {
-        Help { error, help: None }
+        Help::new(error, None)
     }
 }
@@ -1,4 +1,5 @@
 use std::collections::BTreeMap;
+use std::fmt;
 
 use symbol::SymbolRef;
 
@@ -12,22 +13,71 @@ impl<'a, T: ?Sized + MetadataEnv> MetadataEnv for &'a T {
     }
 }
 
+/// The fixity (associativity) of an infix operator
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
+pub enum Fixity {
+    /// Left operator associativity.
+    ///
+    /// For example, when the `(~)` operator is left-associative:
+    ///
+    /// ```text
+    /// x ~ y ~ z ≡ (x ~ y) ~ z
+    /// ```
+    Left,
+    /// Right operator associativity.
+    ///
+    /// For example, when the `(~)` operator is right-associative:
+    ///
+    /// ```text
+    /// x ~ y ~ z ≡ x ~ (y ~ z)
+    /// ```
+    Right,
+}
+
+impl fmt::Display for Fixity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Fixity::Left => write!(f, "infixl"),
+            Fixity::Right => write!(f, "infixr"),
+        }
+    }
+}
+
+/// Precedence and associativity of an infix operator, declared with an `@infix` attribute
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
+pub struct OpInfo {
+    pub precedence: i32,
+    pub fixity: Fixity,
+}
+
+impl fmt::Display for OpInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.fixity, self.precedence)
+    }
+}
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 #[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
 pub struct Metadata {
     pub comment: Option<String>,
+    pub operator: Option<OpInfo>,
     pub module: BTreeMap<String, Metadata>,
 }
 
 impl Metadata {
     pub fn has_data(&self) -> bool {
-        self.comment.is_some() || !self.module.is_empty()
+        self.comment.is_some() || self.operator.is_some() || !self.module.is_empty()
     }
 
     pub fn merge(mut self, other: Metadata) -> Metadata {
         if self.comment.is_none() {
             self.comment = other.comment;
         }
+        if self.operator.is_none() {
+            self.operator = other.operator;
+        }
         if self.module.is_empty() {
             self.module = other.module;
         }
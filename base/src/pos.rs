@@ -259,7 +259,11 @@ impl<T, Pos> Spanned<T, Pos> {
 
 impl<T: fmt::Display, Pos: fmt::Display> fmt::Display for Spanned<T, Pos> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}: {}", self.span.start, self.value)
+        if f.alternate() {
+            write!(f, "{}: {:#}", self.span.start, self.value)
+        } else {
+            write!(f, "{}: {}", self.span.start, self.value)
+        }
     }
 }
 
@@ -166,6 +166,29 @@ impl<K: Eq + Hash + Clone, V> ScopedMap<K, V> {
         self.scopes.push(Some(k));
         vec.len() == 1
     }
+
+    /// Calls `f` with every value in the map, including shadowed values, in the order they were
+    /// inserted. The first argument is the depth of the scope the value was inserted in where the
+    /// scope outside of any `enter_scope` call has depth `0`.
+    pub fn each_scoped<F>(&self, mut f: F)
+    where
+        F: FnMut(usize, &K, &V),
+    {
+        let mut depth = 0;
+        let mut seen: FnvMap<&K, usize> = FnvMap::default();
+        for scope in &self.scopes {
+            match *scope {
+                None => depth += 1,
+                Some(ref key) => {
+                    let index = seen.entry(key).or_insert(0);
+                    if let Some(value) = self.map.get(key).and_then(|values| values.get(*index)) {
+                        f(depth, key, value);
+                    }
+                    *index += 1;
+                }
+            }
+        }
+    }
 }
 
 impl<K: Eq + Hash, V> ScopedMap<K, V> {
@@ -282,4 +305,17 @@ mod tests {
         assert_eq!(map.get(&"a"), Some(&0));
         assert_eq!(map.get(&"c"), None);
     }
+
+    #[test]
+    fn each_scoped() {
+        let mut map = ScopedMap::new();
+        map.insert("a", 0);
+        map.enter_scope();
+        map.insert("b", 1);
+        map.insert("a", 2);
+
+        let mut values = Vec::new();
+        map.each_scoped(|depth, key, value| values.push((depth, *key, *value)));
+        assert_eq!(values, [(0, "a", 0), (1, "b", 1), (1, "a", 2)]);
+    }
 }
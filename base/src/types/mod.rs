@@ -44,6 +44,10 @@ pub trait TypeEnv: KindEnv {
         fields: &[Symbol],
         selector: RecordSelector,
     ) -> Option<(ArcType, ArcType)>;
+
+    /// Calls `f` with the name and information of every type in the environment. Environments
+    /// which are unable to enumerate their types do nothing.
+    fn each_type_info(&self, _f: &mut FnMut(&Symbol, &Alias<Symbol, ArcType>)) {}
 }
 
 pub enum RecordSelector {
@@ -87,18 +91,32 @@ impl<'a, T: ?Sized + TypeEnv> TypeEnv for &'a T {
     ) -> Option<(ArcType, ArcType)> {
         (**self).find_record(fields, selector)
     }
+
+    fn each_type_info(&self, f: &mut FnMut(&Symbol, &Alias<Symbol, ArcType>)) {
+        (**self).each_type_info(f)
+    }
 }
 
 /// Trait which is a `TypeEnv` which also provides access to the type representation of some
 /// primitive types
 pub trait PrimitiveEnv: TypeEnv {
     fn get_bool(&self) -> &ArcType;
+
+    /// Returns the operand type of the primitive operators `#<name>+`, `#<name>==` etc for types
+    /// which are not builtin. Returns `None` if no such operators has been registered.
+    fn find_primitive_operand(&self, _name: &str) -> Option<&ArcType> {
+        None
+    }
 }
 
 impl<'a, T: ?Sized + PrimitiveEnv> PrimitiveEnv for &'a T {
     fn get_bool(&self) -> &ArcType {
         (**self).get_bool()
     }
+
+    fn find_primitive_operand(&self, name: &str) -> Option<&ArcType> {
+        (**self).find_primitive_operand(name)
+    }
 }
 
 type_cache! { TypeCache(Id, T) { T, Type }
@@ -154,8 +172,14 @@ where
         Type::poly_variant(fields, self.empty_row())
     }
 
+    /// Constructs a closed record, returning the cached unit type if it has no fields so that
+    /// `()` and `{}` always share the same representation
     pub fn record(&self, types: Vec<Field<Id, Alias<Id, T>>>, fields: Vec<Field<Id, T>>) -> T {
-        Type::poly_record(types, fields, self.empty_row())
+        if types.is_empty() && fields.is_empty() {
+            self.unit()
+        } else {
+            Type::poly_record(types, fields, self.empty_row())
+        }
     }
 
     pub fn builtin_type(&self, typ: BuiltinType) -> T {
@@ -1669,6 +1693,31 @@ where
     T: Deref<Target = Type<I, T>> + HasSpan + Commented + 'a,
 {
     pub fn pretty<'e>(&self, printer: &Printer<'a, 'e, I>) -> DocBuilder<'a, Arena<'a>>
+    where
+        I: AsRef<str>,
+    {
+        match **self.typ {
+            Type::Hole
+            | Type::Opaque
+            | Type::Variable(_)
+            | Type::Skolem(_)
+            | Type::Generic(_)
+            | Type::Builtin(_)
+            | Type::EmptyRow
+            | Type::Ident(_)
+            | Type::Alias(_) => self.pretty_(printer),
+            _ => {
+                if !printer.enter_type() {
+                    return printer.arena.text("...");
+                }
+                let doc = self.pretty_(printer);
+                printer.exit_type();
+                doc
+            }
+        }
+    }
+
+    fn pretty_<'e>(&self, printer: &Printer<'a, 'e, I>) -> DocBuilder<'a, Arena<'a>>
     where
         I: AsRef<str>,
     {
@@ -2213,6 +2262,35 @@ where
     }
 }
 
+/// Like `walk_move_type_opt` but the return types of a function type (`a -> b -> c`) are walked
+/// in a loop instead of by visiting them, so that long chains of arguments do not use any stack
+/// space in the visitor. `f` is only called on the arguments and on the final return type.
+pub fn walk_move_type_spine_opt<F: ?Sized, I, T>(typ: &T, f: &mut F) -> Option<T>
+where
+    F: TypeVisitor<I, T>,
+    T: Deref<Target = Type<I, T>> + From<Type<I, T>> + Clone,
+    I: Clone,
+{
+    let mut spine = Vec::new();
+    let mut current = typ;
+    while let Type::Function(arg_type, ref arg, ref ret) = **current {
+        let new_arg = f.visit(arg);
+        spine.push((arg_type, arg, ret, new_arg));
+        current = ret;
+    }
+    if spine.is_empty() {
+        return walk_move_type_opt(typ, f);
+    }
+
+    let mut new_ret = f.visit(current);
+    for (arg_type, arg, ret, new_arg) in spine.into_iter().rev() {
+        new_ret = merge(arg, new_arg, ret, new_ret, |arg, ret| {
+            T::from(Type::Function(arg_type, arg, ret))
+        });
+    }
+    new_ret
+}
+
 pub fn walk_move_types<'a, I, F, T, R>(types: I, mut f: F) -> Option<R>
 where
     I: IntoIterator<Item = &'a T>,
@@ -1,4 +1,5 @@
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::fmt;
 use std::marker::PhantomData;
 use std::ops::Deref;
@@ -81,6 +82,7 @@ where
     T: 'a,
 {
     width: usize,
+    max_depth: Option<usize>,
     typ: &'a T,
     filter: &'a Fn(&I) -> Filter,
     _marker: PhantomData<I>,
@@ -90,6 +92,7 @@ impl<'a, I, T> TypeFormatter<'a, I, T> {
     pub fn new(typ: &'a T) -> Self {
         TypeFormatter {
             width: 80,
+            max_depth: None,
             typ: typ,
             filter: &|_| Filter::Retain,
             _marker: PhantomData,
@@ -103,6 +106,12 @@ impl<'a, I, T> TypeFormatter<'a, I, T> {
         self
     }
 
+    /// Sets how deeply nested types are printed before their subterms are elided as `...`
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
     pub fn filter(mut self, filter: &'a Fn(&I) -> Filter) -> Self {
         self.filter = filter;
         self
@@ -114,11 +123,7 @@ impl<'a, I, T> TypeFormatter<'a, I, T> {
         I: AsRef<str>,
     {
         use super::top;
-        top(self.typ).pretty(&Printer {
-            arena,
-            source: &Source::new(""),
-            filter: self.filter,
-        })
+        top(self.typ).pretty(&self.build(arena, &Source::new("")))
     }
 
     pub fn build<'e>(&self, arena: &'a Arena<'a>, source: &'e Source<'a>) -> Printer<'a, 'e, I> {
@@ -126,6 +131,8 @@ impl<'a, I, T> TypeFormatter<'a, I, T> {
             arena,
             source,
             filter: self.filter,
+            max_depth: self.max_depth,
+            depth: Cell::new(0),
         }
     }
 }
@@ -154,6 +161,9 @@ pub struct Printer<'a: 'e, 'e, I: 'a> {
     pub arena: &'a Arena<'a>,
     pub source: &'e Source<'a>,
     filter: &'a Fn(&I) -> Filter,
+    max_depth: Option<usize>,
+    /// How deeply nested the type currently being printed is
+    depth: Cell<usize>,
 }
 
 impl<'a: 'e, 'e, I> Printer<'a, 'e, I> {
@@ -162,6 +172,8 @@ impl<'a: 'e, 'e, I> Printer<'a, 'e, I> {
             arena,
             source,
             filter: &|_| Filter::Retain,
+            max_depth: None,
+            depth: Cell::new(0),
         }
     }
 
@@ -169,6 +181,20 @@ impl<'a: 'e, 'e, I> Printer<'a, 'e, I> {
         (self.filter)(field)
     }
 
+    /// Enters a nested type, returning `false` if it is nested too deeply to be printed
+    pub(crate) fn enter_type(&self) -> bool {
+        let depth = self.depth.get();
+        if self.max_depth.map_or(false, |max_depth| depth >= max_depth) {
+            return false;
+        }
+        self.depth.set(depth + 1);
+        true
+    }
+
+    pub(crate) fn exit_type(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+
     pub fn space_before(&self, pos: BytePos) -> DocBuilder<'a, Arena<'a>> {
         let (doc, comments) = self.comments_before_(pos);
         if doc.1 == self.arena.nil().1 {
@@ -0,0 +1,68 @@
+#[macro_use]
+extern crate bencher;
+
+extern crate gluon;
+
+use bencher::{black_box, Bencher};
+
+use gluon::{new_vm, Compiler, RootedThread};
+use gluon::vm::api::{Data, Hole, OpaqueValue, ValueRef};
+
+// Benchmarks retrieving the same field from many records
+fn records(vm: &RootedThread) -> OpaqueValue<RootedThread, Hole> {
+    let text = r#"
+    let array = import! std.array
+    let make n : Int -> Array { x : Int, y : String } =
+        if n #Int== 1000 then
+            []
+        else
+            array.append [{ x = n, y = "" }] (make (n #Int+ 1))
+    make 0
+    "#;
+    Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(vm, "records", text)
+        .unwrap()
+        .0
+}
+
+fn for_each_record<F>(records: &OpaqueValue<RootedThread, Hole>, mut f: F)
+where
+    F: FnMut(Data),
+{
+    match records.get_ref() {
+        ValueRef::Array(array) => for value in array.iter() {
+            match value.as_ref() {
+                ValueRef::Data(data) => f(data),
+                _ => unreachable!(),
+            }
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn lookup_field(b: &mut Bencher) {
+    let vm = new_vm();
+    let records = records(&vm);
+    b.iter(|| {
+        for_each_record(&records, |data| {
+            black_box(data.lookup_field(&vm, "x"));
+        })
+    })
+}
+
+fn get_by_token(b: &mut Bencher) {
+    let vm = new_vm();
+    let records = records(&vm);
+    b.iter(|| {
+        let mut token = None;
+        for_each_record(&records, |data| {
+            if token.is_none() {
+                token = data.field_token(&vm, "x");
+            }
+            black_box(data.get_by_token(token.as_ref().unwrap()));
+        })
+    })
+}
+
+benchmark_group!(field_lookup, lookup_field, get_by_token);
+benchmark_main!(field_lookup);
@@ -0,0 +1,48 @@
+#[macro_use]
+extern crate bencher;
+
+extern crate gluon;
+
+use bencher::{black_box, Bencher};
+
+use gluon::{new_vm, Compiler};
+use gluon::vm::api::FunctionRef;
+
+// Benchmarks updating a single field of a large record
+fn update_one_field(b: &mut Bencher) {
+    let vm = new_vm();
+    let fields = (0..32).map(|i| format!("f{}", i)).collect::<Vec<_>>();
+    let text = format!(
+        r#"
+    type Big = {{ {} }}
+    let record : Big = {{ {} }}
+    let loop n r : Int -> Big -> Int =
+        if n #Int== 0 then
+            r.f16
+        else
+            loop (n #Int- 1) {{ f16 = r.f16 #Int+ 1, .. r }}
+    \n -> loop n record
+    "#,
+        fields
+            .iter()
+            .map(|field| format!("{} : Int", field))
+            .collect::<Vec<_>>()
+            .join(", "),
+        fields
+            .iter()
+            .map(|field| format!("{} = 0", field))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    Compiler::new()
+        .load_script(&vm, "record_update", &text)
+        .unwrap();
+    let mut update: FunctionRef<fn(i32) -> i32> = vm.get_global("record_update").unwrap();
+    b.iter(|| {
+        let result = update.call(1000).unwrap();
+        black_box(result)
+    })
+}
+
+benchmark_group!(record_update, update_one_field);
+benchmark_main!(record_update);
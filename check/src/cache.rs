@@ -0,0 +1,201 @@
+//! A stable serialization of diagnostics and symbols, meant for tooling which caches the results
+//! of checking a module across processes.
+//!
+//! `Symbol` and `BytePos` depend on the interner and the compiler version which created them so
+//! the cached forms only store plain data: symbols are stored as their fully qualified names,
+//! spans as absolute byte offsets and types as their rendered text. Cached values should be
+//! wrapped in a `Cached` envelope which records `FORMAT_VERSION` so that caches written by an
+//! incompatible version are rejected when they are loaded.
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer};
+
+use base::error::Fix;
+use base::pos::{self, BytePos, Span, Spanned};
+use base::symbol::{Symbol, Symbols};
+use base::types::ArcType;
+
+use typecheck::SpannedTypeError;
+
+/// The version of the cached format. Incremented whenever a cached type changes in a way which
+/// makes old caches unreadable.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Returned when loading a cache which was written with a different `FORMAT_VERSION`
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionMismatch {
+    pub expected: u32,
+    pub found: u32,
+}
+
+impl fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Expected a cache with format version {} but found version {}",
+            self.expected, self.found
+        )
+    }
+}
+
+impl StdError for VersionMismatch {
+    fn description(&self) -> &str {
+        "Mismatched cache format version"
+    }
+}
+
+/// Envelope which stores the format version next to a cached value
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cached<T> {
+    version: u32,
+    value: T,
+}
+
+impl<T> Cached<T> {
+    pub fn new(value: T) -> Cached<T> {
+        Cached {
+            version: FORMAT_VERSION,
+            value,
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns the cached value if it was written with the current `FORMAT_VERSION`
+    pub fn into_value(self) -> Result<T, VersionMismatch> {
+        if self.version == FORMAT_VERSION {
+            Ok(self.value)
+        } else {
+            Err(VersionMismatch {
+                expected: FORMAT_VERSION,
+                found: self.version,
+            })
+        }
+    }
+
+    /// Deserializes an envelope and returns its value, failing with a `VersionMismatch` error if
+    /// it was written with another format version
+    pub fn load<'de, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        Cached::deserialize(deserializer)?
+            .into_value()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// A span stored as absolute byte offsets into the source of a module
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl CachedSpan {
+    pub fn new(span: Span<BytePos>) -> CachedSpan {
+        CachedSpan {
+            start: span.start.to_usize(),
+            end: span.end.to_usize(),
+        }
+    }
+
+    pub fn to_span(&self) -> Span<BytePos> {
+        pos::span(BytePos::from(self.start), BytePos::from(self.end))
+    }
+}
+
+/// A fix of a diagnostic (see `base::error::Fix`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CachedFix {
+    pub title: String,
+    pub edits: Vec<(CachedSpan, String)>,
+}
+
+impl CachedFix {
+    pub fn new(fix: &Fix) -> CachedFix {
+        CachedFix {
+            title: fix.title.clone(),
+            edits: fix.edits
+                .iter()
+                .map(|&(span, ref text)| (CachedSpan::new(span), text.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn to_fix(&self) -> Fix {
+        Fix {
+            title: self.title.clone(),
+            edits: self.edits
+                .iter()
+                .map(|&(ref span, ref text)| (span.to_span(), text.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// A type error with its message and help rendered to text
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CachedDiagnostic {
+    pub span: CachedSpan,
+    pub message: String,
+    pub help: Option<String>,
+    /// Caches written before fixes were added are loaded without any fixes
+    #[serde(default)]
+    pub fixes: Vec<CachedFix>,
+}
+
+impl CachedDiagnostic {
+    pub fn new(error: &SpannedTypeError<Symbol>) -> CachedDiagnostic {
+        CachedDiagnostic {
+            span: CachedSpan::new(error.span),
+            message: error.value.error.to_string(),
+            help: error.value.help.as_ref().map(|help| help.to_string()),
+            fixes: error.value.fixes().iter().map(CachedFix::new).collect(),
+        }
+    }
+
+    pub fn span(&self) -> Span<BytePos> {
+        self.span.to_span()
+    }
+}
+
+impl fmt::Display for CachedDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(ref help) = self.help {
+            writeln!(f)?;
+            write!(f, "help: {}", help)?;
+        }
+        Ok(())
+    }
+}
+
+/// A symbol bound in a module along with its type
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CachedSymbol {
+    /// The fully qualified name of the symbol
+    pub name: String,
+    pub typ: String,
+    pub span: CachedSpan,
+}
+
+impl CachedSymbol {
+    pub fn new(name: &Symbol, typ: &ArcType, span: Span<BytePos>) -> CachedSymbol {
+        CachedSymbol {
+            name: name.as_ref().to_string(),
+            typ: typ.to_string(),
+            span: CachedSpan::new(span),
+        }
+    }
+
+    /// Interns the name of the symbol in `symbols`, returning it with the span it was bound at
+    pub fn to_symbol(&self, symbols: &mut Symbols) -> Spanned<Symbol, BytePos> {
+        pos::spanned(self.span.to_span(), symbols.symbol(&self.name[..]))
+    }
+}
@@ -9,7 +9,7 @@ use base::resolve;
 use base::scoped_map::ScopedMap;
 use base::symbol::Symbol;
 
-use typecheck::{TcResult, TypeError, Typecheck, TypecheckEnv};
+use typecheck::{HelpError, TypeError, Typecheck, TypecheckEnv};
 use substitution::Substitution;
 
 const MAX_IMPLICIT_LEVEL: u32 = 20;
@@ -28,10 +28,10 @@ impl<'a, 'b> ResolveImplicitsVisitor<'a, 'b> {
         span: Span<BytePos>,
         path: &[TypedIdent<Symbol>],
         to_resolve: &[ArcType],
-    ) -> TcResult<Option<SpannedExpr<Symbol>>> {
+    ) -> Result<Option<SpannedExpr<Symbol>>, HelpError<Symbol>> {
         self.resolve_implicit_application_(level, implicit_bindings, span, path, to_resolve)
             .map_err(|mut err| {
-                if let TypeError::LoopInImplicitResolution(ref mut paths) = err {
+                if let TypeError::LoopInImplicitResolution(ref mut paths) = err.error {
                     paths.push(path.iter().map(|id| &id.name).format(".").to_string());
                 }
                 err
@@ -45,9 +45,9 @@ impl<'a, 'b> ResolveImplicitsVisitor<'a, 'b> {
         span: Span<BytePos>,
         path: &[TypedIdent<Symbol>],
         to_resolve: &[ArcType],
-    ) -> TcResult<Option<SpannedExpr<Symbol>>> {
+    ) -> Result<Option<SpannedExpr<Symbol>>, HelpError<Symbol>> {
         if level > MAX_IMPLICIT_LEVEL {
-            return Err(TypeError::LoopInImplicitResolution(Vec::new()));
+            return Err(TypeError::LoopInImplicitResolution(Vec::new()).into());
         }
 
         let base_ident = path[0].clone();
@@ -86,7 +86,7 @@ impl<'a, 'b> ResolveImplicitsVisitor<'a, 'b> {
                         Err(err) => Some(Err(err)),
                     }
                 })
-                .collect::<TcResult<Vec<_>>>()?;
+                .collect::<Result<Vec<_>, _>>()?;
             if resolved_arguments.len() == to_resolve.len() {
                 Some(pos::spanned(
                     span,
@@ -137,7 +137,7 @@ impl<'a, 'b> ResolveImplicitsVisitor<'a, 'b> {
         implicit_bindings: &'c ImplicitBindings,
         to_resolve: &mut Vec<ArcType>,
         expected_type: &ArcType,
-    ) -> TcResult<&'c [TypedIdent<Symbol>]> {
+    ) -> Result<&'c [TypedIdent<Symbol>], HelpError<Symbol>> {
         let mut iter = implicit_bindings.iter().rev();
         let found_candidate = iter.by_ref()
             .find(|&&(ref path, ref typ)| self.try_implicit(path, to_resolve, expected_type, typ));
@@ -145,26 +145,12 @@ impl<'a, 'b> ResolveImplicitsVisitor<'a, 'b> {
             Some(candidate) => {
                 let mut additional_candidates: Vec<_> = iter.filter(|&&(ref path, ref typ)| {
                     self.try_implicit(path, &mut Vec::new(), expected_type, typ)
-                }).map(|bind| {
-                        (
-                            bind.0.iter().map(|id| &id.name).format(".").to_string(),
-                            bind.1.clone(),
-                        )
-                    })
-                    .collect();
+                }).collect();
                 if additional_candidates.is_empty() {
                     Ok(&candidate.0)
                 } else {
-                    additional_candidates.push((
-                        candidate
-                            .0
-                            .iter()
-                            .map(|id| &id.name)
-                            .format(".")
-                            .to_string(),
-                        candidate.1.clone(),
-                    ));
-                    Err(TypeError::AmbiguousImplicit(additional_candidates))
+                    additional_candidates.push(candidate);
+                    Err(self.ambiguous_implicit(&additional_candidates))
                 }
             }
             None => Err(TypeError::UnableToResolveImplicit(
@@ -173,8 +159,44 @@ impl<'a, 'b> ResolveImplicitsVisitor<'a, 'b> {
                     .iter()
                     .map(|&(ref path, _)| path.iter().map(|id| &id.name).format(".").to_string())
                     .collect(),
-            )),
+            ).into()),
+        }
+    }
+
+    /// Creates the error for an implicit argument which could be resolved by every binding in
+    /// `candidates`, noting where each candidate which was bound by a pattern was defined.
+    fn ambiguous_implicit(
+        &self,
+        candidates: &[&(Vec<TypedIdent<Symbol>>, ArcType)],
+    ) -> HelpError<Symbol> {
+        let mut error = HelpError::new(
+            TypeError::AmbiguousImplicit(
+                candidates
+                    .iter()
+                    .map(|&&(ref path, ref typ)| {
+                        (
+                            path.iter().map(|id| &id.name).format(".").to_string(),
+                            typ.clone(),
+                        )
+                    })
+                    .collect(),
+            ),
+            None,
+        );
+        let mut definitions: Vec<_> = candidates
+            .iter()
+            .filter_map(|&&(ref path, _)| {
+                self.tc
+                    .binding_spans
+                    .get(&path[0].name)
+                    .map(|&span| (span, path.iter().map(|id| &id.name).format(".").to_string()))
+            })
+            .collect();
+        definitions.sort_by_key(|&(span, _)| span.start);
+        for (span, name) in definitions {
+            error.add_note(span, format!("`{}` is defined here", name));
         }
+        error
     }
 }
 
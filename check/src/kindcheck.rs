@@ -191,7 +191,12 @@ impl<'a> KindCheck<'a> {
             }
             Type::Builtin(builtin_typ) => Ok(self.builtin_kind(builtin_typ)),
             Type::Forall(ref mut params, ref mut typ, _) => {
+                let mut annotations = Vec::with_capacity(params.len());
                 for param in &mut *params {
+                    let mut annotation = param.kind.clone();
+                    self.instantiate_kinds(&mut annotation);
+                    annotations.push(annotation);
+
                     param.kind = self.subs.new_var();
                     self.locals.push((param.id.clone(), param.kind.clone()));
                 }
@@ -200,6 +205,13 @@ impl<'a> KindCheck<'a> {
                 let offset = self.locals.len() - params.len();
                 self.locals.drain(offset..);
 
+                // Check the annotations after the body so that a conflict is reported at the
+                // binders instead of at the first use of the variable
+                let binder_span = Span::new(span.start, typ.span().start);
+                for (param, annotation) in params.iter().zip(annotations) {
+                    self.unify(binder_span, &annotation, param.kind.clone())?;
+                }
+
                 Ok(ret_kind)
             }
             Type::Function(_, ref mut arg, ref mut ret) => {
@@ -10,10 +10,16 @@ extern crate collect_mac;
 #[cfg(test)]
 extern crate env_logger;
 extern crate itertools;
-#[macro_use]
+#[cfg_attr(not(feature = "no-logging"), macro_use)]
 extern crate log;
+#[cfg(feature = "pretty-errors")]
 extern crate pretty;
 extern crate rpds;
+#[cfg(feature = "serialization")]
+extern crate serde;
+#[cfg(feature = "serialization")]
+#[macro_use]
+extern crate serde_derive;
 extern crate smallvec;
 extern crate strsim;
 extern crate union_find;
@@ -21,6 +27,9 @@ extern crate union_find;
 #[macro_use]
 extern crate gluon_base as base;
 
+#[macro_use]
+mod macros;
+
 pub mod typecheck;
 pub mod unify_type;
 pub mod unify;
@@ -28,13 +37,93 @@ pub mod kindcheck;
 pub mod substitution;
 pub mod rename;
 pub mod metadata;
+pub mod type_at;
+pub mod repro;
+#[cfg(feature = "serialization")]
+pub mod cache;
 
 mod implicits;
+mod unreachable;
 
-use base::types::{ArcType, TypeEnv};
+use std::cell::Cell;
+use std::fmt;
+
+use base::error::Errors;
+use base::metadata::Metadata;
+use base::symbol::Symbol;
+use base::types::{ArcType, Field, Type, TypeEnv};
+
+/// How the types in type and kind errors are laid out when the errors are displayed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ErrorLayout {
+    /// The width which the types are wrapped to
+    pub width: usize,
+    /// How deeply nested types are printed before their subterms are elided as `...`
+    pub max_depth: Option<usize>,
+}
+
+impl Default for ErrorLayout {
+    fn default() -> ErrorLayout {
+        ErrorLayout {
+            width: 80,
+            max_depth: None,
+        }
+    }
+}
+
+thread_local!(static ERROR_LAYOUT: Cell<ErrorLayout> = Cell::new(ErrorLayout::default()));
+
+impl ErrorLayout {
+    /// Returns the layout used by the errors which are currently being displayed
+    pub fn current() -> ErrorLayout {
+        ERROR_LAYOUT.with(|layout| layout.get())
+    }
+
+    /// Wraps `value` (a type error or anything containing type errors, such as `gluon::Error`)
+    /// so that it is displayed with this layout
+    pub fn display<T>(self, value: T) -> WithLayout<T> {
+        WithLayout {
+            layout: self,
+            value,
+        }
+    }
+}
+
+/// Displays `value` with `layout`. Created by `ErrorLayout::display`
+pub struct WithLayout<T> {
+    layout: ErrorLayout,
+    value: T,
+}
+
+impl<T: fmt::Display> fmt::Display for WithLayout<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let previous = ERROR_LAYOUT.with(|layout| layout.replace(self.layout));
+        let result = if f.alternate() {
+            write!(f, "{:#}", self.value)
+        } else {
+            write!(f, "{}", self.value)
+        };
+        ERROR_LAYOUT.with(|layout| layout.set(previous));
+        result
+    }
+}
 
 /// Checks if `actual` can be assigned to a binding with the type signature `signature`
 pub fn check_signature(env: &TypeEnv, signature: &ArcType, actual: &ArcType) -> bool {
+    let result = check_signature_result(env, signature, actual);
+    if let Err(ref err) = result {
+        debug!("Check signature error: {}", err);
+    }
+    result.is_ok()
+}
+
+/// Checks if `actual` can be assigned to a binding with the type signature `signature`, returning
+/// the errors which were found if it can't
+pub fn check_signature_result(
+    env: &TypeEnv,
+    signature: &ArcType,
+    actual: &ArcType,
+) -> Result<(), Errors<unify_type::Error<Symbol>>> {
     use base::kind::Kind;
     use base::scoped_map::ScopedMap;
     use base::fnv::FnvMap;
@@ -45,11 +134,46 @@ pub fn check_signature(env: &TypeEnv, signature: &ArcType, actual: &ArcType) ->
     let state = unify_type::State::new(env, &subs);
     let actual = unify_type::new_skolem_scope(&subs, actual);
     let actual = actual.instantiate_generics(&mut FnvMap::default());
-    let result = unify_type::subsumes(&subs, &mut ScopedMap::new(), 0, state, signature, &actual);
-    if let Err(ref err) = result {
-        debug!("Check signature error: {}", err);
+    unify_type::subsumes(&subs, &mut ScopedMap::new(), 0, state, signature, &actual).map(|_| ())
+}
+
+/// Returns `true` if the field `name` of a module record is private to the module. Fields are
+/// private if their name starts with `_` or if they are marked with the `@private` attribute.
+pub fn is_private_field(name: &str, metadata: Option<&Metadata>) -> bool {
+    name.starts_with('_')
+        || metadata
+            .and_then(|metadata| metadata.comment.as_ref())
+            .map_or(false, |comment| {
+                metadata::attributes(comment).any(|(key, _)| key == "private")
+            })
+}
+
+/// Returns the type of a module as seen by the modules which import it, that is, `typ` without
+/// any private fields or associated types (see `is_private_field`). Returns `None` if `typ` is not
+/// a record or if it does not have any private fields.
+pub fn public_module_type(typ: &ArcType, metadata: &Metadata) -> Option<ArcType> {
+    let is_private = |name: &Symbol| {
+        is_private_field(name.as_ref(), metadata.module.get(name.as_ref()))
+    };
+    match **typ {
+        Type::Record(_) => {
+            if !typ.type_field_iter().any(|field| is_private(&field.name))
+                && !typ.row_iter().any(|field| is_private(&field.name))
+            {
+                return None;
+            }
+            let types = typ.type_field_iter()
+                .filter(|field| !is_private(&field.name))
+                .cloned()
+                .collect();
+            let fields = typ.row_iter()
+                .filter(|field| !is_private(&field.name))
+                .map(|field| Field::new(field.name.clone(), field.typ.clone()))
+                .collect();
+            Some(Type::record(types, fields))
+        }
+        _ => None,
     }
-    result.is_ok()
 }
 
 #[cfg(test)]
@@ -58,10 +182,22 @@ mod tests {
     use std::rc::Rc;
 
     use base::kind::{ArcKind, KindEnv};
+    use base::metadata::{Metadata, MetadataEnv};
     use base::symbol::{Symbol, SymbolModule, SymbolRef, Symbols};
-    use base::types::{Alias, ArcType, RecordSelector, TypeEnv};
+    use base::types::{Alias, ArcType, PrimitiveEnv, RecordSelector, Type, TypeEnv};
 
-    pub struct MockEnv;
+    pub struct MockEnv {
+        bool: Alias<Symbol, ArcType>,
+    }
+
+    impl MockEnv {
+        pub fn new() -> MockEnv {
+            let bool_sym = intern("Bool");
+            MockEnv {
+                bool: Alias::new(bool_sym.clone(), Type::ident(bool_sym)),
+            }
+        }
+    }
 
     impl KindEnv for MockEnv {
         fn find_kind(&self, _type_name: &SymbolRef) -> Option<ArcKind> {
@@ -83,6 +219,21 @@ mod tests {
         ) -> Option<(ArcType, ArcType)> {
             None
         }
+        fn each_type_info(&self, f: &mut FnMut(&Symbol, &Alias<Symbol, ArcType>)) {
+            f(&self.bool.name, &self.bool)
+        }
+    }
+
+    impl PrimitiveEnv for MockEnv {
+        fn get_bool(&self) -> &ArcType {
+            self.bool.as_type()
+        }
+    }
+
+    impl MetadataEnv for MockEnv {
+        fn get_metadata(&self, _id: &SymbolRef) -> Option<&Metadata> {
+            None
+        }
     }
 
     /// Returns a reference to the interner stored in TLD
@@ -0,0 +1,43 @@
+//! Logging macros used by the typechecker.
+//!
+//! With the `no-logging` feature these shadow the macros from `log` so that no logging code is
+//! emitted. The arguments are still type checked so that both configurations compile the same.
+
+#[cfg(feature = "no-logging")]
+macro_rules! log_nothing {
+    (target: $target:expr, $($arg:tt)+) => {
+        log_nothing!($($arg)+)
+    };
+    ($($arg:tt)+) => {
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
+}
+
+#[cfg(feature = "no-logging")]
+#[allow(unused_macros)]
+macro_rules! error {
+    ($($arg:tt)+) => { log_nothing!($($arg)+) };
+}
+
+#[cfg(feature = "no-logging")]
+#[allow(unused_macros)]
+macro_rules! warn {
+    ($($arg:tt)+) => { log_nothing!($($arg)+) };
+}
+
+#[cfg(feature = "no-logging")]
+macro_rules! info {
+    ($($arg:tt)+) => { log_nothing!($($arg)+) };
+}
+
+#[cfg(feature = "no-logging")]
+macro_rules! debug {
+    ($($arg:tt)+) => { log_nothing!($($arg)+) };
+}
+
+#[cfg(feature = "no-logging")]
+macro_rules! trace {
+    ($($arg:tt)+) => { log_nothing!($($arg)+) };
+}
@@ -1,12 +1,43 @@
 use std::collections::BTreeMap;
+use std::fmt;
 
-use base::ast::{self, AstType, Commented, Expr, Pattern, SpannedExpr, SpannedPattern, ValueBinding};
+use base::ast::{self, AstType, Comment, Commented, Expr, Pattern, SpannedExpr, SpannedPattern,
+                ValueBinding};
 use base::ast::Visitor;
+use base::error::Errors;
 use base::fnv::FnvMap;
-use base::metadata::{Metadata, MetadataEnv};
+use base::metadata::{Fixity, Metadata, MetadataEnv, OpInfo};
+use base::pos::{self, BytePos, Span, Spanned};
 use base::symbol::{Name, Symbol};
 use base::types::row_iter;
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The `@infix` attribute could not be parsed
+    InvalidInfix(String),
+    /// The same operator was declared with different fixities in the same module
+    ConflictingInfix(String, OpInfo, OpInfo),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidInfix(ref attribute) => write!(
+                f,
+                "Invalid infix attribute `{}`, expected `@infix <left|right> <precedence>`",
+                attribute
+            ),
+            Error::ConflictingInfix(ref name, ref previous, ref current) => write!(
+                f,
+                "Operator `{}` is declared as `{}` but was previously declared as `{}`",
+                name, current, previous
+            ),
+        }
+    }
+}
+
+pub type SpannedError = Spanned<Error, BytePos>;
+
 pub struct AttributesIter<'a> {
     comment: ::std::str::Lines<'a>,
 }
@@ -33,35 +64,82 @@ pub fn attributes(comment: &str) -> AttributesIter {
     }
 }
 
+/// Parses the value of an `@infix` attribute such as `left 6`
+fn parse_infix(value: &str) -> Option<OpInfo> {
+    let mut iter = value.split_whitespace();
+    let fixity = match iter.next()? {
+        "left" => Fixity::Left,
+        "right" => Fixity::Right,
+        _ => return None,
+    };
+    let precedence = iter.next()?.parse().ok()?;
+    if iter.next().is_some() {
+        return None;
+    }
+    Some(OpInfo { precedence, fixity })
+}
+
+/// Looks up the operator information of the field `name` in the metadata of a module
+pub fn operator_info(metadata: &Metadata, name: &str) -> Option<OpInfo> {
+    metadata
+        .module
+        .get(name)
+        .and_then(|metadata| metadata.operator)
+}
+
 struct Environment<'b> {
     env: &'b MetadataEnv,
     stack: FnvMap<Symbol, Metadata>,
 }
 
 /// Queries `expr` for the metadata which it contains.
+///
+/// Invalid or conflicting `@infix` attributes are ignored, use `check_metadata` to report them.
 pub fn metadata(
     env: &MetadataEnv,
     expr: &SpannedExpr<Symbol>,
 ) -> (Metadata, FnvMap<Symbol, Metadata>) {
+    let (metadata, stack, _) = metadata_with_errors(env, expr);
+    (metadata, stack)
+}
+
+/// Queries `expr` for the metadata which it contains, returning an error if any operator has an
+/// invalid `@infix` attribute or is declared with different fixities.
+pub fn check_metadata(
+    env: &MetadataEnv,
+    expr: &SpannedExpr<Symbol>,
+) -> Result<(Metadata, FnvMap<Symbol, Metadata>), Errors<SpannedError>> {
+    let (metadata, stack, errors) = metadata_with_errors(env, expr);
+    if errors.has_errors() {
+        Err(errors)
+    } else {
+        Ok((metadata, stack))
+    }
+}
+
+fn metadata_with_errors(
+    env: &MetadataEnv,
+    expr: &SpannedExpr<Symbol>,
+) -> (Metadata, FnvMap<Symbol, Metadata>, Errors<SpannedError>) {
     struct MetadataVisitor<'b> {
         env: Environment<'b>,
+        operators: FnvMap<String, OpInfo>,
+        errors: Errors<SpannedError>,
     }
 
     impl<'b> MetadataVisitor<'b> {
         fn new_binding(&mut self, metadata: Metadata, bind: &ValueBinding<Symbol>) {
             match bind.name.value {
                 Pattern::As(ref id, _) => {
-                    let metadata = bind.comment.as_ref().map_or(metadata, |comment| Metadata {
-                        comment: Some(comment.content.clone()),
-                        module: BTreeMap::new(),
+                    let metadata = bind.comment.as_ref().map_or(metadata, |comment| {
+                        self.comment_metadata(id.definition_name(), comment, bind.name.span)
                     });
                     self.stack_var(id.clone(), metadata.clone());
                     self.new_pattern(metadata, &bind.name);
                 }
                 Pattern::Ident(ref id) => {
-                    let metadata = bind.comment.as_ref().map_or(metadata, |comment| Metadata {
-                        comment: Some(comment.content.clone()),
-                        module: BTreeMap::new(),
+                    let metadata = bind.comment.as_ref().map_or(metadata, |comment| {
+                        self.comment_metadata(id.name.definition_name(), comment, bind.name.span)
                     });
                     self.stack_var(id.name.clone(), metadata);
                 }
@@ -69,6 +147,8 @@ pub fn metadata(
                 | Pattern::Tuple { .. }
                 | Pattern::Record { .. }
                 | Pattern::Literal(_)
+                | Pattern::StringAffix { .. }
+                | Pattern::Or(_)
                 | Pattern::Error => self.new_pattern(metadata, &bind.name),
             }
         }
@@ -123,10 +203,53 @@ pub fn metadata(
                 Pattern::Tuple { .. }
                 | Pattern::Constructor(..)
                 | Pattern::Literal(_)
+                | Pattern::StringAffix { .. }
+                | Pattern::Or(_)
                 | Pattern::Error => (),
             }
         }
 
+        /// Creates the metadata of the binding `name` from its doc comment
+        fn comment_metadata(
+            &mut self,
+            name: &str,
+            comment: &Comment,
+            span: Span<BytePos>,
+        ) -> Metadata {
+            let mut operator = None;
+            for (key, value) in attributes(&comment.content) {
+                if key != "infix" {
+                    continue;
+                }
+                let value = value.unwrap_or("");
+                match parse_infix(value) {
+                    Some(info) => {
+                        self.declare_operator(name, info, span);
+                        operator = Some(info);
+                    }
+                    None => self.errors
+                        .push(pos::spanned(span, Error::InvalidInfix(value.to_string()))),
+                }
+            }
+            Metadata {
+                comment: Some(comment.content.clone()),
+                operator,
+                module: BTreeMap::new(),
+            }
+        }
+
+        fn declare_operator(&mut self, name: &str, info: OpInfo, span: Span<BytePos>) {
+            let previous = *self.operators
+                .entry(name.to_string())
+                .or_insert(info);
+            if previous != info {
+                self.errors.push(pos::spanned(
+                    span,
+                    Error::ConflictingInfix(name.to_string(), previous, info),
+                ));
+            }
+        }
+
         fn stack_var(&mut self, id: Symbol, metadata: Metadata) {
             if metadata.has_data() {
                 debug!("Insert {}", id);
@@ -165,9 +288,12 @@ pub fn metadata(
                             }
                             None => self.metadata(&field.name.value).cloned(),
                         };
-                        let field_metadata = field.comment.clone().map(|comment| Metadata {
-                            comment: Some(comment.content),
-                            module: BTreeMap::new(),
+                        let field_metadata = field.comment.as_ref().map(|comment| {
+                            self.comment_metadata(
+                                field.name.value.definition_name(),
+                                comment,
+                                field.name.span,
+                            )
                         });
                         let maybe_metadata = match (field_metadata, maybe_metadata) {
                             (Some(l), Some(r)) => Some(l.merge(r)),
@@ -187,17 +313,23 @@ pub fn metadata(
                     }
                     Metadata {
                         comment: None,
+                        operator: None,
                         module: module,
                     }
                 }
                 Expr::LetBindings(ref bindings, ref expr) => {
-                    let is_recursive = bindings.iter().all(|bind| !bind.args.is_empty());
+                    let is_recursive = ast::is_recursive_binding_group(bindings);
                     if is_recursive {
                         for bind in bindings {
                             self.new_binding(Metadata::default(), bind);
                         }
                         for bind in bindings {
-                            self.metadata_expr(&bind.expr);
+                            let metadata = self.metadata_expr(&bind.expr);
+                            // Values constructed in a recursive group have their metadata
+                            // available once the constructed record has been visited
+                            if bind.args.is_empty() {
+                                self.new_binding(metadata, bind);
+                            }
                         }
                     } else {
                         for bind in bindings {
@@ -262,6 +394,7 @@ pub fn metadata(
             } else {
                 Some(Metadata {
                     comment: None,
+                    operator: None,
                     module,
                 })
             }
@@ -281,7 +414,9 @@ pub fn metadata(
             env: env,
             stack: FnvMap::default(),
         },
+        operators: FnvMap::default(),
+        errors: Errors::new(),
     };
     let metadata = visitor.metadata_expr(expr);
-    (metadata, visitor.env.stack)
+    (metadata, visitor.env.stack, visitor.errors)
 }
@@ -1,5 +1,6 @@
 use base::ast::{self, DisplayEnv, Do, Expr, MutVisitor, Pattern, SpannedAlias, SpannedExpr,
                 TypedIdent};
+use base::fnv::FnvSet;
 use base::pos::{self, BytePos, Span};
 use base::scoped_map::ScopedMap;
 use base::symbol::{Symbol, SymbolModule};
@@ -9,7 +10,9 @@ struct Environment {
     stack: ScopedMap<Symbol, (Symbol, Span<BytePos>)>,
 }
 
-pub fn rename(symbols: &mut SymbolModule, expr: &mut SpannedExpr<Symbol>) {
+/// Gives every binding in `expr` a unique name. Returns the (renamed) local variables which are
+/// referred to outside of their own definition.
+pub fn rename(symbols: &mut SymbolModule, expr: &mut SpannedExpr<Symbol>) -> FnvSet<Symbol> {
     enum TailCall {
         TailCall,
         Return,
@@ -18,6 +21,14 @@ pub fn rename(symbols: &mut SymbolModule, expr: &mut SpannedExpr<Symbol>) {
     struct RenameVisitor<'a: 'b, 'b> {
         symbols: &'b mut SymbolModule<'a>,
         env: Environment,
+        /// Set while renaming the alternatives of an or-pattern after the first, which refer to
+        /// the variables bound by the first alternative instead of binding new ones
+        in_or_pattern: bool,
+        /// The variables which have been referred to so far
+        used: FnvSet<Symbol>,
+        /// The bindings of recursive groups whose definitions are being renamed. References to
+        /// them from their own definitions do not count as uses
+        defining: Vec<Symbol>,
     }
 
     impl<'a, 'b> RenameVisitor<'a, 'b> {
@@ -36,7 +47,10 @@ pub fn rename(symbols: &mut SymbolModule, expr: &mut SpannedExpr<Symbol>) {
                         }
                     }
                 },
-                Pattern::Ident(ref mut id) => {
+                Pattern::Ident(ref mut id)
+                | Pattern::StringAffix {
+                    rest: ref mut id, ..
+                } => {
                     let new_name = self.stack_var(id.name.clone(), pattern.span);
                     id.name = new_name;
                 }
@@ -51,11 +65,22 @@ pub fn rename(symbols: &mut SymbolModule, expr: &mut SpannedExpr<Symbol>) {
                 Pattern::Constructor(_, ref mut args) => for arg in args {
                     self.new_pattern(arg);
                 },
+                Pattern::Or(ref mut alternatives) => {
+                    let in_or_pattern = self.in_or_pattern;
+                    for (i, alternative) in alternatives.iter_mut().enumerate() {
+                        self.in_or_pattern = i != 0;
+                        self.new_pattern(alternative);
+                    }
+                    self.in_or_pattern = in_or_pattern;
+                }
                 Pattern::Literal(_) | Pattern::Error => (),
             }
         }
 
         fn stack_var(&mut self, id: Symbol, span: Span<BytePos>) -> Symbol {
+            if self.in_or_pattern && self.env.stack.in_current_scope(&id) {
+                return self.env.stack.get(&id).unwrap().0.clone();
+            }
             let old_id = id.clone();
             let name = self.symbols.string(&id).to_owned();
             let new_id = self.symbols.symbol(format!("{}:{}", name, span.start));
@@ -83,8 +108,14 @@ pub fn rename(symbols: &mut SymbolModule, expr: &mut SpannedExpr<Symbol>) {
         /// Renames `id` to the unique identifier which have the type `expected`
         /// Returns `Some(new_id)` if renaming was necessary or `None` if no renaming was necessary
         /// as `id` was currently unique (#Int+, #Float*, etc)
-        fn rename(&self, id: &Symbol) -> Option<Symbol> {
-            self.env.stack.get(id).map(|t| t.0.clone())
+        fn rename(&mut self, id: &Symbol) -> Option<Symbol> {
+            let new_id = self.env.stack.get(id).map(|t| t.0.clone());
+            if let Some(ref new_id) = new_id {
+                if !self.defining.contains(new_id) {
+                    self.used.insert(new_id.clone());
+                }
+            }
+            new_id
         }
 
         fn rename_expr(&mut self, expr: &mut SpannedExpr<Symbol>) -> TailCall {
@@ -143,13 +174,16 @@ pub fn rename(symbols: &mut SymbolModule, expr: &mut SpannedExpr<Symbol>) {
                     for alt in alts {
                         self.env.stack.enter_scope();
                         self.new_pattern(&mut alt.pattern);
+                        if let Some(ref mut guard) = alt.guard {
+                            self.visit_expr(guard);
+                        }
                         self.visit_expr(&mut alt.expr);
                         self.env.stack.exit_scope();
                     }
                 }
                 Expr::LetBindings(ref mut bindings, ref mut expr) => {
                     self.env.stack.enter_scope();
-                    let is_recursive = bindings.iter().all(|bind| !bind.args.is_empty());
+                    let is_recursive = ast::is_recursive_binding_group(bindings);
                     for bind in bindings.iter_mut() {
                         if !is_recursive {
                             self.visit_expr(&mut bind.expr);
@@ -166,7 +200,15 @@ pub fn rename(symbols: &mut SymbolModule, expr: &mut SpannedExpr<Symbol>) {
                                 arg.name.value.name =
                                     self.stack_var(arg.name.value.name.clone(), expr.span);
                             }
+                            let defining = match bind.name.value {
+                                Pattern::Ident(ref id) => Some(id.name.clone()),
+                                _ => None,
+                            };
+                            self.defining.extend(defining.clone());
                             self.visit_expr(&mut bind.expr);
+                            if defining.is_some() {
+                                self.defining.pop();
+                            }
                             self.env.stack.exit_scope();
                         }
                     }
@@ -261,6 +303,10 @@ pub fn rename(symbols: &mut SymbolModule, expr: &mut SpannedExpr<Symbol>) {
         env: Environment {
             stack: ScopedMap::new(),
         },
+        in_or_pattern: false,
+        used: FnvSet::default(),
+        defining: Vec::new(),
     };
     visitor.visit_expr(expr);
+    visitor.used
 }
@@ -0,0 +1,312 @@
+//! Extraction of minimal reproductions of type errors.
+//!
+//! Given an expression which fails to typecheck and the span of one of its errors, `minimize`
+//! slices out the smallest expression enclosing the error together with the `let` and `type`
+//! bindings it refers to, making it easier to produce a standalone snippet for a bug report.
+use std::mem;
+use std::ops::Deref;
+
+use base::ast::{self, Do, Expr, Pattern, SpannedExpr, TypeBinding, ValueBinding, Visitor};
+use base::fnv::FnvSet;
+use base::pos::{self, BytePos, Span};
+use base::symbol::Symbol;
+use base::types::{self, ArcType, Type};
+
+use typecheck::Typecheck;
+
+/// Extracts the smallest expression in `expr` which contains `error_span` along with the `let`
+/// and `type` bindings it references.
+///
+/// Every candidate is checked with `tc` and is only returned if its first error is of the same
+/// kind as the error found at `error_span` in `expr`. If no candidate reproduces the error (or
+/// `expr` does not have an error at `error_span`) a copy of `expr` is returned.
+///
+/// The bindings in the returned expression have been renamed, so it should be displayed with the
+/// declared names of its symbols.
+pub fn minimize(
+    tc: &mut Typecheck,
+    expr: &SpannedExpr<Symbol>,
+    error_span: Span<BytePos>,
+) -> SpannedExpr<Symbol> {
+    let error_kind = {
+        let mut checked = expr.clone();
+        match tc.typecheck_expr(&mut checked) {
+            Ok(_) => return expr.clone(),
+            Err(errors) => match errors
+                .into_iter()
+                .find(|err| err.span.contains(error_span) || error_span.contains(err.span))
+            {
+                Some(err) => mem::discriminant(&err.value.error),
+                None => return expr.clone(),
+            },
+        }
+    };
+
+    // Free variables are only meaningful once every binding has a unique name
+    let mut renamed = expr.clone();
+    tc.rename(&mut renamed);
+
+    let mut path = Vec::new();
+    find_path(&renamed, error_span, &mut path);
+
+    for target in (0..path.len()).rev() {
+        let candidate = slice(&path[..target + 1]);
+        let mut checked = candidate.clone();
+        let reproduces = match tc.typecheck_expr(&mut checked) {
+            Ok(_) => false,
+            Err(errors) => errors.into_iter().next().map_or(false, |err| {
+                mem::discriminant(&err.value.error) == error_kind
+                    && (err.span.contains(error_span) || error_span.contains(err.span))
+            }),
+        };
+        debug!("Minimized candidate {} reproduces the error: {}", target, reproduces);
+        if reproduces {
+            return candidate;
+        }
+    }
+    expr.clone()
+}
+
+/// Pushes `expr` and the chain of sub-expressions which contain `span` onto `path`
+fn find_path<'e>(
+    expr: &'e SpannedExpr<Symbol>,
+    span: Span<BytePos>,
+    path: &mut Vec<&'e SpannedExpr<Symbol>>,
+) {
+    struct Children<'e> {
+        children: Vec<&'e SpannedExpr<Symbol>>,
+    }
+
+    impl<'e> Visitor<'e> for Children<'e> {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, e: &'e SpannedExpr<Symbol>) {
+            self.children.push(e);
+        }
+    }
+
+    if !expr.span.contains(span) {
+        return;
+    }
+    path.push(expr);
+
+    let mut children = Children {
+        children: Vec::new(),
+    };
+    ast::walk_expr(&mut children, expr);
+    if let Some(child) = children
+        .children
+        .into_iter()
+        .find(|child| child.span.contains(span))
+    {
+        find_path(child, span, path);
+    }
+}
+
+/// Builds an expression from the last element of `path`, wrapped in the bindings of the
+/// enclosing `let` and `type` expressions which it (transitively) references
+fn slice(path: &[&SpannedExpr<Symbol>]) -> SpannedExpr<Symbol> {
+    let (target, enclosing) = path.split_last().expect("Non-empty path");
+
+    let mut needed = FnvSet::default();
+    FreeNames { names: &mut needed }.visit_expr(target);
+
+    let mut result = (*target).clone();
+    for parent in enclosing.iter().rev() {
+        match parent.value {
+            Expr::LetBindings(ref bindings, _) => {
+                let kept = keep_bindings(
+                    bindings,
+                    &mut needed,
+                    value_binding_defines,
+                    |names, bind| FreeNames { names }.visit_value_binding(bind),
+                );
+                if !kept.is_empty() {
+                    result = pos::spanned(parent.span, Expr::LetBindings(kept, Box::new(result)));
+                }
+            }
+            Expr::TypeBindings(ref bindings, _) => {
+                let kept = keep_bindings(
+                    bindings,
+                    &mut needed,
+                    type_binding_defines,
+                    |names, bind| insert_type_names(names, bind.alias.value.unresolved_type()),
+                );
+                if !kept.is_empty() {
+                    result = pos::spanned(parent.span, Expr::TypeBindings(kept, Box::new(result)));
+                }
+            }
+            _ => (),
+        }
+    }
+    result
+}
+
+/// Returns the bindings which define a name in `needed`. The names referenced by each kept
+/// binding are added to `needed` as well, so bindings in the same group which they depend on
+/// are kept as well.
+fn keep_bindings<B, D, F>(
+    bindings: &[B],
+    needed: &mut FnvSet<Symbol>,
+    defines: D,
+    mut add_free_names: F,
+) -> Vec<B>
+where
+    B: Clone,
+    D: Fn(&B, &FnvSet<Symbol>) -> bool,
+    F: FnMut(&mut FnvSet<Symbol>, &B),
+{
+    let mut kept = vec![false; bindings.len()];
+    loop {
+        let mut changed = false;
+        for (bind, kept) in bindings.iter().zip(&mut kept) {
+            if !*kept && defines(bind, needed) {
+                *kept = true;
+                changed = true;
+                add_free_names(needed, bind);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    bindings
+        .iter()
+        .zip(kept)
+        .filter(|&(_, kept)| kept)
+        .map(|(bind, _)| bind.clone())
+        .collect()
+}
+
+fn value_binding_defines(bind: &ValueBinding<Symbol>, needed: &FnvSet<Symbol>) -> bool {
+    let mut binders = Vec::new();
+    pattern_binders(&bind.name.value, &mut binders);
+    binders.iter().any(|binder| needed.contains(binder))
+}
+
+fn type_binding_defines(bind: &TypeBinding<Symbol>, needed: &FnvSet<Symbol>) -> bool {
+    if needed.contains(&bind.name.value) {
+        return true;
+    }
+    // Constructors of a variant type are only defined by the type binding
+    match **types::remove_forall(bind.alias.value.unresolved_type()) {
+        Type::Variant(ref row) => types::row_iter(row).any(|field| needed.contains(&field.name)),
+        _ => false,
+    }
+}
+
+fn pattern_binders(pattern: &Pattern<Symbol>, binders: &mut Vec<Symbol>) {
+    match *pattern {
+        Pattern::Ident(ref id) | Pattern::StringAffix { rest: ref id, .. } => {
+            binders.push(id.name.clone())
+        }
+        Pattern::As(ref id, ref pattern) => {
+            binders.push(id.clone());
+            pattern_binders(&pattern.value, binders);
+        }
+        Pattern::Record {
+            ref types,
+            ref fields,
+            ..
+        } => {
+            for field in types {
+                binders.push(field.value.clone().unwrap_or_else(|| field.name.value.clone()));
+            }
+            for field in fields {
+                match field.value {
+                    Some(ref pattern) => pattern_binders(&pattern.value, binders),
+                    None => binders.push(field.name.value.clone()),
+                }
+            }
+        }
+        Pattern::Tuple { ref elems, .. }
+        | Pattern::Constructor(_, ref elems)
+        | Pattern::Or(ref elems) => for elem in elems {
+            pattern_binders(&elem.value, binders);
+        },
+        Pattern::Literal(_) | Pattern::Error => (),
+    }
+}
+
+fn insert_type_names<T>(names: &mut FnvSet<Symbol>, typ: &T)
+where
+    T: Deref<Target = Type<Symbol, T>>,
+{
+    types::walk_type(typ, |typ: &T| match **typ {
+        Type::Ident(ref id) => {
+            names.insert(id.clone());
+        }
+        Type::Alias(ref alias) => {
+            names.insert(alias.name.clone());
+        }
+        _ => (),
+    });
+}
+
+/// Collects every name referenced by an expression. As the expression has been renamed, names
+/// bound inside of it never collide with the names of enclosing bindings.
+struct FreeNames<'n> {
+    names: &'n mut FnvSet<Symbol>,
+}
+
+impl<'n> FreeNames<'n> {
+    fn visit_value_binding(&mut self, bind: &ValueBinding<Symbol>) {
+        if let Some(ref typ) = bind.typ {
+            insert_type_names(self.names, typ);
+        }
+        self.visit_pattern(&bind.name);
+        self.visit_expr(&bind.expr);
+    }
+}
+
+impl<'a, 'n> Visitor<'a> for FreeNames<'n> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, e: &'a SpannedExpr<Symbol>) {
+        match e.value {
+            Expr::Ident(ref id) => {
+                self.names.insert(id.name.clone());
+            }
+            Expr::Infix { ref op, .. } => {
+                self.names.insert(op.value.name.clone());
+            }
+            Expr::Record {
+                ref types,
+                ref exprs,
+                ..
+            } => {
+                for field in types {
+                    self.names.insert(field.name.value.clone());
+                }
+                for field in exprs.iter().filter(|field| field.value.is_none()) {
+                    self.names.insert(field.name.value.clone());
+                }
+            }
+            Expr::LetBindings(ref bindings, _) => for bind in bindings {
+                if let Some(ref typ) = bind.typ {
+                    insert_type_names(self.names, typ);
+                }
+            },
+            Expr::TypeBindings(ref bindings, _) => for bind in bindings {
+                insert_type_names(self.names, bind.alias.value.unresolved_type());
+            },
+            Expr::Do(Do {
+                flat_map_id: Some(ref flat_map_id),
+                ..
+            }) => self.visit_expr(flat_map_id),
+            _ => (),
+        }
+        ast::walk_expr(self, e);
+    }
+
+    fn visit_pattern(&mut self, p: &'a ast::SpannedPattern<Symbol>) {
+        if let Pattern::Constructor(ref id, _) = p.value {
+            self.names.insert(id.name.clone());
+        }
+        ast::walk_pattern(self, &p.value);
+    }
+
+    fn visit_typ(&mut self, typ: &'a ArcType) {
+        insert_type_names(self.names, typ);
+    }
+}
@@ -18,7 +18,6 @@ pub enum Error<T> {
 impl<T> fmt::Display for Error<T>
 where
     T: fmt::Display,
-    T: for<'a> types::ToDoc<'a, ::pretty::Arena<'a>, ()>,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use self::Error::*;
@@ -224,6 +223,7 @@ impl<T: Substitutable> Substitution<T> {
     }
 
     pub fn clear(&mut self) {
+        *self.union.get_mut() = QuickFindUf::new(0);
         self.types.clear();
         self.variables.clear();
     }
@@ -387,7 +387,7 @@ impl Substitution<ArcType> {
                 };
                 result.or(replacement)
             }
-            _ => types::walk_move_type_opt(
+            _ => types::walk_move_type_spine_opt(
                 typ,
                 &mut types::ControlVisitation(|typ: &ArcType| self.set_type_(typ)),
             ),
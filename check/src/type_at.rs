@@ -0,0 +1,119 @@
+//! Finds the type of the expression or binding at a position in a typechecked expression.
+
+use std::cmp::Ordering;
+
+use base::ast::{walk_expr, walk_pattern, Argument, Do, Expr, Pattern, SpannedExpr, SpannedPattern,
+                Visitor};
+use base::pos::{BytePos, Span};
+use base::symbol::Symbol;
+use base::types::{ArcType, Type};
+
+struct TypeAt {
+    pos: BytePos,
+    found: Option<(Span<BytePos>, ArcType)>,
+}
+
+impl TypeAt {
+    fn candidate(&mut self, span: Span<BytePos>, typ: &ArcType) {
+        if span.containment(&self.pos) != Ordering::Equal {
+            return;
+        }
+        match **typ {
+            Type::Variable(_) | Type::Hole => return,
+            _ => (),
+        }
+        let smaller = match self.found {
+            Some((found_span, _)) => {
+                span.end.to_usize() - span.start.to_usize()
+                    <= found_span.end.to_usize() - found_span.start.to_usize()
+            }
+            None => true,
+        };
+        if smaller {
+            self.found = Some((span, typ.clone()));
+        }
+    }
+
+    fn arguments(&mut self, args: &[Argument<Symbol>]) {
+        for arg in args {
+            self.candidate(arg.name.span, &arg.name.value.typ);
+        }
+    }
+}
+
+impl<'a> Visitor<'a> for TypeAt {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<Symbol>) {
+        match expr.value {
+            Expr::Ident(ref id) => self.candidate(expr.span, &id.typ),
+            Expr::Projection(_, _, ref typ) => self.candidate(expr.span, typ),
+            Expr::Lambda(ref lambda) => self.arguments(&lambda.args),
+            Expr::LetBindings(ref bindings, _) => for bind in bindings {
+                self.arguments(&bind.args);
+            },
+            Expr::Do(Do { ref id, .. }) => self.candidate(id.span, &id.value.typ),
+            _ => (),
+        }
+        walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'a SpannedPattern<Symbol>) {
+        match pattern.value {
+            Pattern::Ident(ref id) => self.candidate(pattern.span, &id.typ),
+            Pattern::Record {
+                ref typ,
+                ref fields,
+                ..
+            } => {
+                let typ = remove_aliases(typ.clone());
+                for field in fields {
+                    let field_type = typ.row_iter()
+                        .find(|f| f.name.name_eq(&field.name.value))
+                        .map(|f| f.typ.clone());
+                    if let Some(field_type) = field_type {
+                        self.candidate(field.name.span, &field_type);
+                    }
+                }
+            }
+            _ => (),
+        }
+        walk_pattern(self, &pattern.value);
+    }
+}
+
+/// Removes the aliases around `typ`. The aliases in a typechecked expression contain their
+/// definitions so no environment is needed to look them up.
+fn remove_aliases(mut typ: ArcType) -> ArcType {
+    loop {
+        let next = {
+            let (alias, args) = match *typ {
+                Type::Alias(ref alias) => (alias, &[][..]),
+                Type::App(ref f, ref args) => match **f {
+                    Type::Alias(ref alias) => (alias, &args[..]),
+                    _ => return typ.clone(),
+                },
+                _ => return typ.clone(),
+            };
+            if **alias.unresolved_type().remove_forall() == Type::Opaque {
+                return typ.clone();
+            }
+            match alias.typ().apply_args(args) {
+                Some(next) => next,
+                None => return typ.clone(),
+            }
+        };
+        typ = next;
+    }
+}
+
+/// Returns the type of the innermost identifier, projection, function argument or binding in a
+/// pattern which covers `pos`, along with its span. Nodes whose types were not inferred (type
+/// variables and `_`) are skipped.
+///
+/// `expr` needs to have been typechecked for the types to be filled in.
+pub fn type_at(expr: &SpannedExpr<Symbol>, pos: BytePos) -> Option<(Span<BytePos>, ArcType)> {
+    let mut visitor = TypeAt { pos, found: None };
+    visitor.visit_expr(expr);
+    visitor.found
+}
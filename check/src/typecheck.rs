@@ -2,24 +2,29 @@
 //! etc. Only checks which need to be aware of expressions are handled here the actual unifying and
 //! checking of types are done in the `unify_type` and `kindcheck` modules.
 use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::cmp;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
 use std::iter::once;
 use std::mem;
 
 use itertools::Itertools;
 
 use base::scoped_map::ScopedMap;
-use base::ast::{Argument, AstType, DisplayEnv, Do, Expr, Literal, MutVisitor, Pattern,
-                PatternField, SpannedExpr, SpannedIdent, SpannedPattern, TypeBinding, Typed,
-                TypedIdent, ValueBinding};
-use base::error::Errors;
-use base::fnv::{FnvMap, FnvSet};
+use base::ast::{self, Alternative, Argument, AstType, DisplayEnv, Do, Expr, Literal, MutVisitor,
+                Pattern, PatternField, SpannedExpr, SpannedIdent, SpannedPattern, TypeBinding,
+                Typed, TypedIdent, ValueBinding};
+use base::error::{Errors, Fix};
+use base::fnv::{FnvHasher, FnvMap, FnvSet};
 use base::metadata::{Metadata, MetadataEnv};
 use base::resolve;
 use base::kind::{ArcKind, Kind, KindCache, KindEnv};
 use base::merge;
-use base::pos::{self, BytePos, Span, Spanned};
-use base::symbol::{Symbol, SymbolModule, SymbolRef, Symbols};
+use base::pos::{self, BytePos, Location, Span, Spanned};
+use base::source::Source;
+use base::symbol::{Name, Symbol, SymbolModule, SymbolRef, Symbols};
 use base::types::{self, Alias, AliasRef, AppVec, ArcType, ArgType, BuiltinType, Field, Filter,
                   Generic, PrimitiveEnv, RecordSelector, Skolem, Type, TypeCache, TypeEnv,
                   TypeFormatter, TypeVariable};
@@ -32,26 +37,47 @@ use unify_type::{self, new_skolem_scope, Error as UnifyTypeError};
 /// Type representing a single error when checking a type
 #[derive(Debug, PartialEq)]
 pub enum TypeError<I> {
-    /// Variable has not been defined before it was used
-    UndefinedVariable(I),
+    /// Variable has not been defined before it was used. Lists the names in scope which are similar
+    /// to the undefined name
+    UndefinedVariable(I, Vec<String>),
     /// Attempt to call a type which is not a function
     NotAFunction(ArcType<I>),
     /// Type has not been defined before it was used
-    UndefinedType(I),
-    /// Type were expected to have a certain field
-    UndefinedField(ArcType<I>, I),
+    UndefinedType(I, Vec<String>),
+    /// Type were expected to have a certain field. Also contains the fields of the type which are
+    /// similar to the missing field
+    UndefinedField(ArcType<I>, I, Vec<String>),
     /// Constructor type was found in a pattern but did not have the expected number of arguments
     PatternError(ArcType<I>, usize),
+    /// A variant constructor were applied to more arguments than it takes, or to fewer in a
+    /// position where a fully applied constructor were expected
+    ConstructorArityMismatch {
+        constructor: I,
+        expected: usize,
+        actual: usize,
+    },
+    /// A tuple pattern has a different number of elements than the tuple it is matched against
+    TupleArityMismatch { expected: usize, found: usize },
     /// Errors found when trying to unify two types
     Unification(ArcType<I>, ArcType<I>, Vec<UnifyTypeError<I>>),
     /// Error were found when trying to unify the kinds of two types
     KindError(KindCheckError<I>),
+    /// A recursive binding without a type signature were used at a type which contains its own
+    /// type
+    PolymorphicRecursion {
+        binding: I,
+        cycle_type: ArcType<I>,
+    },
     /// Multiple types were declared with the same name in the same expression
     DuplicateTypeDefinition(I),
+    /// A type alias is defined in terms of itself without a variant or function type in between.
+    /// Contains the aliases of the cycle, starting and ending with the reported alias
+    RecursiveAliasWithoutIndirection(Vec<I>),
     /// A field was defined more than once in a record constructor or pattern match
     DuplicateField(String),
-    /// Type is not a type which has any fields
-    InvalidProjection(ArcType<I>),
+    /// Type is not a type which has any fields. Contains the type of the projected expression and
+    /// the type it resolves to once aliases are removed
+    InvalidProjection(ArcType<I>, ArcType<I>),
     /// Expected to find a record with the following fields
     UndefinedRecord {
         fields: Vec<I>,
@@ -63,87 +89,241 @@ pub enum TypeError<I> {
     UnableToResolveImplicit(ArcType<I>, Vec<String>),
     LoopInImplicitResolution(Vec<String>),
     AmbiguousImplicit(Vec<(String, ArcType<I>)>),
+    /// The pattern can never match a value of the scrutinee's type, such as a record pattern
+    /// matching on a variant
+    PatternShapeMismatch {
+        pattern_kind: PatternKind,
+        scrutinee: ArcType<I>,
+    },
+    /// A variable is bound by some but not all of the alternatives of an or-pattern
+    OrPatternMissingBinding(I),
+    /// A variable is bound with a different type by an alternative of an or-pattern than by the
+    /// first alternative
+    OrPatternBindingMismatch {
+        name: I,
+        expected: ArcType<I>,
+        actual: ArcType<I>,
+    },
+    /// A lint which is set to `LintLevel::Deny`
+    Lint(Warning<I>),
+    /// A hole (`_`) were found in an expression. Lists the type expected in its place along with
+    /// some of the bindings in scope which have that type
+    HoleFound {
+        expected: ArcType<I>,
+        bindings_in_scope: Vec<(I, ArcType<I>)>,
+    },
+    /// More errors than the limit set with `Typecheck::set_error_limit` were found. Contains the
+    /// number of errors which were not reported
+    ErrorsNotShown(usize),
+}
+
+/// Type representing a problem found by a lint. Warnings do not stop typechecking from succeeding
+/// unless the lint is set to `LintLevel::Deny`, in which case they are reported as
+/// `TypeError::Lint`
+#[derive(Debug, PartialEq)]
+pub enum Warning<I> {
+    /// A type parameter or `forall` binder has the same name as a type variable bound by an
+    /// enclosing signature
+    ShadowedTypeVariable(I),
+    /// A binding has the same name as a binding imported from a module (the implicit prelude or
+    /// an `import!`)
+    ShadowedImport(I, String),
+    /// A binding has the same name as a local binding which is in scope
+    ShadowedBinding(I),
+    /// A binding has the same name as the bindings in scope but a type which is unrelated to all
+    /// of their types, so it can't be an overload of them
+    ShadowedUnrelatedBinding {
+        name: I,
+        shadowed: ArcType<I>,
+        actual: ArcType<I>,
+    },
+    /// Every value matched by the alternative is already matched by an earlier alternative
+    UnreachablePattern,
+    /// A `let` binding is never referred to
+    UnusedBinding(I),
+}
+
+/// The kinds of patterns which only match values of a certain shape
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternKind {
+    Record,
+    Tuple,
+    Constructor,
+}
+
+impl fmt::Display for PatternKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatternKind::Record => write!(f, "record"),
+            PatternKind::Tuple => write!(f, "tuple"),
+            PatternKind::Constructor => write!(f, "constructor"),
+        }
+    }
+}
+
+/// The number of candidates listed by `TypeError::AmbiguousImplicit`. The remaining candidates
+/// are only counted.
+pub const MAX_PRINTED_CANDIDATES: usize = 5;
+
+/// The number of characters each candidate listed by `TypeError::AmbiguousImplicit` is truncated
+/// to
+pub const MAX_CANDIDATE_WIDTH: usize = 80;
+
+/// The number of bindings listed by `TypeError::HoleFound`
+pub const MAX_HOLE_BINDINGS: usize = 5;
+
+/// The number of distinct aliases which `Help::AliasDefinitions` notes the definitions of
+pub const MAX_ALIAS_DEFINITIONS: usize = 3;
+
+/// Truncates `candidate` to at most `width` characters, ending it with `...` if anything was
+/// removed
+fn truncate_candidate(candidate: String, width: usize) -> String {
+    // Types which span multiple lines are joined into one to keep the list readable
+    let candidate = candidate.split_whitespace().format(" ").to_string();
+    match candidate.char_indices().nth(width.saturating_sub(3)) {
+        Some((end, _)) if candidate.chars().count() > width => {
+            format!("{}...", &candidate[..end])
+        }
+        _ => candidate,
+    }
+}
+
+/// The maximum number of names suggested for an undefined variable or type
+pub const MAX_SIMILAR_NAMES: usize = 3;
+
+/// Returns the names in `candidates` which are close enough to `name` to likely be what was
+/// meant, most similar first
+fn similar_names<'a, I>(name: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = cmp::max(1, name.chars().count() / 3);
+    let lowercase_name = name.to_lowercase();
+    let mut similar: Vec<_> = candidates
+        .into_iter()
+        .filter(|&candidate| candidate != name)
+        .filter_map(|candidate| {
+            // Names which only differ in case (`fold` and `Fold`) are always suggested
+            if candidate.to_lowercase() == lowercase_name {
+                return Some((0, candidate));
+            }
+            // Swapped characters (`nmae` and `name`) only count as a single edit
+            let distance = ::strsim::osa_distance(name, candidate);
+            if distance <= max_distance {
+                Some((distance, candidate))
+            } else {
+                None
+            }
+        })
+        .collect();
+    similar.sort();
+    similar.dedup();
+    similar
+        .into_iter()
+        .take(MAX_SIMILAR_NAMES)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+fn fmt_similar_names(similar: &[String], f: &mut fmt::Formatter) -> fmt::Result {
+    match similar.split_last() {
+        None => Ok(()),
+        Some((last, init)) if init.is_empty() => write!(f, "\nDid you mean `{}`?", last),
+        Some((last, init)) => write!(
+            f,
+            "\nDid you mean {} or `{}`?",
+            init.iter().map(|name| format!("`{}`", name)).format(", "),
+            last
+        ),
+    }
 }
 
 impl<I> From<KindCheckError<I>> for TypeError<I> {
     fn from(e: KindCheckError<I>) -> Self {
         match e {
-            UnifyError::Other(KindError::UndefinedType(name)) => TypeError::UndefinedType(name),
+            UnifyError::Other(KindError::UndefinedType(name)) => {
+                TypeError::UndefinedType(name, Vec::new())
+            }
             e => TypeError::KindError(e),
         }
     }
 }
 
-impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
+impl<I: fmt::Display + AsRef<str> + Clone + PartialEq> fmt::Display for TypeError<I> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use self::TypeError::*;
-        use pretty::{Arena, DocAllocator};
         match *self {
-            UndefinedVariable(ref name) => write!(f, "Undefined variable `{}`", name),
+            UndefinedVariable(ref name, ref similar) => {
+                write!(f, "Undefined variable `{}`", name)?;
+                fmt_similar_names(similar, f)
+            }
             NotAFunction(ref typ) => write!(f, "`{}` is not a function", typ),
-            UndefinedType(ref name) => write!(f, "Type `{}` is not defined", name),
-            UndefinedField(ref typ, ref field) => {
-                write!(f, "Type `{}` does not have the field `{}`", typ, field)
+            UndefinedType(ref name, ref similar) => {
+                write!(f, "Type `{}` is not defined", name)?;
+                fmt_similar_names(similar, f)
+            }
+            UndefinedField(ref typ, ref field, ref similar) => {
+                write!(f, "Type `{}` does not have the field `{}`", typ, field)?;
+                if !similar.is_empty() {
+                    write!(
+                        f,
+                        ". Closest matches: {}",
+                        similar.iter().map(|name| format!("`{}`", name)).format(", ")
+                    )?;
+                }
+                Ok(())
             }
             Unification(ref expected, ref actual, ref errors) => {
-                let filters = errors
-                    .iter()
-                    .filter_map(|err| match *err {
-                        UnifyError::Other(ref err) => Some(err.make_filter()),
-                        _ => None,
-                    })
-                    .collect::<Vec<_>>();
-                let filter = move |field: &I| {
-                    if filters.is_empty() {
-                        Filter::Retain
-                    } else {
-                        filters
-                            .iter()
-                            .fold(Filter::Drop, move |filter, f| match filter {
-                                Filter::Retain => filter,
-                                _ => match f(field) {
-                                    Filter::Drop => filter,
-                                    Filter::RetainKey => Filter::RetainKey,
-                                    Filter::Retain => Filter::Retain,
-                                },
-                            })
+                // The complete types are only shown for records when asked for with `{:#}`
+                if !f.alternate() {
+                    if let Some(diff) = RecordDiff::new(expected, actual) {
+                        return write!(f, "{}", diff);
                     }
-                };
-
-                let arena = Arena::new();
-                let types = chain![&arena;
-                    "Expected:",
-                    chain![&arena;
-                        arena.space(),
-                        TypeFormatter::new(expected).filter(&filter).pretty(&arena)
-                    ].nest(4).group(),
-                    arena.newline(),
-                    "Found:",
-                    chain![&arena;
-                        arena.space(),
-                        TypeFormatter::new(actual).filter(&filter).pretty(&arena)
-                    ].nest(4).group()
-                ].group();
-                let doc = chain![&arena;
-                    "Expected the following types to be equal",
-                    arena.newline(),
-                    types,
-                    arena.newline(),
-                    arena.as_string(errors.len()),
-                    " errors were found during unification:"
-                ];
-                writeln!(f, "{}", doc.1.pretty(80))?;
+                }
+                let filter = unification_filter(errors);
+                fmt_unification_types(expected, actual, &*filter, errors.len(), f)?;
                 if errors.is_empty() {
                     return Ok(());
                 }
                 for error in &errors[..errors.len() - 1] {
                     match *error {
-                        UnifyError::Other(ref err) => err.filter_fmt(&filter, f)?,
+                        UnifyError::Other(ref err) => err.filter_fmt(&*filter, f)?,
                         _ => writeln!(f, "{}", error)?,
                     }
                 }
                 write!(f, "{}", errors.last().unwrap())
             }
+            ConstructorArityMismatch {
+                ref constructor,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "constructor `{}` takes {} argument{} but {} {} supplied",
+                // Only the declared name, without the module the constructor were defined in
+                constructor.as_ref().rsplit('.').next().unwrap(),
+                expected,
+                if expected == 1 { "" } else { "s" },
+                actual,
+                if actual == 1 { "was" } else { "were" }
+            ),
+            PolymorphicRecursion {
+                ref binding,
+                ref cycle_type,
+            } => write!(
+                f,
+                "`{}` is used recursively at the type `{}` which contains its own type.\n\
+                 Recursive uses must have the same type as the binding unless it has a type \
+                 signature",
+                binding, cycle_type
+            ),
+            TupleArityMismatch { expected, found } => write!(
+                f,
+                "tuple pattern has {} element{} but the matched tuple has {}",
+                found,
+                if found == 1 { "" } else { "s" },
+                expected
+            ),
             PatternError(ref typ, expected_len) => {
                 write!(f, "Type {} has {} to few arguments", typ, expected_len)
             }
@@ -153,14 +333,35 @@ impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
                 "Type '{}' has been already been defined in this module",
                 id
             ),
+            RecursiveAliasWithoutIndirection(ref cycle) => {
+                write!(
+                    f,
+                    "The type `{}` is defined in terms of itself without a variant or function \
+                     type in between: ",
+                    cycle[0]
+                )?;
+                for (i, name) in cycle.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " -> ")?;
+                    }
+                    write!(f, "{}", name)?;
+                }
+                Ok(())
+            }
             DuplicateField(ref id) => {
                 write!(f, "The record has more than one field named '{}'", id)
             }
-            InvalidProjection(ref typ) => write!(
-                f,
-                "Type '{}' is not a type which allows field accesses",
-                typ
-            ),
+            InvalidProjection(ref typ, ref resolved) => {
+                write!(
+                    f,
+                    "Type '{}' is not a type which allows field accesses",
+                    typ
+                )?;
+                if typ != resolved {
+                    write!(f, "\nThe type resolves to `{}`", resolved)?;
+                }
+                Ok(())
+            }
             UndefinedRecord { ref fields } => {
                 write!(f, "No type found with the following fields: ")?;
                 write!(f, "{}", fields[0])?;
@@ -170,6 +371,7 @@ impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
                 Ok(())
             }
             EmptyCase => write!(f, "`case` expression with no alternatives"),
+            ErrorsNotShown(count) => write!(f, "{} additional errors not shown", count),
             Message(ref msg) => write!(f, "{}", msg),
             UnableToResolveImplicit(ref typ, ref paths) => write!(
                 f,
@@ -182,23 +384,586 @@ impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
                 "Unable to resolve implicit, possible infinite loop. When resolving, {}",
                 paths.iter().format(", ")
             ),
-            AmbiguousImplicit(ref candidates) => write!(
-                f,
-                "Unable to resolve implicit. Multiple candidates were found: {}",
-                candidates
+            AmbiguousImplicit(ref candidates) => {
+                // Sort the candidates so the message does not depend on the order the implicit
+                // bindings were found in
+                let mut candidates: Vec<_> = candidates
                     .iter()
-                    .format_with(", ", |&(ref path, ref typ), fmt| fmt(&format_args!(
-                        "{}: {}",
-                        path, typ
-                    )))
+                    .map(|&(ref path, ref typ)| {
+                        truncate_candidate(format!("{}: {}", path, typ), MAX_CANDIDATE_WIDTH)
+                    })
+                    .collect();
+                candidates.sort();
+                write!(
+                    f,
+                    "Unable to resolve implicit. Multiple candidates were found: {}",
+                    candidates
+                        .iter()
+                        .take(MAX_PRINTED_CANDIDATES)
+                        .format(", ")
+                )?;
+                if candidates.len() > MAX_PRINTED_CANDIDATES {
+                    write!(f, ", +{} more", candidates.len() - MAX_PRINTED_CANDIDATES)?;
+                }
+                Ok(())
+            }
+            PatternShapeMismatch {
+                pattern_kind,
+                ref scrutinee,
+            } => write!(
+                f,
+                "A {} pattern can never match a value of type `{}`",
+                pattern_kind, scrutinee
+            ),
+            OrPatternMissingBinding(ref id) => write!(
+                f,
+                "`{}` is not bound by every alternative of the or-pattern",
+                id
+            ),
+            OrPatternBindingMismatch {
+                ref name,
+                ref expected,
+                ref actual,
+            } => write!(
+                f,
+                "`{}` has the type `{}` in this alternative of the or-pattern but the type `{}` in \
+                 the first alternative",
+                name, actual, expected
             ),
+            Lint(ref warning) => write!(f, "{}", warning),
+            HoleFound {
+                ref expected,
+                ref bindings_in_scope,
+            } => {
+                write!(f, "Found a hole with the type `{}`", expected)?;
+                if !bindings_in_scope.is_empty() {
+                    write!(f, "\nBindings in scope with a matching type:")?;
+                    for &(ref name, ref typ) in bindings_in_scope {
+                        write!(f, "\n    {} : {}", name, typ)?;
+                    }
+                }
+                Ok(())
+            }
         }
     }
 }
 
+impl<I: fmt::Display + AsRef<str>> fmt::Display for Warning<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Warning::*;
+        match *self {
+            ShadowedTypeVariable(ref id) => write!(
+                f,
+                "Type variable `{}` shadows a type variable of the same name bound by an \
+                 enclosing signature",
+                id
+            ),
+            ShadowedImport(ref id, ref module) => write!(
+                f,
+                "`{}` shadows the binding of the same name imported from `{}`",
+                id, module
+            ),
+            ShadowedBinding(ref id) => write!(
+                f,
+                "`{}` shadows a binding of the same name which is in scope",
+                id
+            ),
+            ShadowedUnrelatedBinding {
+                ref name,
+                ref shadowed,
+                ref actual,
+            } => write!(
+                f,
+                "`{}` has the type `{}` which is unrelated to the type `{}` of the binding of the \
+                 same name that it shadows",
+                name, actual, shadowed
+            ),
+            UnreachablePattern => write!(
+                f,
+                "Unreachable pattern. Every value it matches is matched by an earlier alternative"
+            ),
+            UnusedBinding(ref id) => write!(f, "`{}` is bound but never used", id),
+        }
+    }
+}
+
+/// Returns the fields of `typ` if it is a record type or an alias of one
+fn record_fields<I>(typ: &ArcType<I>) -> Option<Vec<(I, ArcType<I>)>>
+where
+    I: Clone + PartialEq,
+{
+    let typ = typ.remove_forall();
+    match **typ {
+        Type::Record(_) => Some(
+            typ.row_iter()
+                .map(|field| (field.name.clone(), field.typ.clone()))
+                .collect(),
+        ),
+        Type::Alias(ref alias) if alias.params().is_empty() => record_fields(&alias.typ()),
+        _ => None,
+    }
+}
+
+/// Returns the number of elements in `typ` if it is a closed record with only the fields
+/// `_0, _1, ..` of a tuple
+fn tuple_arity(typ: &ArcType) -> Option<usize> {
+    match **typ {
+        Type::Record(ref row) => match **row {
+            Type::ExtendRow { ref types, .. } if !types.is_empty() => return None,
+            _ => (),
+        },
+        _ => return None,
+    }
+    let mut iter = typ.row_iter();
+    let mut arity = 0;
+    for field in iter.by_ref() {
+        if field.name.declared_name() != format!("_{}", arity) {
+            return None;
+        }
+        arity += 1;
+    }
+    if arity != 0 && **iter.current_type() == Type::EmptyRow {
+        Some(arity)
+    } else {
+        None
+    }
+}
+
+/// Returns the name of the function `func` if it is a variable or a field of one, such as `f` or
+/// `list.map`
+fn callee_name(func: &SpannedExpr<Symbol>) -> Option<String> {
+    match func.value {
+        Expr::Ident(ref id) => Some(id.name.declared_name().to_string()),
+        Expr::Projection(ref expr, ref field, _) => callee_name(expr)
+            .map(|name| format!("{}.{}", name, field.declared_name())),
+        _ => None,
+    }
+}
+
+/// Collects the indexes of the aliases in `names` which `typ` refers to without going through a
+/// variant or function type. The arguments of applied types are not followed as their definitions
+/// may not expand them.
+fn direct_alias_references(
+    names: &[&Symbol],
+    typ: &AstType<Symbol>,
+    references: &mut Vec<usize>,
+) {
+    match **typ {
+        Type::Ident(ref id) => {
+            if let Some(i) = names.iter().position(|name| *name == id) {
+                if !references.contains(&i) {
+                    references.push(i);
+                }
+            }
+        }
+        Type::App(ref f, _) => direct_alias_references(names, f, references),
+        Type::Forall(_, ref typ, _) | Type::Record(ref typ) => {
+            direct_alias_references(names, typ, references)
+        }
+        Type::ExtendRow {
+            ref fields,
+            ref rest,
+            ..
+        } => {
+            for field in fields {
+                direct_alias_references(names, &field.typ, references);
+            }
+            direct_alias_references(names, rest, references);
+        }
+        _ => (),
+    }
+}
+
+/// Returns a path of aliases from `start` which leads back to `start` through `references`
+fn find_alias_cycle(references: &[Vec<usize>], start: usize) -> Option<Vec<usize>> {
+    fn search(
+        references: &[Vec<usize>],
+        start: usize,
+        path: &mut Vec<usize>,
+        visited: &mut [bool],
+    ) -> bool {
+        let current = *path.last().unwrap();
+        for &next in &references[current] {
+            if next == start {
+                return true;
+            }
+            if !visited[next] {
+                visited[next] = true;
+                path.push(next);
+                if search(references, start, path, visited) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+
+    let mut path = vec![start];
+    let mut visited = vec![false; references.len()];
+    visited[start] = true;
+    if search(references, start, &mut path, &mut visited) {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Collects the variables bound by the identifier and record patterns in `pattern` along with
+/// where they are bound
+fn pattern_variables<'p>(
+    pattern: &'p SpannedPattern<Symbol>,
+    variables: &mut Vec<(&'p Symbol, Span<BytePos>)>,
+) {
+    match pattern.value {
+        Pattern::Ident(ref id) => variables.push((&id.name, pattern.span)),
+        Pattern::Record { ref fields, .. } => for field in fields {
+            match field.value {
+                Some(ref pattern) => pattern_variables(pattern, variables),
+                None => variables.push((&field.name.value, field.name.span)),
+            }
+        },
+        _ => (),
+    }
+}
+
+/// Collects every variable bound by `pattern` along with the span which binds it
+fn bound_variables<'p>(
+    pattern: &'p SpannedPattern<Symbol>,
+    variables: &mut Vec<(&'p Symbol, Span<BytePos>)>,
+) {
+    match pattern.value {
+        Pattern::Ident(ref id)
+        | Pattern::StringAffix {
+            rest: ref id, ..
+        } => variables.push((&id.name, pattern.span)),
+        Pattern::As(ref id, ref pat) => {
+            variables.push((id, pattern.span));
+            bound_variables(pat, variables);
+        }
+        Pattern::Record { ref fields, .. } => for field in fields {
+            match field.value {
+                Some(ref pattern) => bound_variables(pattern, variables),
+                None => variables.push((&field.name.value, field.name.span)),
+            }
+        },
+        Pattern::Tuple { ref elems, .. }
+        | Pattern::Constructor(_, ref elems)
+        | Pattern::Or(ref elems) => for elem in elems {
+            bound_variables(elem, variables);
+        },
+        Pattern::Literal(_) | Pattern::Error => (),
+    }
+}
+
+/// Returns `true` if `typ` or any type inside it matches `predicate`
+fn contains_type<F>(typ: &ArcType, mut predicate: F) -> bool
+where
+    F: FnMut(&ArcType) -> bool,
+{
+    let mut found = false;
+    types::walk_type(typ, |inner: &ArcType| {
+        found = found || predicate(inner);
+    });
+    found
+}
+
+/// Returns `true` if `typ` contains the type given to expressions which failed to typecheck
+fn contains_hole(typ: &ArcType) -> bool {
+    contains_type(typ, |inner| match **inner {
+        Type::Hole => true,
+        _ => false,
+    })
+}
+
+/// Returns `true` if `typ` contains any type variables, i.e. if it is not fully inferred
+fn contains_variables(typ: &ArcType) -> bool {
+    contains_type(typ, |inner| match **inner {
+        Type::Variable(_) => true,
+        _ => false,
+    })
+}
+
+/// Returns true if the variable `var` appears in `typ` or in the types its variables have been
+/// substituted with
+fn contains_variable(subs: &Substitution<ArcType>, typ: &ArcType, var: &ArcType) -> bool {
+    let mut found = false;
+    types::walk_type(typ, |inner: &ArcType| {
+        if let Type::Variable(_) = **inner {
+            let resolved = subs.real(inner);
+            found = found || inner == var
+                || (resolved != inner && contains_variable(subs, resolved, var));
+        }
+    });
+    found
+}
+
+/// Replaces the aliases in `typ` which do not take any parameters with the type they alias
+fn expand_aliases<I>(typ: &ArcType<I>) -> ArcType<I>
+where
+    I: Clone + PartialEq,
+{
+    expand_aliases_(typ, &mut Vec::new())
+}
+
+/// Recursive aliases are only expanded once. `expanding` holds the aliases which are being
+/// expanded
+fn expand_aliases_<I>(typ: &ArcType<I>, expanding: &mut Vec<I>) -> ArcType<I>
+where
+    I: Clone + PartialEq,
+{
+    types::walk_move_type(typ.clone(), &mut |typ: &ArcType<I>| match **typ {
+        Type::Alias(ref alias)
+            if alias.params().is_empty() && !expanding.contains(&alias.name) =>
+        {
+            expanding.push(alias.name.clone());
+            let typ = expand_aliases_(alias.typ().remove_forall(), expanding);
+            expanding.pop();
+            Some(typ)
+        }
+        _ => None,
+    })
+}
+
+/// The fields which differ between two record types which failed to unify. Printing the
+/// differences is a lot more readable than printing both types when the records are large.
+struct RecordDiff<I> {
+    /// Fields of the found record which the expected record lacks
+    missing_from_expected: Vec<String>,
+    /// Fields of the expected record which the found record lacks
+    missing_from_actual: Vec<String>,
+    /// Fields which exist in both records but with different types as `(path, expected, actual)`
+    mismatched: Vec<(String, ArcType<I>, ArcType<I>)>,
+}
+
+impl<I> RecordDiff<I>
+where
+    I: fmt::Display + AsRef<str> + Clone + PartialEq,
+{
+    /// Returns the differences between `expected` and `actual` if both are records with fields
+    fn new(expected: &ArcType<I>, actual: &ArcType<I>) -> Option<RecordDiff<I>> {
+        let expected = record_fields(expected)?;
+        let actual = record_fields(actual)?;
+        if expected.is_empty() || actual.is_empty() {
+            return None;
+        }
+        let mut diff = RecordDiff {
+            missing_from_expected: Vec::new(),
+            missing_from_actual: Vec::new(),
+            mismatched: Vec::new(),
+        };
+        diff.diff_fields("", &expected, &actual);
+        if diff.missing_from_expected.is_empty() && diff.missing_from_actual.is_empty()
+            && diff.mismatched.is_empty()
+        {
+            None
+        } else {
+            Some(diff)
+        }
+    }
+
+    fn diff_fields(
+        &mut self,
+        path: &str,
+        expected: &[(I, ArcType<I>)],
+        actual: &[(I, ArcType<I>)],
+    ) {
+        for &(ref name, ref expected_type) in expected {
+            let field_path = format!("{}{}", path, name);
+            let actual_type = match actual
+                .iter()
+                .find(|field| field.0.as_ref() == name.as_ref())
+            {
+                Some(&(_, ref actual_type)) => actual_type,
+                None => {
+                    self.missing_from_actual.push(field_path);
+                    continue;
+                }
+            };
+            if expand_aliases(expected_type) == expand_aliases(actual_type) {
+                continue;
+            }
+            // Mismatches between nested records are reported for the fields which differ
+            match (record_fields(expected_type), record_fields(actual_type)) {
+                (Some(ref expected), Some(ref actual))
+                    if !expected.is_empty() && !actual.is_empty() =>
+                {
+                    self.diff_fields(&format!("{}.", field_path), expected, actual)
+                }
+                _ => self.mismatched.push((
+                    field_path,
+                    expected_type.clone(),
+                    actual_type.clone(),
+                )),
+            }
+        }
+        for &(ref name, _) in actual {
+            if expected
+                .iter()
+                .all(|field| field.0.as_ref() != name.as_ref())
+            {
+                self.missing_from_expected.push(format!("{}{}", path, name));
+            }
+        }
+    }
+}
+
+impl<I> fmt::Display for RecordDiff<I>
+where
+    I: AsRef<str>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Expected the record types to be equal but they differ in the following fields"
+        )?;
+        if !self.missing_from_actual.is_empty() {
+            write!(
+                f,
+                "\nMissing from the found type: {}",
+                self.missing_from_actual.iter().format(", ")
+            )?;
+        }
+        if !self.missing_from_expected.is_empty() {
+            write!(
+                f,
+                "\nMissing from the expected type: {}",
+                self.missing_from_expected.iter().format(", ")
+            )?;
+        }
+        for &(ref path, ref expected, ref actual) in &self.mismatched {
+            write!(f, "\n    {}: expected {}, found {}", path, expected, actual)?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates a filter which only retains the record fields that are relevant to `errors`
+fn unification_filter<'a, I>(
+    errors: &'a [UnifyTypeError<I>],
+) -> Box<Fn(&I) -> Filter + 'a>
+where
+    I: fmt::Display + AsRef<str>,
+{
+    let filters = errors
+        .iter()
+        .filter_map(|err| match *err {
+            UnifyError::Other(ref err) => Some(err.make_filter()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    Box::new(move |field: &I| {
+        if filters.is_empty() {
+            Filter::Retain
+        } else {
+            filters
+                .iter()
+                .fold(Filter::Drop, move |filter, f| match filter {
+                    Filter::Retain => filter,
+                    _ => match f(field) {
+                        Filter::Drop => filter,
+                        Filter::RetainKey => Filter::RetainKey,
+                        Filter::Retain => Filter::Retain,
+                    },
+                })
+        }
+    })
+}
+
+#[cfg(feature = "pretty-errors")]
+fn fmt_unification_types<I>(
+    expected: &ArcType<I>,
+    actual: &ArcType<I>,
+    filter: &Fn(&I) -> Filter,
+    error_count: usize,
+    f: &mut fmt::Formatter,
+) -> fmt::Result
+where
+    I: AsRef<str>,
+{
+    use pretty::{Arena, DocAllocator};
+
+    let layout = ::ErrorLayout::current();
+    let arena = Arena::new();
+    let types = chain![&arena;
+        "Expected:",
+        chain![&arena;
+            arena.space(),
+            TypeFormatter::new(expected)
+                .filter(filter)
+                .max_depth(layout.max_depth)
+                .pretty(&arena)
+        ].nest(4).group(),
+        arena.newline(),
+        "Found:",
+        chain![&arena;
+            arena.space(),
+            TypeFormatter::new(actual)
+                .filter(filter)
+                .max_depth(layout.max_depth)
+                .pretty(&arena)
+        ].nest(4).group()
+    ].group();
+    let doc = chain![&arena;
+        "Expected the following types to be equal",
+        arena.newline(),
+        types,
+        arena.newline(),
+        arena.as_string(error_count),
+        " errors were found during unification:"
+    ];
+    writeln!(f, "{}", doc.1.pretty(layout.width))
+}
+
+#[cfg(not(feature = "pretty-errors"))]
+fn fmt_unification_types<I>(
+    expected: &ArcType<I>,
+    actual: &ArcType<I>,
+    filter: &Fn(&I) -> Filter,
+    error_count: usize,
+    f: &mut fmt::Formatter,
+) -> fmt::Result
+where
+    I: AsRef<str>,
+{
+    let layout = ::ErrorLayout::current();
+    let format = |typ| {
+        TypeFormatter::new(typ)
+            .filter(filter)
+            .width(layout.width)
+            .max_depth(layout.max_depth)
+    };
+    writeln!(f, "Expected the following types to be equal")?;
+    writeln!(f, "Expected: {}", format(expected))?;
+    writeln!(f, "Found: {}", format(actual))?;
+    writeln!(f, "{} errors were found during unification:", error_count)
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Help {
     UndefinedFlatMapInDo,
+    /// The same undefined name were also used at these locations
+    AlsoUsedAt(Vec<Span<BytePos>>),
+    /// The shadowed type variable is bound at this location
+    ShadowedTypeVariableBoundAt(Span<BytePos>),
+    /// The type were previously defined at this location (and line and column, if the source is
+    /// known)
+    PreviouslyDefinedAt(Span<BytePos>, Option<Location>),
+    UseConstructorPattern,
+    UseRecordPattern,
+    /// The field may exist but is private to the module which defines it
+    PrivateField,
+    /// Where the aliases mentioned in the error were defined
+    AliasDefinitions(Vec<(String, AliasOrigin)>),
+}
+
+/// Where an alias were defined
+#[derive(Clone, Debug, PartialEq)]
+pub enum AliasOrigin {
+    /// Defined in the source being checked
+    Location(Location),
+    /// Defined in another module
+    Module(String),
 }
 
 impl fmt::Display for Help {
@@ -209,12 +974,73 @@ impl fmt::Display for Help {
                 "Try bringing the `flat_map` function found in the `Monad`\
                  instance for your type into scope"
             ),
+            Help::AlsoUsedAt(ref spans) => write!(
+                f,
+                "The name is also used in {} other place{}",
+                spans.len(),
+                if spans.len() == 1 { "" } else { "s" }
+            ),
+            Help::PreviouslyDefinedAt(_, Some(ref location)) => write!(
+                f,
+                "The type was previously defined at {}:{}",
+                location.line.to_usize() + 1,
+                location.column.to_usize() + 1
+            ),
+            Help::PreviouslyDefinedAt(_, None) => {
+                write!(f, "The type was previously defined in this module")
+            }
+            Help::ShadowedTypeVariableBoundAt(_) => write!(
+                f,
+                "Rename one of the type variables to refer to the outer variable unambiguously"
+            ),
+            Help::UseConstructorPattern => {
+                write!(f, "Use a constructor pattern like `Some x` instead")
+            }
+            Help::UseRecordPattern => write!(f, "Use a record pattern like `{{ x }}` instead"),
+            Help::PrivateField => write!(
+                f,
+                "Fields starting with `_` or marked with `@private` are private to the module \
+                 which defines them"
+            ),
+            Help::AliasDefinitions(ref aliases) => {
+                for (i, &(ref name, ref origin)) in aliases.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, "\n      ")?;
+                    }
+                    match *origin {
+                        AliasOrigin::Location(ref location) => write!(
+                            f,
+                            "`{}` defined at {}:{}",
+                            name,
+                            location.line.to_usize() + 1,
+                            location.column.to_usize() + 1
+                        )?,
+                        AliasOrigin::Module(ref module) => {
+                            write!(f, "`{}` defined in module {}", name, module)?
+                        }
+                    }
+                }
+                Ok(())
+            }
         }
     }
 }
 
 pub type HelpError<Id> = ::base::error::Help<TypeError<Id>, Help>;
 pub type SpannedTypeError<Id> = Spanned<HelpError<Id>, BytePos>;
+pub type HelpWarning<Id> = ::base::error::Help<Warning<Id>, Help>;
+pub type SpannedWarning<Id> = Spanned<HelpWarning<Id>, BytePos>;
+
+/// How the problems found by a lint are reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintLevel {
+    /// The lint is not checked
+    Allow,
+    /// Problems are reported as warnings which do not stop typechecking from succeeding
+    Warn,
+    /// Problems are reported as errors
+    Deny,
+}
 
 pub(crate) type TcResult<T> = Result<T, TypeError<Symbol>>;
 
@@ -229,6 +1055,10 @@ where
 #[derive(Clone, Debug)]
 struct StackBinding {
     typ: ArcType,
+    /// The module the binding was imported from, if any
+    origin: Option<Symbol>,
+    /// The number of arguments the binding takes if it is a variant constructor
+    constructor_arity: Option<usize>,
 }
 
 pub(crate) struct Environment<'a> {
@@ -291,12 +1121,23 @@ impl<'a> TypeEnv for Environment<'a> {
             .map(|t| ((t.1).0.clone(), (t.1).1.typ().into_owned()))
             .or_else(|| self.environment.find_record(fields, selector))
     }
+
+    fn each_type_info(&self, f: &mut FnMut(&Symbol, &Alias<Symbol, ArcType>)) {
+        for (id, &(_, ref alias)) in self.stack_types.iter() {
+            f(id, alias);
+        }
+        self.environment.each_type_info(f)
+    }
 }
 
 impl<'a> PrimitiveEnv for Environment<'a> {
     fn get_bool(&self) -> &ArcType {
         self.environment.get_bool()
     }
+
+    fn find_primitive_operand(&self, name: &str) -> Option<&ArcType> {
+        self.environment.find_primitive_operand(name)
+    }
 }
 
 impl<'a> MetadataEnv for Environment<'a> {
@@ -314,27 +1155,239 @@ enum TailCall {
     TailCall,
 }
 
+struct Hole {
+    span: Span<BytePos>,
+    typ: ArcType,
+    /// The bindings in scope at the hole, along with the depth of the scope they were bound in
+    bindings: Vec<(usize, Symbol, ArcType)>,
+    /// The innermost binding without a type signature which contains the hole
+    binding: Option<HoleBinding>,
+}
+
+struct HoleBinding {
+    name: Symbol,
+    /// Where a type signature would be inserted (after the name and arguments of the binding)
+    signature_pos: BytePos,
+    typ: ArcType,
+}
+
 /// Struct which provides methods to typecheck expressions.
 pub struct Typecheck<'a> {
     pub(crate) environment: Environment<'a>,
     symbols: SymbolModule<'a>,
     /// Mapping from the fresh symbol generated during typechecking to the symbol that was assigned
     /// during typechecking
-    original_symbols: ScopedMap<Symbol, Symbol>,
+    original_symbols: OriginalSymbols,
     pub(crate) subs: Substitution<ArcType>,
     named_variables: FnvMap<Symbol, ArcType>,
     pub(crate) errors: Errors<SpannedTypeError<Symbol>>,
+    warnings: Errors<SpannedWarning<Symbol>>,
+    /// Holes (`_`) found in expressions. These are reported once typechecking is done so that
+    /// their types are as complete as possible
+    holes: Vec<Hole>,
     /// Type variables `let test: a -> b` (`a` and `b`)
     type_variables: ScopedMap<Symbol, ArcType>,
+    /// Maps the skolems of the type variables bound by signatures to where they were bound
+    type_variable_spans: FnvMap<u32, Span<BytePos>>,
+    shadowed_type_variables: LintLevel,
+    shadowed_imports: LintLevel,
+    shadowed_bindings: LintLevel,
+    unrelated_shadowed_bindings: LintLevel,
+    unreachable_patterns: LintLevel,
+    unused_bindings: LintLevel,
+    /// The local variables which are referred to by the expression being checked
+    used_variables: FnvSet<Symbol>,
+    /// The maximum number of errors which are reported
+    error_limit: Option<usize>,
+    /// The module which the bindings currently being introduced were imported from
+    binding_origin: Option<Symbol>,
+    /// The source of the expression being checked, used to locate definitions mentioned in errors
+    source: Option<Source<'a>>,
+    /// Where the aliases defined in the expression being checked were defined, keyed by their
+    /// scoped names
+    alias_definitions: FnvMap<Symbol, Span<BytePos>>,
+    /// Where the values bound by patterns in the expression being checked were bound
+    pub(crate) binding_spans: FnvMap<Symbol, Span<BytePos>>,
+    /// Set by `typecheck_and_commit` to keep the top level bindings of the expression in scope
+    /// once it has been checked
+    commit_bindings: bool,
     type_cache: TypeCache<Symbol, ArcType>,
     kind_cache: KindCache,
 
     pub(crate) implicit_resolver: ::implicits::ImplicitResolver<'a>,
+    unification_trace: Option<UnificationTrace<'a>>,
+}
+
+/// Maps the symbols bound by the module being checked to the symbols they were originally given.
+///
+/// Chains of renamings are resolved as they are inserted so every lookup is a single probe. A
+/// small counting filter over the hashes of the inserted names lets most lookups of names which
+/// were never renamed skip the map entirely.
+struct OriginalSymbols {
+    map: ScopedMap<Symbol, Symbol>,
+    /// The number of names in `map` which hash to each bucket
+    filter: Vec<u32>,
+    /// The number of lookups which probed `map`
+    probes: Cell<usize>,
+    /// The number of lookups which were answered by `filter` alone
+    skipped: Cell<usize>,
+}
+
+const ORIGINAL_SYMBOLS_BUCKETS: usize = 256;
+
+impl OriginalSymbols {
+    fn new() -> OriginalSymbols {
+        OriginalSymbols {
+            map: ScopedMap::new(),
+            filter: vec![0; ORIGINAL_SYMBOLS_BUCKETS],
+            probes: Cell::new(0),
+            skipped: Cell::new(0),
+        }
+    }
+
+    fn bucket(name: &Symbol) -> usize {
+        let mut hasher = FnvHasher::default();
+        name.hash(&mut hasher);
+        hasher.finish() as usize % ORIGINAL_SYMBOLS_BUCKETS
+    }
+
+    fn get(&self, name: &Symbol) -> Option<&Symbol> {
+        if self.filter[Self::bucket(name)] == 0 {
+            self.skipped.set(self.skipped.get() + 1);
+            return None;
+        }
+        self.probes.set(self.probes.get() + 1);
+        self.map.get(name)
+    }
+
+    /// Maps `name` to `original`, or to what `original` is mapped to if it has been renamed
+    /// itself. Returns the symbol `name` ends up mapped to.
+    fn insert(&mut self, name: Symbol, original: Symbol) -> Symbol {
+        let original = self.map.get(&original).cloned().unwrap_or(original);
+        self.filter[Self::bucket(&name)] += 1;
+        self.map.insert(name, original.clone());
+        original
+    }
+
+    fn enter_scope(&mut self) {
+        self.map.enter_scope();
+    }
+
+    fn exit_scope(&mut self) {
+        for (name, _) in self.map.exit_scope() {
+            self.filter[Self::bucket(&name)] -= 1;
+        }
+    }
+}
+
+/// Writes the steps taken by each top level unification to a sink. Every call to `unify` or
+/// `subsumes` gets an id which prefixes all lines written for that call.
+struct UnificationTrace<'a> {
+    sink: RefCell<Box<io::Write + 'a>>,
+    id: Cell<usize>,
+}
+
+impl<'a> UnificationTrace<'a> {
+    fn start(&self, kind: &str, expected: &ArcType, actual: &ArcType) {
+        self.id.set(self.id.get() + 1);
+        let _ = writeln!(
+            self.sink.borrow_mut(),
+            "#{} {}: {} <=> {}",
+            self.id.get(),
+            kind,
+            expected,
+            actual
+        );
+    }
+
+    fn finish<T>(&self, result: Result<ArcType, T>)
+    where
+        T: fmt::Display,
+    {
+        let mut sink = self.sink.borrow_mut();
+        let _ = match result {
+            Ok(ref typ) => writeln!(sink, "#{} ok: {}", self.id.get(), typ),
+            Err(err) => writeln!(sink, "#{} error: {}", self.id.get(), err),
+        };
+    }
+
+    fn original_symbol(&self, name: &Symbol, original: &Symbol) {
+        let _ = writeln!(
+            self.sink.borrow_mut(),
+            "original symbol: {} -> {}",
+            name,
+            original
+        );
+    }
+
+    fn original_symbols(&self, original_symbols: &OriginalSymbols) {
+        let _ = writeln!(
+            self.sink.borrow_mut(),
+            "original symbols: {} probes, {} skipped",
+            original_symbols.probes.get(),
+            original_symbols.skipped.get()
+        );
+    }
+}
+
+impl<'a> unify_type::Trace for UnificationTrace<'a> {
+    fn step(&self, step: fmt::Arguments) {
+        let _ = writeln!(self.sink.borrow_mut(), "#{}   {}", self.id.get(), step);
+    }
 }
 
 /// Error returned when unsuccessfully typechecking an expression
 pub type Error = Errors<SpannedTypeError<Symbol>>;
 
+/// A value binding visible to the typechecker
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvBinding {
+    /// The depth of the scope the binding was declared in
+    pub depth: usize,
+    pub name: Symbol,
+    /// The type of the binding with all known type variables substituted
+    pub typ: ArcType,
+}
+
+/// A type visible to the typechecker
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvAlias {
+    /// The depth of the scope the type was declared in or `None` for types from the global
+    /// environment
+    pub depth: Option<usize>,
+    pub name: Symbol,
+    pub params: Vec<Generic<Symbol>>,
+    pub kind: ArcKind,
+}
+
+/// Everything the typechecker can see at some point during typechecking.
+/// Bindings and types are listed in the order they were declared.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EnvSnapshot {
+    pub bindings: Vec<EnvBinding>,
+    pub aliases: Vec<EnvAlias>,
+}
+
+impl fmt::Display for EnvSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for binding in &self.bindings {
+            writeln!(f, "[{}] {} : {}", binding.depth, binding.name, binding.typ)?;
+        }
+        for alias in &self.aliases {
+            match alias.depth {
+                Some(depth) => write!(f, "[{}] ", depth)?,
+                None => write!(f, "[global] ")?,
+            }
+            write!(f, "type {}", alias.name)?;
+            for param in &alias.params {
+                write!(f, " {}", param.id)?;
+            }
+            writeln!(f, " : {}", alias.kind)?;
+        }
+        Ok(())
+    }
+}
+
 impl<'a> Typecheck<'a> {
     /// Create a new typechecker which typechecks expressions in `module`
     pub fn new(
@@ -352,15 +1405,149 @@ impl<'a> Typecheck<'a> {
                 stack_types: ScopedMap::new(),
             },
             symbols: symbols,
-            original_symbols: ScopedMap::new(),
+            original_symbols: OriginalSymbols::new(),
             subs: Substitution::new(kind_cache.typ()),
             named_variables: FnvMap::default(),
             errors: Errors::new(),
+            warnings: Errors::new(),
+            holes: Vec::new(),
             type_variables: ScopedMap::new(),
+            type_variable_spans: FnvMap::default(),
+            shadowed_type_variables: LintLevel::Warn,
+            shadowed_imports: LintLevel::Warn,
+            shadowed_bindings: LintLevel::Allow,
+            unrelated_shadowed_bindings: LintLevel::Warn,
+            unreachable_patterns: LintLevel::Warn,
+            unused_bindings: LintLevel::Warn,
+            used_variables: FnvSet::default(),
+            error_limit: None,
+            binding_origin: None,
+            source: None,
+            alias_definitions: FnvMap::default(),
+            binding_spans: FnvMap::default(),
+            commit_bindings: false,
             type_cache: type_cache,
             kind_cache: kind_cache,
             implicit_resolver: ::implicits::ImplicitResolver::new(environment),
+            unification_trace: None,
+        }
+    }
+
+    /// Writes a trace of every unification performed while typechecking to `sink`. Each
+    /// unification lists the types being unified, the variables bound, the aliases expanded and
+    /// the record fields matched while unifying them and finally the result of the unification.
+    /// Once typechecking is done the number of lookups of renamed symbols is written as well.
+    pub fn with_unification_trace<W>(mut self, sink: W) -> Typecheck<'a>
+    where
+        W: io::Write + 'a,
+    {
+        self.unification_trace = Some(UnificationTrace {
+            sink: RefCell::new(Box::new(sink)),
+            id: Cell::new(0),
+        });
+        self
+    }
+
+    /// Sets the source of the expressions being checked. Errors mentioning aliases defined in the
+    /// source then include the line and column the aliases were defined at.
+    pub fn with_source(mut self, source: &'a str) -> Typecheck<'a> {
+        self.source = Some(Source::new(source));
+        self
+    }
+
+    /// Sets how type parameters and `forall` binders which shadow a type variable bound by an
+    /// enclosing signature are reported. Defaults to `LintLevel::Warn`.
+    pub fn shadowed_type_variables(mut self, level: LintLevel) -> Typecheck<'a> {
+        self.shadowed_type_variables = level;
+        self
+    }
+
+    /// Sets how bindings which shadow a binding imported from the implicit prelude or an
+    /// `import!` are reported. Defaults to `LintLevel::Warn`.
+    pub fn shadowed_imports(mut self, level: LintLevel) -> Typecheck<'a> {
+        self.shadowed_imports = level;
+        self
+    }
+
+    /// Sets how bindings which shadow a local binding are reported. Defaults to
+    /// `LintLevel::Allow`.
+    pub fn shadowed_bindings(mut self, level: LintLevel) -> Typecheck<'a> {
+        self.shadowed_bindings = level;
+        self
+    }
+
+    /// Sets how `let` bindings which shadow local bindings of the same name are reported when
+    /// their type is unrelated to the types of all the shadowed bindings. Nothing is reported for
+    /// bindings which are already reported by `shadowed_bindings`. Defaults to `LintLevel::Warn`.
+    pub fn unrelated_shadowed_bindings(mut self, level: LintLevel) -> Typecheck<'a> {
+        self.unrelated_shadowed_bindings = level;
+        self
+    }
+
+    /// Sets how `match` alternatives which can never be reached because the earlier alternatives
+    /// match every value they match are reported. Defaults to `LintLevel::Warn`.
+    pub fn unreachable_patterns(mut self, level: LintLevel) -> Typecheck<'a> {
+        self.unreachable_patterns = level;
+        self
+    }
+
+    /// Sets how `let` bindings which are never used are reported. Defaults to `LintLevel::Warn`.
+    pub fn unused_bindings(mut self, level: LintLevel) -> Typecheck<'a> {
+        self.unused_bindings = level;
+        self
+    }
+
+    /// Only reports the first `limit` errors (ordered by their location), followed by an
+    /// `ErrorsNotShown` error with the number of errors which were left out. Typechecking is not
+    /// stopped when the limit is reached. Errors are not limited by default.
+    pub fn set_error_limit(&mut self, limit: usize) {
+        self.error_limit = Some(limit);
+    }
+
+    /// Removes and returns the warnings found so far
+    pub fn take_warnings(&mut self) -> Errors<SpannedWarning<Symbol>> {
+        mem::replace(&mut self.warnings, Errors::new())
+    }
+
+    /// Returns the bindings and types currently in scope. Types from the global environment are
+    /// only included if `include_globals` is `true`.
+    pub fn env_snapshot(&self, include_globals: bool) -> EnvSnapshot {
+        fn env_alias(depth: Option<usize>, alias: &Alias<Symbol, ArcType>) -> EnvAlias {
+            let kind = alias.params().iter().rev().fold(Kind::typ(), |acc, param| {
+                Kind::function(param.kind.clone(), acc)
+            });
+            EnvAlias {
+                depth,
+                name: alias.name.clone(),
+                params: alias.params().to_owned(),
+                kind,
+            }
+        }
+
+        let mut snapshot = EnvSnapshot::default();
+        self.environment.stack.each_scoped(|depth, name, binding| {
+            snapshot.bindings.push(EnvBinding {
+                depth,
+                name: name.clone(),
+                typ: self.subs.set_type(binding.typ.clone()),
+            });
+        });
+        self.environment
+            .stack_types
+            .each_scoped(|depth, _, &(_, ref alias)| {
+                snapshot.aliases.push(env_alias(Some(depth), alias));
+            });
+        if include_globals {
+            self.environment.environment.each_type_info(&mut |_, alias| {
+                snapshot.aliases.push(env_alias(None, alias));
+            });
         }
+        snapshot
+    }
+
+    /// Writes the bindings and types currently in scope to `out`, see `env_snapshot`
+    pub fn dump_env(&self, out: &mut fmt::Write, include_globals: bool) -> fmt::Result {
+        write!(out, "{}", self.env_snapshot(include_globals))
     }
 
     pub(crate) fn error<E>(&mut self, span: Span<BytePos>, error: E) -> ArcType
@@ -371,7 +1558,13 @@ impl<'a> Typecheck<'a> {
             span: span,
             value: error.into(),
         });
-        self.subs.new_var()
+        self.error_type()
+    }
+
+    /// Returns the type given to expressions which failed to typecheck. It unifies with every
+    /// type (and is never generalized) which prevents a single error from causing several more
+    fn error_type(&self) -> ArcType {
+        self.type_cache.hole()
     }
 
     fn bool(&self) -> ArcType {
@@ -401,7 +1594,8 @@ impl<'a> Typecheck<'a> {
                 if id.is_global() {
                     Ok(self.subs.new_var())
                 } else {
-                    Err(TypeError::UndefinedVariable(id.clone()))
+                    let similar = self.similar_names(false, id);
+                    Err(TypeError::UndefinedVariable(id.clone(), similar))
                 }
             }
         }
@@ -420,21 +1614,59 @@ impl<'a> Typecheck<'a> {
                 fields: fields.to_owned(),
             })
         } else {
-            self.environment
-                .find_record(fields, selector)
-                .ok_or(TypeError::UndefinedRecord {
-                    fields: fields.to_owned(),
-                })
+            self.environment
+                .find_record(fields, selector)
+                .ok_or(TypeError::UndefinedRecord {
+                    fields: fields.to_owned(),
+                })
+        }
+    }
+
+    fn find_type_info(&mut self, id: &Symbol) -> TcResult<&Alias<Symbol, ArcType>> {
+        if self.environment.find_type_info(id).is_none() {
+            let similar = self.similar_names(true, id);
+            return Err(TypeError::UndefinedType(id.clone(), similar));
+        }
+        Ok(self.environment.find_type_info(id).unwrap())
+    }
+
+    /// Returns the names of the values (or types if `types` is set) in scope which are similar to
+    /// `id`. Types defined in the global environment are suggested as well.
+    fn similar_names(&self, types: bool, id: &Symbol) -> Vec<String> {
+        let name = id.declared_name();
+        if types {
+            let mut candidates = Vec::new();
+            self.environment.each_type_info(&mut |id, _| {
+                candidates.push(id.declared_name().to_string())
+            });
+            similar_names(name, candidates.iter().map(|candidate| &candidate[..]))
+        } else {
+            similar_names(
+                name,
+                self.environment
+                    .stack
+                    .iter()
+                    .map(|(id, _)| id.declared_name()),
+            )
         }
     }
 
-    fn find_type_info(&self, id: &Symbol) -> TcResult<&Alias<Symbol, ArcType>> {
-        self.environment
-            .find_type_info(id)
-            .ok_or_else(|| TypeError::UndefinedType(id.clone()))
+    /// Adds the similar names in scope to `UndefinedType` errors from the kindchecker
+    fn add_similar_type_names(&mut self, err: TypeError<Symbol>) -> TypeError<Symbol> {
+        match err {
+            TypeError::UndefinedType(id, _) => {
+                let similar = self.similar_names(true, &id);
+                TypeError::UndefinedType(id, similar)
+            }
+            err => err,
+        }
     }
 
     fn stack_var(&mut self, id: Symbol, typ: ArcType) {
+        self.stack_binding(id, typ, None)
+    }
+
+    fn stack_binding(&mut self, id: Symbol, typ: ArcType, constructor_arity: Option<usize>) {
         debug!("Insert {} : {}", id, typ);
 
         self.implicit_resolver.on_stack_var(&id, &typ);
@@ -443,11 +1675,31 @@ impl<'a> Typecheck<'a> {
         // Insert the non_renamed symbol so that type projections in types can be translated (see
         // translate_projected_type)
         let non_renamed_symbol = self.symbols.symbol(id.declared_name());
-        self.environment
-            .stack
-            .insert(non_renamed_symbol, StackBinding { typ: typ.clone() });
+        let origin = self.binding_origin.clone();
+        self.environment.stack.insert(
+            non_renamed_symbol,
+            StackBinding {
+                typ: typ.clone(),
+                origin: origin.clone(),
+                constructor_arity,
+            },
+        );
 
-        self.environment.stack.insert(id, StackBinding { typ: typ });
+        self.environment.stack.insert(
+            id,
+            StackBinding {
+                typ: typ,
+                origin,
+                constructor_arity,
+            },
+        );
+    }
+
+    fn insert_original_symbol(&mut self, name: Symbol, original: Symbol) {
+        let original = self.original_symbols.insert(name.clone(), original);
+        if let Some(ref trace) = self.unification_trace {
+            trace.original_symbol(&name, &original);
+        }
     }
 
     fn stack_type(&mut self, id: Symbol, alias: &Alias<Symbol, ArcType>) {
@@ -456,10 +1708,12 @@ impl<'a> Typecheck<'a> {
         if let Type::Variant(ref row) = **aliased_type.remove_forall() {
             for field in row.row_iter().cloned() {
                 let symbol = self.symbols.symbol(field.name.as_ref());
-                self.original_symbols.insert(symbol, field.name.clone());
-                self.stack_var(
+                self.insert_original_symbol(symbol, field.name.clone());
+                let arity = types::arg_iter(&field.typ).count();
+                self.stack_binding(
                     field.name,
                     Type::forall(alias.params().to_owned(), field.typ),
+                    Some(arity),
                 );
             }
         }
@@ -562,21 +1816,52 @@ impl<'a> Typecheck<'a> {
             use self::TypeError::*;
 
             match err.value.error {
-                UndefinedVariable(_)
-                | UndefinedType(_)
+                UndefinedVariable(..)
+                | UndefinedType(..)
                 | DuplicateTypeDefinition(_)
+                | RecursiveAliasWithoutIndirection(_)
                 | DuplicateField(_)
                 | UndefinedRecord { .. }
                 | EmptyCase
+                | ErrorsNotShown(_)
                 | KindError(_)
                 | Message(_)
-                | LoopInImplicitResolution(..) => (),
+                | LoopInImplicitResolution(..)
+                | Lint(_)
+                | ConstructorArityMismatch { .. }
+                | TupleArityMismatch { .. }
+                | OrPatternMissingBinding(_) => (),
+                HoleFound {
+                    ref mut expected,
+                    ref mut bindings_in_scope,
+                } => {
+                    self.generalize_type_without_forall(0, expected);
+                    for &mut (_, ref mut typ) in bindings_in_scope {
+                        self.generalize_type_without_forall(0, typ);
+                    }
+                }
                 NotAFunction(ref mut typ)
-                | UndefinedField(ref mut typ, _)
+                | UndefinedField(ref mut typ, _, _)
                 | PatternError(ref mut typ, _)
-                | InvalidProjection(ref mut typ)
-                | UnableToResolveImplicit(ref mut typ, _) => {
+                | UnableToResolveImplicit(ref mut typ, _)
+                | PatternShapeMismatch {
+                    scrutinee: ref mut typ,
+                    ..
+                }
+                | PolymorphicRecursion {
+                    cycle_type: ref mut typ,
+                    ..
+                } => {
+                    self.generalize_type(0, typ);
+                }
+                InvalidProjection(ref mut typ, ref mut resolved)
+                | OrPatternBindingMismatch {
+                    expected: ref mut typ,
+                    actual: ref mut resolved,
+                    ..
+                } => {
                     self.generalize_type(0, typ);
+                    self.generalize_type(0, resolved);
                 }
                 AmbiguousImplicit(ref mut xs) => for &mut (_, ref mut typ) in xs {
                     self.generalize_type(0, typ);
@@ -609,6 +1894,58 @@ impl<'a> Typecheck<'a> {
         self.type_variables.exit_scope();
     }
 
+    /// Notes where the aliases mentioned in the types of each error were defined, unless the error
+    /// already has a help message
+    fn add_alias_definitions(&self, errors: &mut Error) {
+        for err in errors {
+            if err.value.help.is_some() {
+                continue;
+            }
+            let mut aliases = Vec::new();
+            {
+                let mut add_aliases = |typ: &ArcType| {
+                    types::walk_type(typ, |typ: &ArcType| {
+                        let name = match **typ {
+                            Type::Alias(ref alias) => &alias.name,
+                            Type::Ident(ref id) => id,
+                            _ => return,
+                        };
+                        if aliases.len() < MAX_ALIAS_DEFINITIONS && !aliases.contains(name) {
+                            aliases.push(name.clone());
+                        }
+                    })
+                };
+                error_types(&err.value.error, &mut add_aliases);
+            }
+            let definitions: Vec<_> = aliases
+                .iter()
+                .filter_map(|name| {
+                    let origin = match self.alias_definitions.get(name) {
+                        Some(span) => AliasOrigin::Location(self.source
+                            .as_ref()
+                            .and_then(|source| source.location(span.start))?),
+                        None => {
+                            let module = Name::new(name.as_ref()).module().as_str();
+                            if module.is_empty() || module == self.symbols.module().as_str() {
+                                return None;
+                            }
+                            AliasOrigin::Module(module.to_string())
+                        }
+                    };
+                    Some((name.declared_name().to_string(), origin))
+                })
+                .collect();
+            if !definitions.is_empty() {
+                err.value.help = Some(Help::AliasDefinitions(definitions));
+            }
+        }
+    }
+
+    /// Gives every binding in `expr` a unique name without typechecking it
+    pub(crate) fn rename(&mut self, expr: &mut SpannedExpr<Symbol>) {
+        ::rename::rename(&mut self.symbols, expr);
+    }
+
     /// Typecheck `expr`. If successful the type of the expression will be returned and all
     /// identifiers in `expr` will be filled with the inferred type
     pub fn typecheck_expr(&mut self, expr: &mut SpannedExpr<Symbol>) -> Result<ArcType, Error> {
@@ -620,6 +1957,55 @@ impl<'a> Typecheck<'a> {
         expr: &mut SpannedExpr<Symbol>,
         expected_type: Option<&ArcType>,
     ) -> Result<ArcType, Error> {
+        match self.typecheck_expr_partial(expr, expected_type) {
+            (typ, None) => Ok(typ),
+            (_, Some(errors)) => Err(errors),
+        }
+    }
+
+    /// Typechecks `expr` like `typecheck_expr` but keeps the bindings and types declared by the
+    /// `let` and `type` expressions at the top of `expr` in scope afterwards, so that expressions
+    /// checked later (for instance the next line in a REPL) can refer to them directly. The types
+    /// of the bindings are generalized before they are kept so they never refer to type variables
+    /// of `expr`. Nothing is kept if `expr` fails to typecheck.
+    ///
+    /// Unlike `typecheck_expr` the bindings kept by earlier calls are not cleared.
+    pub fn typecheck_and_commit(
+        &mut self,
+        expr: &mut SpannedExpr<Symbol>,
+    ) -> Result<ArcType, Error> {
+        // Each expression gets its own scope so that its bindings can be dropped on errors
+        self.enter_scope();
+        self.commit_bindings = true;
+        let result = match self.typecheck_expr_(expr, None, false) {
+            (typ, None) => Ok(typ),
+            (_, Some(errors)) => Err(errors),
+        };
+        self.commit_bindings = false;
+        if result.is_err() {
+            self.exit_scope();
+        }
+        result
+    }
+
+    /// Typechecks `expr` like `typecheck_expr_expected` but returns the type of `expr` even if
+    /// errors were found, along with the errors. Parts of the type which could not be inferred
+    /// due to an error are `_`. The types in `expr` are filled in as far as they could be
+    /// inferred in either case.
+    pub fn typecheck_expr_partial(
+        &mut self,
+        expr: &mut SpannedExpr<Symbol>,
+        expected_type: Option<&ArcType>,
+    ) -> (ArcType, Option<Error>) {
+        self.typecheck_expr_(expr, expected_type, true)
+    }
+
+    fn typecheck_expr_(
+        &mut self,
+        expr: &mut SpannedExpr<Symbol>,
+        expected_type: Option<&ArcType>,
+        clear_scope: bool,
+    ) -> (ArcType, Option<Error>) {
         fn tail_expr(e: &mut SpannedExpr<Symbol>) -> &mut SpannedExpr<Symbol> {
             match e.value {
                 Expr::LetBindings(_, ref mut b) | Expr::TypeBindings(_, ref mut b) => tail_expr(b),
@@ -627,10 +2013,14 @@ impl<'a> Typecheck<'a> {
             }
         }
         info!("Typechecking {}", self.symbols.module());
-        self.subs.clear();
-        self.environment.stack.clear();
+        if clear_scope {
+            self.subs.clear();
+            self.environment.stack.clear();
+        }
+        self.alias_definitions.clear();
+        self.binding_spans.clear();
 
-        let _ = ::rename::rename(&mut self.symbols, expr);
+        self.used_variables = ::rename::rename(&mut self.symbols, expr);
         self.implicit_resolver.metadata = ::metadata::metadata(&self.environment, expr).1;
 
         let mut typ = self.typecheck_opt(expr, expected_type);
@@ -639,20 +2029,163 @@ impl<'a> Typecheck<'a> {
                 .unwrap_or_else(|| expected.clone());
             typ = self.subsumes_expr(expr_check_span(expr), 0, &expected, typ, expr);
         }
-        // Only the 'tail' expression need to be generalized at this point as all bindings
-        // will have already been generalized
-        self.generalize_variables(0, &mut [].iter_mut(), tail_expr(expr));
+        if self.errors.has_errors() {
+            // An error may have stopped the checking of a binding before it was generalized
+            self.generalize_variables(0, &mut [].iter_mut(), expr);
+        } else {
+            // Only the 'tail' expression need to be generalized at this point as all bindings
+            // will have already been generalized
+            self.generalize_variables(0, &mut [].iter_mut(), tail_expr(expr));
+        }
 
         self.generalize_type(0, &mut typ);
         typ = types::walk_move_type(typ, &mut unroll_typ);
 
+        self.report_holes();
+
+        if let Some(ref trace) = self.unification_trace {
+            trace.original_symbols(&self.original_symbols);
+        }
+
         if self.errors.has_errors() {
-            let mut errors = mem::replace(&mut self.errors, Errors::new());
+            let errors = mem::replace(&mut self.errors, Errors::new());
+            let mut errors = aggregate_undefined_errors(errors);
+            add_rename_fixes(&mut errors);
             self.generalize_type_errors(&mut errors);
-            Err(errors)
+            self.add_alias_definitions(&mut errors);
+            (typ, Some(sort_and_limit_errors(errors, self.error_limit)))
         } else {
             debug!("Typecheck result: {}", typ);
-            Ok(typ)
+            (typ, None)
+        }
+    }
+
+    /// Reports the holes found while typechecking, listing the innermost bindings which could be
+    /// used in their place
+    fn report_holes(&mut self) {
+        for hole in mem::replace(&mut self.holes, Vec::new()) {
+            let span = hole.span;
+            let mut expected = hole.typ;
+            self.generalize_type_without_forall(0, &mut expected);
+
+            let mut bindings = hole.bindings;
+            bindings.sort_by(|l, r| r.0.cmp(&l.0));
+            let mut bindings_in_scope = Vec::new();
+            for (_, name, mut typ) in bindings {
+                if bindings_in_scope.len() == MAX_HOLE_BINDINGS {
+                    break;
+                }
+                // Shadowed bindings can't be referred to in the hole
+                if name.declared_name() == "_"
+                    || bindings_in_scope
+                        .iter()
+                        .any(|&(ref other, _): &(Symbol, _)| other.name_eq(&name))
+                {
+                    continue;
+                }
+                self.generalize_type_without_forall(0, &mut typ);
+                if ::check_signature(&self.environment, &expected, &typ) {
+                    bindings_in_scope.push((name, typ));
+                }
+            }
+
+            let mut fixes: Vec<_> = bindings_in_scope
+                .iter()
+                .map(|&(ref name, _)| Fix {
+                    title: format!("Replace the hole with `{}`", name.declared_name()),
+                    edits: vec![(span, name.declared_name().to_string())],
+                })
+                .collect();
+            if let Some(binding) = hole.binding {
+                let mut typ = binding.typ;
+                self.generalize_type_without_forall(0, &mut typ);
+                if !contains_hole(&typ) {
+                    let signature = typ.to_string();
+                    fixes.push(Fix {
+                        title: format!(
+                            "Add the inferred type signature `{}` to `{}`",
+                            signature,
+                            binding.name.declared_name()
+                        ),
+                        edits: vec![
+                            (
+                                Span::new(binding.signature_pos, binding.signature_pos),
+                                format!(" : {}", signature),
+                            ),
+                        ],
+                    });
+                }
+            }
+
+            let mut error = HelpError::from(TypeError::HoleFound {
+                expected,
+                bindings_in_scope,
+            });
+            for fix in fixes {
+                error.add_fix(fix);
+            }
+            self.errors.push(Spanned { span, value: error });
+        }
+    }
+
+    /// Adds a fix which imports `module_expr` to the undefined variable error reported for it if it
+    /// looks like the path to a module (`std.list`). Only the shortest such path of a projection
+    /// is suggested.
+    fn suggest_import(&mut self, errors_before: usize, module_expr: &SpannedExpr<Symbol>) {
+        let mut root = match module_expr.value {
+            Expr::Projection(ref expr, _, _) => &**expr,
+            _ => return,
+        };
+        while let Expr::Projection(ref expr, _, _) = root.value {
+            root = expr;
+        }
+
+        let mut path = String::new();
+        if ast::expr_to_path(module_expr, &mut path).is_err()
+            || !path.split('.').all(|name| name.starts_with(char::is_lowercase))
+        {
+            return;
+        }
+
+        for error in (&mut self.errors).into_iter().skip(errors_before) {
+            match error.value.error {
+                TypeError::UndefinedVariable(..)
+                    if error.span == root.span && error.value.fixes().is_empty() =>
+                {
+                    error.value.add_fix(Fix {
+                        title: format!("Import `{}`", path),
+                        edits: vec![(module_expr.span, format!("(import! {})", path))],
+                    });
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Marks the holes found in `bindings` (which do not already belong to an inner binding) with
+    /// the binding containing them, so that the hole can suggest the inferred type signature of
+    /// the binding
+    fn mark_hole_bindings(&mut self, holes_before: usize, bindings: &[ValueBinding<Symbol>]) {
+        for hole in &mut self.holes[holes_before..] {
+            if hole.binding.is_some() {
+                continue;
+            }
+            let bind = match bindings
+                .iter()
+                .find(|bind| bind.expr.span.contains(hole.span))
+            {
+                Some(bind) => bind,
+                None => continue,
+            };
+            if let (None, &Pattern::Ident(ref id)) = (bind.typ.as_ref(), &bind.name.value) {
+                hole.binding = Some(HoleBinding {
+                    name: id.name.clone(),
+                    signature_pos: bind.args
+                        .last()
+                        .map_or(bind.name.span.end, |arg| arg.name.span.end),
+                    typ: bind.resolved_type.clone(),
+                });
+            }
         }
     }
 
@@ -674,6 +2207,8 @@ impl<'a> Typecheck<'a> {
         fn moving<T>(t: T) -> T {
             t
         }
+        // Only the bindings at the top of the outermost expression are committed
+        let commit_bindings = mem::replace(&mut self.commit_bindings, false);
         // How many scopes that have been entered in this "tailcall" loop
         let mut scope_count = 0;
         let returned_type;
@@ -709,21 +2244,60 @@ impl<'a> Typecheck<'a> {
                     }
                 }
                 Err(err) => {
-                    returned_type = self.subs.new_var();
+                    returned_type = self.error_type();
                     self.errors.push(Spanned {
                         span: expr_check_span(expr),
                         value: err.into(),
                     });
+                    // Unifying with the error type always succeeds and marks the variables of the
+                    // expected type as coming from an error
+                    if let Some(expected_type) = expected_type {
+                        let _ = self.unify(expected_type, returned_type.clone());
+                    }
                     break;
                 }
             }
         }
-        for _ in 0..scope_count {
-            self.exit_scope();
+        if commit_bindings {
+            self.commit_scopes(scope_count);
+        } else {
+            for _ in 0..scope_count {
+                self.exit_scope();
+            }
         }
         returned_type
     }
 
+    /// Exits the `count` innermost scopes, moving the bindings and types declared in them into
+    /// the enclosing scope
+    fn commit_scopes(&mut self, count: usize) {
+        let implicit_bindings = self.implicit_resolver.implicit_bindings.last().cloned();
+        let mut bindings = Vec::new();
+        let mut types = Vec::new();
+        for _ in 0..count {
+            bindings.extend(self.environment.stack.exit_scope());
+            types.extend(self.environment.stack_types.exit_scope());
+            self.original_symbols.exit_scope();
+            self.implicit_resolver.exit_scope();
+        }
+
+        // Scopes are exited from the innermost binding and out so the order is reversed to let
+        // later bindings shadow earlier ones
+        for (name, mut binding) in bindings.into_iter().rev() {
+            self.generalize_type(0, &mut binding.typ);
+            self.environment.stack.insert(name, binding);
+        }
+        for (name, typ) in types.into_iter().rev() {
+            self.environment.stack_types.insert(name, typ);
+        }
+        if let Some(implicit_bindings) = implicit_bindings {
+            match self.implicit_resolver.implicit_bindings.last_mut() {
+                Some(last) => *last = implicit_bindings,
+                None => self.implicit_resolver.implicit_bindings.push(implicit_bindings),
+            }
+        }
+    }
+
     /// `expected_type` should be set to `None` if subsumption is done with it (to prevent us from
     /// doing it twice)
     fn typecheck_(
@@ -732,6 +2306,24 @@ impl<'a> Typecheck<'a> {
         expected_type: &mut Option<&ArcType<Symbol>>,
     ) -> Result<TailCall, TypeError<Symbol>> {
         match expr.value {
+            Expr::Ident(ref mut id) if id.name.declared_name() == "_" => {
+                let typ = match expected_type.take() {
+                    Some(expected_type) => expected_type.clone(),
+                    None => self.subs.new_var(),
+                };
+                let mut bindings = Vec::new();
+                self.environment.stack.each_scoped(|depth, name, binding| {
+                    bindings.push((depth, name.clone(), binding.typ.clone()));
+                });
+                self.holes.push(Hole {
+                    span: expr.span,
+                    typ: typ.clone(),
+                    bindings,
+                    binding: None,
+                });
+                id.typ = typ.clone();
+                Ok(TailCall::Type(typ))
+            }
             Expr::Ident(ref mut id) => {
                 if let Some(new) = self.original_symbols.get(&id.name) {
                     id.name = new.clone();
@@ -752,7 +2344,39 @@ impl<'a> Typecheck<'a> {
                 ref mut args,
             } => {
                 let func_type = self.infer_expr(func);
-                self.typecheck_application(expr.span, func_type, implicit_args, args)
+                let constructor = match func.value {
+                    Expr::Ident(ref id) => self.environment
+                        .stack
+                        .get(&id.name)
+                        .and_then(|bind| bind.constructor_arity)
+                        .map(|arity| (id.name.clone(), arity)),
+                    _ => None,
+                };
+                if let Some((constructor, arity)) = constructor {
+                    // Partially applying a constructor is only an error if the result can't be
+                    // a function
+                    let under_applied = args.len() < arity
+                        && expected_type.map_or(false, |typ| !self.may_be_function(typ));
+                    if args.len() > arity || under_applied {
+                        let applied = args.iter_mut().take(arity);
+                        self.typecheck_application(
+                            expr.span,
+                            Some(func),
+                            func_type,
+                            implicit_args,
+                            applied,
+                        )?;
+                        for arg in args.iter_mut().skip(arity) {
+                            self.infer_expr(arg);
+                        }
+                        return Err(TypeError::ConstructorArityMismatch {
+                            constructor,
+                            expected: arity,
+                            actual: args.len(),
+                        });
+                    }
+                }
+                self.typecheck_application(expr.span, Some(func), func_type, implicit_args, args)
             }
             Expr::IfElse(ref mut pred, ref mut if_true, ref mut if_false) => {
                 let bool_type = self.bool();
@@ -778,14 +2402,27 @@ impl<'a> Typecheck<'a> {
                 let func_type = if op_name.starts_with('#') {
                     // Handle primitives
                     let op_type = op_name.trim_matches(|c: char| !c.is_alphabetic());
-                    let builtin_type = op_type.parse().map_err(|_| {
-                        TypeError::Message("Invalid builtin type for operator".to_string())
-                    })?;
-                    let prim_type = self.type_cache.builtin_type(builtin_type);
+                    let prim_type = match op_type.parse() {
+                        Ok(builtin_type) => self.type_cache.builtin_type(builtin_type),
+                        // Fallback to any primitive operators registered by the environment
+                        Err(()) => self.environment
+                            .find_primitive_operand(op_type)
+                            .cloned()
+                            .ok_or_else(|| {
+                                TypeError::Message(
+                                    "Invalid builtin type for operator".to_string(),
+                                )
+                            })?,
+                    };
                     let return_type = match &op_name[1 + op_type.len()..] {
                         "+" | "-" | "*" | "/" => prim_type.clone(),
                         "==" | "<" => self.bool(),
-                        _ => return Err(TypeError::UndefinedVariable(op.value.name.clone())),
+                        _ => {
+                            return Err(TypeError::UndefinedVariable(
+                                op.value.name.clone(),
+                                Vec::new(),
+                            ))
+                        }
                     };
                     self.type_cache.function(
                         vec![prim_type.clone(), prim_type.clone()],
@@ -803,6 +2440,7 @@ impl<'a> Typecheck<'a> {
 
                 self.typecheck_application(
                     op.span,
+                    None,
                     func_type,
                     implicit_args,
                     Some(&mut **lhs).into_iter().chain(Some(&mut **rhs)),
@@ -813,7 +2451,7 @@ impl<'a> Typecheck<'a> {
                 elems: ref mut exprs,
             } => {
                 *typ = match exprs.len() {
-                    0 => Type::unit(),
+                    0 => self.type_cache.unit(),
                     1 => self.typecheck_opt(&mut exprs[0], expected_type.take()),
                     _ => {
                         let fields = exprs
@@ -838,9 +2476,15 @@ impl<'a> Typecheck<'a> {
 
                 let expected_type = expected_type.take();
 
+                let error_count = self.errors.len();
                 for alt in alts.iter_mut() {
                     self.enter_scope();
                     self.typecheck_pattern(&mut alt.pattern, typ.clone());
+                    if let Some(ref mut guard) = alt.guard {
+                        let bool_type = self.bool();
+                        let guard_type = self.typecheck(guard, &bool_type);
+                        self.unify_span(expr_check_span(guard), &bool_type, guard_type);
+                    }
                     let mut alt_type = self.typecheck_opt(&mut alt.expr, expected_type);
                     alt_type = self.instantiate_generics(&alt_type);
                     self.exit_scope();
@@ -850,16 +2494,23 @@ impl<'a> Typecheck<'a> {
                     }
                     expected_alt_type = Some(alt_type);
                 }
+                // Patterns which failed to typecheck could make alternatives look unreachable
+                if self.errors.len() == error_count {
+                    self.check_unreachable_alternatives(alts);
+                }
                 expected_alt_type
                     .ok_or(TypeError::EmptyCase)
                     .map(TailCall::Type)
             }
             Expr::LetBindings(ref mut bindings, _) => {
+                self.check_unused_bindings(bindings);
                 self.typecheck_bindings(bindings)?;
                 Ok(TailCall::TailCall)
             }
-            Expr::Projection(ref mut expr, ref field_id, ref mut ast_field_typ) => {
-                let mut expr_typ = self.infer_expr(&mut **expr);
+            Expr::Projection(ref mut record_expr, ref field_id, ref mut ast_field_typ) => {
+                let errors_before = self.errors.len();
+                let mut expr_typ = self.infer_expr(&mut **record_expr);
+                self.suggest_import(errors_before, record_expr);
                 debug!(
                     "Projection {} . {:?}",
                     &expr_typ,
@@ -886,8 +2537,39 @@ impl<'a> Typecheck<'a> {
                             .row_iter()
                             .find(|field| field.name.name_eq(field_id))
                             .map(|field| field.typ.clone());
+                        let is_closed = match *record {
+                            Type::Record(_) => {
+                                let mut iter = record.row_iter();
+                                iter.by_ref().count();
+                                **iter.current_type() == Type::EmptyRow
+                            }
+                            _ => false,
+                        };
                         *ast_field_typ = match field_type {
                             Some(typ) => self.new_skolem_scope(&typ),
+                            None if is_closed => {
+                                let similar = similar_names(
+                                    field_id.declared_name(),
+                                    record.row_iter().map(|field| field.name.declared_name()),
+                                );
+                                // Private fields are removed from the types of imported modules
+                                let help = if ::is_private_field(field_id.as_ref(), None) {
+                                    Some(Help::PrivateField)
+                                } else {
+                                    None
+                                };
+                                self.error(
+                                    expr.span,
+                                    HelpError::new(
+                                        TypeError::UndefinedField(
+                                            record.clone(),
+                                            field_id.clone(),
+                                            similar,
+                                        ),
+                                        help,
+                                    ),
+                                )
+                            }
                             None => {
                                 // FIXME As the polymorphic `record_type` do not have the type
                                 // fields which `typ` this unification is only done after we
@@ -903,7 +2585,7 @@ impl<'a> Typecheck<'a> {
                         };
                         Ok(TailCall::Type(ast_field_typ.clone()))
                     }
-                    _ => Err(TypeError::InvalidProjection(record)),
+                    _ => Err(TypeError::InvalidProjection(expr_typ, record)),
                 }
             }
             Expr::Array(ref mut array) => {
@@ -934,8 +2616,8 @@ impl<'a> Typecheck<'a> {
                 lambda.id.typ = typ.clone();
                 Ok(TailCall::Type(typ))
             }
-            Expr::TypeBindings(ref mut bindings, ref expr) => {
-                self.typecheck_type_bindings(bindings, expr);
+            Expr::TypeBindings(ref mut bindings, _) => {
+                self.typecheck_type_bindings(bindings);
                 Ok(TailCall::TailCall)
             }
             Expr::Record {
@@ -952,7 +2634,7 @@ impl<'a> Typecheck<'a> {
 
                 let mut new_types: Vec<Field<_, _>> = Vec::with_capacity(types.len());
 
-                let mut duplicated_fields = FnvSet::default();
+                let mut duplicated_fields = FnvMap::default();
                 for field in types {
                     if let Some(ref mut typ) = field.value {
                         *typ = self.create_unifiable_signature(typ)
@@ -1053,15 +2735,39 @@ impl<'a> Typecheck<'a> {
                     new_types.extend(
                         base_type
                             .type_field_iter()
-                            .filter(|field| !duplicated_fields.contains(field.name.declared_name()))
+                            .filter(|field| {
+                                !duplicated_fields.contains_key(field.name.declared_name())
+                            })
                             .cloned(),
                     );
-                    new_fields.extend(
+                    // Updating fields which already exist in `base` keeps the order of its fields
+                    // so the new record has the same type (and layout) as `base`
+                    let is_update = new_fields.iter().all(|field| {
                         base_type
                             .row_iter()
-                            .filter(|field| !duplicated_fields.contains(field.name.declared_name()))
-                            .cloned(),
-                    );
+                            .any(|base_field| base_field.name.name_eq(&field.name))
+                    });
+                    if is_update {
+                        let mut updated_fields = mem::replace(&mut new_fields, Vec::new());
+                        new_fields.extend(base_type.row_iter().map(|base_field| {
+                            match updated_fields
+                                .iter()
+                                .position(|field| field.name.name_eq(&base_field.name))
+                            {
+                                Some(i) => updated_fields.swap_remove(i),
+                                None => base_field.clone(),
+                            }
+                        }));
+                    } else {
+                        new_fields.extend(
+                            base_type
+                                .row_iter()
+                                .filter(|field| {
+                                    !duplicated_fields.contains_key(field.name.declared_name())
+                                })
+                                .cloned(),
+                        );
+                    }
                 }
 
                 let record_fields = new_fields
@@ -1115,10 +2821,7 @@ impl<'a> Typecheck<'a> {
                         Err(error) => {
                             self.error(
                                 id.span,
-                                ::base::error::Help {
-                                    error,
-                                    help: Some(Help::UndefinedFlatMapInDo),
-                                },
+                                HelpError::new(error, Some(Help::UndefinedFlatMapInDo)),
                             );
                             self.subs.new_var()
                         }
@@ -1169,6 +2872,7 @@ impl<'a> Typecheck<'a> {
 
                 self.unify_span(bound.span, &arg2, bound_type);
 
+                self.check_shadowed_binding(&id.value.name, id.span);
                 self.stack_var(id.value.name.clone(), id.value.typ.clone());
 
                 let body_type = self.typecheck(body, &ret);
@@ -1183,9 +2887,22 @@ impl<'a> Typecheck<'a> {
         }
     }
 
+    /// Returns `false` if `typ` is known to not be a function type
+    fn may_be_function(&self, typ: &ArcType) -> bool {
+        let typ = self.remove_aliases(self.subs.real(typ).clone());
+        match **typ.remove_forall() {
+            Type::Function(..) | Type::Variable(_) | Type::Generic(_) | Type::Skolem(_)
+            | Type::Hole => true,
+            _ => typ.as_function().is_some(),
+        }
+    }
+
+    /// Checks the application of a function of type `func_type` to `args`. Errors in an argument
+    /// are noted with the position of the argument and the name of `callee`, if any.
     fn typecheck_application<'e, I>(
         &mut self,
         span: Span<BytePos>,
+        callee: Option<&SpannedExpr<Symbol>>,
         mut func_type: ArcType,
         implicit_args: &mut Vec<SpannedExpr<Symbol>>,
         args: I,
@@ -1220,7 +2937,11 @@ impl<'a> Typecheck<'a> {
             prev_arg_end = arg.span.end;
         }
 
-        for arg in args {
+        let callee = callee.map(|func| (func.span, callee_name(func)));
+        // Lambdas whose parameter types are not known yet are checked after the other arguments
+        // so that `map (\x -> x.name) people` can take the type of `x` from `people`
+        let mut deferred_lambdas = Vec::new();
+        for (i, arg) in args.into_iter().enumerate() {
             let f = self.type_cache
                 .function(once(self.subs.new_var()), self.subs.new_var());
             func_type = self.instantiate_generics(&func_type);
@@ -1232,22 +2953,84 @@ impl<'a> Typecheck<'a> {
             func_type = match f.as_function() {
                 Some((arg_ty, ret_ty)) => {
                     let arg_ty = self.subs.real(arg_ty).clone();
-                    let actual = self.typecheck(arg, &arg_ty);
-                    let actual = self.instantiate_generics(&actual);
-
-                    let level = self.subs.var_id();
-                    self.subsumes_expr(expr_check_span(arg), level, &arg_ty, actual, arg);
-
+                    prev_arg_end = arg.span.end;
+                    if self.has_unknown_parameters(arg, &arg_ty) {
+                        deferred_lambdas.push((i, arg, arg_ty));
+                    } else {
+                        self.typecheck_argument(arg, &arg_ty, i, callee.as_ref());
+                    }
                     ret_ty.clone()
                 }
                 None => return Err(TypeError::NotAFunction(func_type.clone())),
             };
-
-            prev_arg_end = arg.span.end;
+        }
+        for (i, arg, arg_ty) in deferred_lambdas {
+            let arg_ty = self.subs.real(&arg_ty).clone();
+            self.typecheck_argument(arg, &arg_ty, i, callee.as_ref());
         }
         Ok(TailCall::Type(func_type))
     }
 
+    /// Checks the `index`th (from 0) argument `arg` of an application against `arg_ty`. If the
+    /// argument does not have the expected type the error notes which argument of `callee` it is.
+    fn typecheck_argument(
+        &mut self,
+        arg: &mut SpannedExpr<Symbol>,
+        arg_ty: &ArcType,
+        index: usize,
+        callee: Option<&(Span<BytePos>, Option<String>)>,
+    ) {
+        let errors_before = self.errors.len();
+        let actual = self.typecheck(arg, arg_ty);
+        let actual = self.instantiate_generics(&actual);
+
+        let level = self.subs.var_id();
+        let span = expr_check_span(arg);
+        self.subsumes_expr(span, level, arg_ty, actual, arg);
+
+        let (callee_span, name) = match callee {
+            Some(&(callee_span, ref name)) => (callee_span, name),
+            None => return,
+        };
+        for error in (&mut self.errors).into_iter().skip(errors_before) {
+            if let TypeError::Unification(..) = error.value.error {
+                if error.span == span {
+                    let note = match *name {
+                        Some(ref name) => format!("in argument {} of `{}`", index + 1, name),
+                        None => format!("in argument {} of this function", index + 1),
+                    };
+                    error.value.add_note(callee_span, note);
+                }
+            }
+        }
+    }
+
+    /// Returns true if `arg` is a lambda and the types of its parameters are still unknown in
+    /// `arg_ty`
+    fn has_unknown_parameters(&self, arg: &SpannedExpr<Symbol>, arg_ty: &ArcType) -> bool {
+        let lambda = match arg.value {
+            Expr::Lambda(ref lambda) => lambda,
+            // `(\x -> ..)`
+            Expr::Tuple { ref elems, .. } if elems.len() == 1 => {
+                return self.has_unknown_parameters(&elems[0], arg_ty)
+            }
+            _ => return false,
+        };
+        let mut typ = arg_ty.clone();
+        for _ in &lambda.args {
+            typ = match typ.as_function() {
+                Some((param, ret)) => {
+                    if let Type::Variable(_) = **self.subs.real(param) {
+                        return true;
+                    }
+                    self.subs.real(ret).clone()
+                }
+                None => return false,
+            };
+        }
+        false
+    }
+
     fn typecheck_lambda<'i>(
         &mut self,
         mut function_type: ArcType,
@@ -1348,6 +3131,8 @@ impl<'a> Typecheck<'a> {
                             let arg_type = arg_type.unwrap_or_else(|| {
                                 make_new_arg(iter1.tc, arg.span, &mut iter1.typ)
                             });
+
+                            iter1.tc.check_shadowed_binding(&arg.value.name, arg.span);
                             let arg = &mut arg.value;
 
                             arg.typ = arg_type;
@@ -1375,8 +3160,28 @@ impl<'a> Typecheck<'a> {
         mut match_type: ArcType,
     ) -> ArcType {
         let span = pattern.span;
+        if let Some(pattern_kind) = self.pattern_shape_mismatch(&pattern.value, &match_type) {
+            let help = match pattern_kind {
+                PatternKind::Constructor => Help::UseRecordPattern,
+                PatternKind::Record | PatternKind::Tuple => Help::UseConstructorPattern,
+            };
+            self.errors.push(Spanned {
+                span,
+                value: HelpError::new(
+                    TypeError::PatternShapeMismatch {
+                        pattern_kind,
+                        scrutinee: match_type,
+                    },
+                    Some(help),
+                ),
+            });
+            // Still bind the variables in the pattern so that they are not reported as undefined
+            let typ = self.subs.new_var();
+            return self.typecheck_pattern(pattern, typ);
+        }
         match pattern.value {
             Pattern::As(ref id, ref mut pat) => {
+                self.check_shadowed_binding(id, span);
                 self.stack_var(id.clone(), match_type.clone());
                 self.typecheck_pattern(pat, match_type.clone());
                 match_type
@@ -1411,7 +3216,7 @@ impl<'a> Typecheck<'a> {
 
                 let mut pattern_fields = Vec::with_capacity(associated_types.len() + fields.len());
 
-                let mut duplicated_fields = FnvSet::default();
+                let mut duplicated_fields = FnvMap::default();
                 {
                     let all_fields = associated_types
                         .iter()
@@ -1476,6 +3281,7 @@ impl<'a> Typecheck<'a> {
                             self.typecheck_pattern(pattern, field_type);
                         }
                         None => {
+                            self.check_shadowed_binding(name, field.name.span);
                             self.stack_var(name.clone(), field_type);
                         }
                     }
@@ -1493,8 +3299,10 @@ impl<'a> Typecheck<'a> {
                         Some(field_type) => {
                             // This forces refresh_type to remap the name a type was given
                             // in this module to its actual name
-                            self.original_symbols
-                                .insert(name.clone(), field_type.typ.name.clone());
+                            self.insert_original_symbol(
+                                name.clone(),
+                                field_type.typ.name.clone(),
+                            );
 
                             if let Some(meta) = self.implicit_resolver.metadata.remove(&name) {
                                 self.implicit_resolver
@@ -1505,7 +3313,16 @@ impl<'a> Typecheck<'a> {
                             self.stack_type(name, &field_type.typ);
                         }
                         None => {
-                            self.error(span, TypeError::UndefinedField(match_type.clone(), name));
+                            let similar = similar_names(
+                                name.declared_name(),
+                                actual_type
+                                    .type_field_iter()
+                                    .map(|field| field.name.declared_name()),
+                            );
+                            self.error(
+                                span,
+                                TypeError::UndefinedField(match_type.clone(), name, similar),
+                            );
                         }
                     }
                 }
@@ -1530,13 +3347,28 @@ impl<'a> Typecheck<'a> {
                     self.type_cache
                         .tuple(&mut self.symbols, (0..elems.len()).map(|_| subs.new_var()))
                 };
-                *typ = self.unify_span(span, &tuple_type, match_type);
+                let scrutinee = self.remove_aliases(self.subs.real(&match_type).clone());
+                match tuple_arity(&scrutinee) {
+                    Some(expected) if expected != elems.len() => {
+                        // Unifying would only report the missing or extra `_N` fields
+                        self.error(
+                            span,
+                            TypeError::TupleArityMismatch {
+                                expected,
+                                found: elems.len(),
+                            },
+                        );
+                        *typ = tuple_type.clone();
+                    }
+                    _ => *typ = self.unify_span(span, &tuple_type, match_type),
+                }
                 for (elem, field) in elems.iter_mut().zip(tuple_type.row_iter()) {
                     self.typecheck_pattern(elem, field.typ.clone());
                 }
                 tuple_type
             }
             Pattern::Ident(ref mut id) => {
+                self.check_shadowed_binding(&id.name, span);
                 self.stack_var(id.name.clone(), match_type.clone());
                 id.typ = match_type.clone();
                 match_type
@@ -1546,10 +3378,112 @@ impl<'a> Typecheck<'a> {
                 self.unify_span(span, &match_type, typ);
                 match_type
             }
+            Pattern::StringAffix { ref mut rest, .. } => {
+                let string_type = self.type_cache.string();
+                self.unify_span(span, &match_type, string_type.clone());
+                self.check_shadowed_binding(&rest.name, span);
+                self.stack_var(rest.name.clone(), string_type.clone());
+                rest.typ = string_type;
+                match_type
+            }
+            Pattern::Or(ref mut alternatives) => {
+                // Each alternative binds its variables in a scope of its own. They are compared
+                // against the variables of the first alternative which are then bound only once
+                let mut bindings = Vec::new();
+                for (i, alternative) in alternatives.iter_mut().enumerate() {
+                    self.enter_scope();
+                    self.typecheck_pattern(alternative, match_type.clone());
+                    let mut variables = Vec::new();
+                    bound_variables(alternative, &mut variables);
+                    let alternative_bindings: Vec<_> = variables
+                        .into_iter()
+                        // `_` does not bind anything, it only matches any value
+                        .filter(|&(id, _)| id.declared_name() != "_")
+                        .map(|(id, span)| {
+                            let typ = self.environment
+                                .stack
+                                .get(id)
+                                .expect("ICE: Variable not inserted")
+                                .typ
+                                .clone();
+                            (id.clone(), span, typ)
+                        })
+                        .collect();
+                    self.exit_scope();
+
+                    if i == 0 {
+                        bindings = alternative_bindings;
+                        continue;
+                    }
+                    for &(ref id, _, ref expected) in &bindings {
+                        match alternative_bindings.iter().find(|binding| binding.0 == *id) {
+                            Some(&(_, span, ref actual)) => {
+                                if self.unify(expected, actual.clone()).is_err() {
+                                    self.error(
+                                        span,
+                                        TypeError::OrPatternBindingMismatch {
+                                            name: id.clone(),
+                                            expected: expected.clone(),
+                                            actual: actual.clone(),
+                                        },
+                                    );
+                                }
+                            }
+                            None => {
+                                self.error(
+                                    alternative.span,
+                                    TypeError::OrPatternMissingBinding(id.clone()),
+                                );
+                            }
+                        }
+                    }
+                    for &(ref id, span, _) in &alternative_bindings {
+                        if bindings.iter().all(|binding| binding.0 != *id) {
+                            self.error(span, TypeError::OrPatternMissingBinding(id.clone()));
+                        }
+                    }
+                }
+                for (id, _, typ) in bindings {
+                    self.stack_var(id, typ);
+                }
+                match_type
+            }
             Pattern::Error => self.subs.new_var(),
         }
     }
 
+    /// Returns the kind of `pattern` if it can never match a value of `match_type`, such as a
+    /// record pattern matching on a variant. Variables and other unknown types are left to
+    /// unification.
+    fn pattern_shape_mismatch(
+        &self,
+        pattern: &Pattern<Symbol>,
+        match_type: &ArcType,
+    ) -> Option<PatternKind> {
+        let pattern_kind = match *pattern {
+            Pattern::Constructor(..) => PatternKind::Constructor,
+            Pattern::Record { .. } => PatternKind::Record,
+            Pattern::Tuple { .. } => PatternKind::Tuple,
+            _ => return None,
+        };
+        let typ = self.remove_aliases(self.subs.real(match_type).clone());
+        let mismatch = match (pattern_kind, &*typ) {
+            (PatternKind::Constructor, &Type::Record(_)) => true,
+            (PatternKind::Constructor, _) => false,
+            (_, &Type::Variant(_)) | (_, &Type::Builtin(_)) => true,
+            (_, &Type::App(ref func, _)) => match **func {
+                Type::Builtin(_) => true,
+                _ => false,
+            },
+            _ => false,
+        };
+        if mismatch {
+            Some(pattern_kind)
+        } else {
+            None
+        }
+    }
+
     fn typecheck_pattern_rec(
         &mut self,
         args: &mut [SpannedPattern<Symbol>],
@@ -1576,21 +3510,42 @@ impl<'a> Typecheck<'a> {
         for component in id.name().module().components() {
             let symbol = self.symbols.symbol(component);
             lookup_type = match lookup_type {
-                Some(typ) => Some(self.remove_aliases(typ.clone())
-                    .row_iter()
-                    .find(|field| field.name.name_eq(&symbol))
-                    .map(|field| field.typ.clone())
-                    .ok_or_else(|| TypeError::UndefinedField(typ, symbol))?),
+                Some(typ) => {
+                    let record = self.remove_aliases(typ.clone());
+                    let field_type = record
+                        .row_iter()
+                        .find(|field| field.name.name_eq(&symbol))
+                        .map(|field| field.typ.clone());
+                    match field_type {
+                        Some(field_type) => Some(field_type),
+                        None => {
+                            let similar = similar_names(
+                                symbol.declared_name(),
+                                record.row_iter().map(|field| field.name.declared_name()),
+                            );
+                            return Err(TypeError::UndefinedField(typ, symbol, similar));
+                        }
+                    }
+                }
                 None => Some(self.find(&symbol)?),
             };
         }
         let typ = lookup_type.unwrap();
         let type_symbol = self.symbols.symbol(id.name().name());
-        self.remove_aliases(typ.clone())
+        let record = self.remove_aliases(typ.clone());
+        let field_type = record
             .type_field_iter()
             .find(|field| field.name.name_eq(&type_symbol))
-            .map(|field| field.typ.clone().into_type())
-            .ok_or_else(|| TypeError::UndefinedField(typ, type_symbol))
+            .map(|field| field.typ.clone().into_type());
+        field_type.ok_or_else(|| {
+            let similar = similar_names(
+                type_symbol.declared_name(),
+                record
+                    .type_field_iter()
+                    .map(|field| field.name.declared_name()),
+            );
+            TypeError::UndefinedField(typ, type_symbol, similar)
+        })
     }
 
     fn translate_ast_type(
@@ -1617,8 +3572,9 @@ impl<'a> Typecheck<'a> {
         self.enter_scope();
         self.type_variables.enter_scope();
         let level = self.subs.var_id();
+        let holes_before = self.holes.len();
 
-        let is_recursive = bindings.iter().all(|bind| !bind.args.is_empty());
+        let is_recursive = ast::is_recursive_binding_group(bindings);
         // When the definitions are allowed to be mutually recursive
         if is_recursive {
             for bind in bindings.iter_mut() {
@@ -1640,6 +3596,7 @@ impl<'a> Typecheck<'a> {
                 }
                 let typ = {
                     if let Some(ref mut typ) = bind.typ {
+                        self.check_shadowed_forall_binders(typ);
                         self.kindcheck(typ);
 
                         let type_cache = self.type_cache.clone();
@@ -1651,7 +3608,7 @@ impl<'a> Typecheck<'a> {
                         bind.resolved_type = typ;
                     }
 
-                    self.new_skolem_scope_signature(&bind.resolved_type)
+                    self.new_skolem_scope_signature(&bind.resolved_type, bind.typ.as_ref())
                 };
                 self.typecheck_pattern(&mut bind.name, typ);
                 if let Expr::Lambda(ref mut lambda) = bind.expr.value {
@@ -1668,10 +3625,13 @@ impl<'a> Typecheck<'a> {
         for bind in bindings.iter_mut() {
             self.type_variables.enter_scope();
 
+            let errors_before = self.errors.len();
             // Functions which are declared as `let f x = ...` are allowed to be self
-            // recursive
-            let mut typ = if bind.args.is_empty() {
+            // recursive, as are values built by a constructor in a recursive group. Their
+            // signatures have already been translated above
+            let mut typ = if bind.args.is_empty() && !is_recursive {
                 if let Some(ref mut typ) = bind.typ {
+                    self.check_shadowed_forall_binders(typ);
                     self.kindcheck(typ);
 
                     let type_cache = self.type_cache.clone();
@@ -1683,10 +3643,10 @@ impl<'a> Typecheck<'a> {
                     bind.resolved_type = typ;
                 }
 
-                let typ = self.new_skolem_scope_signature(&bind.resolved_type);
+                let typ = self.new_skolem_scope_signature(&bind.resolved_type, bind.typ.as_ref());
                 self.typecheck_lambda(typ, bind.name.span.end, &mut bind.args, &mut bind.expr)
             } else {
-                let typ = self.new_skolem_scope_signature(&bind.resolved_type);
+                let typ = self.new_skolem_scope_signature(&bind.resolved_type, bind.typ.as_ref());
                 let function_type = self.skolemize(&typ);
 
                 self.typecheck_lambda(
@@ -1697,13 +3657,22 @@ impl<'a> Typecheck<'a> {
                 )
             };
 
+            if let Some(ref signature) = bind.typ {
+                self.note_signature(errors_before, signature);
+            } else if is_recursive {
+                self.report_polymorphic_recursion(errors_before, &bind.name.value, &bind.expr);
+            }
+
             debug!("let {:?} : {}", bind.name, typ);
 
             if !is_recursive {
                 // Merge the type declaration and the actual type
                 debug!("Generalize at {} = {}", level, bind.resolved_type);
                 self.generalize_binding(level, bind);
+                self.binding_origin = self.import_origin(&bind.name, &bind.expr);
+                self.check_unrelated_shadowed_binding(bind, 0);
                 self.typecheck_pattern(&mut bind.name, bind.resolved_type.clone());
+                self.binding_origin = None;
                 debug!("Generalized to {}", bind.resolved_type);
                 self.finish_pattern(level, &mut bind.name, &bind.resolved_type);
             } else {
@@ -1719,6 +3688,8 @@ impl<'a> Typecheck<'a> {
             for bind in bindings.iter_mut() {
                 debug!("Generalize {}", bind.resolved_type);
                 self.generalize_binding(level, bind);
+                // The bindings of the group are already in scope
+                self.check_unrelated_shadowed_binding(bind, 1);
                 self.finish_pattern(level, &mut bind.name, &bind.resolved_type);
                 debug!("Generalized to {}", bind.resolved_type);
             }
@@ -1751,16 +3722,14 @@ impl<'a> Typecheck<'a> {
             *self.implicit_resolver.implicit_bindings.last_mut().unwrap() = bindings;
         }
 
+        self.mark_hole_bindings(holes_before, bindings);
+
         debug!("Typecheck `in`");
         self.type_variables.exit_scope();
         Ok(())
     }
 
-    fn typecheck_type_bindings(
-        &mut self,
-        bindings: &mut [TypeBinding<Symbol>],
-        expr: &SpannedExpr<Symbol>,
-    ) {
+    fn typecheck_type_bindings(&mut self, bindings: &mut [TypeBinding<Symbol>]) {
         self.enter_scope();
 
         // Rename the types so they get a name which is distinct from types from other
@@ -1770,8 +3739,12 @@ impl<'a> Typecheck<'a> {
                 bind.alias.value.params(),
                 bind.alias.value.unresolved_type(),
             );
+            for param in bind.alias.value.params() {
+                self.check_shadowed_type_variable(&param.id, bind.name.span);
+            }
         }
 
+        let mut kind_errors = Vec::new();
         {
             let mut check =
                 KindCheck::new(&self.environment, &self.symbols, self.kind_cache.clone());
@@ -1806,8 +3779,7 @@ impl<'a> Typecheck<'a> {
                     .unresolved_type_mut()
                     .remove_single_forall();
                 if let Err(err) = check.kindcheck_type(typ) {
-                    self.errors
-                        .push(pos::spanned(err.span, TypeError::from(err.value).into()));
+                    kind_errors.push(err);
                 }
             }
 
@@ -1820,12 +3792,17 @@ impl<'a> Typecheck<'a> {
                 }
             }
         }
+        for err in kind_errors {
+            let err_value = self.add_similar_type_names(TypeError::from(err.value));
+            self.errors.push(pos::spanned(err.span, err_value.into()));
+        }
+
+        self.check_recursive_aliases(bindings);
 
         for bind in &mut *bindings {
             let s = String::from(self.symbols.string(&bind.alias.value.name));
             let new = self.symbols.scoped_symbol(&s);
-            self.original_symbols
-                .insert(bind.alias.value.name.clone(), new.clone());
+            self.insert_original_symbol(bind.alias.value.name.clone(), new.clone());
 
             if let Some(meta) = self.implicit_resolver
                 .metadata
@@ -1834,6 +3811,11 @@ impl<'a> Typecheck<'a> {
                 self.implicit_resolver.metadata.insert(new.clone(), meta);
             }
 
+            // A duplicated definition has the same name as the definition it duplicates
+            self.alias_definitions
+                .entry(new.clone())
+                .or_insert(bind.name.span);
+
             // Rename the aliase's name to its global name
             bind.alias.value.name = new;
         }
@@ -1868,11 +3850,19 @@ impl<'a> Typecheck<'a> {
 
         // Finally insert the declared types into the global scope
         for bind in bindings {
-            if self.environment.stack_types.get(&bind.name.value).is_some() {
+            if let Some(&(_, ref previous)) = self.environment.stack_types.get(&bind.name.value) {
+                let help = self.alias_definitions.get(&previous.name).map(|&span| {
+                    let location = self.source
+                        .as_ref()
+                        .and_then(|source| source.location(span.start));
+                    Help::PreviouslyDefinedAt(span, location)
+                });
                 self.errors.push(Spanned {
-                    span: expr_check_span(expr),
-                    // TODO Help to the position of the other field
-                    value: TypeError::DuplicateTypeDefinition(bind.name.value.clone()).into(),
+                    span: bind.name.span,
+                    value: HelpError::new(
+                        TypeError::DuplicateTypeDefinition(bind.name.value.clone()),
+                        help,
+                    ),
                 });
             } else {
                 self.stack_type(
@@ -1883,6 +3873,43 @@ impl<'a> Typecheck<'a> {
         }
     }
 
+    /// Reports the aliases in `bindings` which are defined in terms of themselves without a variant
+    /// or function type in between. Such a type could only be expanded forever and no value of it
+    /// could ever be constructed. Each cycle is reported once, at the first alias of the group
+    /// which is part of it.
+    fn check_recursive_aliases(&mut self, bindings: &[TypeBinding<Symbol>]) {
+        let names: Vec<_> = bindings.iter().map(|bind| &bind.name.value).collect();
+        let references: Vec<_> = bindings
+            .iter()
+            .map(|bind| {
+                let mut references = Vec::new();
+                let typ = bind.alias.value.unresolved_type();
+                direct_alias_references(&names, typ, &mut references);
+                references
+            })
+            .collect();
+
+        let mut reported = vec![false; bindings.len()];
+        for start in 0..bindings.len() {
+            if reported[start] {
+                continue;
+            }
+            if let Some(path) = find_alias_cycle(&references, start) {
+                for &i in &path {
+                    reported[i] = true;
+                }
+                let cycle = path.iter()
+                    .chain(Some(&start))
+                    .map(|&i| names[i].clone())
+                    .collect();
+                self.errors.push(Spanned {
+                    span: bindings[start].name.span,
+                    value: TypeError::RecursiveAliasWithoutIndirection(cycle).into(),
+                });
+            }
+        }
+    }
+
     fn kindcheck(&mut self, typ: &mut AstType<Symbol>) {
         let result = {
             let mut check =
@@ -1890,16 +3917,279 @@ impl<'a> Typecheck<'a> {
             check.kindcheck_type(typ)
         };
         if let Err(err) = result {
-            self.errors
-                .push(pos::spanned(err.span, TypeError::from(err.value).into()));
+            let err_value = self.add_similar_type_names(TypeError::from(err.value));
+            self.errors.push(pos::spanned(err.span, err_value.into()));
+        }
+    }
+
+    /// Reports `id`, bound at `span`, if it shadows a type variable from an enclosing signature.
+    /// Variables which were not bound by a signature, such as those created during inference,
+    /// are never reported.
+    fn check_shadowed_type_variable(&mut self, id: &Symbol, span: Span<BytePos>) {
+        if self.shadowed_type_variables == LintLevel::Allow {
+            return;
+        }
+        let outer = match self.type_variables.get(id).map(|typ| &**typ) {
+            Some(&Type::Skolem(ref skolem)) => self.type_variable_spans.get(&skolem.id).cloned(),
+            _ => None,
+        };
+        if let Some(outer) = outer {
+            let warning = HelpWarning::new(
+                Warning::ShadowedTypeVariable(id.clone()),
+                Some(Help::ShadowedTypeVariableBoundAt(outer)),
+            );
+            self.report_lint(self.shadowed_type_variables, span, warning);
+        }
+    }
+
+    /// Returns the module which the variables bound by `pattern` are imported from if `expr` is
+    /// (a field of) an imported module or if `pattern` destructures (a field of) a variable bound
+    /// to one
+    fn import_origin(
+        &self,
+        pattern: &SpannedPattern<Symbol>,
+        mut expr: &SpannedExpr<Symbol>,
+    ) -> Option<Symbol> {
+        while let Expr::Projection(ref record, _, _) = expr.value {
+            expr = record;
+        }
+        match (&pattern.value, &expr.value) {
+            (_, &Expr::Ident(ref id)) if id.name.is_global() => Some(id.name.clone()),
+            (&Pattern::Record { .. }, &Expr::Ident(ref id)) => self.environment
+                .stack
+                .get(&id.name)
+                .and_then(|bind| bind.origin.clone()),
+            _ => None,
+        }
+    }
+
+    /// Records that `id` is bound at `span` and reports it if it shadows a binding which is in
+    /// scope. Bindings which are themselves imported and names starting with `_` are never
+    /// reported.
+    fn check_shadowed_binding(&mut self, id: &Symbol, span: Span<BytePos>) {
+        self.binding_spans.insert(id.clone(), span);
+        if self.binding_origin.is_some()
+            || (self.shadowed_imports == LintLevel::Allow
+                && self.shadowed_bindings == LintLevel::Allow)
+            || id.declared_name().starts_with('_')
+        {
+            return;
+        }
+        let non_renamed_symbol = self.symbols.symbol(id.declared_name());
+        let (level, warning) = match self.environment.stack.get(&non_renamed_symbol) {
+            Some(&StackBinding {
+                origin: Some(ref module),
+                ..
+            }) => (
+                self.shadowed_imports,
+                Warning::ShadowedImport(id.clone(), module.definition_name().to_string()),
+            ),
+            Some(_) => (self.shadowed_bindings, Warning::ShadowedBinding(id.clone())),
+            None => return,
+        };
+        self.report_lint(level, span, warning.into());
+    }
+
+    /// Reports `bind` if it shadows local bindings of the same name and its (generalized) type is
+    /// unrelated to all of theirs. `in_scope` is the number of bindings of the name which `bind`
+    /// itself has already put in scope. Types which are not fully inferred are assumed to be
+    /// related.
+    fn check_unrelated_shadowed_binding(&mut self, bind: &ValueBinding<Symbol>, in_scope: usize) {
+        if self.unrelated_shadowed_bindings == LintLevel::Allow || self.binding_origin.is_some() {
+            return;
+        }
+        let id = match bind.name.value {
+            Pattern::Ident(ref id) if !id.name.declared_name().starts_with('_') => &id.name,
+            _ => return,
+        };
+        let non_renamed_symbol = self.symbols.symbol(id.declared_name());
+        let shadowed = {
+            let all = self.environment
+                .stack
+                .get_all(&non_renamed_symbol)
+                .unwrap_or(&[]);
+            let shadowed = &all[..all.len().saturating_sub(in_scope)];
+            match shadowed.last() {
+                // Shadowed imports are reported by `shadowed_imports` (imports are never
+                // overloads of local bindings)
+                Some(&StackBinding {
+                    origin: Some(_), ..
+                }) => return,
+                Some(_) if self.shadowed_bindings != LintLevel::Allow => return,
+                Some(_) => (),
+                None => return,
+            }
+            shadowed
+                .iter()
+                .map(|shadowed| self.subs.set_type(shadowed.typ.clone()))
+                .collect::<Vec<_>>()
+        };
+        let actual = self.subs.set_type(bind.resolved_type.clone());
+        if contains_variables(&actual) {
+            return;
+        }
+        let is_related = |shadowed: &ArcType| {
+            contains_variables(shadowed)
+                || ::check_signature(&self.environment, shadowed, &actual)
+                || ::check_signature(&self.environment, &actual, shadowed)
+        };
+        if shadowed.iter().any(is_related) {
+            return;
+        }
+        let warning = Warning::ShadowedUnrelatedBinding {
+            name: id.clone(),
+            shadowed: shadowed.last().unwrap().clone(),
+            actual,
+        };
+        let level = self.unrelated_shadowed_bindings;
+        self.report_lint(level, bind.name.span, warning.into());
+    }
+
+    /// Reports `warning` as a warning or as a `TypeError::Lint` error depending on `level`
+    fn report_lint(&mut self, level: LintLevel, span: Span<BytePos>, warning: HelpWarning<Symbol>) {
+        match level {
+            LintLevel::Allow => (),
+            LintLevel::Warn => self.warnings.push(Spanned {
+                span,
+                value: warning,
+            }),
+            LintLevel::Deny => self.errors.push(Spanned {
+                span,
+                value: warning.map_error(TypeError::Lint),
+            }),
+        }
+    }
+
+    /// Reports the variables bound by `bindings` which are not referred to outside of their own
+    /// definitions, as found when the expression was renamed. Names starting with `_`, implicit
+    /// bindings, bindings from macro expansions and the bindings kept in scope by
+    /// `typecheck_and_commit` are never reported.
+    fn check_unused_bindings(&mut self, bindings: &[ValueBinding<Symbol>]) {
+        if self.unused_bindings == LintLevel::Allow || self.commit_bindings {
+            return;
+        }
+        let mut variables = Vec::new();
+        for bind in bindings {
+            let is_implicit = bind.comment.as_ref().map_or(false, |comment| {
+                ::metadata::attributes(&comment.content).any(|(key, _)| key == "implicit")
+            });
+            // Bindings inserted by macros or the implicit prelude are not written by the user
+            if is_implicit || bind.name.span.expansion_id != pos::NO_EXPANSION {
+                continue;
+            }
+            variables.clear();
+            pattern_variables(&bind.name, &mut variables);
+            for &(id, span) in &variables {
+                if id.declared_name().starts_with('_') || self.used_variables.contains(id) {
+                    continue;
+                }
+                let level = self.unused_bindings;
+                self.report_lint(level, span, Warning::UnusedBinding(id.clone()).into());
+            }
+        }
+    }
+
+    /// Reports the alternatives which can't match any value that the alternatives before them
+    /// do not already match. A guarded alternative may not be taken so it never makes a later
+    /// alternative unreachable
+    fn check_unreachable_alternatives(&mut self, alts: &[Alternative<Symbol>]) {
+        if self.unreachable_patterns == LintLevel::Allow {
+            return;
+        }
+        let mut previous = Vec::with_capacity(alts.len());
+        for alt in alts {
+            let pattern = self.coverage_pattern(&alt.pattern.value);
+            if !::unreachable::is_reachable(&previous, &pattern) {
+                let level = self.unreachable_patterns;
+                self.report_lint(level, alt.pattern.span, Warning::UnreachablePattern.into());
+            }
+            if alt.guard.is_none() {
+                previous.push(pattern);
+            }
+        }
+    }
+
+    fn coverage_pattern(&self, pattern: &Pattern<Symbol>) -> ::unreachable::Pat {
+        use unreachable::Pat;
+
+        match *pattern {
+            Pattern::As(_, ref pattern) => self.coverage_pattern(&pattern.value),
+            Pattern::Ident(_) | Pattern::Error => Pat::Wild,
+            Pattern::Literal(ref literal) => Pat::Literal(literal.clone()),
+            Pattern::StringAffix { .. } => Pat::Opaque,
+            Pattern::Or(ref alternatives) => Pat::Or(
+                alternatives
+                    .iter()
+                    .map(|alternative| self.coverage_pattern(&alternative.value))
+                    .collect(),
+            ),
+            Pattern::Constructor(ref id, ref args) => Pat::Constructor {
+                name: id.name.declared_name().to_string(),
+                count: self.constructor_count(&id.typ),
+                args: args.iter()
+                    .map(|arg| self.coverage_pattern(&arg.value))
+                    .collect(),
+            },
+            Pattern::Record { ref fields, .. } => Pat::Record(
+                fields
+                    .iter()
+                    .map(|field| {
+                        let pattern = field
+                            .value
+                            .as_ref()
+                            .map_or(Pat::Wild, |pattern| self.coverage_pattern(&pattern.value));
+                        (field.name.value.declared_name().to_string(), pattern)
+                    })
+                    .collect(),
+            ),
+            Pattern::Tuple { ref elems, .. } => Pat::Record(
+                elems
+                    .iter()
+                    .enumerate()
+                    .map(|(i, elem)| (format!("_{}", i), self.coverage_pattern(&elem.value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns the number of constructors of the variant type constructed by `ctor_type`
+    fn constructor_count(&self, ctor_type: &ArcType) -> Option<usize> {
+        let mut typ = ctor_type.remove_forall();
+        while let Some((_, ret)) = typ.as_function() {
+            typ = ret;
+        }
+        let typ = resolve::remove_aliases(&self.environment, typ.clone());
+        match *typ {
+            Type::Variant(ref row) => Some(row.row_iter().count()),
+            _ => None,
+        }
+    }
+
+    fn check_shadowed_forall_binders(&mut self, typ: &AstType<Symbol>) {
+        use base::pos::HasSpan;
+        if let Type::Forall(ref params, ref body, _) = **typ {
+            let binder_span = Span::new(typ.span().start, body.span().start);
+            for param in params {
+                self.check_shadowed_type_variable(&param.id, binder_span);
+            }
         }
+        types::walk_move_type_opt(
+            typ,
+            &mut types::ControlVisitation(|typ: &AstType<_>| {
+                self.check_shadowed_forall_binders(typ);
+                None
+            }),
+        );
     }
 
     fn check_undefined_variables(&mut self, args: &[Generic<Symbol>], typ: &AstType<Symbol>) {
         use base::pos::HasSpan;
         match **typ {
             Type::Generic(ref id) => if args.iter().all(|arg| arg.id != id.id) {
-                self.error(typ.span(), TypeError::UndefinedVariable(id.id.clone()));
+                self.error(
+                    typ.span(),
+                    TypeError::UndefinedVariable(id.id.clone(), Vec::new()),
+                );
             },
             Type::Record(_) => {
                 // Inside records variables are bound implicitly to the closest field
@@ -1989,18 +4279,35 @@ impl<'a> Typecheck<'a> {
             }
             Pattern::Constructor(ref id, ref mut args) => {
                 debug!("{}: {}", self.symbols.string(&id.name), final_type);
-                let len = args.len();
-                let iter = args.iter_mut().zip(
-                    function_arg_iter(self, final_type.clone())
-                        .map(|t| t.1)
-                        .take(len)
-                        .collect::<Vec<_>>(),
-                );
-                for (arg, arg_type) in iter {
+                let typ = self.top_skolem_scope(final_type);
+                let typ = self.instantiate_generics(&typ);
+
+                // `final_type` is the type being matched on, the types of the arguments are
+                // found by instantiating the constructor and unifying its return type with it
+                let mut ctor_type = self.instantiate_generics(&id.typ);
+                let mut arg_types = Vec::with_capacity(args.len());
+                for _ in 0..args.len() {
+                    ctor_type = match ctor_type.as_function() {
+                        Some((arg, ret)) => {
+                            arg_types.push(arg.clone());
+                            ret.clone()
+                        }
+                        None => break,
+                    };
+                }
+                // Any error has already been reported when the pattern was typechecked
+                let _ = self.unify(&typ, ctor_type);
+
+                for (arg, arg_type) in args.iter_mut().zip(arg_types) {
+                    let mut arg_type = self.subs.set_type(arg_type);
+                    self.generalize_type(level, &mut arg_type);
                     self.finish_pattern(level, arg, &arg_type);
                 }
             }
-            Pattern::Literal(_) | Pattern::Error => (),
+            Pattern::Or(ref mut alternatives) => for alternative in alternatives {
+                self.finish_pattern(level, alternative, final_type);
+            },
+            Pattern::Literal(_) | Pattern::StringAffix { .. } | Pattern::Error => (),
         }
     }
 
@@ -2046,10 +4353,23 @@ impl<'a> Typecheck<'a> {
         }
     }
 
-    fn new_skolem_scope_signature(&mut self, typ: &ArcType) -> ArcType {
+    fn new_skolem_scope_signature(
+        &mut self,
+        typ: &ArcType,
+        signature: Option<&AstType<Symbol>>,
+    ) -> ArcType {
         let typ = self.new_skolem_scope(typ);
         // Put all new generic variable names into scope
         if let Type::Forall(ref params, _, Some(ref vars)) = *typ {
+            // Remember where the signature bound each variable so shadowing can be reported
+            if let Some(signature) = signature {
+                for (param, var) in params.iter().zip(vars) {
+                    if let Some(span) = type_variable_span(signature, &param.id) {
+                        self.type_variable_spans
+                            .insert(var.as_variable().unwrap().id, span);
+                    }
+                }
+            }
             self.type_variables
                 .extend(params.iter().zip(vars).map(|(param, var)| {
                     (
@@ -2275,15 +4595,27 @@ impl<'a> Typecheck<'a> {
     ) -> ArcType {
         debug!("Merge {} : {}", expected, actual);
         let expected = self.skolemize(&expected);
-        let state = unify_type::State::new(&self.environment, &self.subs);
-        match unify_type::subsumes(
+        let mut state = unify_type::State::new(&self.environment, &self.subs);
+        if let Some(ref trace) = self.unification_trace {
+            trace.start(
+                "subsumes",
+                &self.subs.set_type(expected.clone()),
+                &self.subs.set_type(actual.clone()),
+            );
+            state = state.with_trace(trace);
+        }
+        let result = unify_type::subsumes(
             &self.subs,
             &mut self.type_variables,
             level,
             state,
             &expected,
             &actual,
-        ) {
+        );
+        if let Some(ref trace) = self.unification_trace {
+            trace.finish(result.as_ref().map(|typ| self.subs.set_type(typ.clone())));
+        }
+        match result {
             Ok(typ) => self.subs.set_type(typ),
             Err(errors) => {
                 let mut expected = expected.clone();
@@ -2299,7 +4631,7 @@ impl<'a> Typecheck<'a> {
                     // TODO Help what caused this unification failure
                     value: err.into(),
                 });
-                self.subs.new_var()
+                self.error_type()
             }
         }
     }
@@ -2313,15 +4645,27 @@ impl<'a> Typecheck<'a> {
                     // TODO Help what caused this unification failure
                     value: err.into(),
                 });
-                self.subs.new_var()
+                self.error_type()
             }
         }
     }
 
     fn unify(&self, expected: &ArcType, mut actual: ArcType) -> TcResult<ArcType> {
         debug!("Unify start {} <=> {}", expected, actual);
-        let state = unify_type::State::new(&self.environment, &self.subs);
-        match unify::unify(&self.subs, state, expected, &actual) {
+        let mut state = unify_type::State::new(&self.environment, &self.subs);
+        if let Some(ref trace) = self.unification_trace {
+            trace.start(
+                "unify",
+                &self.subs.set_type(expected.clone()),
+                &self.subs.set_type(actual.clone()),
+            );
+            state = state.with_trace(trace);
+        }
+        let result = unify::unify(&self.subs, state, expected, &actual);
+        if let Some(ref trace) = self.unification_trace {
+            trace.finish(result.as_ref().map(|typ| self.subs.set_type(typ.clone())));
+        }
+        match result {
             Ok(typ) => Ok(self.subs.set_type(typ)),
             Err(errors) => {
                 let mut expected = expected.clone();
@@ -2373,22 +4717,144 @@ impl<'a> Typecheck<'a> {
         ::unify_type::top_skolem_scope(&self.subs, typ)
     }
 
+    /// Points the unification errors found since `errors_before` at the `signature` of the
+    /// binding they were found in (unless they already point to an inner binding's signature)
+    fn note_signature(&mut self, errors_before: usize, signature: &AstType<Symbol>) {
+        use base::pos::HasSpan;
+
+        let span = signature.span();
+        for error in (&mut self.errors).into_iter().skip(errors_before) {
+            if let TypeError::Unification(..) = error.value.error {
+                if error.value.notes().is_empty() {
+                    error
+                        .value
+                        .add_note(span, "signature declared here".to_string());
+                }
+            }
+        }
+    }
+
+    /// Replaces the occurs check failures found since `errors_before` which are caused by the
+    /// recursive binding `name` being used at a type which nests its own type. Only errors found
+    /// at a recursive reference to `name` in `expr` (or in the arguments it is applied to) are
+    /// replaced.
+    fn report_polymorphic_recursion(
+        &mut self,
+        errors_before: usize,
+        name: &Pattern<Symbol>,
+        expr: &SpannedExpr<Symbol>,
+    ) {
+        let id = match *name {
+            Pattern::Ident(ref id) => id,
+            _ => return,
+        };
+        let recursive_spans = recursive_reference_spans(expr, &id.name);
+        let subs = &self.subs;
+        for error in (&mut self.errors).into_iter().skip(errors_before) {
+            if !recursive_spans.iter().any(|span| span.contains(error.span)) {
+                continue;
+            }
+            let cycle_type = match error.value.error {
+                TypeError::Unification(_, _, ref errors) => errors
+                    .iter()
+                    .filter_map(|err| match *err {
+                        UnifyError::Substitution(substitution::Error::Occurs(ref var, ref typ))
+                            if contains_variable(subs, &id.typ, var) =>
+                        {
+                            Some(typ.clone())
+                        }
+                        _ => None,
+                    })
+                    .next(),
+                _ => None,
+            };
+            if let Some(cycle_type) = cycle_type {
+                error.value.error = TypeError::PolymorphicRecursion {
+                    binding: id.name.clone(),
+                    cycle_type,
+                };
+            }
+        }
+    }
+
     fn error_on_duplicated_field(
         &mut self,
-        duplicated_fields: &mut FnvSet<String>,
+        duplicated_fields: &mut FnvMap<String, Span<BytePos>>,
         new_name: Spanned<Symbol, BytePos>,
     ) -> bool {
         let span = new_name.span;
-        duplicated_fields
-            .replace(new_name.value.declared_name().to_string())
-            .map_or(true, |name| {
+        let name = new_name.value.declared_name().to_string();
+        match duplicated_fields.get(&name).cloned() {
+            Some(first_span) => {
+                let error = HelpError::from(TypeError::DuplicateField(name))
+                    .with_note(first_span, "first defined here".to_string());
                 self.errors.push(Spanned {
                     span: span,
-                    // TODO Help to the other fields location
-                    value: TypeError::DuplicateField(name).into(),
+                    value: error,
                 });
                 false
-            })
+            }
+            None => {
+                duplicated_fields.insert(name, span);
+                true
+            }
+        }
+    }
+}
+
+/// Returns the spans of the references to `name` in `expr`. References which are applied to
+/// arguments span the entire application.
+fn recursive_reference_spans(expr: &SpannedExpr<Symbol>, name: &Symbol) -> Vec<Span<BytePos>> {
+    struct FindReferences<'n> {
+        name: &'n Symbol,
+        spans: Vec<Span<BytePos>>,
+    }
+
+    impl<'a, 'n> ast::Visitor<'a> for FindReferences<'n> {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, expr: &'a SpannedExpr<Symbol>) {
+            match expr.value {
+                Expr::Ident(ref id) if id.name == *self.name => self.spans.push(expr.span),
+                Expr::App { ref func, .. } => match func.value {
+                    Expr::Ident(ref id) if id.name == *self.name => self.spans.push(expr.span),
+                    _ => (),
+                },
+                _ => (),
+            }
+            ast::walk_expr(self, expr)
+        }
+    }
+
+    let mut visitor = FindReferences {
+        name,
+        spans: Vec::new(),
+    };
+    ast::Visitor::visit_expr(&mut visitor, expr);
+    visitor.spans
+}
+
+/// Returns the span of the first place where `id` is bound or used in `typ`
+fn type_variable_span(typ: &AstType<Symbol>, id: &Symbol) -> Option<Span<BytePos>> {
+    use base::pos::HasSpan;
+    match **typ {
+        Type::Generic(ref generic) if generic.id == *id => Some(typ.span()),
+        Type::Forall(ref params, ref body, _) if params.iter().any(|param| param.id == *id) => {
+            Some(Span::new(typ.span().start, body.span().start))
+        }
+        _ => {
+            let mut span = None;
+            types::walk_move_type_opt(
+                typ,
+                &mut types::ControlVisitation(|typ: &AstType<_>| {
+                    if span.is_none() {
+                        span = type_variable_span(typ, id);
+                    }
+                    None
+                }),
+            );
+            span
+        }
     }
 }
 
@@ -2517,6 +4983,144 @@ fn expr_check_span(e: &SpannedExpr<Symbol>) -> Span<BytePos> {
     }
 }
 
+/// Calls `f` with each type which is shown when displaying `error`
+fn error_types<F>(error: &TypeError<Symbol>, f: &mut F)
+where
+    F: FnMut(&ArcType),
+{
+    use self::TypeError::*;
+
+    match *error {
+        NotAFunction(ref typ)
+        | UndefinedField(ref typ, _, _)
+        | PatternError(ref typ, _)
+        | UnableToResolveImplicit(ref typ, _)
+        | PatternShapeMismatch {
+            scrutinee: ref typ,
+            ..
+        }
+        | PolymorphicRecursion {
+            cycle_type: ref typ,
+            ..
+        } => f(typ),
+        InvalidProjection(ref typ, ref resolved)
+        | OrPatternBindingMismatch {
+            expected: ref typ,
+            actual: ref resolved,
+            ..
+        } => {
+            f(typ);
+            f(resolved);
+        }
+        Unification(ref expected, ref actual, ref errors) => {
+            f(expected);
+            f(actual);
+            for err in errors {
+                if let unify::Error::TypeMismatch(ref l, ref r) = *err {
+                    f(l);
+                    f(r);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Merges `UndefinedType` and `UndefinedVariable` errors which refer to the same name into the
+/// first error, recording the spans of the others with `Help::AlsoUsedAt` (and as notes, which
+/// show their locations) so that a single missing import does not bury the other errors
+fn aggregate_undefined_errors(errors: Error) -> Error {
+    let mut aggregated: Vec<SpannedTypeError<Symbol>> = Vec::new();
+    let mut first_errors = FnvMap::default();
+    for error in errors {
+        let key = match error.value {
+            HelpError {
+                error: TypeError::UndefinedType(ref id, _),
+                help: None,
+                ..
+            } => Some((true, id.as_ref().to_string())),
+            HelpError {
+                error: TypeError::UndefinedVariable(ref id, _),
+                help: None,
+                ..
+            } => Some((false, id.as_ref().to_string())),
+            _ => None,
+        };
+        match key {
+            Some(key) => match first_errors.get(&key).cloned() {
+                Some(index) => {
+                    let first: &mut SpannedTypeError<Symbol> = &mut aggregated[index];
+                    match first.value.help {
+                        Some(Help::AlsoUsedAt(ref mut spans)) => spans.push(error.span),
+                        _ => first.value.help = Some(Help::AlsoUsedAt(vec![error.span])),
+                    }
+                    first.value.add_note(error.span, "also used here".to_string());
+                }
+                None => {
+                    first_errors.insert(key, aggregated.len());
+                    aggregated.push(error);
+                }
+            },
+            None => aggregated.push(error),
+        }
+    }
+    aggregated.into()
+}
+
+/// Adds a fix renaming every use of an undefined variable to each of the similar names which were
+/// found for it
+fn add_rename_fixes(errors: &mut Error) {
+    for error in errors {
+        let fixes: Vec<_> = match error.value.error {
+            TypeError::UndefinedVariable(_, ref similar) => {
+                let mut spans = vec![error.span];
+                if let Some(Help::AlsoUsedAt(ref also_used_at)) = error.value.help {
+                    spans.extend(also_used_at.iter().cloned());
+                }
+                similar
+                    .iter()
+                    .map(|name| Fix {
+                        title: format!("Rename to `{}`", name),
+                        edits: spans.iter().map(|&span| (span, name.clone())).collect(),
+                    })
+                    .collect()
+            }
+            _ => continue,
+        };
+        for fix in fixes {
+            error.value.add_fix(fix);
+        }
+    }
+}
+
+/// Sorts `errors` by their location and removes duplicated errors (errors at the same location
+/// with the same message) so that the errors are reported in the same order regardless of the
+/// order they were found in. Errors after the first `limit` are replaced by a single
+/// `ErrorsNotShown` error.
+fn sort_and_limit_errors(errors: Error, limit: Option<usize>) -> Error {
+    let mut errors: Vec<_> = errors.into();
+    let mut messages: Vec<_> = errors
+        .drain(..)
+        .map(|error| (error.to_string(), error))
+        .collect();
+    messages.sort_by(|l, r| (l.1.span, &l.0).cmp(&(r.1.span, &r.0)));
+    messages.dedup_by(|l, r| l.1.span == r.1.span && l.0 == r.0);
+    let mut errors: Vec<_> = messages.into_iter().map(|(_, error)| error).collect();
+
+    if let Some(limit) = limit {
+        if errors.len() > limit {
+            let span = errors[limit].span;
+            let not_shown = errors.len() - limit;
+            errors.truncate(limit);
+            errors.push(Spanned {
+                span,
+                value: TypeError::ErrorsNotShown(not_shown).into(),
+            });
+        }
+    }
+    errors.into()
+}
+
 /// Removes layers of `Type::App` and `Type::Record` by packing them into a single `Type::App` or
 /// `Type::Record`
 ///
@@ -2742,7 +5346,7 @@ impl<'a, 'b> TypeGeneralizer<'a, 'b> {
                         .extend(params.iter().map(|param| (param.id.clone(), typ.clone())));
                 }
 
-                let new_type = types::walk_move_type_opt(
+                let new_type = types::walk_move_type_spine_opt(
                     typ,
                     &mut types::ControlVisitation(|typ: &ArcType| self.generalize_type(typ)),
                 );
@@ -2772,21 +5376,22 @@ struct TypeVariableGenerator {
 
 impl TypeVariableGenerator {
     fn new(level: u32, subs: &Substitution<ArcType>, typ: &ArcType) -> TypeVariableGenerator {
-        fn gather_foralls(map: &mut FnvSet<Symbol>, subs: &Substitution<ArcType>, typ: &ArcType) {
-            let typ = subs.real(typ);
+        // Types are visited with an explicit stack as they may be arbitrarily deep
+        let mut map = FnvSet::default();
+        let mut stack = vec![typ.clone()];
+        while let Some(typ) = stack.pop() {
+            let typ = subs.real(&typ);
             if let Type::Forall(ref params, _, _) = **typ {
                 map.extend(params.iter().map(|param| param.id.clone()));
             }
             types::walk_move_type_opt(
                 typ,
                 &mut types::ControlVisitation(|typ: &ArcType| {
-                    gather_foralls(map, subs, typ);
+                    stack.push(typ.clone());
                     None
                 }),
             );
         }
-        let mut map = FnvSet::default();
-        gather_foralls(&mut map, subs, typ);
         TypeVariableGenerator {
             map,
             name: "".to_string(),
@@ -2827,3 +5432,196 @@ impl TypeVariableGenerator {
         self.next_variable_(tc)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tests::*;
+
+    #[test]
+    fn ambiguous_implicit_candidates_are_sorted_and_capped() {
+        let record: ArcType = Type::record(
+            vec![],
+            (0..20)
+                .map(|i| Field::new(intern(&format!("field_{}", i)), Type::int()))
+                .collect(),
+        );
+        let candidates: Vec<_> = (0..8)
+            .rev()
+            .map(|i| (format!("candidate_{}", i), record.clone()))
+            .collect();
+        let error = TypeError::AmbiguousImplicit(candidates);
+
+        let message = error.to_string();
+        assert_eq!(message, error.to_string());
+
+        let expected_candidates = (0..MAX_PRINTED_CANDIDATES)
+            .map(|i| {
+                let candidate = format!("candidate_{}: {}", i, record)
+                    .split_whitespace()
+                    .format(" ")
+                    .to_string();
+                format!("{}...", &candidate[..MAX_CANDIDATE_WIDTH - 3])
+            })
+            .format(", ");
+        assert_eq!(
+            message,
+            format!(
+                "Unable to resolve implicit. Multiple candidates were found: {}, +3 more",
+                expected_candidates
+            )
+        );
+    }
+
+    #[test]
+    fn errors_are_sorted_and_deduplicated() {
+        let span = |start: usize| pos::span(BytePos::from(start), BytePos::from(start + 1));
+        let error = |start, msg: &str| Spanned {
+            span: span(start),
+            value: HelpError::from(TypeError::Message(msg.to_string())),
+        };
+        let errors: Error = vec![
+            error(3, "b"),
+            error(1, "a"),
+            error(3, "b"),
+            error(3, "a"),
+            error(2, "c"),
+        ].into();
+
+        let errors: Vec<_> = sort_and_limit_errors(errors, None).into();
+        assert_eq!(
+            errors,
+            vec![error(1, "a"), error(2, "c"), error(3, "a"), error(3, "b")]
+        );
+    }
+
+    #[test]
+    fn deeply_nested_function_types_do_not_overflow_the_stack() {
+        const DEPTH: usize = 50_000;
+
+        let env = MockEnv::new();
+        let mut symbols = Symbols::new();
+        let mut tc = Typecheck::new("test".into(), &mut symbols, &env, TypeCache::new());
+
+        let arg = tc.subs.new_var();
+        let ret = tc.subs.new_var();
+        match *ret {
+            Type::Variable(ref var) => tc.subs.insert(var.id, Type::int()),
+            _ => unreachable!(),
+        }
+        let mut typ = ret;
+        for _ in 0..DEPTH {
+            typ = tc.type_cache.function(Some(arg.clone()), typ);
+        }
+        // Dropping a type recurses through every function type so all the types are leaked
+        let mut types = vec![typ.clone()];
+
+        let mut typ = tc.subs.set_type(typ);
+        types.push(typ.clone());
+        {
+            let mut current = &typ;
+            for _ in 0..DEPTH {
+                current = current.as_function().expect("Function").1;
+            }
+            assert_eq!(*current, Type::int());
+        }
+
+        tc.generalize_type(0, &mut typ);
+        match *typ {
+            Type::Forall(ref params, ref function, _) => {
+                assert_eq!(params.len(), 1);
+                let (arg, _) = function.as_function().expect("Function");
+                assert_eq!(*arg, Type::generic(params[0].clone()));
+            }
+            _ => panic!("Expected a forall"),
+        }
+        types.push(typ);
+
+        mem::forget(types);
+    }
+
+    #[test]
+    fn original_symbols_resolve_chains_in_a_single_probe() {
+        let imported = intern("Imported");
+        let (alias, alias_of_alias, unrelated) = (intern("Alias"), intern("Alias2"), intern("x"));
+
+        let mut original_symbols = OriginalSymbols::new();
+        assert_eq!(original_symbols.get(&unrelated), None);
+        assert_eq!(original_symbols.skipped.get(), 1);
+        assert_eq!(original_symbols.probes.get(), 0);
+
+        let empty_filter = original_symbols.filter.clone();
+        original_symbols.enter_scope();
+        original_symbols.insert(alias.clone(), imported.clone());
+        original_symbols.insert(alias_of_alias.clone(), alias.clone());
+        original_symbols.insert(unrelated.clone(), alias_of_alias.clone());
+
+        assert_eq!(original_symbols.get(&unrelated), Some(&imported));
+        assert_eq!(original_symbols.probes.get(), 1);
+
+        original_symbols.exit_scope();
+        assert_eq!(original_symbols.filter, empty_filter);
+        assert_eq!(original_symbols.get(&unrelated), None);
+    }
+
+    #[test]
+    fn env_snapshot_lists_bindings_and_aliases_by_scope() {
+        let _ = ::env_logger::try_init();
+
+        let env = MockEnv::new();
+        let bool_sym = intern("Bool");
+        let mut symbols = Symbols::new();
+        let mut tc = Typecheck::new("test".into(), &mut symbols, &env, TypeCache::new());
+
+        let x = tc.symbols.symbol("x");
+        let test = tc.symbols.scoped_symbol("Test");
+        tc.stack_var(x.clone(), Type::int());
+        tc.enter_scope();
+        tc.stack_type(test.clone(), &Alias::new(test.clone(), Type::int()));
+        tc.stack_var(x.clone(), Type::string());
+
+        let snapshot = tc.env_snapshot(false);
+        let bindings: Vec<_> = snapshot
+            .bindings
+            .iter()
+            .filter(|binding| binding.name == x)
+            .map(|binding| (binding.depth, binding.typ.clone()))
+            .collect();
+        assert!(bindings.contains(&(0, Type::int())), "{}", snapshot);
+        assert!(bindings.contains(&(1, Type::string())), "{}", snapshot);
+        assert!(
+            snapshot.aliases.contains(&EnvAlias {
+                depth: Some(1),
+                name: test.clone(),
+                params: Vec::new(),
+                kind: Kind::typ(),
+            }),
+            "{}",
+            snapshot
+        );
+        assert!(snapshot.aliases.iter().all(|alias| alias.name != bool_sym));
+
+        let snapshot = tc.env_snapshot(true);
+        assert!(
+            snapshot
+                .aliases
+                .iter()
+                .any(|alias| alias.name == bool_sym && alias.depth.is_none()),
+            "{}",
+            snapshot
+        );
+
+        tc.exit_scope();
+        let snapshot = tc.env_snapshot(false);
+        assert!(snapshot.aliases.is_empty(), "{}", snapshot);
+        assert!(
+            snapshot
+                .bindings
+                .iter()
+                .all(|binding| binding.depth == 0 && binding.typ == Type::int()),
+            "{}",
+            snapshot
+        );
+    }
+}
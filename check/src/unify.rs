@@ -1,9 +1,11 @@
 use std::fmt;
 
+#[cfg(feature = "pretty-errors")]
 use pretty::{Arena, DocAllocator};
 
 use base::error::Errors;
 use base::symbol::Symbol;
+#[cfg(feature = "pretty-errors")]
 use base::types::ToDoc;
 
 use substitution::{self, Substitutable, Substitution};
@@ -15,36 +17,82 @@ pub enum Error<T, E> {
     Other(E),
 }
 
-impl<T, E> fmt::Display for Error<T, E>
+/// Types which can be shown in a unification error.
+///
+/// With the `pretty-errors` feature the types are laid out with `pretty`, otherwise only their
+/// `Display` implementation is required.
+#[cfg(feature = "pretty-errors")]
+pub trait ErrorType: fmt::Display + for<'a> ToDoc<'a, Arena<'a>, ()> {}
+
+#[cfg(feature = "pretty-errors")]
+impl<T> ErrorType for T
 where
     T: fmt::Display + for<'a> ToDoc<'a, Arena<'a>, ()>,
+{
+}
+
+/// Types which can be shown in a unification error.
+///
+/// With the `pretty-errors` feature the types are laid out with `pretty`, otherwise only their
+/// `Display` implementation is required.
+#[cfg(not(feature = "pretty-errors"))]
+pub trait ErrorType: fmt::Display {}
+
+#[cfg(not(feature = "pretty-errors"))]
+impl<T> ErrorType for T
+where
+    T: fmt::Display,
+{
+}
+
+impl<T, E> fmt::Display for Error<T, E>
+where
+    T: ErrorType,
     E: fmt::Display,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use unify::Error::*;
 
         match *self {
-            TypeMismatch(ref l, ref r) => {
-                let arena = Arena::new();
-                let doc = chain![&arena;
-                    arena.newline(),
-                    "Expected:",
-                    arena.space(),
-                    l.to_doc(&arena, ()).group(),
-                    arena.newline(),
-                    "Found:",
-                    arena.space(),
-                    r.to_doc(&arena, ()).group()
-                ].group()
-                    .nest(4);
-                write!(f, "Types do not match:{}", doc.1.pretty(80))
-            }
+            TypeMismatch(ref l, ref r) => fmt_type_mismatch(l, r, f),
             Substitution(ref err) => err.fmt(f),
             Other(ref err) => write!(f, "{}", err),
         }
     }
 }
 
+#[cfg(feature = "pretty-errors")]
+fn fmt_type_mismatch<T>(l: &T, r: &T, f: &mut fmt::Formatter) -> fmt::Result
+where
+    T: ErrorType,
+{
+    let arena = Arena::new();
+    let doc = chain![&arena;
+        arena.newline(),
+        "Expected:",
+        arena.space(),
+        l.to_doc(&arena, ()).group(),
+        arena.newline(),
+        "Found:",
+        arena.space(),
+        r.to_doc(&arena, ()).group()
+    ].group()
+        .nest(4);
+    write!(
+        f,
+        "Types do not match:{}",
+        doc.1.pretty(::ErrorLayout::current().width)
+    )
+}
+
+#[cfg(not(feature = "pretty-errors"))]
+fn fmt_type_mismatch<T>(l: &T, r: &T, f: &mut fmt::Formatter) -> fmt::Result
+where
+    T: ErrorType,
+{
+    write!(f, "Types do not match:\n    Expected: {}\n    Found: {}", l, r)
+}
+
 impl<T, E> From<substitution::Error<T>> for Error<T, E> {
     fn from(err: substitution::Error<T>) -> Self {
         Error::Substitution(err)
@@ -106,6 +154,11 @@ pub trait Unifiable<S>: Substitutable + Sized {
     ) -> Result<Option<Self>, Error<Self, Self::Error>>
     where
         UnifierState<S, U>: Unifier<S, Self>;
+
+    /// Called after the type variable `variable` has been bound to `typ`
+    fn on_bind(state: &S, variable: &Self, typ: &Self) {
+        let _ = (state, variable, typ);
+    }
 }
 
 /// Unify `l` and `r` taking into account and updating the substitution `subs` using the
@@ -171,11 +224,13 @@ where
             (_, Some(r_var)) => {
                 let replacement = subs.union(r_var, l)?;
                 debug!("Union {} <> {}", l, replacement.as_ref().unwrap_or(r));
+                T::on_bind(&self.state, r, l);
                 Ok(replacement)
             }
             (Some(l_var), _) => {
                 let replacement = subs.union(l_var, r)?;
                 debug!("Union {} <> {}", replacement.as_ref().unwrap_or(l), r);
+                T::on_bind(&self.state, l, r);
                 Ok(replacement.or_else(|| Some(r.clone())))
             }
             (None, None) => {
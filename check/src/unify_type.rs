@@ -36,6 +36,12 @@ impl GenericVariant for ArcType {
 
 pub type Error<I> = UnifyError<ArcType<I>, TypeError<I>>;
 
+/// Receives a description of each decision made by the unifier (variables being bound, aliases
+/// being expanded and record fields being matched).
+pub trait Trace {
+    fn step(&self, step: fmt::Arguments);
+}
+
 #[derive(Clone)]
 pub struct State<'a> {
     env: &'a (TypeEnv + 'a),
@@ -45,6 +51,7 @@ pub struct State<'a> {
     subs: &'a Substitution<ArcType>,
     record_context: Option<(ArcType, ArcType)>,
     pub in_alias: bool,
+    trace: Option<&'a (Trace + 'a)>,
 }
 
 impl<'a> State<'a> {
@@ -55,6 +62,19 @@ impl<'a> State<'a> {
             subs: subs,
             record_context: None,
             in_alias: false,
+            trace: None,
+        }
+    }
+
+    /// Reports each step of the unification to `trace`
+    pub fn with_trace(mut self, trace: &'a (Trace + 'a)) -> State<'a> {
+        self.trace = Some(trace);
+        self
+    }
+
+    fn trace(&self, step: fmt::Arguments) {
+        if let Some(trace) = self.trace {
+            trace.step(step);
         }
     }
 
@@ -63,6 +83,7 @@ impl<'a> State<'a> {
         subs: &Substitution<ArcType>,
         typ: &ArcType,
     ) -> Result<Option<ArcType>, TypeError<Symbol>> {
+        let original = typ;
         if let Some(alias_id) = typ.alias_ident() {
             if self.reduced_aliases.iter().any(|name| name == alias_id) {
                 return Err(TypeError::SelfRecursiveAlias(alias_id.clone()));
@@ -99,6 +120,7 @@ impl<'a> State<'a> {
                         None => break,
                     }
                 }
+                self.trace(format_args!("expand alias {} => {}", original, typ));
                 Ok(Some(typ))
             }
             None => Ok(None),
@@ -208,10 +230,14 @@ where
                 id
             ),
             TypeError::MissingFields(ref typ, ref fields) => {
+                let layout = ::ErrorLayout::current();
                 write!(
                     f,
                     "The type `{}` lacks the following fields: ",
-                    TypeFormatter::new(typ).filter(filter)
+                    TypeFormatter::new(typ)
+                        .filter(filter)
+                        .width(layout.width)
+                        .max_depth(layout.max_depth)
                 )?;
                 for (i, field) in fields.iter().enumerate() {
                     let sep = match i {
@@ -269,6 +295,10 @@ impl Substitutable for ArcType<Symbol> {
 impl<'a> Unifiable<State<'a>> for ArcType {
     type Error = TypeError<Symbol>;
 
+    fn on_bind(state: &State<'a>, variable: &Self, typ: &Self) {
+        state.trace(format_args!("bind {} := {}", variable, typ));
+    }
+
     fn zip_match<U>(
         &self,
         other: &Self,
@@ -277,6 +307,20 @@ impl<'a> Unifiable<State<'a>> for ArcType {
     where
         UnifierState<'a, U>: Unifier<State<'a>, Self>,
     {
+        // `_` is the type given to expressions which failed to typecheck. It is compatible with
+        // every type so that the error is only reported once
+        match (&**self, &**other) {
+            (&Type::Hole, &Type::Hole) => return Ok(None),
+            (&Type::Hole, _) => {
+                bind_variables_to_hole(unifier, self, other);
+                return Ok(None);
+            }
+            (_, &Type::Hole) => {
+                bind_variables_to_hole(unifier, other, self);
+                return Ok(None);
+            }
+            _ => (),
+        }
         let reduced_aliases = unifier.state.reduced_aliases.len();
         debug!("{} <=> {}", self, other);
         let (l_temp, r_temp);
@@ -719,6 +763,10 @@ where
 
     // Unify the fields that exists in both records
     let new_both = merge::merge_tuple_iter(both.iter().cloned(), |l, r| {
+        unifier.state.trace(format_args!(
+            "match field {}: {} <=> {}",
+            l.name, l.typ, r.typ
+        ));
         unifier
             .try_match(&l.typ, &r.typ)
             .map(|typ| Field::new(l.name.clone(), typ))
@@ -904,6 +952,9 @@ where
         l = match find_alias(unifier, l.clone(), r_id)? {
             None => l,
             Some(typ) => {
+                unifier
+                    .state
+                    .trace(format_args!("expand alias {} => {}", expected, typ));
                 *through_alias = true;
                 return Ok((typ, actual.clone()));
             }
@@ -914,6 +965,9 @@ where
         r = match find_alias(unifier, r.clone(), l_id)? {
             None => r,
             Some(typ) => {
+                unifier
+                    .state
+                    .trace(format_args!("expand alias {} => {}", actual, typ));
                 *through_alias = true;
                 typ
             }
@@ -1092,6 +1146,7 @@ impl<'a, 'e> Unifier<State<'a>, ArcType> for UnifierState<'a, Subsume<'e>> {
                 };
                 debug!("Union merge {} <> {}", left, r_var);
                 subs.union(r_var, left)?;
+                self.state.trace(format_args!("bind {} := {}", r, left));
                 Ok(None)
             }
 
@@ -1124,14 +1179,16 @@ impl<'a, 'e> Unifier<State<'a>, ArcType> for UnifierState<'a, Subsume<'e>> {
                 let l = l.instantiate_generics(&mut variables);
                 self.try_match_res(&l, r)
             }
-            (_, &Type::Variable(ref r)) => {
-                debug!("Union merge {} <> {}", l, r);
-                subs.union(r, l)?;
+            (_, &Type::Variable(ref r_var)) => {
+                debug!("Union merge {} <> {}", l, r_var);
+                subs.union(r_var, l)?;
+                self.state.trace(format_args!("bind {} := {}", r, l));
                 Ok(None)
             }
-            (&Type::Variable(ref l), _) => {
-                debug!("Union merge {} <> {}", l, r);
-                subs.union(l, r)?;
+            (&Type::Variable(ref l_var), _) => {
+                debug!("Union merge {} <> {}", l_var, r);
+                subs.union(l_var, r)?;
+                self.state.trace(format_args!("bind {} := {}", l, r));
                 Ok(Some(r.clone()))
             }
             _ => {
@@ -1148,6 +1205,23 @@ impl<'a, 'e> Unifier<State<'a>, ArcType> for UnifierState<'a, Subsume<'e>> {
     }
 }
 
+/// Unifies the variables in `typ` with `hole` so that values whose types are only known from an
+/// erroneous expression do not cause more errors
+fn bind_variables_to_hole<'a, U>(unifier: &mut UnifierState<'a, U>, hole: &ArcType, typ: &ArcType)
+where
+    UnifierState<'a, U>: Unifier<State<'a>, ArcType>,
+{
+    let mut variables = Vec::new();
+    types::walk_type(typ, |typ: &ArcType| {
+        if let Type::Variable(_) = **typ {
+            variables.push(typ.clone());
+        }
+    });
+    for variable in variables {
+        unifier.try_match(&variable, hole);
+    }
+}
+
 fn reconstruct_forall(
     subs: &Substitution<ArcType>,
     params: &[Generic<Symbol>],
@@ -1200,7 +1274,7 @@ mod tests {
             ],
         );
         let subs = Substitution::new(Kind::typ());
-        let env = MockEnv;
+        let env = MockEnv::new();
         let state = State::new(&env, &subs);
         let result = unify(&subs, state, &l, &r);
         assert_eq!(
@@ -1216,7 +1290,7 @@ mod tests {
     fn unify_row_polymorphism() {
         let _ = ::env_logger::try_init();
 
-        let env = MockEnv;
+        let env = MockEnv::new();
         let subs = Substitution::new(Kind::typ());
         let state = State::new(&env, &subs);
 
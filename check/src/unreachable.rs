@@ -0,0 +1,208 @@
+//! Detection of `match` alternatives which can never be reached.
+//!
+//! An alternative is unreachable if every value it matches is already matched by an earlier
+//! alternative. This is decided with the usefulness algorithm from "Warnings for pattern
+//! matching" (Luc Maranget), on a simplified form of the patterns where everything which
+//! matches any value is a wildcard and tuples are records with the fields `_0`, `_1`, ...
+
+use std::iter;
+
+use base::ast::Literal;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Pat {
+    /// Matches every value (variables, `_` and patterns which could not be typechecked)
+    Wild,
+    /// A constructor of a variant type. `count` is the number of constructors of the type, if it
+    /// is known
+    Constructor {
+        name: String,
+        count: Option<usize>,
+        args: Vec<Pat>,
+    },
+    /// A record or tuple pattern. Fields which are not mentioned match any value.
+    Record(Vec<(String, Pat)>),
+    Literal(Literal),
+    /// Matches the values matched by any of the patterns
+    Or(Vec<Pat>),
+    /// Matches some values which can't be described by the other patterns, such as the strings
+    /// matched by `"GET " ++ rest`. These never make a later alternative unreachable.
+    Opaque,
+}
+
+impl Pat {
+    fn contains_opaque(&self) -> bool {
+        match *self {
+            Pat::Opaque => true,
+            Pat::Wild | Pat::Literal(_) => false,
+            Pat::Constructor { ref args, .. } => args.iter().any(Pat::contains_opaque),
+            Pat::Record(ref fields) => fields.iter().any(|&(_, ref pat)| pat.contains_opaque()),
+            Pat::Or(ref alternatives) => alternatives.iter().any(Pat::contains_opaque),
+        }
+    }
+}
+
+/// What the first pattern of a row must be to be kept when specializing a matrix
+enum Head<'a> {
+    Constructor(&'a str, usize),
+    Record(Vec<&'a str>),
+    Literal(&'a Literal),
+}
+
+/// Returns `true` if `pattern` matches some value which is not matched by any of `previous`
+pub(crate) fn is_reachable(previous: &[Pat], pattern: &Pat) -> bool {
+    // An opaque pattern may not match any particular value so rows containing one do not cover
+    // anything. In `pattern` they are treated as wildcards as they match a subset of those.
+    let rows: Vec<_> = previous
+        .iter()
+        .filter(|pat| !pat.contains_opaque())
+        .map(|pat| vec![pat])
+        .collect();
+    useful(&rows, &[pattern])
+}
+
+fn useful(rows: &[Vec<&Pat>], row: &[&Pat]) -> bool {
+    let (first, rest) = match row.split_first() {
+        Some(x) => x,
+        None => return rows.is_empty(),
+    };
+    if rows.iter().any(|row| match *row[0] {
+        Pat::Or(_) => true,
+        _ => false,
+    }) {
+        return useful(&expand_or_patterns(rows), row);
+    }
+    let column = || rows.iter().map(|row| row[0]).chain(Some(*first));
+    let specialize_by = |head: &Head| {
+        let rows: Vec<_> = rows.iter()
+            .filter_map(|row| specialize(row, head))
+            .collect();
+        specialize(row, head).map_or(false, |row| useful(&rows, &row))
+    };
+    match **first {
+        Pat::Constructor {
+            ref name, ref args, ..
+        } => specialize_by(&Head::Constructor(name, args.len())),
+        Pat::Literal(ref literal) => specialize_by(&Head::Literal(literal)),
+        Pat::Record(_) => specialize_by(&record_head(column())),
+        Pat::Or(ref alternatives) => alternatives.iter().any(|alternative| {
+            let mut row = vec![alternative];
+            row.extend_from_slice(rest);
+            useful(rows, &row)
+        }),
+        Pat::Wild | Pat::Opaque => {
+            if column().any(|pat| match *pat {
+                Pat::Record(_) => true,
+                _ => false,
+            }) {
+                // Records only have a single "constructor" so they are always complete
+                return specialize_by(&record_head(column()));
+            }
+
+            let mut constructors = Vec::new();
+            let mut count = None;
+            for pat in rows.iter().map(|row| row[0]) {
+                if let Pat::Constructor {
+                    ref name,
+                    count: ref ctor_count,
+                    ref args,
+                } = *pat
+                {
+                    count = count.or(*ctor_count);
+                    if constructors.iter().all(|&(other, _)| other != &name[..]) {
+                        constructors.push((&name[..], args.len()));
+                    }
+                }
+            }
+            if count.is_some() && count == Some(constructors.len()) {
+                constructors
+                    .iter()
+                    .any(|&(name, arity)| specialize_by(&Head::Constructor(name, arity)))
+            } else {
+                // Some values are not covered by the constructors (or literals) of this column
+                // so only the rows which match anything in it need to be considered
+                let rows: Vec<_> = rows.iter()
+                    .filter(|row| *row[0] == Pat::Wild)
+                    .map(|row| row[1..].to_owned())
+                    .collect();
+                useful(&rows, rest)
+            }
+        }
+    }
+}
+
+/// Replaces each row which starts with an or-pattern with one row for each of its alternatives
+fn expand_or_patterns<'a>(rows: &[Vec<&'a Pat>]) -> Vec<Vec<&'a Pat>> {
+    let mut expanded = Vec::with_capacity(rows.len());
+    for row in rows {
+        match *row[0] {
+            Pat::Or(ref alternatives) => for alternative in alternatives {
+                let mut new_row = vec![alternative];
+                new_row.extend_from_slice(&row[1..]);
+                expanded.push(new_row);
+            },
+            _ => expanded.push(row.clone()),
+        }
+    }
+    expanded
+}
+
+fn record_head<'a, I>(column: I) -> Head<'a>
+where
+    I: Iterator<Item = &'a Pat>,
+{
+    let mut fields = Vec::new();
+    for pat in column {
+        if let Pat::Record(ref pattern_fields) = *pat {
+            for &(ref name, _) in pattern_fields {
+                if !fields.contains(&&name[..]) {
+                    fields.push(&name[..]);
+                }
+            }
+        }
+    }
+    Head::Record(fields)
+}
+
+/// Returns the row which remains after matching the first pattern of `row` against `head`, or
+/// `None` if it can't match
+fn specialize<'a>(row: &[&'a Pat], head: &Head) -> Option<Vec<&'a Pat>> {
+    static WILD: Pat = Pat::Wild;
+
+    let (first, rest) = row.split_first().expect("Non-empty row");
+    let first = match **first {
+        Pat::Opaque => &WILD,
+        _ => *first,
+    };
+    let mut new_row: Vec<&Pat> = match (first, head) {
+        (&Pat::Wild, &Head::Constructor(_, arity)) => iter::repeat(&WILD).take(arity).collect(),
+        (&Pat::Wild, &Head::Record(ref fields)) => {
+            iter::repeat(&WILD).take(fields.len()).collect()
+        }
+        (&Pat::Wild, &Head::Literal(_)) => Vec::new(),
+        (
+            &Pat::Constructor {
+                ref name, ref args, ..
+            },
+            &Head::Constructor(head_name, _),
+        ) if name == head_name =>
+        {
+            args.iter().collect()
+        }
+        (&Pat::Record(ref pattern_fields), &Head::Record(ref fields)) => fields
+            .iter()
+            .map(|field| {
+                pattern_fields
+                    .iter()
+                    .find(|&&(ref name, _)| name == field)
+                    .map_or(&WILD, |&(_, ref pat)| pat)
+            })
+            .collect(),
+        (&Pat::Literal(ref literal), &Head::Literal(head_literal)) if literal == head_literal => {
+            Vec::new()
+        }
+        _ => return None,
+    };
+    new_row.extend_from_slice(rest);
+    Some(new_row)
+}
@@ -0,0 +1,128 @@
+#![cfg(feature = "serialization")]
+#[macro_use]
+extern crate collect_mac;
+extern crate serde_json;
+
+extern crate gluon_base as base;
+extern crate gluon_check as check;
+extern crate gluon_parser as parser;
+
+mod support;
+
+use base::ast::{walk_expr, Expr, Pattern, SpannedExpr, Visitor};
+use base::pos::{self, BytePos};
+use base::symbol::{Symbol, Symbols};
+use base::types::TypeCache;
+
+use check::cache::{Cached, CachedDiagnostic, CachedSymbol, FORMAT_VERSION};
+use check::typecheck::Typecheck;
+
+fn cached_diagnostics(text: &str) -> Vec<CachedDiagnostic> {
+    let mut expr = support::parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = support::MockEnv::new();
+    let interner = support::get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
+
+    let errors = tc.typecheck_expr(&mut expr).unwrap_err();
+    errors.into_iter().map(|err| CachedDiagnostic::new(&err)).collect()
+}
+
+fn cached_symbols(expr: &SpannedExpr<Symbol>) -> Vec<CachedSymbol> {
+    struct Bindings(Vec<CachedSymbol>);
+    impl<'a> Visitor<'a> for Bindings {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, expr: &'a SpannedExpr<Symbol>) {
+            if let Expr::LetBindings(ref binds, _) = expr.value {
+                for bind in binds {
+                    if let Pattern::Ident(ref id) = bind.name.value {
+                        self.0
+                            .push(CachedSymbol::new(&id.name, &id.typ, bind.name.span));
+                    }
+                }
+            }
+            walk_expr(self, expr)
+        }
+    }
+    let mut visitor = Bindings(Vec::new());
+    visitor.visit_expr(expr);
+    visitor.0
+}
+
+#[test]
+fn diagnostics_survive_a_round_trip() {
+    let text = r#"
+let x : Int = "abc"
+let y = z
+x
+"#;
+    let diagnostics = cached_diagnostics(text);
+    assert_eq!(diagnostics.len(), 2);
+
+    let json = serde_json::to_string(&Cached::new(diagnostics.clone())).unwrap();
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    let loaded: Vec<CachedDiagnostic> = Cached::load(&mut deserializer).unwrap();
+    assert_eq!(loaded, diagnostics);
+
+    assert_eq!(
+        loaded[0].span(),
+        pos::span(BytePos::from(15), BytePos::from(20))
+    );
+    assert!(
+        loaded[0].message.contains("String") && loaded[0].message.contains("Int"),
+        "{}",
+        loaded[0]
+    );
+    assert_eq!(&text[loaded[1].span.start..loaded[1].span.end], "z");
+    assert!(loaded[1].message.contains("`z`"), "{}", loaded[1]);
+}
+
+#[test]
+fn symbols_survive_a_round_trip_with_a_new_interner() {
+    let text = r#"
+let id x = x
+let pair = { first = 1, second = "a" }
+pair
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    result.unwrap();
+
+    let symbols = cached_symbols(&expr);
+    let json = serde_json::to_string(&Cached::new(symbols)).unwrap();
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    let loaded: Vec<CachedSymbol> = Cached::load(&mut deserializer).unwrap();
+
+    let mut interner = Symbols::new();
+    let reloaded: Vec<_> = loaded
+        .iter()
+        .map(|symbol| symbol.to_symbol(&mut interner))
+        .collect();
+
+    let names: Vec<_> = reloaded
+        .iter()
+        .map(|symbol| symbol.value.declared_name().to_string())
+        .collect();
+    assert_eq!(names, ["id", "pair"]);
+    let span = reloaded[1].span;
+    assert_eq!(&text[span.start.to_usize()..span.end.to_usize()], "pair");
+    assert_eq!(reloaded[0].value, interner.symbol("id"));
+
+    let types: Vec<_> = loaded.iter().map(|symbol| &symbol.typ[..]).collect();
+    assert_eq!(
+        types,
+        ["forall a . a -> a", "{ first : Int, second : String }"]
+    );
+}
+
+#[test]
+fn loading_a_cache_with_another_version_fails() {
+    let json = format!(r#"{{ "version": {}, "value": [] }}"#, FORMAT_VERSION + 1);
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    let result: Result<Vec<CachedSymbol>, _> = Cached::load(&mut deserializer);
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("format version"), "{}", err);
+}
@@ -8,10 +8,11 @@ extern crate gluon_base as base;
 extern crate gluon_check as check;
 extern crate gluon_parser as parser;
 
+use base::ast::{Expr, Pattern};
 use base::symbol::Symbol;
 use base::types::{ArcType, Type};
 
-use check::typecheck::TypeError;
+use check::typecheck::{Help, HelpError, PatternKind, TypeError};
 
 #[macro_use]
 mod support;
@@ -44,6 +45,102 @@ in x
     assert_err!(result, UndefinedType(..));
 }
 
+#[test]
+fn repeated_undefined_type_is_reported_once() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let f1 x : Foo -> Foo = x
+let f2 x : Foo -> Foo = x
+let f3 x : Foo -> Foo = x
+let f4 x : Foo -> Foo = x
+let f5 x : Foo -> Foo = x
+f1
+"#;
+    let result = support::typecheck(text);
+    let err = result.unwrap_err();
+    let rendered = err.to_string();
+
+    let errors: Vec<_> = err.errors().into();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    match errors[0].value {
+        HelpError {
+            error: TypeError::UndefinedType(..),
+            help: Some(Help::AlsoUsedAt(ref spans)),
+            ..
+        } => assert_eq!(spans.len(), 4),
+        ref err => panic!("Expected an aggregated `UndefinedType` error, found {:?}", err),
+    }
+    assert_eq!(errors[0].value.notes().len(), 4);
+    assert!(
+        rendered.contains("test:Line: 3, Column: 12: note: also used here"),
+        "{}",
+        rendered
+    );
+}
+
+#[test]
+fn different_undefined_types_are_reported_separately() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let f1 x : Foo -> Foo = x
+let f2 x : Bar -> Bar = x
+let f3 x : Foo -> Foo = x
+f1
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, UndefinedType(..), UndefinedType(..));
+}
+
+#[test]
+fn unregistered_primitive_operator() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let f a b = a #Foo* b
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, Message(..));
+}
+
+#[test]
+fn recursive_value_which_is_not_guarded_by_a_constructor() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let x = x #Int+ 1
+x
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, UndefinedVariable(..));
+}
+
+#[test]
+fn recursive_value_with_an_unguarded_reference_in_a_constructor() {
+    let _ = env_logger::try_init();
+    let text = r#"
+type List a = | Nil | Cons a (List a)
+let xs = Cons 1 xs
+xs
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, UndefinedVariable(..));
+}
+
+#[test]
+fn recursive_record_with_an_unguarded_reference() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let r = { a = r.b, b = 1 }
+r
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, InvalidProjection(..), UndefinedVariable(..));
+}
+
 #[test]
 fn undefined_variant() {
     let _ = env_logger::try_init();
@@ -96,8 +193,52 @@ let { Test2 } = { Test }
     assert_err!(result, UndefinedField(..));
 }
 
+#[test]
+fn recursive_alias_without_indirection() {
+    let _ = env_logger::try_init();
+    let text = r#"
+type T = { next : T }
+1
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, RecursiveAliasWithoutIndirection(..));
+
+    let errors: Vec<_> = support::typecheck(text).unwrap_err().errors().into();
+    assert_eq!(
+        errors[0].value.to_string(),
+        "The type `T` is defined in terms of itself without a variant or function type in \
+         between: T -> T"
+    );
+}
+
+#[test]
+fn mutually_recursive_aliases_without_indirection() {
+    let _ = env_logger::try_init();
+    let text = r#"
+type A = { b : B, i : Int }
+and B = A
+and C = | C A
+1
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, RecursiveAliasWithoutIndirection(..));
+
+    let errors: Vec<_> = support::typecheck(text).unwrap_err().errors().into();
+    match errors[0].value.error {
+        TypeError::RecursiveAliasWithoutIndirection(ref cycle) => {
+            let cycle: Vec<_> = cycle.iter().map(|name| name.declared_name()).collect();
+            assert_eq!(cycle, ["A", "B", "A"]);
+        }
+        ref err => panic!("Unexpected error: {}", err),
+    }
+}
+
 #[test]
 fn duplicate_type_definition() {
+    use base::pos::Span;
+
     let _ = env_logger::try_init();
     let text = r#"
 type Test = Int
@@ -108,6 +249,23 @@ in 1
     let result = support::typecheck(text);
 
     assert_err!(result, DuplicateTypeDefinition(..));
+
+    let errors: Vec<_> = support::typecheck(text).unwrap_err().errors().into();
+    assert_eq!(
+        errors[0].span.map(|loc| loc.absolute),
+        Span::new(25.into(), 29.into())
+    );
+    match errors[0].value.help {
+        Some(Help::PreviouslyDefinedAt(span, _)) => {
+            assert_eq!(span, Span::new(6.into(), 10.into()))
+        }
+        ref help => panic!("Expected the previous definition, found {:?}", help),
+    }
+    assert_eq!(
+        errors[0].value.to_string(),
+        "Type 'Test' has been already been defined in this module\n\
+         help: The type was previously defined at 2:6"
+    );
 }
 
 #[test]
@@ -217,7 +375,7 @@ let g x: A a -> () = x
 ";
     let result = support::typecheck(text);
 
-    assert_unify_err!(result, Other(SelfRecursiveAlias(..)));
+    assert_err!(result, RecursiveAliasWithoutIndirection(..), Unification(..));
 }
 
 #[test]
@@ -259,6 +417,158 @@ let { Test, Test, x = y, x } = { Test, x = 1 }
     assert_err!(result, DuplicateField(..), DuplicateField(..));
 }
 
+#[test]
+fn duplicate_field_notes_the_first_definition() {
+    use base::pos::Span;
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+{ x = 1, y = 2, x = 3 }
+"#;
+    let result = support::typecheck(text);
+    let err = result.unwrap_err();
+    let rendered = err.to_string();
+
+    let errors: Vec<_> = err.errors().into();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    let notes: Vec<_> = errors[0]
+        .value
+        .notes()
+        .iter()
+        .map(|note| (note.span, &note.value[..]))
+        .collect();
+    assert_eq!(
+        notes,
+        [(Span::new(3.into(), 4.into()), "first defined here")]
+    );
+    assert!(
+        rendered.contains("test:Line: 2, Column: 3: note: first defined here"),
+        "{}",
+        rendered
+    );
+}
+
+#[test]
+fn signature_mismatch_notes_the_signature() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let x : String = 1
+x
+"#;
+    let result = support::typecheck(text);
+
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    let notes: Vec<_> = errors[0]
+        .value
+        .notes()
+        .iter()
+        .map(|note| (&text[note.span.start.to_usize()..note.span.end.to_usize()], &note.value[..]))
+        .collect();
+    assert_eq!(notes, [("String", "signature declared here")]);
+}
+
+/// Returns the titles of the fixes of all errors along with the text each edit replaces and its
+/// replacement
+fn error_fixes(text: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let result = support::typecheck(text);
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    errors
+        .iter()
+        .flat_map(|error| error.value.fixes())
+        .map(|fix| {
+            let edits = fix.edits
+                .iter()
+                .map(|&(span, ref replacement)| {
+                    (
+                        text[span.start.to_usize()..span.end.to_usize()].to_string(),
+                        replacement.clone(),
+                    )
+                })
+                .collect();
+            (fix.title.clone(), edits)
+        })
+        .collect()
+}
+
+#[test]
+fn undefined_variable_has_a_rename_fix() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let length = 1
+lenght #Int+ lenght
+"#;
+    let fixes = error_fixes(text);
+
+    assert_eq!(
+        fixes,
+        [
+            (
+                "Rename to `length`".to_string(),
+                vec![
+                    ("lenght".to_string(), "length".to_string()),
+                    ("lenght".to_string(), "length".to_string()),
+                ],
+            ),
+        ]
+    );
+}
+
+#[test]
+fn hole_has_a_fix_adding_the_inferred_signature_of_its_binding() {
+    use base::pos::Span;
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let count = 1
+let add_count x = x #Int+ _
+add_count
+"#;
+    let result = support::typecheck(text);
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    let fixes = errors[0].value.fixes();
+    assert!(!fixes.is_empty());
+
+    assert!(
+        fixes
+            .iter()
+            .any(|fix| fix.title == "Replace the hole with `count`"),
+        "{:?}",
+        fixes
+    );
+    let signature_pos = (text.find(" x =").unwrap() + 2).into();
+    assert_eq!(
+        fixes.last().unwrap().edits,
+        [
+            (
+                Span::new(signature_pos, signature_pos),
+                " : Int -> Int".to_string(),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn undefined_module_path_has_an_import_fix() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+std.list.empty
+"#;
+    let fixes = error_fixes(text);
+
+    assert_eq!(
+        fixes,
+        [
+            (
+                "Import `std.list`".to_string(),
+                vec![
+                    ("std.list".to_string(), "(import! std.list)".to_string()),
+                ],
+            ),
+        ]
+    );
+}
+
 #[test]
 fn type_alias_with_explicit_type_kind() {
     let _ = ::env_logger::try_init();
@@ -300,6 +610,117 @@ type Foo = Test Int
     assert_err!(result, KindError(TypeMismatch(..)));
 }
 
+#[test]
+fn forall_with_explicit_kind_error_is_reported_at_binder() {
+    use base::pos::Span;
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let f : forall (m : Type) a . m a -> m a = \x -> x
+()
+"#;
+    assert_err!(support::typecheck(text), KindError(TypeMismatch(..)));
+
+    let errors: Vec<_> = support::typecheck(text).unwrap_err().errors().into();
+    assert_eq!(
+        errors[0].span.map(|loc| loc.absolute),
+        Span::new(9.into(), 31.into())
+    );
+}
+
+#[test]
+fn shadowed_type_variable_is_an_error_when_denied() {
+    use check::typecheck::LintLevel;
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let f : a -> a = \x ->
+    type Wrap a = | Wrap a
+    x
+f
+"#;
+    let (result, warnings) =
+        support::typecheck_with_shadowed_type_variables(text, LintLevel::Deny);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_err!(result, Lint(Warning::ShadowedTypeVariable(..)));
+}
+
+#[test]
+fn shadowing_imported_binding_is_an_error_when_denied() {
+    use check::typecheck::LintLevel;
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let { show } = prelude
+let show x = x
+show 1
+"#;
+    let (result, warnings) = support::typecheck_with_shadowed_bindings(
+        text,
+        &["prelude"],
+        LintLevel::Deny,
+        LintLevel::Allow,
+    );
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_err!(result, Lint(Warning::ShadowedImport(..)));
+}
+
+#[test]
+fn or_pattern_binding_a_variable_with_different_types() {
+    let _ = env_logger::try_init();
+    let text = r#"
+type Lookup = | Ok Int | Missing String
+let f x =
+    match x with
+    | Ok y | Missing y -> 1
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, OrPatternBindingMismatch { .. });
+
+    let errors: Vec<_> = support::typecheck(text).unwrap_err().errors().into();
+    assert_eq!(
+        errors[0].value.to_string(),
+        "`y` has the type `String` in this alternative of the or-pattern but the type `Int` in \
+         the first alternative"
+    );
+}
+
+#[test]
+fn or_pattern_alternatives_must_bind_the_same_variables() {
+    let _ = env_logger::try_init();
+    let text = r#"
+type Lookup = | Ok Int | Missing Int
+let f x =
+    match x with
+    | Ok y | Missing z -> 1
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, OrPatternMissingBinding(..), OrPatternMissingBinding(..));
+}
+
+#[test]
+fn pattern_guard_must_be_a_bool() {
+    use base::pos::Span;
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+match 1 with
+| x if x -> 1
+| _ -> 2
+"#;
+    let result = support::typecheck(text);
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].span.map(|loc| loc.absolute),
+        Span::new(21.into(), 22.into())
+    );
+}
+
 #[test]
 fn type_error_span() {
     use base::pos::Span;
@@ -318,6 +739,55 @@ y
     );
 }
 
+#[test]
+fn each_bad_argument_is_reported_at_its_span() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let f x y z : Int -> Int -> Int -> Int = x
+f "a" 2 "c"
+"#;
+    let result = support::typecheck(text);
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|error| {
+            let span = error.span.map(|loc| loc.absolute.to_usize());
+            let notes: Vec<_> = error
+                .value
+                .notes()
+                .iter()
+                .map(|note| {
+                    let span = note.span.map(|pos| pos.to_usize());
+                    (&text[span.start..span.end], note.value.as_str())
+                })
+                .collect();
+            (&text[span.start..span.end], notes)
+        })
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            ("\"a\"", vec![("f", "in argument 1 of `f`")]),
+            ("\"c\"", vec![("f", "in argument 3 of `f`")]),
+        ]
+    );
+}
+
+#[test]
+fn bad_argument_of_a_field_names_the_field() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let r = { f = \x y -> x #Int+ y }
+r.f 1 ""
+"#;
+    let result = support::typecheck(text);
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].value.notes()[0].value, "in argument 2 of `r.f`");
+}
+
 #[test]
 fn issue_286() {
     let _ = ::env_logger::try_init();
@@ -372,6 +842,8 @@ Found: test.B
 Types do not match:
     Expected: test.A
     Found: test.B
+help: `A` defined at 2:6
+      `B` defined at 3:6
 eq (A 0) (B 0.0)
           ^~~~~
 "#
@@ -398,10 +870,14 @@ Found: { x : Int }
 The type `()` lacks the following fields: x
 f { } { x = 1 }
       ^~~~~~~~~
+test:Line: 4, Column: 1: note: in argument 2 of `f`
+f { } { x = 1 }
+^
 "#
     );
 }
 
+#[cfg(feature = "pretty-errors")]
 #[test]
 fn long_type_error_format() {
     let long_type: ArcType = Type::function(
@@ -423,6 +899,21 @@ Found:
     );
 }
 
+#[cfg(not(feature = "pretty-errors"))]
+#[test]
+fn plain_unification_error_format() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let f x y : a -> a -> a = x
+f 1 ""
+"#;
+    let result = support::typecheck(text);
+
+    let err = format!("{}", result.unwrap_err());
+    assert!(err.contains("Expected: Int"), "{}", err);
+    assert!(err.contains("Found: String"), "{}", err);
+}
+
 #[test]
 fn undefined_field_after_overload() {
     let _ = ::env_logger::try_init();
@@ -595,3 +1086,666 @@ let test x : () = () in 1
 
     assert_unify_err!(result, TypeMismatch(..));
 }
+
+#[test]
+fn record_pattern_on_variant() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Test = | A Int | B
+match A 1 with
+| { x } -> x
+"#;
+    let result = support::typecheck(text);
+
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    match errors[0].value {
+        HelpError {
+            error:
+                TypeError::PatternShapeMismatch {
+                    pattern_kind: PatternKind::Record,
+                    ..
+                },
+            help: Some(Help::UseConstructorPattern),
+            ..
+        } => (),
+        ref err => panic!("Expected a `PatternShapeMismatch` error, found {:?}", err),
+    }
+}
+
+#[test]
+fn constructor_pattern_on_record() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Test = | A Int | B
+match { x = 1 } with
+| A y -> y
+"#;
+    let result = support::typecheck(text);
+
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    match errors[0].value {
+        HelpError {
+            error:
+                TypeError::PatternShapeMismatch {
+                    pattern_kind: PatternKind::Constructor,
+                    ..
+                },
+            help: Some(Help::UseRecordPattern),
+            ..
+        } => (),
+        ref err => panic!("Expected a `PatternShapeMismatch` error, found {:?}", err),
+    }
+}
+
+#[test]
+fn tuple_pattern_on_builtin() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+match 1 with
+| (x, y) -> x
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, PatternShapeMismatch { .. });
+}
+
+#[test]
+fn tuple_pattern_with_too_few_elements() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+match (1, 2, 3) with
+| (x, y) -> x
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(
+        result,
+        TupleArityMismatch {
+            expected: 3,
+            found: 2,
+        }
+    );
+}
+
+#[test]
+fn tuple_pattern_with_too_many_elements() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+match (1, 2) with
+| (x, y, z) -> x
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(
+        result,
+        TupleArityMismatch {
+            expected: 2,
+            found: 3,
+        }
+    );
+}
+
+#[test]
+fn tuple_pattern_on_non_tuple_record_is_a_unification_error() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+match { x = 1 } with
+| (x, y) -> x
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, Unification(..));
+}
+
+#[test]
+fn empty_record_is_printed_as_unit_in_errors() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let x : () = { }
+x #Int+ 1
+"#;
+    let result = support::typecheck(text);
+
+    let message = result.as_ref().unwrap_err().to_string();
+    assert_err!(result, Unification(..));
+    assert!(message.contains("()"), "{}", message);
+    assert!(!message.contains("{"), "{}", message);
+}
+
+#[test]
+fn hole_reports_expected_type_and_matching_bindings() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let f x y : Int -> String -> Int =
+    let z = 1.0
+    x #Int+ _
+f
+"#;
+    let result = support::typecheck(text);
+
+    let errors: Vec<_> = result.unwrap_err().errors().into_iter().collect();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    match errors[0].value.error {
+        TypeError::HoleFound {
+            ref expected,
+            ref bindings_in_scope,
+        } => {
+            assert_eq!(*expected, Type::int());
+            let names: Vec<_> = bindings_in_scope
+                .iter()
+                .map(|&(ref name, _)| name.declared_name())
+                .collect();
+            assert_eq!(names, ["x"]);
+        }
+        ref err => panic!("Expected a hole, found {}", err),
+    }
+}
+
+#[test]
+fn hole_type_is_generalized() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let f x = _
+f
+"#;
+    let result = support::typecheck(text);
+
+    let errors: Vec<_> = result.unwrap_err().errors().into_iter().collect();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    match errors[0].value.error {
+        TypeError::HoleFound { ref expected, .. } => match **expected {
+            Type::Generic(_) => (),
+            _ => panic!("Expected a generic type, found {}", expected),
+        },
+        ref err => panic!("Expected a hole, found {}", err),
+    }
+}
+
+#[test]
+fn record_unification_error_lists_differing_fields() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Server = { host : String, port : Int, workers : Int }
+let start config : {
+        name : String,
+        verbose : Bool,
+        retries : Int,
+        timeout : Float,
+        server : Server,
+        log : { level : Int, file : String }
+    } -> Int =
+    0
+let config = {
+    name = "app",
+    verbose = True,
+    retries = 3,
+    timeout = 1.0,
+    server = { host = "localhost", port = 8080, workers = 4 },
+    log = { level = "debug", file = "app.log" }
+}
+start config
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(
+        &*format!("{}", result.unwrap_err()).replace("\t", "        "),
+        r#"test:Line: 20, Column: 7: Expected the record types to be equal but they differ in the following fields
+    log.level: expected Int, found String
+help: `Server` defined at 2:6
+start config
+      ^~~~~~
+test:Line: 20, Column: 1: note: in argument 1 of `start`
+start config
+^~~~~
+"#
+    );
+}
+
+#[test]
+fn record_unification_error_with_a_recursive_alias_field() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Tree = | Node Tree Tree | Leaf
+type R = { a : Tree, b : Int }
+let f r : R -> Int = r.b
+let g r : { a : Tree, b : String } -> Int = f r
+g
+"#;
+    let result = support::typecheck(text);
+
+    let err = result.unwrap_err();
+    let message = format!("{}", err);
+    assert!(message.contains("b: expected Int, found String"), "{}", message);
+    assert!(!message.contains("a: expected"), "{}", message);
+}
+
+#[test]
+fn record_unification_error_lists_missing_fields() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let start config : { name : String, retries : Int, timeout : Float } -> Int = 0
+let config = { name = "app", retries = 3.0, verbose = True }
+start config
+"#;
+    let result = support::typecheck(text);
+
+    let err = result.unwrap_err();
+    assert_eq!(
+        &*format!("{}", err).replace("\t", "        "),
+        r#"test:Line: 4, Column: 7: Expected the record types to be equal but they differ in the following fields
+Missing from the found type: timeout
+Missing from the expected type: verbose
+    retries: expected Int, found Float
+start config
+      ^~~~~~
+test:Line: 4, Column: 1: note: in argument 1 of `start`
+start config
+^~~~~
+"#
+    );
+    let verbose = format!("{:#}", err);
+    assert!(
+        verbose.contains("Expected the following types to be equal"),
+        "{}",
+        verbose
+    );
+    assert!(verbose.contains("timeout : Float"), "{}", verbose);
+}
+
+#[test]
+fn undefined_variable_suggests_similar_names() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let foldl f x xs = x
+let fOLD = 1
+fold
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(
+        &*format!("{}", result.unwrap_err()).replace("\t", "        "),
+        r#"test:Line: 4, Column: 1: Undefined variable `fold`
+Did you mean `fOLD` or `foldl`?
+fold
+^~~~
+"#
+    );
+}
+
+#[test]
+fn undefined_variable_does_not_suggest_names_which_are_out_of_scope() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let a =
+    let count = 1
+    count
+let b =
+    let mount = 2
+    bount
+a
+"#;
+    let result = support::typecheck(text);
+
+    let err = format!("{}", result.unwrap_err());
+    assert!(
+        err.contains("Undefined variable `bount`\nDid you mean `mount`?"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn undefined_type_suggests_global_types() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let x : Bol = True
+x
+"#;
+    let result = support::typecheck(text);
+
+    let err = format!("{}", result.unwrap_err());
+    assert!(
+        err.contains("Type `Bol` is not defined\nDid you mean `Bool`?"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn undefined_variable_without_similar_names() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let foldl f x xs = x
+unrelated
+"#;
+    let result = support::typecheck(text);
+
+    let err = format!("{}", result.unwrap_err());
+    assert!(!err.contains("Did you mean"), "{}", err);
+}
+
+#[test]
+fn undefined_type_suggests_similar_names() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Option a = | None | Some a
+let x : Optoin Int = None
+x
+"#;
+    let result = support::typecheck(text);
+
+    let err = format!("{}", result.unwrap_err());
+    assert!(
+        err.contains("Type `Optoin` is not defined\nDid you mean `Option`?"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn string_affix_pattern_on_non_string() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+match 1 with
+| "GET " ++ rest -> rest
+| _ -> ""
+"#;
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
+#[test]
+fn undefined_field_suggests_closest_matches() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let r = { name = "", age = 1 }
+r.nmae
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(
+        &*format!("{}", result.unwrap_err()).replace("\t", "        "),
+        r#"test:Line: 3, Column: 1: Type `{ name : String, age : Int }` does not have the field `nmae`. Closest matches: `name`
+r.nmae
+^~~~~~
+"#
+    );
+}
+
+#[test]
+fn invalid_projection_shows_the_resolved_type() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Test = | A | B
+let x : Test = A
+x.field
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(
+        &*format!("{}", result.unwrap_err()).replace("\t", "        "),
+        r#"test:Line: 4, Column: 1: Type 'test.Test' is not a type which allows field accesses
+The type resolves to `| A | B`
+help: `Test` defined at 2:6
+x.field
+^~~~~~~
+"#
+    );
+}
+
+#[test]
+fn error_type_does_not_cause_more_errors() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let module =
+    let { parse, name } = make_parsr 1
+    {
+        int = parse "1" #Int+ 1,
+        float = parse "1.0" #Float+ 1.0,
+        is_int = parse #Int== 1,
+        name_length = name #Int+ 1,
+        name_float = name #Float+ 1.0,
+        name,
+    }
+module
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, UndefinedVariable(..));
+}
+
+fn many_errors(count: usize) -> String {
+    let fields = (0..count)
+        .map(|i| format!("    field{} = 1 #Int+ \"{}\",\n", i, i))
+        .collect::<String>();
+    format!("{{\n{}}}\n", fields)
+}
+
+#[test]
+fn error_limit_reports_how_many_errors_were_not_shown() {
+    let _ = ::env_logger::try_init();
+    let text = many_errors(20);
+    let result = support::typecheck_with_error_limit(&text, 5);
+
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    assert_eq!(errors.len(), 6, "{:?}", errors);
+    match errors[5].value.error {
+        TypeError::ErrorsNotShown(15) => (),
+        ref err => panic!("Expected ErrorsNotShown(15), found {}", err),
+    }
+    assert!(
+        errors[5].to_string().contains("15 additional errors not shown"),
+        "{}",
+        errors[5]
+    );
+}
+
+#[test]
+fn errors_are_sorted_by_location() {
+    let _ = ::env_logger::try_init();
+    // Holes are reported after every other error has been found
+    let text = r#"
+let x : Int = _
+let y : Int = ""
+x
+"#;
+    let result = support::typecheck(text);
+
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    match errors[0].value.error {
+        TypeError::HoleFound { .. } => (),
+        ref err => panic!("Expected HoleFound, found {}", err),
+    }
+    assert!(errors[0].span < errors[1].span);
+}
+
+#[test]
+fn bindings_after_an_error_are_typed() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let x = undefined_fn 1
+let y = 1
+{ x, y }
+"#;
+    let (expr, typ, error) = support::typecheck_expr_partial(text);
+
+    assert!(error.is_some());
+    assert_eq!(typ.to_string(), "{ x : _, y : Int }");
+    let y = match expr.value {
+        Expr::LetBindings(_, ref body) => match body.value {
+            Expr::LetBindings(ref bindings, _) => &bindings[0],
+            _ => panic!("Expected a let binding"),
+        },
+        _ => panic!("Expected a let binding"),
+    };
+    match y.name.value {
+        Pattern::Ident(ref id) => assert_eq!(id.typ, Type::int()),
+        _ => panic!("Expected an identifier"),
+    }
+}
+
+#[test]
+fn constructor_applied_to_too_many_arguments() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Test = | Test Int String
+let f x : Test -> Int = 1
+let g x : Int -> Int = x
+g (f (Test 1 "" 2))
+"#;
+    let result = support::typecheck(text);
+
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    match errors[0].value.error {
+        TypeError::ConstructorArityMismatch {
+            ref constructor,
+            expected,
+            actual,
+        } => {
+            assert_eq!(constructor.declared_name(), "Test");
+            assert_eq!((expected, actual), (2, 3));
+        }
+        ref err => panic!("Unexpected error: {}", err),
+    }
+    assert_eq!(
+        errors[0].value.error.to_string(),
+        "constructor `Test` takes 2 arguments but 3 were supplied"
+    );
+}
+
+#[test]
+fn constructor_applied_to_too_few_arguments() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Test = | Test Int String
+let f x : Test -> Int = 1
+let g x : Int -> Int = x
+g (f (Test 1))
+"#;
+    let result = support::typecheck(text);
+
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(
+        errors[0].value.error.to_string(),
+        "constructor `Test` takes 2 arguments but 1 was supplied"
+    );
+}
+
+#[test]
+fn partially_applied_constructor_is_a_function() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Test = | Test Int String
+let apply f : (String -> Test) -> Test = f ""
+apply (Test 1)
+"#;
+    let result = support::typecheck(text);
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+}
+
+#[test]
+fn unification_error_is_wrapped_to_the_layout_width() {
+    use check::ErrorLayout;
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let x : Int -> String -> Float -> Array Int -> Array String -> Array Float -> Int = 1
+x
+"#;
+    let errors: Vec<_> = support::typecheck(text).unwrap_err().errors().into();
+    let error = &errors[0].value;
+
+    let wide = ErrorLayout {
+        width: 120,
+        ..ErrorLayout::default()
+    }.display(error)
+        .to_string();
+    let narrow = ErrorLayout {
+        width: 40,
+        ..ErrorLayout::default()
+    }.display(error)
+        .to_string();
+    assert!(
+        wide.contains(
+            "Expected: Int -> String -> Float -> Array Int -> Array String -> Array Float -> Int\n"
+        ),
+        "{}",
+        wide
+    );
+    assert!(
+        narrow.starts_with(
+            "Expected the following types to be equal\n\
+             Expected:\n    Int\n        -> String\n        -> Float\n"
+        ),
+        "{}",
+        narrow
+    );
+}
+
+#[test]
+fn deeply_nested_types_are_elided_in_errors() {
+    use check::ErrorLayout;
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let x : Array (Array (Array (Array Int))) = 1
+x
+"#;
+    let errors: Vec<_> = support::typecheck(text).unwrap_err().errors().into();
+    let error = &errors[0].value;
+
+    let elided = ErrorLayout {
+        max_depth: Some(2),
+        ..ErrorLayout::default()
+    }.display(error)
+        .to_string();
+    assert!(
+        elided.starts_with(
+            "Expected the following types to be equal\n\
+             Expected: Array (Array ...)\n\
+             Found: Int\n"
+        ),
+        "{}",
+        elided
+    );
+    assert!(
+        error
+            .to_string()
+            .contains("Expected: Array (Array (Array (Array Int)))\n"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn polymorphic_recursion_without_signature() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type List a = | Nil | Cons a (List a)
+let f x = f (Cons x Nil)
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, PolymorphicRecursion { .. });
+}
+
+#[test]
+fn occurs_check_in_recursive_binding_which_is_not_at_a_recursive_reference() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let b = True
+let f x = if b then f 1 else x x
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, Unification(..));
+}
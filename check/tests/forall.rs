@@ -11,7 +11,7 @@ extern crate gluon_parser as parser;
 use base::ast::{Expr, Pattern, SpannedExpr};
 use base::kind::Kind;
 use base::pos::{BytePos, Span};
-use base::types::{Field, Generic, Type};
+use base::types::{ArcType, Field, Generic, Type};
 use base::symbol::Symbol;
 
 use support::{alias, intern, typ, MockEnv};
@@ -156,6 +156,46 @@ make
     assert!(result.is_ok(), "{}", result.unwrap_err());
 }
 
+#[test]
+fn nested_annotation_uses_variable_from_signature() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let f : a -> a = \x -> let y : a = x in y
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result.map(|typ| typ.to_string()), Ok("forall a . a -> a".to_string()));
+}
+
+#[test]
+fn nested_annotation_generalizes_new_variables() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let f : a -> a = \x ->
+    let g : b -> b = \z -> z
+    let _ = g 1
+    let _ = g ""
+    g x
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result.map(|typ| typ.to_string()), Ok("forall a . a -> a".to_string()));
+}
+
+#[test]
+fn nested_annotation_can_not_use_scoped_variable_at_a_different_type() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let f : a -> b -> a = \x z -> let y : a = z in x
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert!(result.is_err(), "{}", result.unwrap());
+}
+
 #[test]
 fn simplified_applicative() {
     let _ = ::env_logger::try_init();
@@ -188,6 +228,58 @@ make_applicative applicative_Function
     assert!(result.is_ok(), "{}", result.unwrap_err());
 }
 
+/// `make_applicative` from `simplified_applicative` returning `map` obtained through `access`
+fn applicative_map_type(access: &str) -> ArcType {
+    let text = format!(
+        r#"
+type Applicative f = {{
+    map : forall a b . (a -> b) -> f a -> f b,
+    apply : forall c d . f (c -> d) -> f c -> f d
+}}
+
+let applicative_Function : forall a. Applicative ((->) a) = {{
+    map = \f g x -> f (g x),
+    apply = \f g x -> f x (g x)
+}}
+
+let make_applicative app : forall f. Applicative f -> _ =
+    {}
+
+make_applicative applicative_Function
+"#,
+        access
+    );
+    let result = support::typecheck(&text);
+    result.unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[test]
+fn destructured_polymorphic_field_is_as_general_as_projection() {
+    let _ = ::env_logger::try_init();
+
+    let destructured = applicative_map_type("let { map, apply } = app\n    map");
+    let projected = applicative_map_type("let map = app.map\n    map");
+    assert_eq!(destructured.to_string(), projected.to_string());
+    assert_eq!(
+        destructured.to_string(),
+        "forall a a0 b . (a -> a0) -> (b -> a) -> b -> a0"
+    );
+}
+
+#[test]
+fn destructured_polymorphic_field_can_be_used_at_different_types() {
+    let _ = ::env_logger::try_init();
+
+    let destructured = applicative_map_type(
+        r#"let { map, apply } = app
+    let x = map (\x -> x #Int+ 1)
+    let y = map (\x -> "")
+    map"#,
+    );
+    let projected = applicative_map_type("app.map");
+    assert_eq!(destructured.to_string(), projected.to_string());
+}
+
 #[test]
 fn type_alias_with_explicit_hole_kind() {
     let _ = ::env_logger::try_init();
@@ -236,6 +328,17 @@ type Bar = Test Foo
     assert!(result.is_ok(), "{}", result.unwrap_err());
 }
 
+#[test]
+fn forall_with_explicit_function_kind() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let f : forall (m : Type -> Type) a . m a -> m a = \x -> x
+f [1]
+"#;
+    let result = support::typecheck(text);
+    assert_eq!(result, Ok(Type::array(Type::int())));
+}
+
 /// Check that after typechecking, the resulting types are `Alias`, not `Ident`. This is necessary
 /// so that when the type is later propagated it knows what its internal representation are without
 /// any extra information
@@ -447,6 +550,29 @@ let record = { x = 1 }
     );
 }
 
+#[test]
+fn record_expr_base_update_keeps_field_order() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let vec3 = { x = 1, y = 2, z = 3 }
+let vec3_2 : { x : Int, y : Int, z : Int } = { z = 0, y = 0, .. vec3 }
+vec3_2
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(
+        result,
+        Ok(Type::record(
+            vec![],
+            vec![
+                Field::new(intern("x"), typ("Int")),
+                Field::new(intern("y"), typ("Int")),
+                Field::new(intern("z"), typ("Int")),
+            ]
+        ))
+    );
+}
+
 #[test]
 fn undefined_type_variable_in_record() {
     let _ = ::env_logger::try_init();
@@ -1058,3 +1184,16 @@ append (Cons "" Nil) Nil
 
     assert!(result.is_ok(), "{}", result.unwrap_err());
 }
+
+#[test]
+fn polymorphic_recursion_with_signature() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type List a = | Nil | Cons a (List a)
+let depth x : a -> Int = depth (Cons x Nil)
+depth
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result.map(|typ| typ.to_string()), Ok("forall a . a -> Int".to_string()));
+}
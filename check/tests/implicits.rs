@@ -712,3 +712,38 @@ f Nil
     let result = support::typecheck(text);
     assert_err!(result, TypeError::LoopInImplicitResolution(..));
 }
+
+#[test]
+fn ambiguous_implicit_notes_where_the_candidates_are_defined() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let f ?x y: [Int] -> Int -> Int = x
+/// @implicit
+let i = 123
+/// @implicit
+let j = 456
+f 42
+"#;
+    let result = support::typecheck(text);
+    let err = result.unwrap_err();
+    let rendered = err.to_string();
+
+    let errors: Vec<_> = err.errors().into();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    match errors[0].value.error {
+        TypeError::AmbiguousImplicit(..) => (),
+        ref err => panic!("Expected an ambiguous implicit, found {}", err),
+    }
+    let notes: Vec<_> = errors[0]
+        .value
+        .notes()
+        .iter()
+        .map(|note| note.value.as_str())
+        .collect();
+    assert_eq!(notes, ["`i` is defined here", "`j` is defined here"]);
+    assert!(
+        rendered.contains("test:Line: 4, Column: 5: note: `i` is defined here"),
+        "{}",
+        rendered
+    );
+}
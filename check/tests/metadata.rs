@@ -7,7 +7,7 @@ extern crate gluon_check as check;
 extern crate gluon_parser as parser;
 
 use base::ast::SpannedExpr;
-use base::metadata::{Metadata, MetadataEnv};
+use base::metadata::{Fixity, Metadata, MetadataEnv, OpInfo};
 use base::symbol::{Symbol, SymbolRef};
 
 fn metadata(env: &MetadataEnv, expr: &mut SpannedExpr<Symbol>) -> Metadata {
@@ -42,6 +42,7 @@ id
         metadata,
         Metadata {
             comment: Some("The identity function".into()),
+            operator: None,
             module: Default::default(),
         }
     );
@@ -65,6 +66,7 @@ let id x = x
         metadata.module.get("id"),
         Some(&Metadata {
             comment: Some("The identity function".into()),
+            operator: None,
             module: Default::default(),
         })
     );
@@ -88,6 +90,7 @@ type Test = Int
         metadata.module.get("Test"),
         Some(&Metadata {
             comment: Some("A test type".into()),
+            operator: None,
             module: Default::default(),
         })
     );
@@ -112,6 +115,7 @@ fn propagate_metadata_record_field_comment() {
         metadata.module.get("id"),
         Some(&Metadata {
             comment: Some("The identity function".into()),
+            operator: None,
             module: Default::default(),
         })
     );
@@ -137,6 +141,7 @@ x.id
         metadata,
         Metadata {
             comment: Some("The identity function".into()),
+            operator: None,
             module: Default::default(),
         }
     );
@@ -165,7 +170,80 @@ type Test = {
             .and_then(|metadata| metadata.module.get("x")),
         Some(&Metadata {
             comment: Some("A field".into()),
+            operator: None,
             module: Default::default(),
         })
     );
 }
+
+#[test]
+fn propagate_operator_metadata() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+/// Appends two values
+/// @infix right 5
+let (+++) x y : Int -> Int -> Int = x #Int+ y
+/// @infix left 7
+let (***) x y : Int -> Int -> Int = x #Int* y
+{ (+++), (***) }
+"#;
+    let (mut expr, result) = support::typecheck_expr(text);
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let metadata = metadata(&MockEnv, &mut expr);
+    assert_eq!(
+        check::metadata::operator_info(&metadata, "+++"),
+        Some(OpInfo {
+            precedence: 5,
+            fixity: Fixity::Right,
+        })
+    );
+    assert_eq!(
+        check::metadata::operator_info(&metadata, "***"),
+        Some(OpInfo {
+            precedence: 7,
+            fixity: Fixity::Left,
+        })
+    );
+}
+
+#[test]
+fn conflicting_operator_metadata() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+/// @infix left 6
+let (+++) x y : Int -> Int -> Int = x #Int+ y
+/// @infix right 5
+let (+++) x y : Int -> Int -> Int = x #Int+ y
+{ (+++) }
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let errors = check::metadata::check_metadata(&MockEnv, &expr)
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(
+        errors
+            .into_iter()
+            .map(|err| err.value)
+            .collect::<Vec<_>>(),
+        vec![
+            check::metadata::Error::ConflictingInfix(
+                "+++".into(),
+                OpInfo {
+                    precedence: 6,
+                    fixity: Fixity::Left,
+                },
+                OpInfo {
+                    precedence: 5,
+                    fixity: Fixity::Right,
+                },
+            ),
+        ]
+    );
+}
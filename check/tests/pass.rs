@@ -245,6 +245,35 @@ fn primitive_error() {
     assert!(result.is_err());
 }
 
+#[test]
+fn registered_primitive_operator() {
+    let _ = env_logger::try_init();
+
+    let text = r"
+let add a b = a #Decimal+ b
+let eq a b = a #Decimal== b
+{ add, eq }
+";
+    let result = support::typecheck(text);
+    let decimal = typ("Decimal");
+    let bool = Type::app(Type::ident(support::intern_unscoped("Bool")), collect![]);
+    let expected = Ok(Type::record(
+        vec![],
+        vec![
+            Field::new(
+                support::intern_unscoped("add"),
+                Type::function(vec![decimal.clone(), decimal.clone()], decimal.clone()),
+            ),
+            Field::new(
+                support::intern_unscoped("eq"),
+                Type::function(vec![decimal.clone(), decimal.clone()], bool),
+            ),
+        ],
+    ));
+
+    assert_req!(result.map(|t| t.to_string()), expected.map(|t| t.to_string()));
+}
+
 #[test]
 fn binop_as_function() {
     let _ = env_logger::try_init();
@@ -527,6 +556,23 @@ in y
     assert_eq!(result, expected);
 }
 
+/// Patterns on a scrutinee whose type is still a variable are checked by unification
+#[test]
+fn record_pattern_on_type_variable() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let f x =
+    match x with
+    | { y } -> y
+f { y = 1 }
+"#;
+    let result = support::typecheck(text);
+    let expected = Ok(typ("Int"));
+
+    assert_eq!(result, expected);
+}
+
 #[test]
 fn type_pattern() {
     let _ = env_logger::try_init();
@@ -633,6 +679,20 @@ test id
     assert!(result.is_ok(), "{}", result.unwrap_err());
 }
 
+#[test]
+fn recursive_alias_through_a_function() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type Stream = { value : Int, next : () -> Stream }
+let ones : Stream = { value = 1, next = \_ -> ones }
+ones.value
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(typ("Int")));
+}
+
 #[test]
 fn mutually_recursive_types() {
     let _ = env_logger::try_init();
@@ -813,3 +873,718 @@ fn expected_type_do_not_override_actual_type_for_returned_type() {
 
     assert_req!(result, Ok(typ("Int")));
 }
+
+#[test]
+fn unification_trace() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type A = { x : Int }
+type B = A
+let f y : B -> Int = y.x
+let a : A = { x = 1 }
+f a
+"#;
+    let (result, trace) = support::typecheck_with_unification_trace(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let position = |line: &str| {
+        trace
+            .lines()
+            .position(|l| l == line)
+            .unwrap_or_else(|| panic!("`{}` not found in trace:\n{}", line, trace))
+    };
+    let application = position("#6 subsumes: test.B <=> test.A");
+    let binding = position("#5   bind 0 := test.B");
+    let expansion = position("#6   expand alias test.B => test.A");
+    assert!(binding < application, "{}", trace);
+    assert!(application < expansion, "{}", trace);
+    assert!(expansion < position("#6 ok: test.B"), "{}", trace);
+}
+
+#[test]
+fn reexported_constructor_chain() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type Test = | Test Int
+let m = { Test }
+let { Test } = m
+let m2 = { Test }
+let { Test } = m2
+match Test 1 with
+| Test x -> x
+"#;
+    let (result, trace) = support::typecheck_with_unification_trace(text);
+
+    assert_eq!(result, Ok(Type::int()));
+
+    // The type binding and each re-export map `Test` straight to the original `test.Test` (the
+    // constructor is mapped as well)
+    let renamings: Vec<_> = trace
+        .lines()
+        .filter(|line| line.starts_with("original symbol: "))
+        .collect();
+    assert_eq!(
+        renamings,
+        vec![
+            "original symbol: Test -> test.Test",
+            "original symbol: test.Test -> test.Test",
+            "original symbol: Test -> test.Test",
+            "original symbol: test.Test -> test.Test",
+            "original symbol: Test -> test.Test",
+            "original symbol: test.Test -> test.Test",
+        ],
+        "{}",
+        trace
+    );
+    assert!(
+        trace.lines().any(|line| line.starts_with("original symbols: ")),
+        "{}",
+        trace
+    );
+}
+
+#[test]
+fn constructor_pattern_with_record_argument_is_finished() {
+    use base::types::walk_type;
+
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type Wrap a = | Wrap { x : a, y : Int }
+type A = Wrap Int
+type B = A
+let w : B = Wrap { x = 1, y = 2 }
+let (Wrap { x, y }) = w
+x #Int+ y
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    assert_req!(result, Ok(typ("Int")));
+
+    let mut expr = &expr;
+    loop {
+        match expr.value {
+            ast::Expr::TypeBindings(_, ref body) => expr = body,
+            ast::Expr::LetBindings(ref binds, ref body) => match binds[0].name.value {
+                ast::Pattern::Constructor(_, ref args) => {
+                    let record_type = match args[0].value {
+                        ast::Pattern::Record { ref typ, .. } => typ,
+                        ref pattern => panic!("Expected a record pattern, found {:?}", pattern),
+                    };
+                    let mut variables = 0;
+                    walk_type(record_type, |typ: &ArcType| {
+                        if let Type::Variable(_) = **typ {
+                            variables += 1;
+                        }
+                    });
+                    assert_eq!(variables, 0, "{}", record_type);
+                    break;
+                }
+                _ => expr = body,
+            },
+            _ => panic!("Expected a constructor pattern binding"),
+        }
+    }
+}
+
+#[test]
+fn type_binding_shadowing_signature_variable_warns() {
+    use base::pos::{BytePos, Span};
+    use check::typecheck::{Help, LintLevel, Warning};
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let f : a -> a = \x ->
+    type Wrap a = | Wrap a
+    let g : forall a . a -> a = \y -> y
+    g x
+f
+"#;
+    let span = |s: &str, len: usize| {
+        let start = text.find(s).unwrap();
+        Span::new(BytePos::from(start), BytePos::from(start + len))
+    };
+
+    let (result, warnings) =
+        support::typecheck_with_shadowed_type_variables(text, LintLevel::Warn);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let outer = span("a -> a =", 1);
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .map(|warning| (warning.span, warning.value.error, warning.value.help))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (
+                span("Wrap", 4),
+                Warning::ShadowedTypeVariable(intern("a")),
+                Some(Help::ShadowedTypeVariableBoundAt(outer)),
+            ),
+            (
+                span("forall a . ", 11),
+                Warning::ShadowedTypeVariable(intern("a")),
+                Some(Help::ShadowedTypeVariableBoundAt(outer)),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn type_binding_without_outer_type_variable_does_not_warn() {
+    use check::typecheck::LintLevel;
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Wrap a = | Wrap a
+let f : a -> a = \x -> x
+let g x =
+    type Wrap2 a = | Wrap2 a
+    x
+g (f 1)
+"#;
+    let (result, warnings) =
+        support::typecheck_with_shadowed_type_variables(text, LintLevel::Warn);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    assert!(warnings.is_empty(), "{:?}", warnings);
+}
+
+#[test]
+fn shadowing_imported_binding_warns() {
+    use base::pos::{BytePos, Span};
+    use check::typecheck::{LintLevel, Warning};
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let { show } = prelude
+let show = 1
+show
+"#;
+    let (result, warnings) = support::typecheck_with_shadowed_bindings(
+        text,
+        &["prelude"],
+        LintLevel::Warn,
+        LintLevel::Allow,
+    );
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let start = text.find("show = 1").unwrap();
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .map(|warning| match warning.value.error {
+            Warning::ShadowedImport(id, module) => {
+                (warning.span, id.declared_name().to_string(), module)
+            }
+            err => panic!("Unexpected warning: {}", err),
+        })
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (
+                Span::new(BytePos::from(start), BytePos::from(start + 4)),
+                "show".to_string(),
+                "prelude".to_string(),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn shadowing_imported_binding_with_unrelated_type_warns_once() {
+    use check::typecheck::{LintLevel, Warning};
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let { map } = prelude
+let map = 4
+map
+"#;
+    // `shadowed_imports` reports the binding so `unrelated_shadowed_bindings` (which is a warning
+    // by default) stays silent
+    let (result, warnings) = support::typecheck_with_shadowed_bindings(
+        text,
+        &["prelude"],
+        LintLevel::Warn,
+        LintLevel::Allow,
+    );
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .map(|warning| match warning.value.error {
+            Warning::ShadowedImport(id, module) => (id.declared_name().to_string(), module),
+            err => panic!("Unexpected warning: {}", err),
+        })
+        .collect();
+    assert_eq!(warnings, vec![("map".to_string(), "prelude".to_string())]);
+}
+
+#[test]
+fn shadowing_local_binding_does_not_warn_by_default() {
+    use check::typecheck::{LintLevel, Warning};
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let x = 1
+let f x = x
+f x
+"#;
+    let (result, warnings) =
+        support::typecheck_with_shadowed_bindings(text, &[], LintLevel::Warn, LintLevel::Allow);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    assert!(warnings.is_empty(), "{:?}", warnings);
+
+    let (result, warnings) =
+        support::typecheck_with_shadowed_bindings(text, &[], LintLevel::Warn, LintLevel::Warn);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .map(|warning| match warning.value.error {
+            Warning::ShadowedBinding(id) => id.declared_name().to_string(),
+            err => panic!("Unexpected warning: {}", err),
+        })
+        .collect();
+    assert_eq!(warnings, vec!["x"]);
+}
+
+#[test]
+fn shadowing_binding_with_unrelated_type_is_reported() {
+    use check::typecheck::Warning;
+
+    let _ = env_logger::try_init();
+    let text = r#"
+let map f x : (Int -> Int) -> Int -> Int = f x
+let map = 4
+let id x = x
+let id : Int -> Int = id
+let n = 1
+let n = 2
+let f x : Int -> String =
+    let x = "a"
+    x
+let g x =
+    let x = "a"
+    x
+{ map, id, n, f, g }
+"#;
+    let (result, warnings) = support::typecheck_with_warnings(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .filter_map(|warning| match warning.value.error {
+            Warning::ShadowedUnrelatedBinding {
+                name,
+                shadowed,
+                actual,
+            } => Some((
+                name.declared_name().to_string(),
+                shadowed.to_string(),
+                actual.to_string(),
+            )),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            ("map".to_string(), "(Int -> Int) -> Int -> Int".to_string(), "Int".to_string()),
+            ("x".to_string(), "Int".to_string(), "String".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn unit_from_tuples_records_and_patterns_unify() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let a = ()
+let b = { }
+let c = match b with | () -> a
+let d = match a with | { } -> b
+[a, b, c, d]
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(Type::array(Type::unit())));
+    assert_eq!(result.unwrap().to_string(), "Array ()");
+}
+
+#[test]
+fn unreachable_alternatives_are_reported() {
+    use check::typecheck::Warning;
+
+    let _ = env_logger::try_init();
+    let text = r#"
+type Opt a = | Nothing | Just a
+let f x =
+    match x with
+    | y -> 1
+    | Just y -> 2
+let g x =
+    match x with
+    | Just 1 -> 1
+    | Nothing -> 2
+    | Just 1 -> 3
+    | Just y -> 4
+    | Nothing -> 5
+let h x =
+    match x with
+    | (Just { a = 1, b }, _) -> 1
+    | (Just { a, b = "" }, Nothing) -> 2
+    | (Just _, _) -> 3
+    | (Nothing, _) -> 4
+    | (_, Just y) -> 5
+{ f, g, h }
+"#;
+    let (result, warnings) = support::typecheck_with_warnings(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .map(|warning| {
+            assert_eq!(warning.value.error, Warning::UnreachablePattern);
+            &text[warning.span.start.to_usize()..warning.span.end.to_usize()]
+        })
+        .collect();
+    assert_eq!(warnings, ["Just y", "Just 1", "Nothing", "(_, Just y)"]);
+}
+
+#[test]
+fn unused_let_bindings_are_reported() {
+    use check::typecheck::{LintLevel, Warning};
+
+    let _ = env_logger::try_init();
+    let text = r#"
+let unused = 1
+let _ignored = 2
+let used = 3
+let shadowed = 4
+let f shadowed = shadowed
+let rec_unused n = if n #Int== 0 then used else rec_unused (n #Int- 1)
+let even n = if n #Int== 0 then 1 else odd (n #Int- 1)
+and odd n = if n #Int== 0 then 0 else even (n #Int- 1)
+let g x =
+    let y = x
+    x
+let { a, b = c } = { a = 1, b = 2 }
+{ f, even, a }
+"#;
+    let (result, warnings) = support::typecheck_with_unused_bindings(text, LintLevel::Warn);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .map(|warning| {
+            match warning.value.error {
+                Warning::UnusedBinding(_) => (),
+                err => panic!("Unexpected warning: {}", err),
+            }
+            &text[warning.span.start.to_usize()..warning.span.end.to_usize()]
+        })
+        .collect();
+    assert_eq!(warnings, ["unused", "shadowed", "rec_unused", "g", "y", "c"]);
+}
+
+#[test]
+fn unused_let_bindings_can_be_denied() {
+    use check::typecheck::LintLevel;
+
+    let _ = env_logger::try_init();
+    let text = r#"
+let x = 1
+2
+"#;
+    let (result, warnings) = support::typecheck_with_unused_bindings(text, LintLevel::Deny);
+    assert!(result.is_err());
+    assert!(warnings.is_empty(), "{:?}", warnings);
+}
+
+#[test]
+fn record_patterns_with_different_fields_are_reachable() {
+    let _ = env_logger::try_init();
+    let text = r#"
+type R = { a : Int, b : Int }
+let f x : R -> Int =
+    match x with
+    | { a = 1 } -> 1
+    | { b = 2 } -> 2
+    | { a = 2, b = 3 } -> 3
+    | _ -> 4
+f
+"#;
+    let (result, warnings) = support::typecheck_with_warnings(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    assert!(warnings.is_empty(), "{:?}", warnings);
+}
+
+/// Nodes which the parser inserted in place of the parts of the program it could not parse do not
+/// produce any errors of their own, only the parse errors are reported
+#[test]
+fn error_nodes_from_parse_errors_are_not_reported() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let x = 1 #Int+
+let y = x #Int+
+let z = y #Int+
+z
+"#;
+    let (expr, result) = support::typecheck_partial_expr(text);
+
+    // Each of the incomplete bindings is replaced by an error node
+    assert_eq!(format!("{:?}", expr).matches("Error(").count(), 3);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+}
+
+#[test]
+fn string_affix_patterns_bind_the_rest_as_a_string() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let route path =
+    match path with
+    | "GET " ++ rest -> rest
+    | file ++ ".glu" -> file
+    | _ -> path
+route
+"#;
+    let result = support::typecheck(text);
+    let expected = Ok(Type::function(vec![typ("String")], typ("String")));
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn string_affix_patterns_do_not_make_later_alternatives_unreachable() {
+    use check::typecheck::Warning;
+
+    let _ = env_logger::try_init();
+    let text = r#"
+let f path =
+    match path with
+    | "GET " ++ rest -> 1
+    | "GET " ++ other -> 2
+    | "GET " -> 3
+    | _ -> 4
+let g path =
+    match path with
+    | _ -> 1
+    | "GET " ++ rest -> 2
+{ f, g }
+"#;
+    let (result, warnings) = support::typecheck_with_warnings(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .map(|warning| {
+            assert_eq!(warning.value.error, Warning::UnreachablePattern);
+            &text[warning.span.start.to_usize()..warning.span.end.to_usize()]
+        })
+        .collect();
+    assert_eq!(warnings, [r#""GET " ++ rest"#]);
+}
+
+#[test]
+fn or_pattern_binds_the_variables_of_its_alternatives_once() {
+    let _ = env_logger::try_init();
+    let text = r#"
+type Lookup = | Ok Int | Missing Int | Empty
+let f x =
+    match x with
+    | Ok y | Missing y -> y
+    | Empty -> 0
+f
+"#;
+    let result = support::typecheck(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    assert_eq!(result.unwrap().to_string(), "test.Lookup -> Int");
+}
+
+#[test]
+fn or_pattern_over_all_constructors_covers_every_value() {
+    use check::typecheck::Warning;
+
+    let _ = env_logger::try_init();
+    let text = r#"
+type Opt a = | Nothing | Just a
+let f x =
+    match x with
+    | Nothing | Just _ -> 1
+    | _ -> 2
+let g x =
+    match x with
+    | Just 1 | Nothing -> 1
+    | Just _ -> 2
+    | Nothing -> 3
+{ f, g }
+"#;
+    let (result, warnings) = support::typecheck_with_warnings(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .map(|warning| {
+            assert_eq!(warning.value.error, Warning::UnreachablePattern);
+            &text[warning.span.start.to_usize()..warning.span.end.to_usize()]
+        })
+        .collect();
+    assert_eq!(warnings, ["_", "Nothing"]);
+}
+
+#[test]
+fn pattern_guards_can_use_the_variables_of_the_pattern() {
+    let _ = env_logger::try_init();
+    let text = r#"
+type Opt a = | Nothing | Just a
+let f x : Opt Int -> Int =
+    match x with
+    | Just y if 0 #Int< y -> y
+    | Just y -> 0
+    | Nothing -> 0
+f
+"#;
+    let result = support::typecheck(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    assert_eq!(result.unwrap().to_string(), "test.Opt Int -> Int");
+}
+
+#[test]
+fn guarded_alternatives_do_not_make_later_alternatives_unreachable() {
+    use check::typecheck::Warning;
+
+    let _ = env_logger::try_init();
+    let text = r#"
+let f x =
+    match x with
+    | y if y #Int== 0 -> 1
+    | 1 -> 2
+    | y -> 3
+    | 2 -> 4
+f
+"#;
+    let (result, warnings) = support::typecheck_with_warnings(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .map(|warning| {
+            assert_eq!(warning.value.error, Warning::UnreachablePattern);
+            &text[warning.span.start.to_usize()..warning.span.end.to_usize()]
+        })
+        .collect();
+    assert_eq!(warnings, ["2"]);
+}
+
+#[test]
+fn committed_bindings_are_in_scope_in_later_expressions() {
+    let _ = env_logger::try_init();
+
+    let results = support::typecheck_session(&["let x = 1\nx", "x #Int+ 1"]);
+    assert_eq!(results[0], Ok(Type::int()));
+    assert_eq!(results[1], Ok(Type::int()));
+}
+
+#[test]
+fn committed_bindings_are_generalized() {
+    let _ = env_logger::try_init();
+
+    let results = support::typecheck_session(&[
+        "let id x = x\n()",
+        "type Test = | Test Int\n()",
+        "let y : Test = Test (id 1)\nid \"abc\"",
+        "let { y } = { y = id }\ny 1.0",
+    ]);
+    let types: Vec<_> = results
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|err| panic!("{}", err)))
+        .collect();
+    assert_eq!(types[2], Type::string());
+    assert_eq!(types[3], Type::float());
+}
+
+#[test]
+fn bindings_of_expressions_with_errors_are_not_committed() {
+    let _ = env_logger::try_init();
+
+    let results = support::typecheck_session(&["let x = 1\nlet z = \"\" #Int+ 1\nx", "x"]);
+    assert!(results[0].is_err());
+    assert!(results[1].is_err());
+}
+
+#[test]
+fn lambda_parameter_type_from_later_argument() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Person = { name : String, age : Int }
+type Pet = { name : String, legs : Int }
+let map f xs : (a -> b) -> Array a -> Array b = map f xs
+let people : Array Person = []
+map (\x -> x.name) people
+"#;
+    let result = support::typecheck(text);
+
+    assert_req!(result, Ok(Type::array(Type::string())));
+}
+
+#[test]
+fn lambda_parameter_type_from_earlier_argument() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Person = { name : String, age : Int }
+type Pet = { name : String, legs : Int }
+let for xs f : Array a -> (a -> b) -> Array b = for xs f
+let people : Array Person = []
+for people (\x -> x.name)
+"#;
+    let result = support::typecheck(text);
+
+    assert_req!(result, Ok(Type::array(Type::string())));
+}
+
+#[test]
+fn recursive_value_guarded_by_constructor() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Stream a = | Cons a (() -> Stream a)
+let ones = Cons 1 (\_ -> ones)
+ones
+"#;
+    let result = support::typecheck(text);
+
+    assert_req!(result.map(|typ| typ.to_string()), Ok("test.Stream Int".to_string()));
+}
+
+#[test]
+fn recursive_record_of_functions() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Parity = | Even | Odd
+let parity = {
+    even = \n -> if n #Int== 0 then Even else parity.odd (n #Int- 1),
+    odd = \n -> if n #Int== 0 then Odd else parity.even (n #Int- 1),
+}
+parity.even 10
+"#;
+    let result = support::typecheck(text);
+
+    assert_req!(result.map(|typ| typ.to_string()), Ok("test.Parity".to_string()));
+}
+
+#[test]
+fn recursive_value_and_function_in_the_same_group() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Stream a = | Cons a (() -> Stream a)
+let count n = Cons n (\_ -> count (n #Int+ 1))
+and naturals = Cons 0 (\_ -> count 1)
+naturals
+"#;
+    let result = support::typecheck(text);
+
+    assert_req!(result.map(|typ| typ.to_string()), Ok("test.Stream Int".to_string()));
+}
@@ -0,0 +1,77 @@
+#[macro_use]
+extern crate collect_mac;
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_check as check;
+extern crate gluon_parser as parser;
+
+#[macro_use]
+mod support;
+
+use base::ast::{Expr, Pattern, SpannedExpr};
+use base::symbol::Symbol;
+use base::types::TypeCache;
+
+use check::repro;
+use check::typecheck::{TypeError, Typecheck};
+
+/// Returns the declared names of the `let` bindings which wrap the innermost expression
+fn binding_names(mut expr: &SpannedExpr<Symbol>) -> Vec<String> {
+    let mut names = Vec::new();
+    while let Expr::LetBindings(ref bindings, ref body) = expr.value {
+        for bind in bindings {
+            if let Pattern::Ident(ref id) = bind.name.value {
+                names.push(id.name.declared_name().to_string());
+            }
+        }
+        expr = body;
+    }
+    names
+}
+
+#[test]
+fn minimize_nested_error() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let a = 1
+let b = "b"
+let c =
+    let d = 2.0
+    let e = a
+    let f = e #Int+ b
+    f
+c
+"#;
+    let expr = support::parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = support::MockEnv::new();
+    let interner = support::get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
+
+    let error = tc.typecheck_expr(&mut expr.clone())
+        .unwrap_err()
+        .into_iter()
+        .next()
+        .unwrap();
+    match error.value.error {
+        TypeError::Unification(..) => (),
+        _ => panic!("Unexpected error: {}", error.value),
+    }
+
+    let minimized = repro::minimize(&mut tc, &expr, error.span);
+    assert_eq!(binding_names(&minimized), ["a", "b", "e"]);
+
+    let minimized_error = tc.typecheck_expr(&mut minimized.clone())
+        .unwrap_err()
+        .into_iter()
+        .next()
+        .unwrap();
+    match minimized_error.value.error {
+        TypeError::Unification(..) => (),
+        _ => panic!("Unexpected error: {}", minimized_error.value),
+    }
+    assert_eq!(minimized_error.span, error.span);
+}
@@ -59,6 +59,7 @@ pub fn typecheck(text: &str) -> Result<ArcType, InFile<typecheck::HelpError<Symb
 
 pub struct MockEnv {
     bool: Alias<Symbol, ArcType>,
+    decimal: ArcType,
 }
 
 impl MockEnv {
@@ -69,8 +70,11 @@ impl MockEnv {
         let bool_sym = interner.symbol("Bool");
         let bool_ty = Type::app(Type::ident(bool_sym.clone()), collect![]);
 
+        let decimal_sym = SymbolModule::new("test".into(), &mut interner).scoped_symbol("Decimal");
+
         MockEnv {
             bool: Alias::new(bool_sym, bool_ty),
+            decimal: Type::ident(decimal_sym),
         }
     }
 }
@@ -106,12 +110,23 @@ impl TypeEnv for MockEnv {
     ) -> Option<(ArcType, ArcType)> {
         None
     }
+
+    fn each_type_info(&self, f: &mut FnMut(&Symbol, &Alias<Symbol, ArcType>)) {
+        f(&self.bool.name, &self.bool)
+    }
 }
 
 impl PrimitiveEnv for MockEnv {
     fn get_bool(&self) -> &ArcType {
         &self.bool.as_type()
     }
+
+    fn find_primitive_operand(&self, name: &str) -> Option<&ArcType> {
+        match name {
+            "Decimal" => Some(&self.decimal),
+            _ => None,
+        }
+    }
 }
 
 impl MetadataEnv for MockEnv {
@@ -159,11 +174,12 @@ pub fn typecheck_expr_expected(
     let env = MockEnv::new();
     let interner = get_local_interner();
     let mut interner = interner.borrow_mut();
-    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new())
+        .with_source(text);
 
     let result = tc.typecheck_expr_expected(&mut expr, expected);
 
-    (expr, result.map_err(|err| InFile::new("test", text, err)))
+    (expr, result.map_err(|err| InFile::with_notes("test", text, err)))
 }
 
 pub fn typecheck_expr(
@@ -175,6 +191,223 @@ pub fn typecheck_expr(
     typecheck_expr_expected(text, None)
 }
 
+/// Typechecks `text` while tracing all unifications, returning the trace along with the result
+#[allow(dead_code)]
+pub fn typecheck_with_unification_trace(
+    text: &str,
+) -> (
+    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
+    String,
+) {
+    let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = MockEnv::new();
+    let interner = get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut trace = Vec::new();
+    let result = {
+        let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new())
+            .with_unification_trace(&mut trace);
+        tc.typecheck_expr(&mut expr)
+    };
+
+    (
+        result.map_err(|err| InFile::new("test", text, err)),
+        String::from_utf8(trace).unwrap(),
+    )
+}
+
+/// Typechecks `text` reporting shadowed type variables at `level`, returning the warnings along
+/// with the result
+#[allow(dead_code)]
+pub fn typecheck_with_shadowed_type_variables(
+    text: &str,
+    level: typecheck::LintLevel,
+) -> (
+    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
+    Vec<typecheck::SpannedWarning<Symbol>>,
+) {
+    let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = MockEnv::new();
+    let interner = get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new())
+        .shadowed_type_variables(level);
+    let result = tc.typecheck_expr(&mut expr);
+
+    (
+        result.map_err(|err| InFile::new("test", text, err)),
+        tc.take_warnings().into(),
+    )
+}
+
+/// Typechecks `text`, returning the typechecked expression and its type even if errors were found
+#[allow(dead_code)]
+pub fn typecheck_expr_partial(
+    text: &str,
+) -> (
+    SpannedExpr<Symbol>,
+    ArcType,
+    Option<InFile<typecheck::HelpError<Symbol>>>,
+) {
+    let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = MockEnv::new();
+    let interner = get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
+    let (typ, error) = tc.typecheck_expr_partial(&mut expr, None);
+
+    (expr, typ, error.map(|err| InFile::new("test", text, err)))
+}
+
+/// Typechecks each of `lines` with `typecheck_and_commit` using the same typechecker, as a REPL
+/// would
+#[allow(dead_code)]
+pub fn typecheck_session(
+    lines: &[&str],
+) -> Vec<Result<ArcType, InFile<typecheck::HelpError<Symbol>>>> {
+    let exprs: Vec<_> = lines
+        .iter()
+        .map(|text| parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err)))
+        .collect();
+
+    let env = MockEnv::new();
+    let interner = get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
+
+    lines
+        .iter()
+        .zip(exprs)
+        .map(|(text, mut expr)| {
+            tc.typecheck_and_commit(&mut expr)
+                .map_err(|err| InFile::new("test", text, err))
+        })
+        .collect()
+}
+
+/// Typechecks `text` reporting at most `limit` errors
+#[allow(dead_code)]
+pub fn typecheck_with_error_limit(
+    text: &str,
+    limit: usize,
+) -> Result<ArcType, InFile<typecheck::HelpError<Symbol>>> {
+    let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = MockEnv::new();
+    let interner = get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
+    tc.set_error_limit(limit);
+    let result = tc.typecheck_expr(&mut expr);
+
+    result.map_err(|err| InFile::new("test", text, err))
+}
+
+/// Typechecks `text` with the default lint levels, returning the warnings along with the result
+#[allow(dead_code)]
+pub fn typecheck_with_warnings(
+    text: &str,
+) -> (
+    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
+    Vec<typecheck::SpannedWarning<Symbol>>,
+) {
+    let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = MockEnv::new();
+    let interner = get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
+    let result = tc.typecheck_expr(&mut expr);
+
+    (
+        result.map_err(|err| InFile::new("test", text, err)),
+        tc.take_warnings().into(),
+    )
+}
+
+/// Typechecks `text` reporting unused bindings at `level`, returning the warnings along with the
+/// result
+#[allow(dead_code)]
+pub fn typecheck_with_unused_bindings(
+    text: &str,
+    level: typecheck::LintLevel,
+) -> (
+    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
+    Vec<typecheck::SpannedWarning<Symbol>>,
+) {
+    let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = MockEnv::new();
+    let interner = get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new())
+        .unused_bindings(level);
+    let result = tc.typecheck_expr(&mut expr);
+
+    (
+        result.map_err(|err| InFile::new("test", text, err)),
+        tc.take_warnings().into(),
+    )
+}
+
+/// Typechecks `text` reporting shadowed bindings at the given levels and ignoring unused
+/// bindings, returning the warnings along with the result. Variables named in `modules` are
+/// replaced with the globals which `import!` expands to.
+#[allow(dead_code)]
+pub fn typecheck_with_shadowed_bindings(
+    text: &str,
+    modules: &[&str],
+    imports: typecheck::LintLevel,
+    bindings: typecheck::LintLevel,
+) -> (
+    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
+    Vec<typecheck::SpannedWarning<Symbol>>,
+) {
+    use base::ast::{walk_mut_expr, Expr, MutVisitor, TypedIdent};
+
+    struct ReplaceModules<'m>(&'m [&'m str]);
+
+    impl<'a, 'm> MutVisitor<'a> for ReplaceModules<'m> {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, e: &mut SpannedExpr<Symbol>) {
+            let module = match e.value {
+                Expr::Ident(ref id) => self.0
+                    .iter()
+                    .find(|module| id.name.declared_name() == **module)
+                    .cloned(),
+                _ => None,
+            };
+            match module {
+                Some(module) => {
+                    e.value = Expr::Ident(TypedIdent::new(Symbol::from(format!("@{}", module))))
+                }
+                None => walk_mut_expr(self, e),
+            }
+        }
+    }
+
+    let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+    ReplaceModules(modules).visit_expr(&mut expr);
+
+    let env = MockEnv::new();
+    let interner = get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new())
+        .shadowed_imports(imports)
+        .shadowed_bindings(bindings)
+        .unused_bindings(typecheck::LintLevel::Allow);
+    let result = tc.typecheck_expr(&mut expr);
+
+    (
+        result.map_err(|err| InFile::new("test", text, err)),
+        tc.take_warnings().into(),
+    )
+}
+
 #[allow(dead_code)]
 pub fn typecheck_partial_expr(
     text: &str,
@@ -315,6 +548,8 @@ macro_rules! assert_err {
         #[allow(unused_imports)]
         use check::typecheck::TypeError::*;
         #[allow(unused_imports)]
+        use check::typecheck::Warning;
+        #[allow(unused_imports)]
         use check::unify::Error::{TypeMismatch, Substitution, Other};
         #[allow(unused_imports)]
         use check::substitution::Error::Occurs;
@@ -358,6 +593,8 @@ macro_rules! assert_multi_unify_err {
     ($e: expr, $( [ $( $id: pat ),+ ] ),+) => {{
         use check::typecheck::TypeError::*;
         #[allow(unused_imports)]
+        use check::typecheck::Warning;
+        #[allow(unused_imports)]
         use check::unify::Error::{TypeMismatch, Substitution, Other};
         #[allow(unused_imports)]
         use check::substitution::Error::Occurs;
@@ -0,0 +1,85 @@
+#[macro_use]
+extern crate collect_mac;
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_check as check;
+extern crate gluon_parser as parser;
+
+#[allow(unused_macros)]
+mod support;
+
+use base::pos::BytePos;
+
+use check::type_at::type_at;
+
+/// Returns the text and type at the first occurence of `needle` in `text`, offset by `offset`
+fn type_at_needle(text: &str, needle: &str, offset: usize) -> (String, String) {
+    let (expr, result) = support::typecheck_expr(text);
+    result.unwrap_or_else(|err| panic!("{}", err));
+
+    let pos = text.find(needle).expect("needle") + offset;
+    let (span, typ) = type_at(&expr, BytePos::from(pos)).expect("type at position");
+    (
+        text[span.start.to_usize()..span.end.to_usize()].to_string(),
+        typ.to_string(),
+    )
+}
+
+#[test]
+fn argument_of_nested_lambda() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let f = \x -> \y -> x #Int+ y
+f 1 2
+"#;
+    assert_eq!(
+        type_at_needle(text, "y ->", 0),
+        ("y".to_string(), "Int".to_string())
+    );
+    assert_eq!(
+        type_at_needle(text, "+ y", 2),
+        ("y".to_string(), "Int".to_string())
+    );
+}
+
+#[test]
+fn record_field_in_pattern() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type Test = { x : Int, name : String }
+let t : Test = { x = 1, name = "" }
+match t with
+| { x, name = n } -> n
+"#;
+    assert_eq!(
+        type_at_needle(text, "{ x, name = n", 2),
+        ("x".to_string(), "Int".to_string())
+    );
+    assert_eq!(
+        type_at_needle(text, "name = n }", 0),
+        ("name".to_string(), "String".to_string())
+    );
+    assert_eq!(
+        type_at_needle(text, "n } ->", 0),
+        ("n".to_string(), "String".to_string())
+    );
+}
+
+#[test]
+fn projection_and_position_outside_any_node() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let r = { x = 1, y = "" }
+r.y
+"#;
+    assert_eq!(
+        type_at_needle(text, "r.y", 2),
+        ("r.y".to_string(), "String".to_string())
+    );
+    let (expr, _) = support::typecheck_expr(text);
+    assert_eq!(type_at(&expr, BytePos::from(0)), None);
+}
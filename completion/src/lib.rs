@@ -142,7 +142,7 @@ impl<E: TypeEnv> OnFound for Suggest<E> {
                 self.stack.insert(id.clone(), pat.env_type_of(&self.env));
                 self.on_pattern(pat);
             }
-            Pattern::Ident(ref id) => {
+            Pattern::Ident(ref id) | Pattern::StringAffix { rest: ref id, .. } => {
                 self.stack.insert(id.name.clone(), id.typ.clone());
             }
             Pattern::Record {
@@ -179,7 +179,8 @@ impl<E: TypeEnv> OnFound for Suggest<E> {
             Pattern::Tuple {
                 elems: ref args, ..
             }
-            | Pattern::Constructor(_, ref args) => for arg in args {
+            | Pattern::Constructor(_, ref args)
+            | Pattern::Or(ref args) => for arg in args {
                 self.on_pattern(arg);
             },
             Pattern::Literal(_) | Pattern::Error => (),
@@ -399,11 +400,14 @@ where
                     self.found = MatchState::Empty;
                 }
             }
-            Pattern::Tuple { ref elems, .. } => {
+            Pattern::Tuple { ref elems, .. } | Pattern::Or(ref elems) => {
                 let (_, field) = self.select_spanned(elems, |elem| elem.span);
                 self.visit_pattern(field.unwrap());
             }
-            Pattern::Ident(_) | Pattern::Literal(_) | Pattern::Error => {
+            Pattern::Ident(_)
+            | Pattern::Literal(_)
+            | Pattern::StringAffix { .. }
+            | Pattern::Error => {
                 self.found = if current.span.containment(&self.pos) == Ordering::Equal {
                     MatchState::Found(Match::Pattern(current))
                 } else {
@@ -456,8 +460,10 @@ where
                     }
                     Err(alt) => {
                         self.on_found.on_pattern(&alt.pattern);
-                        let iter = [Ok(&alt.pattern), Err(&alt.expr)];
-                        let (_, sel) = self.select_spanned(iter.iter().cloned(), |x| match *x {
+                        let iter = once(Ok(&alt.pattern))
+                            .chain(alt.guard.iter().map(Err))
+                            .chain(once(Err(&alt.expr)));
+                        let (_, sel) = self.select_spanned(iter, |x| match *x {
                             Ok(p) => p.span,
                             Err(e) => e.span,
                         });
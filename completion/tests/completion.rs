@@ -360,6 +360,7 @@ abc
 
     let expected = Some(Metadata {
         comment: Some("test".to_string()),
+        operator: None,
         ..Metadata::default()
     });
     assert_eq!(result, expected);
@@ -378,6 +379,7 @@ let (+++) x y = 1
 
     let expected = Some(Metadata {
         comment: Some("test".to_string()),
+        operator: None,
         ..Metadata::default()
     });
     assert_eq!(result, expected);
@@ -399,6 +401,7 @@ module.abc
 
     let expected = Some(Metadata {
         comment: Some("test".to_string()),
+        operator: None,
         ..Metadata::default()
     });
     assert_eq!(result, expected);
@@ -418,6 +421,7 @@ ab
 
     let expected = Some(Metadata {
         comment: Some("test".to_string()),
+        operator: None,
         ..Metadata::default()
     });
     assert_eq!(result, expected);
@@ -439,6 +443,7 @@ module.ab
 
     let expected = Some(Metadata {
         comment: Some("test".to_string()),
+        operator: None,
         ..Metadata::default()
     });
     assert_eq!(result, expected);
@@ -94,6 +94,10 @@ impl TypeEnv for MockEnv {
     ) -> Option<(ArcType, ArcType)> {
         None
     }
+
+    fn each_type_info(&self, f: &mut FnMut(&Symbol, &Alias<Symbol, ArcType>)) {
+        f(&self.bool.name, &self.bool)
+    }
 }
 
 impl PrimitiveEnv for MockEnv {
@@ -231,6 +231,10 @@ where
                         chain![arena;
                             "| ",
                             self.pretty_pattern(&alt.pattern),
+                            match alt.guard {
+                                Some(ref guard) => arena.text(" if ").append(pretty(guard)).group(),
+                                None => arena.nil(),
+                            },
                             " ->",
                             self.hang(arena.nil(), &alt.expr).group()
                         ]
@@ -617,10 +621,24 @@ where
                 ),
                 ")"
             ].group(),
+            Pattern::Or(ref alternatives) => arena.concat(
+                alternatives
+                    .iter()
+                    .map(|alternative| self.pretty_pattern(alternative))
+                    .intersperse(arena.text(" | ")),
+            ),
             Pattern::Error => arena.text("<error>"),
             Pattern::Literal(_) => arena.text(
                 &self.source.src()[pattern.span.start.to_usize()..pattern.span.end.to_usize()],
             ),
+            Pattern::StringAffix { .. } => prec.enclose(
+                Prec::Constructor,
+                arena,
+                arena.text(
+                    &self.source.src()
+                        [pattern.span.start.to_usize()..pattern.span.end.to_usize()],
+                ),
+            ),
         }
     }
 
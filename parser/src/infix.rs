@@ -5,6 +5,7 @@
 use base::ast::{walk_mut_expr, Expr, IdentEnv, Literal, MutVisitor, SpannedExpr, SpannedIdent};
 use base::error::Errors;
 use base::fnv::FnvMap;
+pub use base::metadata::Fixity;
 use base::pos::{self, BytePos, Spanned};
 use std::cmp::Ordering;
 use std::error::Error as StdError;
@@ -13,36 +14,6 @@ use std::marker::PhantomData;
 use std::mem;
 use std::ops::Index;
 
-/// The fixity (associativity) of an infix operator
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum Fixity {
-    /// Left operator associativity.
-    ///
-    /// For example, when the `(~)` operator is left-associative:
-    ///
-    /// ```text
-    /// x ~ y ~ z ≡ (x ~ y) ~ z
-    /// ```
-    Left,
-    /// Right operator associativity.
-    ///
-    /// For example, when the `(~)` operator is right-associative:
-    ///
-    /// ```text
-    /// x ~ y ~ z ≡ x ~ (y ~ z)
-    /// ```
-    Right,
-}
-
-impl fmt::Display for Fixity {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            Fixity::Left => write!(f, "infixl"),
-            Fixity::Right => write!(f, "infixr"),
-        }
-    }
-}
-
 /// Metadata pertaining to an infix operator
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct OpMeta {
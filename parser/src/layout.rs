@@ -421,7 +421,8 @@ where
             // Some tokens directly insert a new context when emitted
             let push_context = match token.value {
                 Token::Let | Token::Do => Some(Context::Let),
-                Token::If => Some(Context::If),
+                // An `if` in a match clause is a pattern guard which is not closed by an `else`
+                Token::If if offside.context != Context::MatchClause => Some(Context::If),
                 Token::Type => Some(Context::Type),
                 Token::Match => Some(Context::Expr),
                 Token::Lambda => Some(Context::Lambda),
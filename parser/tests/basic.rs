@@ -242,6 +242,66 @@ match None with
     );
 }
 
+#[test]
+fn case_expr_with_or_pattern() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+match x with
+    | Ok y | Missing y -> y
+    | _ -> 0"#;
+    let e = parse_clear_span!(text);
+    let constructor = |name: &str| {
+        no_loc(Pattern::Constructor(
+            TypedIdent::new(intern(name)),
+            vec![no_loc(Pattern::Ident(TypedIdent::new(intern("y"))))],
+        ))
+    };
+    assert_eq!(
+        e,
+        case(
+            id("x"),
+            vec![
+                (
+                    Pattern::Or(vec![constructor("Ok"), constructor("Missing")]),
+                    id("y"),
+                ),
+                (Pattern::Ident(TypedIdent::new(intern("_"))), int(0)),
+            ],
+        )
+    );
+}
+
+#[test]
+fn case_expr_with_guard() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+match x with
+    | Some y if y > 0 -> y
+    | _ -> 0"#;
+    let e = parse_clear_span!(text);
+    assert_eq!(
+        e,
+        no_loc(Expr::Match(
+            Box::new(id("x")),
+            vec![
+                Alternative {
+                    pattern: no_loc(Pattern::Constructor(
+                        TypedIdent::new(intern("Some")),
+                        vec![no_loc(Pattern::Ident(TypedIdent::new(intern("y"))))],
+                    )),
+                    guard: Some(binop(id("y"), ">", int(0))),
+                    expr: id("y"),
+                },
+                Alternative {
+                    pattern: no_loc(Pattern::Ident(TypedIdent::new(intern("_")))),
+                    guard: None,
+                    expr: int(0),
+                },
+            ],
+        ))
+    );
+}
+
 #[test]
 fn array_expr() {
     let _ = ::env_logger::try_init();
@@ -352,7 +352,11 @@ fn set_globals(
             )?;
             set_globals(vm, pattern, typ, value)
         }
-        Pattern::Constructor(..) | Pattern::Literal(_) | Pattern::Error => {
+        Pattern::Constructor(..)
+        | Pattern::Literal(_)
+        | Pattern::StringAffix { .. }
+        | Pattern::Or(_)
+        | Pattern::Error => {
             Err(VMError::Message("The repl cannot bind variables from this pattern".into()).into())
         }
     }
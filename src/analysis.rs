@@ -0,0 +1,197 @@
+//! Analyses over all the modules of a program.
+//!
+//! The analyses work on the typechecked modules stored by a `CheckImporter`, so the program needs
+//! to have been typechecked with an `Import` macro which uses it:
+//!
+//! ```rust
+//! extern crate gluon;
+//!
+//! use gluon::import::{CheckImporter, Import};
+//! use gluon::{new_vm, Compiler};
+//!
+//! fn main() {
+//!     let vm = new_vm();
+//!     let importer = CheckImporter::new();
+//!     // Keep the import paths and extern modules of the default `import!` macro
+//!     let import = {
+//!         let import = vm.get_macros().get("import").unwrap();
+//!         import
+//!             .downcast_ref::<Import>()
+//!             .unwrap()
+//!             .with_importer(importer.clone())
+//!     };
+//!     vm.get_macros().insert(String::from("import"), import);
+//!
+//!     Compiler::new()
+//!         .typecheck_str(&vm, "main", "import! std.option", None)
+//!         .unwrap();
+//!     for (module, field) in gluon::analysis::dead_exports(&importer, &["std.option"]) {
+//!         println!("{}.{} is never used", module, field);
+//!     }
+//! }
+//! ```
+
+use std::collections::VecDeque;
+
+use base::ast::{walk_expr, Expr, Pattern, SpannedExpr, Visitor};
+use base::fnv::{FnvMap, FnvSet};
+use base::symbol::Symbol;
+
+use import::CheckImporter;
+
+/// The fields of a module which are used
+#[derive(Default)]
+struct Used {
+    /// The module is used in a way which makes it impossible to tell which fields are used, such
+    /// as being passed to a function
+    all: bool,
+    fields: FnvSet<String>,
+}
+
+struct UsageVisitor<'a> {
+    modules: &'a FnvMap<String, SpannedExpr<Symbol>>,
+    /// Local variables bound directly to an imported module (`let m = import! m`)
+    aliases: FnvMap<Symbol, String>,
+    used: &'a mut FnvMap<String, Used>,
+}
+
+impl<'a> UsageVisitor<'a> {
+    fn module_of(&self, expr: &SpannedExpr<Symbol>) -> Option<String> {
+        match expr.value {
+            Expr::Ident(ref id) => {
+                if id.name.is_global() {
+                    let name = id.name.definition_name();
+                    if self.modules.contains_key(name) {
+                        return Some(name.to_string());
+                    }
+                }
+                self.aliases.get(&id.name).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    fn use_field(&mut self, module: String, field: &Symbol) {
+        self.used
+            .entry(module)
+            .or_insert_with(Used::default)
+            .fields
+            .insert(field.declared_name().to_string());
+    }
+
+    fn bind_module(&mut self, module: String, pattern: &Pattern<Symbol>) {
+        match *pattern {
+            Pattern::Ident(ref id) => {
+                self.used.entry(module.clone()).or_insert_with(Used::default);
+                self.aliases.insert(id.name.clone(), module);
+            }
+            Pattern::As(ref id, ref pattern) => {
+                self.aliases.insert(id.clone(), module.clone());
+                self.bind_module(module, &pattern.value);
+            }
+            Pattern::Record { ref fields, .. } => {
+                self.used.entry(module.clone()).or_insert_with(Used::default);
+                for field in fields {
+                    self.use_field(module.clone(), &field.name.value);
+                }
+            }
+            _ => self.used.entry(module).or_insert_with(Used::default).all = true,
+        }
+    }
+}
+
+impl<'a, 'b> Visitor<'b> for UsageVisitor<'a> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'b SpannedExpr<Symbol>) {
+        match expr.value {
+            Expr::Projection(ref record, ref field, _) => match self.module_of(record) {
+                Some(module) => self.use_field(module, field),
+                None => walk_expr(self, expr),
+            },
+            Expr::Ident(_) => if let Some(module) = self.module_of(expr) {
+                self.used.entry(module).or_insert_with(Used::default).all = true;
+            },
+            Expr::LetBindings(ref bindings, ref body) => {
+                for binding in bindings {
+                    match self.module_of(&binding.expr) {
+                        Some(ref module) if binding.args.is_empty() => {
+                            self.bind_module(module.clone(), &binding.name.value)
+                        }
+                        _ => self.visit_expr(&binding.expr),
+                    }
+                }
+                self.visit_expr(body);
+            }
+            _ => walk_expr(self, expr),
+        }
+    }
+}
+
+/// Returns the exported fields of `expr`, if it is a module ending in a record expression
+fn exports(mut expr: &SpannedExpr<Symbol>) -> Option<Vec<Symbol>> {
+    loop {
+        match expr.value {
+            Expr::LetBindings(_, ref body) | Expr::TypeBindings(_, ref body) => expr = body,
+            Expr::Record { ref exprs, .. } => {
+                return Some(exprs.iter().map(|field| field.name.value.clone()).collect())
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Returns the exported fields of the modules reachable from `roots` which are never used by any
+/// of the reachable modules, as `(module, field)` pairs sorted by module and field.
+///
+/// A field counts as used if it is accessed with a projection (`(import! m).field` or `m.field`
+/// where `m` is bound to the module) or if it is bound by a record pattern destructuring the
+/// module. Any other use of a module, such as passing it to a function, marks all of its fields
+/// as used.
+///
+/// Only modules whose last expression is a record expression are reported, and the exports of
+/// `roots` as well as modules from the standard library are never reported. Fields which are only
+/// accessed dynamically, for instance by a record pattern matching on a value returned from a
+/// function in the module, are reported even though they are used.
+pub fn dead_exports(importer: &CheckImporter, roots: &[&str]) -> Vec<(String, Symbol)> {
+    let modules = importer.0.lock().unwrap();
+
+    let mut used = FnvMap::default();
+    let mut visited = FnvSet::default();
+    let mut queue: VecDeque<String> = roots.iter().map(|root| root.to_string()).collect();
+    while let Some(module) = queue.pop_front() {
+        if !visited.insert(module.clone()) {
+            continue;
+        }
+        if let Some(expr) = modules.get(&module) {
+            UsageVisitor {
+                modules: &modules,
+                aliases: FnvMap::default(),
+                used: &mut used,
+            }.visit_expr(expr);
+        }
+        queue.extend(used.keys().filter(|m| !visited.contains(*m)).cloned());
+    }
+
+    let mut dead: Vec<_> = visited
+        .iter()
+        .filter(|module| !roots.contains(&&module[..]) && !module.starts_with("std."))
+        .filter_map(|module| {
+            let used = used.get(module)?;
+            if used.all {
+                return None;
+            }
+            let exports = exports(modules.get(module)?)?;
+            Some(
+                exports
+                    .into_iter()
+                    .filter(|field| !used.fields.contains(field.declared_name()))
+                    .map(|field| (module.clone(), field))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .flat_map(|dead| dead)
+        .collect();
+    dead.sort_by(|l, r| (&l.0, l.1.declared_name()).cmp(&(&r.0, r.1.declared_name())));
+    dead
+}
@@ -15,9 +15,10 @@ use std::result::Result as StdResult;
 #[cfg(feature = "serde")]
 use either::Either;
 
-use base::ast::SpannedExpr;
+use base::ast::{self, Expr, Pattern, SpannedExpr, SpannedPattern, Visitor};
 use base::error::{Errors, InFile};
 use base::metadata::Metadata;
+use base::pos::{self, BytePos, Spanned};
 use base::types::{ArcType, Type};
 use base::source::Source;
 use base::symbol::{Name, NameBuf, Symbol, SymbolModule};
@@ -27,9 +28,51 @@ use vm::core;
 use vm::compiler::CompiledModule;
 use vm::future::{BoxFutureValue, FutureValue};
 use vm::macros::MacroExpander;
+use vm::Variants;
 use vm::thread::{Execute, RootedValue, Thread, ThreadInternal, VmRoot};
+use vm::internal::Value;
 
-use {Compiler, Error, Result};
+use {import, Compiler, Error, Result};
+
+/// Returns an error for each pattern (or pattern guard) in `expr` which the typechecker accepts
+/// but which can't be translated by the compiler yet
+fn unsupported_patterns(expr: &SpannedExpr<Symbol>) -> Errors<Spanned<String, BytePos>> {
+    struct UnsupportedPatterns(Errors<Spanned<String, BytePos>>);
+
+    impl<'a> Visitor<'a> for UnsupportedPatterns {
+        type Ident = Symbol;
+
+        fn visit_pattern(&mut self, pattern: &'a SpannedPattern<Symbol>) {
+            let message = match pattern.value {
+                Pattern::StringAffix { .. } => {
+                    Some("String prefix and suffix patterns can't be compiled yet")
+                }
+                Pattern::Or(_) => Some("Or-patterns can't be compiled yet"),
+                _ => None,
+            };
+            if let Some(message) = message {
+                self.0.push(pos::spanned(pattern.span, message.to_string()));
+            }
+            ast::walk_pattern(self, &pattern.value);
+        }
+
+        fn visit_expr(&mut self, expr: &'a SpannedExpr<Symbol>) {
+            if let Expr::Match(_, ref alts) = expr.value {
+                for guard in alts.iter().filter_map(|alt| alt.guard.as_ref()) {
+                    self.0.push(pos::spanned(
+                        guard.span,
+                        "Pattern guards can't be compiled yet".to_string(),
+                    ));
+                }
+            }
+            ast::walk_expr(self, expr);
+        }
+    }
+
+    let mut visitor = UnsupportedPatterns(Errors::new());
+    visitor.visit_expr(expr);
+    visitor.0
+}
 
 fn execute<T, F>(vm: T, f: F) -> FutureValue<Execute<T>>
 where
@@ -121,9 +164,13 @@ impl<'s> MacroExpandable for &'s mut SpannedExpr<Symbol> {
         _expr_str: &str,
     ) -> SalvageResult<MacroValue<Self::Expr>> {
         if compiler.implicit_prelude {
-            compiler.include_implicit_prelude(macros.vm.global_env().type_cache(), file, self);
+            compiler
+                .include_implicit_prelude(macros, file, self)
+                .map_err(|err| (None, err))?;
         }
+        import::set_importing_compiler(macros, compiler);
         macros.run(self);
+        compiler.warnings.extend(import::take_warnings(macros));
         Ok(MacroValue { expr: self })
     }
 }
@@ -139,10 +186,14 @@ impl MacroExpandable for SpannedExpr<Symbol> {
         expr_str: &str,
     ) -> SalvageResult<MacroValue<Self::Expr>> {
         if compiler.implicit_prelude {
-            compiler.include_implicit_prelude(macros.vm.global_env().type_cache(), file, &mut self);
+            compiler
+                .include_implicit_prelude(macros, file, &mut self)
+                .map_err(|err| (None, err))?;
         }
         let prev_errors = mem::replace(&mut macros.errors, Errors::new());
+        import::set_importing_compiler(macros, compiler);
         macros.run(&mut self);
+        compiler.warnings.extend(import::take_warnings(macros));
         let errors = mem::replace(&mut macros.errors, prev_errors);
         if errors.has_errors() {
             Err((None, InFile::new(file, expr_str, errors).into()))
@@ -222,13 +273,34 @@ where
     type Expr = E;
 
     fn typecheck_expected(
-        mut self,
+        self,
         compiler: &mut Compiler,
         thread: &Thread,
         file: &str,
         expr_str: &str,
         expected_type: Option<&ArcType>,
     ) -> Result<TypecheckValue<Self::Expr>> {
+        match self.typecheck_partial(compiler, thread, file, expr_str, expected_type) {
+            (value, None) => Ok(value),
+            (_, Some(err)) => Err(err),
+        }
+    }
+}
+
+impl<E> MacroValue<E>
+where
+    E: BorrowMut<SpannedExpr<Symbol>>,
+{
+    /// Typechecks the expression like `typecheck_expected` but returns the type of the expression
+    /// even if typechecking fails (see `Typecheck::typecheck_expr_partial`)
+    pub fn typecheck_partial(
+        mut self,
+        compiler: &mut Compiler,
+        thread: &Thread,
+        file: &str,
+        expr_str: &str,
+        expected_type: Option<&ArcType>,
+    ) -> (TypecheckValue<E>, Option<Error>) {
         use check::typecheck::Typecheck;
 
         let env = thread.get_env();
@@ -237,18 +309,42 @@ where
             &mut compiler.symbols,
             &*env,
             thread.global_env().type_cache().clone(),
-        );
-
-        let typ = tc.typecheck_expr_expected(self.expr.borrow_mut(), expected_type)
-            .map_err(|err| {
+        ).with_source(expr_str)
+            .shadowed_imports(compiler.shadowed_imports)
+            .shadowed_bindings(compiler.shadowed_bindings)
+            .unrelated_shadowed_bindings(compiler.unrelated_shadowed_bindings)
+            .unreachable_patterns(compiler.unreachable_patterns)
+            .unused_bindings(compiler.unused_bindings);
+
+        let (typ, error) = tc.typecheck_expr_partial(self.expr.borrow_mut(), expected_type);
+        let error = match error {
+            Some(err) => {
                 info!("Error when typechecking `{}`: {}", file, err);
-                InFile::new(file, expr_str, err)
-            })?;
+                Some(InFile::with_notes(file, expr_str, err).into())
+            }
+            None => None,
+        };
 
-        Ok(TypecheckValue {
-            expr: self.expr,
-            typ: typ,
-        })
+        let warnings = tc.take_warnings();
+        if warnings.has_errors() {
+            let warnings = InFile::with_notes(file, expr_str, warnings);
+            warn!("{}", warnings);
+            compiler.warnings.push(warnings);
+        }
+
+        let error = error.or_else(|| {
+            check::metadata::check_metadata(&*env, self.expr.borrow())
+                .err()
+                .map(|err| InFile::new(file, expr_str, err).into())
+        });
+
+        (
+            TypecheckValue {
+                expr: self.expr,
+                typ: typ,
+            },
+            error,
+        )
     }
 }
 
@@ -305,6 +401,14 @@ where
     ) -> Result<CompileValue<Self::Expr>> {
         use vm::compiler::Compiler;
         debug!("Compile `{}`", filename);
+        let unsupported = unsupported_patterns(self.expr.borrow());
+        if unsupported.has_errors() {
+            return Err(Error::Other(Box::new(InFile::new(
+                filename,
+                expr_str,
+                unsupported,
+            ))));
+        }
         let mut module = {
             let env = thread.get_env();
 
@@ -496,7 +600,8 @@ where
             })
             .and_then(move |mut value| {
                 let (metadata, _) = metadata::metadata(&*vm.get_env(), value.expr.borrow_mut());
-                try_future!(vm.set_global(
+                try_future!(set_module_global(
+                    &vm,
                     value.id.clone(),
                     value.typ,
                     metadata,
@@ -509,6 +614,54 @@ where
     }
 }
 
+/// Removes the metadata of the private fields of a module (see `check::is_private_field`)
+pub(crate) fn remove_private_metadata(metadata: &mut Metadata) {
+    use check::is_private_field;
+
+    metadata.module = mem::replace(&mut metadata.module, Default::default())
+        .into_iter()
+        .filter(|&(ref name, ref field_metadata)| !is_private_field(name, Some(field_metadata)))
+        .collect();
+}
+
+/// Sets `value` as the global `id`, removing the private fields of the module (see
+/// `check::is_private_field`) so that only its public fields are visible to importers
+fn set_module_global(
+    vm: &Thread,
+    id: Symbol,
+    typ: ArcType,
+    mut metadata: Metadata,
+    value: Value,
+) -> Result<()> {
+    use check::public_module_type;
+    use vm::api::{push_record, Getable, Hole, OpaqueValue, Pushable, ValueRef};
+
+    let public_typ = match public_module_type(&typ, &metadata) {
+        Some(public_typ) => public_typ,
+        None => return Ok(vm.set_global(id, typ, metadata, value)?),
+    };
+    remove_private_metadata(&mut metadata);
+
+    let public_value = {
+        let data = match unsafe { Variants::new(&value) }.as_ref() {
+            ValueRef::Data(data) => data,
+            _ => ice!("Expected a record for the module `{}`", id),
+        };
+        let fields: Vec<&str> = public_typ.row_iter().map(|field| field.name.as_ref()).collect();
+        let mut context = vm.context();
+        for field in &fields {
+            let field_value = data.lookup_field(vm, field)
+                .unwrap_or_else(|| ice!("Missing field `{}` in the module `{}`", field, id));
+            <OpaqueValue<&Thread, Hole> as Getable>::from_value(vm, field_value)
+                .push(vm, &mut context)?;
+        }
+        push_record(vm, &mut context, &fields)?;
+        let public_value = context.stack.pop();
+        vm.root_value::<&Thread>(public_value)
+    };
+    Ok(vm.set_global(id, public_typ, metadata, public_value.get_value())?)
+}
+
 #[cfg(feature = "serde")]
 pub struct Precompiled<D>(pub D);
 
@@ -584,22 +737,30 @@ where
         T: Send + VmRoot<'vm>,
     {
         use vm::serialization::DeSeed;
-        use vm::internal::Global;
 
-        let Global {
-            metadata,
+        let Module {
             typ,
-            value,
-            id: _,
+            metadata,
+            module,
         } = try_future!(
             DeSeed::new(&vm)
                 .deserialize(self.0)
                 .map_err(|err| err.to_string())
         );
         let id = compiler.symbols.symbol(format!("@{}", name));
-        try_future!(vm.set_global(id, typ, metadata, value,));
-        info!("Loaded module `{}`", name);
-        FutureValue::sync(Ok(())).boxed()
+        let name = name.to_string();
+        let vm1 = vm.clone();
+        let closure = try_future!(vm.global_env().new_global_thunk(module));
+        execute(vm1, |vm| vm.call_thunk(closure))
+            .map_err(Error::from)
+            .and_then(move |(vm, value)| {
+                // The metadata holds the `@implicit` attributes of the module so it must be kept
+                // for implicit arguments to be resolved from the module's bindings
+                try_future!(set_module_global(&vm, id, typ, metadata, value));
+                info!("Loaded module `{}`", name);
+                FutureValue::sync(Ok(()))
+            })
+            .boxed()
     }
 }
 
@@ -617,20 +778,23 @@ where
     S: ::serde::Serializer,
     S::Error: 'static,
     T: Compileable<E>,
+    T::Expr: BorrowMut<SpannedExpr<Symbol>>,
 {
+    use check::metadata;
     use serde::ser::SerializeState;
     use vm::serialization::SeSeed;
     let CompileValue {
-        expr: _,
+        mut expr,
         typ,
         module,
     } = self_
         .compile(compiler, thread, file, expr_str, arg)
         .map_err(Error::from)
         .map_err(Either::Left)?;
+    let (metadata, _) = metadata::metadata(&*thread.get_env(), expr.borrow_mut());
     let module = Module {
         typ,
-        metadata: Metadata::default(),
+        metadata,
         module,
     };
     module
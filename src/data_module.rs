@@ -0,0 +1,329 @@
+//! Loading of data modules.
+//!
+//! A file which starts with `//@DATA-MODULE` may only contain a single value built from arrays,
+//! records and literals. Instead of going through macro expansion, typechecking and compilation
+//! such a file is parsed while it is read, pushing the values straight onto the stack, so very
+//! large generated modules never need to exist as source text and syntax trees at the same time.
+//! The type of the module is synthesized from the values as they are parsed.
+
+use std::io::{Bytes, Read};
+
+use base::fnv::FnvMap;
+use base::metadata::Metadata;
+use base::symbol::Symbol;
+use base::types::{ArcType, Field, Type};
+
+use vm::{Error, ExternModule, Result};
+use vm::api;
+use vm::thread::{Context, Thread};
+use vm::types::{VmIndex, VmInt};
+
+/// Attribute which marks a file as a data module when it appears at the start of the file
+pub const DATA_MODULE_ATTRIBUTE: &str = "//@DATA-MODULE";
+
+/// Parses the data module in `reader` into a value owned by `thread`
+pub fn load<R>(thread: &Thread, reader: R) -> Result<ExternModule>
+where
+    R: Read,
+{
+    let mut parser = Parser {
+        bytes: reader.bytes(),
+        peeked: None,
+        line: 1,
+        field_names: FnvMap::default(),
+        typ: None,
+    };
+    let value = api::Pushable::marshal(&mut parser, thread)?;
+    Ok(ExternModule {
+        value,
+        typ: parser.typ.expect("Type of the parsed data module"),
+        metadata: Metadata::default(),
+    })
+}
+
+struct Parser<R> {
+    bytes: Bytes<R>,
+    peeked: Option<u8>,
+    line: usize,
+    /// Field names are interned so that records with the same fields get equal types
+    field_names: FnvMap<String, Symbol>,
+    typ: Option<ArcType>,
+}
+
+impl<'a, 'vm, R> api::Pushable<'vm> for &'a mut Parser<R>
+where
+    R: Read,
+{
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        let typ = self.value(thread, context)?;
+        self.skip_whitespace()?;
+        if let Some(c) = self.peek()? {
+            return Err(self.error(format!(
+                "Unexpected `{}` after the value of the module",
+                c as char
+            )));
+        }
+        self.typ = Some(typ);
+        Ok(())
+    }
+}
+
+impl<R> Parser<R>
+where
+    R: Read,
+{
+    fn error(&self, message: String) -> Error {
+        Error::Message(format!("Data module line {}: {}", self.line, message))
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        if self.peeked.is_none() {
+            self.peeked = match self.bytes.next() {
+                Some(Ok(c)) => Some(c),
+                Some(Err(err)) => return Err(self.error(err.to_string())),
+                None => None,
+            };
+        }
+        Ok(self.peeked)
+    }
+
+    fn next(&mut self) -> Result<Option<u8>> {
+        let c = self.peek()?;
+        self.peeked = None;
+        if c == Some(b'\n') {
+            self.line += 1;
+        }
+        Ok(c)
+    }
+
+    fn next_required(&mut self) -> Result<u8> {
+        self.next()?
+            .ok_or_else(|| self.error("Unexpected end of file".into()))
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        let c = self.next_required()?;
+        if c == expected {
+            Ok(())
+        } else {
+            Err(self.error(format!(
+                "Expected `{}`, found `{}`",
+                expected as char, c as char
+            )))
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<()> {
+        while let Some(c) = self.peek()? {
+            match c {
+                b' ' | b'\t' | b'\r' | b'\n' => {
+                    self.next()?;
+                }
+                b'/' => {
+                    self.next()?;
+                    self.expect(b'/')?;
+                    while self.next()?.map_or(false, |c| c != b'\n') {}
+                }
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a value, pushing it to the stack and returning its type
+    fn value(&mut self, thread: &Thread, context: &mut Context) -> Result<ArcType> {
+        self.skip_whitespace()?;
+        match self.peek()? {
+            Some(b'[') => self.array(thread, context),
+            Some(b'{') => self.record(thread, context),
+            Some(b'"') => {
+                let s = self.string()?;
+                api::Pushable::push(s, thread, context)?;
+                Ok(Type::string())
+            }
+            Some(b'\'') => {
+                let c = self.character()?;
+                api::Pushable::push(c, thread, context)?;
+                Ok(Type::char())
+            }
+            Some(c) if c == b'-' || (c as char).is_digit(10) => self.number(thread, context),
+            Some(c) => Err(self.error(format!(
+                "Unexpected `{}`. Data modules may only contain arrays, records and literals",
+                c as char
+            ))),
+            None => Err(self.error("Unexpected end of file".into())),
+        }
+    }
+
+    fn array(&mut self, thread: &Thread, context: &mut Context) -> Result<ArcType> {
+        self.expect(b'[')?;
+        let mut len: VmIndex = 0;
+        let mut element_type: Option<ArcType> = None;
+        loop {
+            self.skip_whitespace()?;
+            if self.peek()? == Some(b']') {
+                self.next()?;
+                break;
+            }
+            let typ = self.value(thread, context)?;
+            match element_type {
+                Some(ref expected) if *expected != typ => {
+                    return Err(self.error(format!(
+                        "Array elements must all have the same type. Expected `{}`, found `{}`",
+                        expected, typ
+                    )))
+                }
+                Some(_) => (),
+                None => element_type = Some(typ),
+            }
+            len += 1;
+            self.skip_whitespace()?;
+            match self.next_required()? {
+                b',' => (),
+                b']' => break,
+                c => {
+                    return Err(self.error(format!("Expected `,` or `]`, found `{}`", c as char)))
+                }
+            }
+        }
+        let element_type = element_type.ok_or_else(|| {
+            self.error("Unable to infer the element type of an empty array".into())
+        })?;
+        api::push_array(thread, context, len)?;
+        Ok(Type::array(element_type))
+    }
+
+    fn record(&mut self, thread: &Thread, context: &mut Context) -> Result<ArcType> {
+        self.expect(b'{')?;
+        let mut fields: Vec<Field<Symbol, ArcType>> = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            if self.peek()? == Some(b'}') {
+                self.next()?;
+                break;
+            }
+            let name = self.field_name()?;
+            if fields.iter().any(|field| field.name == name) {
+                return Err(self.error(format!("Field `{}` is defined more than once", name)));
+            }
+            self.skip_whitespace()?;
+            self.expect(b'=')?;
+            let typ = self.value(thread, context)?;
+            fields.push(Field::new(name, typ));
+            self.skip_whitespace()?;
+            match self.next_required()? {
+                b',' => (),
+                b'}' => break,
+                c => {
+                    return Err(self.error(format!("Expected `,` or `}}`, found `{}`", c as char)))
+                }
+            }
+        }
+        {
+            let names: Vec<&str> = fields.iter().map(|field| field.name.as_ref()).collect();
+            api::push_record(thread, context, &names)?;
+        }
+        Ok(Type::record(vec![], fields))
+    }
+
+    fn field_name(&mut self) -> Result<Symbol> {
+        let mut name = String::new();
+        while let Some(c) = self.peek()? {
+            let c = c as char;
+            if c == '_' || c.is_ascii_alphabetic() || (!name.is_empty() && c.is_digit(10)) {
+                name.push(c);
+                self.next()?;
+            } else {
+                break;
+            }
+        }
+        if name.is_empty() {
+            return Err(self.error("Expected a field name".into()));
+        }
+        if let Some(symbol) = self.field_names.get(&name) {
+            return Ok(symbol.clone());
+        }
+        let symbol = Symbol::from(&name[..]);
+        self.field_names.insert(name, symbol.clone());
+        Ok(symbol)
+    }
+
+    fn number(&mut self, thread: &Thread, context: &mut Context) -> Result<ArcType> {
+        let mut text = String::new();
+        let mut is_float = false;
+        while let Some(c) = self.peek()? {
+            let c = c as char;
+            let continues = match c {
+                '0'...'9' | '_' => true,
+                '-' => text.is_empty() || text.ends_with('e'),
+                '.' | 'e' => {
+                    is_float = true;
+                    true
+                }
+                _ => false,
+            };
+            if !continues {
+                break;
+            }
+            if c != '_' {
+                text.push(c);
+            }
+            self.next()?;
+        }
+        if is_float {
+            let f = text.parse::<f64>()
+                .map_err(|err| self.error(format!("Invalid float `{}`: {}", text, err)))?;
+            api::Pushable::push(f, thread, context)?;
+            Ok(Type::float())
+        } else {
+            let i = text.parse::<VmInt>()
+                .map_err(|err| self.error(format!("Invalid integer `{}`: {}", text, err)))?;
+            api::Pushable::push(i, thread, context)?;
+            Ok(Type::int())
+        }
+    }
+
+    fn escape(&mut self) -> Result<char> {
+        Ok(match self.next_required()? {
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'0' => '\0',
+            b'\\' => '\\',
+            b'"' => '"',
+            b'\'' => '\'',
+            c => return Err(self.error(format!("Unknown escape sequence `\\{}`", c as char))),
+        })
+    }
+
+    /// Reads bytes until `terminator`, resolving escape sequences
+    fn quoted(&mut self, terminator: u8) -> Result<String> {
+        self.expect(terminator)?;
+        let mut bytes = Vec::new();
+        loop {
+            match self.next_required()? {
+                c if c == terminator => break,
+                b'\\' => {
+                    let c = self.escape()?;
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                c => bytes.push(c),
+            }
+        }
+        String::from_utf8(bytes).map_err(|err| self.error(err.to_string()))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.quoted(b'"')
+    }
+
+    fn character(&mut self) -> Result<char> {
+        let s = self.quoted(b'\'')?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(self.error(format!("Invalid character literal `'{}'`", s))),
+        }
+    }
+}
@@ -3,11 +3,11 @@
 use std::any::Any;
 use std::borrow::Cow;
 use std::collections::hash_map::Entry;
-use std::sync::{Mutex, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::fs::File;
 use std::mem;
 use std::io;
-use std::io::Read;
+use std::io::{BufRead, BufReader, Read};
 use std::path::PathBuf;
 
 use futures::sync::oneshot;
@@ -18,16 +18,19 @@ use itertools::Itertools;
 use base::filename_to_module;
 use base::error::{Errors, InFile};
 use base::ast::{expr_to_path, Expr, Literal, SpannedExpr, Typed, TypedIdent};
-use base::fnv::FnvMap;
+use base::fnv::{FnvMap, FnvSet};
 use base::pos::{self, BytePos, Span};
 use base::symbol::Symbol;
 use base::types::ArcType;
 
+use check::typecheck::{HelpWarning, LintLevel};
+
 use vm::{ExternLoader, ExternModule};
 use vm::macros::{Error as MacroError, Macro, MacroExpander, MacroFuture};
-use vm::thread::{Thread, ThreadInternal};
+use vm::thread::{GlobalChange, Thread, ThreadInternal};
 
 use super::Compiler;
+use data_module::{self, DATA_MODULE_ATTRIBUTE};
 
 quick_error! {
     /// Error type for the import macro
@@ -81,6 +84,7 @@ static STD_LIBS: &[(&str, &str)] = &std_libs!(
     "stream",
     "string",
     "thread",
+    "cancel",
     "test",
     "unit",
     "writer",
@@ -94,6 +98,16 @@ static STD_LIBS: &[(&str, &str)] = &std_libs!(
 #[cfg(feature = "test")]
 static STD_LIBS: &[(&str, &str)] = &std_libs!();
 
+const DEFAULT_LARGE_MODULE_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// The byte order mark which some editors insert at the start of UTF-8 files.
+///
+/// It is removed from modules before they are parsed so the byte offsets of spans in a module
+/// which started with it are `UTF8_BOM.len()` bytes less than the offsets in the file on disk
+/// (`Import::stripped_byte_order_mark` tells which modules started with it). Nothing else is normalized (`\r\n` line endings are kept), so spans are otherwise byte
+/// accurate against the file.
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
 pub trait Importer: Any + Clone + Sync + Send {
     fn import(
         &self,
@@ -143,6 +157,59 @@ impl Importer for DefaultImporter {
     }
 }
 
+/// Importer which only typechecks the modules it imports, storing their typechecked ASTs (keyed
+/// by module name) instead of running them. Useful for tools which analyze a program without
+/// executing it. Modules which fail to typecheck are stored as well, with the types which could
+/// be inferred.
+#[derive(Clone, Default)]
+pub struct CheckImporter(pub Arc<Mutex<FnvMap<String, SpannedExpr<Symbol>>>>);
+
+impl CheckImporter {
+    pub fn new() -> CheckImporter {
+        CheckImporter::default()
+    }
+}
+
+impl Importer for CheckImporter {
+    fn import(
+        &self,
+        compiler: &mut Compiler,
+        vm: &Thread,
+        _earlier_errors_exist: bool,
+        modulename: &str,
+        input: &str,
+        mut expr: SpannedExpr<Symbol>,
+    ) -> Result<(), (Option<ArcType>, MacroError)> {
+        use check::metadata;
+        use check::public_module_type;
+        use compiler_pipeline::*;
+
+        let (value, error) = MacroValue { expr: &mut expr }
+            .typecheck_partial(compiler, vm, modulename, input, None);
+        let typ = value.typ;
+        if let Some(err) = error {
+            // Modules with errors are still recorded with the types which could be inferred
+            self.0.lock().unwrap().insert(modulename.into(), expr);
+            return Err((Some(typ), err.into()));
+        }
+
+        // The module is never run so a dummy value is used to let later imports of it typecheck
+        let (mut metadata, _) = metadata::metadata(&*vm.get_env(), &mut expr);
+        let typ = match public_module_type(&typ, &metadata) {
+            Some(public_typ) => {
+                remove_private_metadata(&mut metadata);
+                public_typ
+            }
+            None => typ,
+        };
+        vm.global_env()
+            .set_dummy_global(modulename, typ, metadata)
+            .map_err(|err| (None, ::Error::from(err).into()))?;
+        self.0.lock().unwrap().insert(modulename.into(), expr);
+        Ok(())
+    }
+}
+
 enum UnloadedModule {
     Source(Cow<'static, str>),
     Extern(ExternModule),
@@ -154,9 +221,13 @@ pub struct Import<I = DefaultImporter> {
     pub paths: RwLock<Vec<PathBuf>>,
     pub loaders: RwLock<FnvMap<String, ExternLoader>>,
     pub importer: I,
+    /// Modules larger than this many bytes cause a warning to be logged when they are loaded
+    pub large_module_threshold: RwLock<u64>,
 
     /// Map of modules currently being loaded
     loading: Mutex<FnvMap<String, future::Shared<oneshot::Receiver<()>>>>,
+    /// The modules which had a byte order mark removed when they were loaded
+    stripped_byte_order_marks: RwLock<FnvSet<String>>,
 }
 
 impl<I> Import<I> {
@@ -166,7 +237,22 @@ impl<I> Import<I> {
             paths: RwLock::new(vec![PathBuf::from(".")]),
             loaders: RwLock::default(),
             importer: importer,
+            large_module_threshold: RwLock::new(DEFAULT_LARGE_MODULE_THRESHOLD),
+            loading: Mutex::default(),
+            stripped_byte_order_marks: RwLock::default(),
+        }
+    }
+
+    /// Creates a new import macro which uses `importer` but otherwise finds modules in the same
+    /// paths and extern modules as `self`
+    pub fn with_importer<J>(&self, importer: J) -> Import<J> {
+        Import {
+            paths: RwLock::new(self.paths.read().unwrap().clone()),
+            loaders: RwLock::new(self.loaders.read().unwrap().clone()),
+            importer: importer,
+            large_module_threshold: RwLock::new(*self.large_module_threshold.read().unwrap()),
             loading: Mutex::default(),
+            stripped_byte_order_marks: RwLock::default(),
         }
     }
 
@@ -179,6 +265,11 @@ impl<I> Import<I> {
         *self.paths.write().unwrap() = paths;
     }
 
+    /// Sets the size in bytes above which loading a module logs a warning
+    pub fn set_large_module_threshold(&self, threshold: u64) {
+        *self.large_module_threshold.write().unwrap() = threshold;
+    }
+
     pub fn add_loader(&self, module: &str, loader: ExternLoader) {
         self.loaders
             .write()
@@ -186,6 +277,16 @@ impl<I> Import<I> {
             .insert(String::from(module), loader);
     }
 
+    /// Returns `true` if `module` was loaded from a file which started with a byte order mark. The
+    /// byte offsets of the spans in such a module are `UTF8_BOM.len()` bytes less than the
+    /// offsets in the file.
+    pub fn stripped_byte_order_mark(&self, module: &str) -> bool {
+        self.stripped_byte_order_marks
+            .read()
+            .unwrap()
+            .contains(module)
+    }
+
     pub fn modules(&self) -> Vec<Cow<'static, str>> {
         STD_LIBS
             .iter()
@@ -230,7 +331,7 @@ impl<I> Import<I> {
                         }
                     })
                     .next();
-                let mut file = file.ok_or_else(|| {
+                let file = file.ok_or_else(|| {
                     Error::String(format!(
                         "Could not find module '{}'. Searched {}.",
                         module,
@@ -240,7 +341,33 @@ impl<I> Import<I> {
                             .format(", ")
                     ))
                 })?;
-                file.read_to_string(&mut buffer)?;
+                let threshold = *self.large_module_threshold.read().unwrap();
+                let len = file.metadata()?.len();
+                if len > threshold {
+                    warn!(
+                        "Module '{}' is {} bytes which exceeds the large module threshold of {} \
+                         bytes. Modules which only contain data can be marked with `{}` to load \
+                         them without compiling",
+                        module, len, threshold, DATA_MODULE_ATTRIBUTE
+                    );
+                }
+
+                let mut reader = BufReader::new(file);
+                if reader.fill_buf()?.starts_with(UTF8_BOM) {
+                    debug!("Removing the byte order mark from '{}'", module);
+                    reader.consume(UTF8_BOM.len());
+                    self.stripped_byte_order_marks
+                        .write()
+                        .unwrap()
+                        .insert(module.to_string());
+                }
+                if reader
+                    .fill_buf()?
+                    .starts_with(DATA_MODULE_ATTRIBUTE.as_bytes())
+                {
+                    return Ok(UnloadedModule::Extern(data_module::load(vm, reader)?));
+                }
+                reader.read_to_string(&mut buffer)?;
                 UnloadedModule::Source(Cow::Owned(buffer))
             }
         })
@@ -259,6 +386,9 @@ impl<I> Import<I> {
     {
         assert!(module_id.is_global());
         let modulename = module_id.name().definition_name();
+        vm.check_global_change(&GlobalChange::LoadModule {
+            name: modulename.into(),
+        }).map_err(|err| (None, err.into()))?;
         let mut filename = modulename.replace(".", "/");
         filename.push_str(".glu");
         {
@@ -341,10 +471,22 @@ impl<I> Import<I> {
                     .map_err(|err| (None, err.into()))?;
             }
             UnloadedModule::Source(file_contents) => {
-                // Modules marked as this would create a cyclic dependency if they included the implicit
-                // prelude
-                let implicit_prelude = !file_contents.starts_with("//@NO-IMPLICIT-PRELUDE");
+                // Modules marked as this (and a custom implicit prelude) would create a cyclic
+                // dependency if they included the implicit prelude
+                let implicit_prelude = !file_contents.starts_with("//@NO-IMPLICIT-PRELUDE")
+                    && get_state(macros)
+                        .implicit_prelude_module
+                        .as_ref()
+                        .map_or(true, |prelude| *prelude != modulename);
                 compiler.set_implicit_prelude(implicit_prelude);
+                // The standard library defines its instances with the same names as the prelude
+                // functions they implement (`let show : Show Char = ...`) and keeps bindings which
+                // are only used by some of its modules
+                if modulename.starts_with("std.") {
+                    compiler.set_shadowed_imports(LintLevel::Allow);
+                    compiler.set_unrelated_shadowed_bindings(LintLevel::Allow);
+                    compiler.set_unused_bindings(LintLevel::Allow);
+                }
 
                 let mut prev_errors = mem::replace(&mut macros.errors, Errors::new());
 
@@ -444,6 +586,35 @@ pub fn add_extern_module(thread: &Thread, name: &str, loader: ExternLoader) {
     import.add_loader(name, loader);
 }
 
+/// Marks `name` as the implicit prelude of the modules loaded by `macros` so that it is compiled
+/// without including itself
+pub(crate) fn set_implicit_prelude_module(macros: &mut MacroExpander, name: &str) {
+    get_state(macros).implicit_prelude_module = Some(name.to_string());
+}
+
+/// Makes the modules loaded by `macros` report lints the same way as `compiler`, unless an
+/// earlier compiler (the one which imported the module `compiler` is compiling) already did
+pub(crate) fn set_importing_compiler(macros: &mut MacroExpander, compiler: &Compiler) {
+    let state = get_state(macros);
+    if state.compiler.is_none() {
+        state.compiler = Some(compiler.import_compiler());
+    }
+}
+
+/// Removes and returns the warnings found in the modules loaded by `macros`
+pub(crate) fn take_warnings(macros: &mut MacroExpander) -> Vec<InFile<HelpWarning<Symbol>>> {
+    mem::replace(&mut get_state(macros).warnings, Vec::new())
+}
+
+/// Returns the compiler which compiles the module `modulename`. The standard library is always
+/// compiled with the default lint levels.
+fn module_compiler(macros: &mut MacroExpander, modulename: &str) -> Compiler {
+    match get_state(macros).compiler {
+        Some(ref compiler) if !modulename.starts_with("std.") => compiler.import_compiler(),
+        _ => Compiler::new(),
+    }
+}
+
 fn get_state<'m>(macros: &'m mut MacroExpander) -> &'m mut State {
     macros
         .state
@@ -452,6 +623,9 @@ fn get_state<'m>(macros: &'m mut MacroExpander) -> &'m mut State {
             Box::new(State {
                 visited: Vec::new(),
                 modules_with_errors: FnvMap::default(),
+                implicit_prelude_module: None,
+                compiler: None,
+                warnings: Vec::new(),
             })
         })
         .downcast_mut::<State>()
@@ -461,6 +635,11 @@ fn get_state<'m>(macros: &'m mut MacroExpander) -> &'m mut State {
 struct State {
     visited: Vec<String>,
     modules_with_errors: FnvMap<String, Expr<Symbol>>,
+    implicit_prelude_module: Option<String>,
+    /// Compiler whose lint levels are used when compiling the imported modules
+    compiler: Option<Compiler>,
+    /// Warnings found in the imported modules
+    warnings: Vec<InFile<HelpWarning<Symbol>>>,
 }
 
 impl<I> Macro for Import<I>
@@ -517,7 +696,10 @@ where
                 return Box::new(future::ok(pos::spanned(args[0].span, expr)));
             }
 
-            match self.load_module(&mut Compiler::new(), vm, macros, &name, args[0].span) {
+            let mut compiler = module_compiler(macros, &modulename);
+            let result = self.load_module(&mut compiler, vm, macros, &name, args[0].span);
+            get_state(macros).warnings.extend(compiler.take_warnings());
+            match result {
                 Ok(Some(future)) => {
                     let span = args[0].span;
                     return Box::new(
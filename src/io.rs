@@ -13,7 +13,7 @@ use vm::thread::{Thread, ThreadInternal};
 use vm::api::{self, Array, FutureResult, Generic, Getable, OpaqueValue, OwnedFunction,
               PrimitiveFuture, TypedBytecode, Userdata, VmType, WithVM, IO};
 use vm::api::generic::{A, B};
-use vm::stack::{StackFrame, State};
+use vm::stack::{StackFrame, Stacktrace, State};
 use vm::internal::ValuePrinter;
 
 use compiler_pipeline::*;
@@ -143,26 +143,32 @@ fn catch<'vm>(
     FutureResult(Box::new(future))
 }
 
+/// Unwinds the frames left on the stack by a failed evaluation and formats `err`, including a
+/// stacktrace if it was a panic
+pub(crate) fn clear_frames_message(err: Error, mut stack: StackFrame) -> String {
+    let frame_level = stack
+        .stack
+        .get_frames()
+        .iter()
+        .rposition(|frame| frame.state == State::Lock)
+        .unwrap_or(0);
+
+    let fmt = match err {
+        Error::VM(vm::Error::Panic(err, trace)) => {
+            let trace = Stacktrace {
+                thread_name: trace.thread_name,
+                ..stack.stack.stacktrace(frame_level)
+            };
+            format!("{}\n{}", err, trace)
+        }
+        _ => format!("{}", err),
+    };
+    while let Ok(_) = stack.exit_scope() {}
+    fmt
+}
+
 fn clear_frames<T>(err: Error, stack: StackFrame) -> IO<T> {
-    fn clear_frames_(err: Error, mut stack: StackFrame) -> String {
-        let frame_level = stack
-            .stack
-            .get_frames()
-            .iter()
-            .rposition(|frame| frame.state == State::Lock)
-            .unwrap_or(0);
-
-        let fmt = match err {
-            Error::VM(vm::Error::Panic(_)) => {
-                let trace = stack.stack.stacktrace(frame_level);
-                format!("{}\n{}", err, trace)
-            }
-            _ => format!("{}", err),
-        };
-        while let Ok(_) = stack.exit_scope() {}
-        fmt
-    }
-    IO::Exception(clear_frames_(err, stack))
+    IO::Exception(clear_frames_message(err, stack))
 }
 
 field_decl! { value, typ }
@@ -32,13 +32,16 @@ pub extern crate gluon_parser as parser;
 #[macro_use]
 pub extern crate gluon_vm as vm;
 
+pub mod analysis;
 pub mod compiler_pipeline;
+mod data_module;
 pub mod import;
 pub mod io;
 #[cfg(feature = "regex")]
 pub mod regex_bind;
 #[cfg(all(feature = "rand", not(target_arch = "wasm32")))]
 pub mod rand_bind;
+pub mod runtime;
 
 pub use vm::thread::{RootedThread, Thread};
 
@@ -49,6 +52,7 @@ use either::Either;
 use std::error::Error as StdError;
 use std::result::Result as StdResult;
 use std::env;
+use std::mem;
 use std::path::PathBuf;
 
 use base::filename_to_module;
@@ -59,6 +63,8 @@ use base::symbol::{Symbol, SymbolModule, Symbols};
 use base::types::{ArcType, TypeCache};
 use base::pos::{BytePos, Span, Spanned};
 
+use check::typecheck::LintLevel;
+
 use vm::Variants;
 use vm::api::{Getable, Hole, OpaqueValue, VmType};
 use vm::future::{BoxFutureValue, FutureValue};
@@ -102,6 +108,12 @@ quick_error! {
             display("{}", err)
             from()
         }
+        /// Error found when collecting the metadata of gluon code
+        Metadata(err: InFile<check::metadata::Error>) {
+            description(err.description())
+            display("{}", err)
+            from()
+        }
         Other(err: Box<StdError + Send + Sync>) {
             description(err.description())
             display("{}", err)
@@ -168,8 +180,15 @@ pub type Result<T> = StdResult<T, Error>;
 pub struct Compiler {
     symbols: Symbols,
     implicit_prelude: bool,
+    implicit_prelude_module: Option<String>,
     emit_debug_info: bool,
     run_io: bool,
+    shadowed_imports: LintLevel,
+    shadowed_bindings: LintLevel,
+    unrelated_shadowed_bindings: LintLevel,
+    unreachable_patterns: LintLevel,
+    unused_bindings: LintLevel,
+    warnings: Vec<InFile<check::typecheck::HelpWarning<Symbol>>>,
 }
 
 impl Default for Compiler {
@@ -198,8 +217,15 @@ impl Compiler {
         Compiler {
             symbols: Symbols::new(),
             implicit_prelude: true,
+            implicit_prelude_module: None,
             emit_debug_info: true,
             run_io: false,
+            shadowed_imports: LintLevel::Warn,
+            shadowed_bindings: LintLevel::Allow,
+            unrelated_shadowed_bindings: LintLevel::Warn,
+            unreachable_patterns: LintLevel::Warn,
+            unused_bindings: LintLevel::Warn,
+            warnings: Vec::new(),
         }
     }
 
@@ -209,6 +235,13 @@ impl Compiler {
         implicit_prelude set_implicit_prelude: bool
     }
 
+    option!{
+        /// Sets the module which is opened as the implicit prelude instead of `std.prelude`. The
+        /// module is loaded through `import!` and is itself compiled without an implicit prelude.
+        /// (default: None, the standard prelude)
+        implicit_prelude_module set_implicit_prelude_module: Option<String>
+    }
+
     option!{
         /// Sets whether the compiler should emit debug information such as source maps and variable
         /// names.
@@ -222,6 +255,56 @@ impl Compiler {
         run_io set_run_io: bool
     }
 
+    option!{
+        /// Sets how bindings which shadow a binding from the implicit prelude or an `import!` are
+        /// reported. Warnings are logged and do not stop compilation.
+        /// (default: LintLevel::Warn)
+        shadowed_imports set_shadowed_imports: LintLevel
+    }
+
+    option!{
+        /// Sets how bindings which shadow a local binding are reported.
+        /// (default: LintLevel::Allow)
+        shadowed_bindings set_shadowed_bindings: LintLevel
+    }
+
+    option!{
+        /// Sets how bindings which shadow a local binding with an unrelated type are reported.
+        /// (default: LintLevel::Warn)
+        unrelated_shadowed_bindings set_unrelated_shadowed_bindings: LintLevel
+    }
+
+    option!{
+        /// Sets how `match` alternatives which can never be reached are reported.
+        /// (default: LintLevel::Warn)
+        unreachable_patterns set_unreachable_patterns: LintLevel
+    }
+
+    option!{
+        /// Sets how `let` bindings which are never used are reported.
+        /// (default: LintLevel::Warn)
+        unused_bindings set_unused_bindings: LintLevel
+    }
+
+    /// Removes and returns the warnings found while typechecking the expressions compiled by this
+    /// compiler, including the warnings found in the modules they import.
+    pub fn take_warnings(&mut self) -> Vec<InFile<check::typecheck::HelpWarning<Symbol>>> {
+        mem::replace(&mut self.warnings, Vec::new())
+    }
+
+    /// Creates a compiler for the modules imported by this compiler which reports lints the same
+    /// way as this compiler
+    fn import_compiler(&self) -> Compiler {
+        Compiler {
+            shadowed_imports: self.shadowed_imports,
+            shadowed_bindings: self.shadowed_bindings,
+            unrelated_shadowed_bindings: self.unrelated_shadowed_bindings,
+            unreachable_patterns: self.unreachable_patterns,
+            unused_bindings: self.unused_bindings,
+            ..Compiler::new()
+        }
+    }
+
     pub fn mut_symbols(&mut self) -> &mut Symbols {
         &mut self.symbols
     }
@@ -465,26 +548,40 @@ impl Compiler {
         expr_str
             .run_expr(self, vm, name, expr_str, Some(&expected))
             .and_then(move |execute_value| unsafe {
-                FutureValue::sync(Ok((
-                    T::from_value(vm, Variants::new(&execute_value.value.get_value())),
-                    execute_value.typ,
-                )))
+                FutureValue::sync(
+                    T::try_from_value(vm, Variants::new(&execute_value.value.get_value()))
+                        .map(|value| (value, execute_value.typ))
+                        .map_err(Error::from),
+                )
             })
             .boxed()
     }
 
     fn include_implicit_prelude(
         &mut self,
-        type_cache: &TypeCache<Symbol, ArcType>,
+        macros: &mut macros::MacroExpander,
         name: &str,
         expr: &mut SpannedExpr<Symbol>,
-    ) {
+    ) -> Result<()> {
         use std::mem;
-        if name == "std.prelude" {
-            return;
-        }
 
-        let prelude_expr = self.parse_expr(type_cache, "", PRELUDE).unwrap();
+        let prelude = match self.implicit_prelude_module.clone() {
+            Some(module) => {
+                if name == module {
+                    return Ok(());
+                }
+                self.custom_prelude(macros, &module)?
+            }
+            None => {
+                if name == "std.prelude" {
+                    return Ok(());
+                }
+                PRELUDE.to_string()
+            }
+        };
+
+        let type_cache = macros.vm.global_env().type_cache();
+        let prelude_expr = self.parse_expr(type_cache, "", &prelude)?;
         let original_expr = mem::replace(expr, prelude_expr);
 
         // Set all spans in the prelude expression to -1 so that completion requests always
@@ -518,6 +615,40 @@ impl Compiler {
             }
         }
         assign_last_body(expr, original_expr);
+        Ok(())
+    }
+
+    /// Returns a prelude which opens every field of `module`. The module is imported first as its
+    /// fields must be known to bind them.
+    fn custom_prelude(
+        &mut self,
+        macros: &mut macros::MacroExpander,
+        module: &str,
+    ) -> Result<String> {
+        use std::fmt::Write;
+
+        import::set_implicit_prelude_module(macros, module);
+
+        let import = format!("import! {}", module);
+        let mut import_expr = self.parse_expr(macros.vm.global_env().type_cache(), "", &import)?;
+        macros.run(&mut import_expr);
+
+        let mut fields = String::new();
+        // If the module failed to load the error is reported when the prelude is expanded
+        if let Ok((_, typ)) = macros.vm.global_env().get_env().get_binding(module) {
+            for field in typ.type_field_iter() {
+                write!(fields, "{}, ", field.name.declared_name()).unwrap();
+            }
+            for field in typ.row_iter() {
+                let name = field.name.declared_name();
+                if name.contains(ast::is_operator_char) {
+                    write!(fields, "({}), ", name).unwrap();
+                } else {
+                    write!(fields, "{}, ", name).unwrap();
+                }
+            }
+        }
+        Ok(format!("let {{ {}? }} = {}\nin ()", fields, import))
     }
 }
 
@@ -593,20 +724,29 @@ impl VmBuilder {
             .sync_or_error()
             .unwrap_or_else(|err| panic!("{}", err));
 
+        // Registered eagerly so that embedders can expose typed loaders without requiring
+        // `std.runtime` to be imported first
+        runtime::LoadError::register_type(&vm).unwrap_or_else(|err| panic!("{}", err));
+
         add_extern_module(&vm, "std.prim", ::vm::primitives::load);
         add_extern_module(&vm, "std.int.prim", ::vm::primitives::load_int);
         add_extern_module(&vm, "std.float.prim", ::vm::primitives::load_float);
         add_extern_module(&vm, "std.string.prim", ::vm::primitives::load_string);
+        add_extern_module(&vm, "std.string.builder", ::vm::string_builder::load);
         add_extern_module(&vm, "std.char.prim", ::vm::primitives::load_char);
         add_extern_module(&vm, "std.array.prim", ::vm::primitives::load_array);
 
         add_extern_module(&vm, "std.lazy", ::vm::lazy::load);
         add_extern_module(&vm, "std.reference", ::vm::reference::load);
+        add_extern_module(&vm, "std.weakmap", ::vm::weakmap::load);
 
         add_extern_module(&vm, "std.channel", ::vm::channel::load_channel);
         add_extern_module(&vm, "std.thread.prim", ::vm::channel::load_thread);
         add_extern_module(&vm, "std.debug", ::vm::debug::load);
+        add_extern_module(&vm, "std.test.prim", ::vm::testing::load);
+        add_extern_module(&vm, "std.cancel.prim", ::vm::cancel::load);
         add_extern_module(&vm, "std.io.prim", ::io::load);
+        add_extern_module(&vm, "std.runtime", ::runtime::load);
 
         load_regex(&vm);
         load_random(&vm);
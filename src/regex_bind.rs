@@ -1,6 +1,11 @@
 //! Module containing bindings to the `regex` library.
+//!
+//! All positions in strings (and in patterns) are byte offsets. Matches always start and end at
+//! character boundaries so the offsets can be used to slice the string which was searched.
 
 extern crate regex;
+#[cfg(feature = "regex-syntax")]
+extern crate regex_syntax;
 
 use std::error::Error as StdError;
 
@@ -8,6 +13,7 @@ use vm::{self, ExternModule};
 use vm::api::{Userdata, VmType};
 use vm::gc::{Gc, Traverseable};
 use vm::thread::Thread;
+use vm::types::VmInt;
 
 #[derive(Debug)]
 struct Regex(regex::Regex);
@@ -23,7 +29,11 @@ impl Traverseable for Regex {
 }
 
 #[derive(Debug)]
-struct Error(regex::Error);
+struct Error {
+    error: regex::Error,
+    /// The byte offset in the pattern where the error was found, if it is a syntax error
+    position: Option<usize>,
+}
 
 impl Userdata for Error {}
 
@@ -35,10 +45,39 @@ impl Traverseable for Error {
     fn traverse(&self, _: &mut Gc) {}
 }
 
+field_decl! { start, end, text }
+
+type Match = record_type!{
+    start => VmInt,
+    end => VmInt,
+    text => String
+};
+
+/// Returns the byte offset of the syntax error in `re`, if there is one
+#[cfg(feature = "regex-syntax")]
+fn syntax_error_position(re: &str) -> Option<usize> {
+    match regex_syntax::Parser::new().parse(re) {
+        Err(regex_syntax::Error::Parse(ref err)) => Some(err.span().start.offset),
+        Err(regex_syntax::Error::Translate(ref err)) => Some(err.span().start.offset),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "regex-syntax"))]
+fn syntax_error_position(_re: &str) -> Option<usize> {
+    None
+}
+
 fn new(re: &str) -> Result<Regex, Error> {
     match regex::Regex::new(re) {
         Ok(r) => Ok(Regex(r)),
-        Err(e) => Err(Error(e)),
+        Err(error) => {
+            let position = match error {
+                regex::Error::Syntax(_) => syntax_error_position(re),
+                _ => None,
+            };
+            Err(Error { error, position })
+        }
     }
 }
 
@@ -47,9 +86,37 @@ fn is_match(re: &Regex, text: &str) -> bool {
     re.is_match(text)
 }
 
+/// Returns the leftmost-first match of `re` in `text`
+fn find(re: &Regex, text: &str) -> Option<Match> {
+    let &Regex(ref re) = re;
+    re.find(text).map(|m| {
+        record_no_decl!{
+            start => m.start() as VmInt,
+            end => m.end() as VmInt,
+            text => m.as_str().to_string()
+        }
+    })
+}
+
+/// Returns the groups captured by the leftmost-first match of `re` in `text`. The first element
+/// is the whole match and groups which did not participate in the match are `None`.
+fn captures(re: &Regex, text: &str) -> Option<Vec<Option<String>>> {
+    let &Regex(ref re) = re;
+    re.captures(text).map(|captures| {
+        captures
+            .iter()
+            .map(|group| group.map(|m| m.as_str().to_string()))
+            .collect()
+    })
+}
+
 fn error_to_string(err: &Error) -> &str {
-    let &Error(ref err) = err;
-    err.description()
+    err.error.description()
+}
+
+/// Returns the byte offset in the pattern where a syntax error was found
+fn error_position(err: &Error) -> Option<VmInt> {
+    err.position.map(|position| position as VmInt)
 }
 
 mod std {
@@ -67,7 +134,10 @@ pub fn load(vm: &Thread) -> vm::Result<ExternModule> {
         record!{
             new => primitive!(1 std::regex::new),
             is_match => primitive!(2 std::regex::is_match),
-            error_to_string => primitive!(1 std::regex::error_to_string)
+            find => primitive!(2 std::regex::find),
+            captures => primitive!(2 std::regex::captures),
+            error_to_string => primitive!(1 std::regex::error_to_string),
+            error_position => primitive!(1 std::regex::error_position)
         },
     )
 }
@@ -0,0 +1,174 @@
+//! Loading of gluon modules while a program is running.
+//!
+//! `import!` is resolved when a program is compiled which makes it unable to load modules whose
+//! names are only known at runtime (such as the files in a plugin directory). The functions in
+//! this module compile and load a module through the same `Import` machinery as `import!`, so a
+//! module loaded at runtime is cached and shared with any later `import!` of the same file.
+//!
+//! Since gluon erases types before a program runs, a primitive can't observe which type its
+//! caller expects. The expected type of a loaded module is therefore fixed by the embedder by
+//! registering `load_with_type` for a concrete rust type.
+//!
+//! ```rust
+//! #[macro_use]
+//! extern crate gluon_vm;
+//! extern crate gluon;
+//!
+//! use gluon::vm::ExternModule;
+//! use gluon::import::add_extern_module;
+//! use gluon::{new_vm, Thread};
+//!
+//! field_decl! { name }
+//!
+//! type Plugin = record_type!{ name => String };
+//!
+//! fn load_plugins(vm: &Thread) -> gluon::vm::Result<ExternModule> {
+//!     ExternModule::new(
+//!         vm,
+//!         record! {
+//!             load => primitive!(1 gluon::runtime::load_with_type::<Plugin>)
+//!         },
+//!     )
+//! }
+//!
+//! fn main() {
+//!     let vm = new_vm();
+//!     add_extern_module(&vm, "plugins", load_plugins);
+//!     // `(import! plugins).load : String -> IO (Result LoadError { name : String })`
+//! }
+//! ```
+use std::result::Result as StdResult;
+
+use base::types::ArcType;
+use vm::{ExternModule, Result};
+use vm::api::{OpaqueValue, PrimitiveFuture, VmType, WithVM, IO};
+use vm::future::FutureValue;
+use vm::stack::StackFrame;
+use vm::thread::{RootedThread, RootedValue, Thread, ThreadInternal};
+
+use compiler_pipeline::*;
+use import::Import;
+use io::clear_frames_message;
+
+use super::Compiler;
+
+marshal_variant! {
+    /// The ways in which loading a module at runtime can fail.
+    ///
+    /// * `NotFound path` - the module could not be found in any of the import paths
+    /// * `Compile msg` - the module failed to compile or to evaluate
+    /// * `TypeMismatch expected actual` - the module was loaded but does not have the expected
+    ///   type
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum LoadError {
+        NotFound(String),
+        Compile(String),
+        TypeMismatch(String, String),
+    }
+}
+
+/// Returns `true` if `path` refers to a file in any of the paths searched by `import!`
+fn module_file_exists(vm: &Thread, path: &str) -> bool {
+    let opt_macro = vm.get_macros().get("import");
+    match opt_macro
+        .as_ref()
+        .and_then(|mac| mac.downcast_ref::<Import>())
+    {
+        Some(import) => import
+            .paths
+            .read()
+            .unwrap()
+            .iter()
+            .any(|p| p.join(path).is_file()),
+        // Without the default import macro we can't tell why the module failed to load
+        None => true,
+    }
+}
+
+fn load_module(
+    vm: &Thread,
+    path: &str,
+) -> PrimitiveFuture<StdResult<(RootedValue<RootedThread>, ArcType), LoadError>> {
+    let vm = vm.root_thread();
+    let vm1 = vm.clone();
+    let path = path.to_string();
+    let expr = format!("import! {:?}", path);
+    expr.run_expr(
+        &mut Compiler::new().implicit_prelude(false),
+        vm1,
+        "<runtime.load>",
+        &expr,
+        None,
+    ).then(move |run_result| {
+        FutureValue::sync(Ok(match run_result {
+            Ok(execute_value) => Ok((execute_value.value, execute_value.typ)),
+            Err(err) => {
+                let msg = {
+                    let mut context = vm.context();
+                    let stack = StackFrame::current(&mut context.stack);
+                    clear_frames_message(err, stack)
+                };
+                if module_file_exists(&vm, &path) {
+                    Err(LoadError::Compile(msg))
+                } else {
+                    Err(LoadError::NotFound(path))
+                }
+            }
+        }))
+    })
+        .boxed()
+}
+
+/// Loads the module at `path` and returns it if its type matches `T`.
+///
+/// `path` is resolved in the same way as `import! "path"`.
+pub fn load_with_type<'vm, T>(
+    WithVM { vm, value: path }: WithVM<'vm, &str>,
+) -> PrimitiveFuture<IO<StdResult<OpaqueValue<RootedThread, T>, LoadError>>>
+where
+    T: VmType + Send + 'static,
+    T::Type: Sized,
+{
+    let expected = T::make_type(vm);
+    let vm = vm.root_thread();
+    load_module(&vm, path)
+        .map(move |result| {
+            IO::Value(result.and_then(|(value, actual)| {
+                let env = vm.global_env().get_env();
+                if ::check::check_signature(&*env, &expected, &actual) {
+                    Ok(OpaqueValue::from_value(value))
+                } else {
+                    Err(LoadError::TypeMismatch(
+                        expected.to_string(),
+                        actual.to_string(),
+                    ))
+                }
+            }))
+        })
+        .boxed()
+}
+
+/// Loads the module at `path` without returning it, making it available to later `import!`s.
+fn load_untyped<'vm>(
+    WithVM { vm, value: path }: WithVM<'vm, &str>,
+) -> PrimitiveFuture<IO<StdResult<(), LoadError>>> {
+    load_module(vm, path)
+        .map(|result| IO::Value(result.map(|_| ())))
+        .boxed()
+}
+
+mod std {
+    pub use runtime;
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    ExternModule::new(
+        vm,
+        record! {
+            load => named_primitive!(1, "std.runtime.load", std::runtime::load_untyped)
+        },
+    )
+}
+
@@ -0,0 +1,34 @@
+extern crate env_logger;
+extern crate gluon;
+
+use gluon::import::{CheckImporter, Import};
+use gluon::{new_vm, Compiler};
+
+#[test]
+fn dead_exports_across_modules() {
+    let _ = env_logger::try_init();
+
+    let vm = new_vm();
+    let importer = CheckImporter::new();
+    let import = {
+        let import = vm.get_macros().get("import").expect("Import macro");
+        import
+            .downcast_ref::<Import>()
+            .expect("Default importer")
+            .with_importer(importer.clone())
+    };
+    vm.get_macros().insert(String::from("import"), import);
+
+    Compiler::new()
+        .typecheck_str(&vm, "test", "import! tests.dead_exports.main", None)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let dead: Vec<_> = gluon::analysis::dead_exports(&importer, &["tests.dead_exports.main"])
+        .into_iter()
+        .map(|(module, field)| (module, field.declared_name().to_string()))
+        .collect();
+    assert_eq!(
+        dead,
+        vec![("tests.dead_exports.lib".to_string(), "unused".to_string())]
+    );
+}
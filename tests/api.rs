@@ -5,14 +5,26 @@ extern crate gluon;
 #[macro_use]
 extern crate gluon_vm;
 
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicUsize};
+
 use futures::{Future, IntoFuture};
 use futures::future::lazy;
 
-use gluon::base::types::Type;
-use gluon::vm::{Error, ExternModule};
-use gluon::vm::api::{FunctionRef, FutureResult, Userdata, VmType, IO};
-use gluon::vm::thread::{Root, RootStr, RootedThread, Thread, Traverseable};
-use gluon::vm::types::VmInt;
+use gluon::base::metadata::Metadata;
+use gluon::base::symbol::Symbol;
+use gluon::base::types::{ArcType, Type};
+use gluon::vm::{Error, ExternModule, GlobalTypeError, Variants};
+use gluon::vm::api::{Array, Bytes, CPrimitive, ClosurePrimitive, Data, FunctionRef, FutureResult,
+                     Getable, Hole, Lossy, OpaqueValue, OwnedFunction, Pushable, RuntimeResult,
+                     Strict, Userdata, UserdataMut, UserdataMutGuard, UserdataValue, ValueBuilder,
+                     ValueRef, VmType, WithVM, IO};
+use gluon::vm::thread::{Context, Root, RootStr, RootedThread, RootedValue, SizeReport, Status,
+                        Thread, ThreadInternal, Traverseable};
+use gluon::vm::api::generic::A;
+use gluon::vm::types::{VmIndex, VmInt};
 use gluon::Compiler;
 use gluon::import::{add_extern_module, Import};
 
@@ -55,6 +67,39 @@ fn call_function() {
     assert_eq!(result, 20.);
 }
 
+#[test]
+fn get_global_with_the_wrong_type_reports_the_global() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    load_script(&vm, "value", "1").unwrap_or_else(|err| panic!("{}", err));
+
+    let error = vm.get_global::<FunctionRef<fn(VmInt) -> VmInt>>("value")
+        .err()
+        .expect("Type error");
+    match error {
+        Error::GlobalType(GlobalTypeError::Mismatch {
+            ref name,
+            ref expected,
+            ref actual,
+            ref errors,
+        }) => {
+            assert_eq!(name, "value");
+            assert_eq!(expected.to_string(), "Int -> Int");
+            assert_eq!(*actual, Type::int());
+            assert!(!errors.is_empty());
+        }
+        _ => panic!("{}", error),
+    }
+    let error_string = error.to_string();
+    assert!(
+        error_string.starts_with(
+            "Global `value` has the type `Int` which does not match the expected type `Int -> Int`"
+        ),
+        "{}",
+        error_string
+    );
+}
+
 #[test]
 fn root_data() {
     let _ = ::env_logger::try_init();
@@ -140,6 +185,123 @@ fn array() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn bytes_checksum() {
+    let _ = ::env_logger::try_init();
+
+    let expr = r#"
+        let checksum = import! checksum
+        checksum [100b, 42b, 3b, 15b]
+    "#;
+    fn checksum(bytes: Bytes) -> i32 {
+        bytes.iter().fold(0, |acc, &b| acc ^ b as i32)
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "checksum", |thread| {
+        ExternModule::new(thread, primitive!(1 checksum))
+    });
+
+    let result = Compiler::new()
+        .run_expr::<i32>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, (100 ^ 42 ^ 3 ^ 15, Type::int()));
+}
+
+#[test]
+fn bytes_refer_to_the_value_in_the_vm() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let run = |expr| {
+        Compiler::new()
+            .implicit_prelude(false)
+            .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "test", expr)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .0
+    };
+
+    let array = run("[1b, 2b, 3b]");
+    let bytes = Bytes::from_value(&vm, array.get_variant());
+    assert_eq!(bytes, Bytes(&[1, 2, 3]));
+    match array.get_ref() {
+        ValueRef::Array(array) => {
+            assert_eq!(bytes.as_ptr(), array.as_slice::<u8>().unwrap().as_ptr())
+        }
+        _ => panic!("Expected an array"),
+    }
+
+    let string = run(r#" "abc" "#);
+    let bytes = Bytes::from_value(&vm, string.get_variant());
+    assert_eq!(bytes, Bytes(b"abc"));
+    match string.get_ref() {
+        ValueRef::String(s) => assert_eq!(bytes.as_ptr(), s.as_ptr()),
+        _ => panic!("Expected a string"),
+    }
+
+    assert!(Bytes::try_from_value(&vm, run("1").get_variant()).is_err());
+}
+
+#[test]
+fn push_bytes() {
+    let _ = ::env_logger::try_init();
+
+    let expr = r#"
+        let bytes = import! bytes
+        bytes ()
+    "#;
+    fn bytes(_: ()) -> Bytes<'static> {
+        Bytes(b"abc")
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "bytes", |thread| {
+        ExternModule::new(thread, primitive!(1 bytes))
+    });
+
+    let (result, typ) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(typ, Type::array(Type::byte()));
+    assert_eq!(Bytes::from_value(&vm, result.get_variant()), Bytes(b"abc"));
+}
+
+#[test]
+fn lossy_integers_accept_integral_floats() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let run = |expr| {
+        Compiler::new()
+            .implicit_prelude(false)
+            .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "test", expr)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .0
+    };
+
+    let float = run("3.0");
+    assert_eq!(
+        Lossy::<i32>::try_from_value(&vm, float.get_variant()).ok(),
+        Some(Lossy(3))
+    );
+    assert_eq!(Lossy::<i64>::try_from_float(-2.0).ok(), Some(Lossy(-2)));
+    assert!(Strict::<i32>::try_from_value(&vm, run("3").get_variant()).is_ok());
+
+    let err = Lossy::<i64>::try_from_value(&vm, run("3.5").get_variant()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Float `3.5` can't be converted to `i64` without loss"
+    );
+    assert!(Lossy::<i32>::try_from_value(&vm, run("100000000000000000000.0").get_variant()).is_err());
+    assert!(Lossy::<u16>::try_from_value(&vm, run("-1.0").get_variant()).is_err());
+
+    assert_eq!(
+        Lossy::<i32>::try_from_value(&vm, run("7").get_variant()).ok(),
+        Some(Lossy(7))
+    );
+    assert!(Lossy::<u16>::try_from_value(&vm, run("70000").get_variant()).is_err());
+}
+
 #[test]
 fn return_finished_future() {
     let _ = ::env_logger::try_init();
@@ -301,3 +463,1888 @@ fn tuples_start_at_0() {
         "{ _0 : Int, _1 : Float, _2 : String }"
     );
 }
+
+#[test]
+fn get_tuple_from_reordered_record() {
+    let _ = ::env_logger::try_init();
+    let thread = make_vm();
+    // The record does not unify with the tuple type since its fields are in a different order
+    // so it is retrieved without a type before being converted
+    let (value, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<&Thread, Hole>>(&thread, "<top>", r#" { _1 = "x", _0 = 1 } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let result = <(VmInt, String)>::from_value(&thread, value.get_variant());
+    assert_eq!(result, (1, "x".to_string()));
+}
+
+#[test]
+fn traverse_nested_record_without_unsafe() {
+    let _ = ::env_logger::try_init();
+    let thread = make_vm();
+    let (value, _) = Compiler::new()
+        .run_expr::<OpaqueValue<&Thread, Hole>>(
+            &thread,
+            "<top>",
+            r#" { outer = { middle = { inner = Some 3, values = [1, 2, 3] } } } "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let root = value.get_variant();
+    let middle = root.field(&thread, "outer")
+        .and_then(|outer| outer.field(&thread, "middle"))
+        .expect("middle");
+
+    let inner = middle.field(&thread, "inner").expect("inner");
+    assert_eq!(
+        inner.tag_name(&thread, "std.types.Option"),
+        Some("Some".to_string())
+    );
+    assert_eq!(inner.index(0).map(|x| x.as_ref()), Some(ValueRef::Int(3)));
+
+    let values = middle.field(&thread, "values").expect("values");
+    assert_eq!(values.index(2).map(|x| x.as_ref()), Some(ValueRef::Int(3)));
+    assert_eq!(values.index(3).map(|x| x.as_ref()), None);
+
+    assert!(root.field(&thread, "missing").is_none());
+    assert!(values.field(&thread, "inner").is_none());
+}
+
+#[test]
+fn registered_primitive_operator() {
+    let _ = ::env_logger::try_init();
+
+    #[derive(Debug)]
+    struct Decimal(VmInt);
+    impl Userdata for Decimal {}
+    impl Traverseable for Decimal {}
+    impl VmType for Decimal {
+        type Type = Decimal;
+    }
+
+    fn decimal(i: VmInt) -> Decimal {
+        Decimal(i)
+    }
+    fn decimal_add(l: Root<Decimal>, r: Root<Decimal>) -> Decimal {
+        Decimal(l.0 + r.0)
+    }
+    fn to_int(d: Root<Decimal>) -> VmInt {
+        d.0
+    }
+
+    let vm = make_vm();
+    let typ = vm.register_type::<Decimal>("Decimal", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    vm.register_primitive_operand("Decimal", typ).unwrap();
+    vm.define_global("#Decimal+", primitive!(2 decimal_add))
+        .unwrap();
+    add_extern_module(&vm, "decimal", |thread| {
+        ExternModule::new(
+            thread,
+            record! {
+                decimal => primitive!(1 decimal),
+                to_int => primitive!(1 to_int)
+            },
+        )
+    });
+
+    let expr = r#"
+        let { decimal, to_int } = import! decimal
+        to_int (decimal 1 #Decimal+ decimal 2)
+    "#;
+    let result = Compiler::new()
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, 3);
+}
+
+#[test]
+fn closure_primitive_captures_state() {
+    let _ = ::env_logger::try_init();
+
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    let vm = make_vm();
+    {
+        let counter = counter.clone();
+        vm.define_global(
+            "counter_next",
+            primitive!(1, move |step: VmInt| {
+                counter.fetch_add(step as usize, atomic::Ordering::SeqCst) as VmInt
+            }),
+        ).unwrap();
+    }
+
+    let expr = r#"
+        counter_next 1 #Int+ counter_next 2
+    "#;
+    let (result, _) = Compiler::new()
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 1);
+    assert_eq!(counter.load(atomic::Ordering::SeqCst), 3);
+}
+
+marshal_variant! {
+    #[derive(Debug, PartialEq)]
+    pub enum Shape {
+        Circle(f64),
+        Rectangle(f64, f64),
+        Empty,
+    }
+}
+
+#[test]
+fn marshal_variant() {
+    let _ = ::env_logger::try_init();
+
+    fn scale(shape: Shape) -> Shape {
+        match shape {
+            Shape::Circle(r) => Shape::Circle(2. * r),
+            Shape::Rectangle(w, h) => Shape::Rectangle(2. * w, 2. * h),
+            Shape::Empty => Shape::Empty,
+        }
+    }
+
+    let vm = make_vm();
+    Shape::register_type(&vm).unwrap_or_else(|err| panic!("{}", err));
+    add_extern_module(&vm, "shape", |thread| {
+        ExternModule::new(thread, record!(scale => primitive!(1 scale)))
+    });
+
+    let expr = r#"
+        let { scale } = import! shape
+        let size s =
+            match s with
+            | Circle r -> r
+            | Rectangle w h -> w #Float* h
+            | Empty -> 100.0
+        size (scale (Circle 1.0)) #Float+ size (scale (Rectangle 1.0 2.0)) #Float+ size Empty
+    "#;
+    let result = Compiler::new()
+        .run_expr::<f64>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, 110.);
+
+    let result = Compiler::new()
+        .run_expr::<Shape>(&vm, "rectangle", "Rectangle 1.0 2.0")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, Shape::Rectangle(1., 2.));
+}
+
+#[test]
+fn marshal_variant_without_arguments() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    Shape::register_type(&vm).unwrap_or_else(|err| panic!("{}", err));
+
+    let expr = r#"
+        let is_empty s =
+            match s with
+            | Empty -> True
+            | _ -> False
+        is_empty
+    "#;
+    load_script(&vm, "is_empty", expr).unwrap_or_else(|err| panic!("{}", err));
+    let mut is_empty: FunctionRef<fn(Shape) -> bool> = vm.get_global("is_empty").unwrap();
+    assert_eq!(is_empty.call(Shape::Empty).unwrap(), true);
+    assert_eq!(is_empty.call(Shape::Circle(1.)).unwrap(), false);
+
+    let result = Compiler::new()
+        .run_expr::<Shape>(&vm, "empty", "Empty")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, Shape::Empty);
+}
+
+#[test]
+fn marshal_variant_with_constructor_names_shared_by_another_type() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    Shape::register_type(&vm).unwrap_or_else(|err| panic!("{}", err));
+
+    // `Local` has constructors with the same names as `Shape` but with different tags
+    let local = r#"
+        type Local = | Empty | Circle Int
+        let to_int l =
+            match l with
+            | Empty -> 0
+            | Circle x -> x
+        { Local, to_int, circle = Circle 3, empty = Empty }
+    "#;
+    load_script(&vm, "local", local).unwrap_or_else(|err| panic!("{}", err));
+
+    let expr = r#"
+        let local = import! local
+        local.to_int local.circle #Int+ local.to_int local.empty
+    "#;
+    let result = Compiler::new()
+        .run_expr::<i32>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, 3);
+
+    let result = Compiler::new()
+        .run_expr::<Shape>(&vm, "empty", "Empty")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, Shape::Empty);
+
+    let result = Compiler::new()
+        .run_expr::<Shape>(&vm, "circle", "Circle 2.0")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, Shape::Circle(2.));
+}
+
+#[test]
+fn match_variant_option() {
+    let _ = ::env_logger::try_init();
+
+    fn describe(vm: &Thread, value: &OpaqueValue<RootedThread, Hole>) -> gluon_vm::Result<String> {
+        match_variant!(vm, value.get_variant(), "std.types.Option", {
+            Some(x: VmInt) => format!("Some {}", x),
+            None => "None".to_string(),
+        })
+    }
+
+    let vm = make_vm();
+    let expr = r#"
+        let { Option } = import! std.types
+        Some 1
+    "#;
+    let (some, _) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "some", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let expr = r#"
+        let { Option } = import! std.types
+        let x : Option Int = None
+        x
+    "#;
+    let (none, _) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "none", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(describe(&vm, &some).unwrap(), "Some 1");
+    assert_eq!(describe(&vm, &none).unwrap(), "None");
+
+    let result = match_variant!(&vm, none.get_variant(), "std.types.Option", {
+        Some(_x: VmInt) => false,
+        _ => true,
+    });
+    assert_eq!(result.unwrap(), true);
+}
+
+#[test]
+fn match_variant_registered_type() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    Shape::register_type(&vm).unwrap_or_else(|err| panic!("{}", err));
+
+    let (value, _) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "rectangle", "Rectangle 2.0 3.0")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let area = match_variant!(&vm, value.get_variant(), "Shape", {
+        Circle(r: f64) => 3. * r * r,
+        Rectangle(w: f64, h: f64) => w * h,
+        Empty => 0.,
+    });
+    assert_eq!(area.unwrap(), 6.);
+
+    let result = match_variant!(&vm, value.get_variant(), "Shape", {
+        Circle(_r: f64) => (),
+    });
+    assert!(result.is_err());
+
+    // `Square` is not a constructor of `Shape` so the match is rejected even if the `_` arm
+    // would have matched
+    let result = match_variant!(&vm, value.get_variant(), "Shape", {
+        Square(_w: f64) => (),
+        _ => (),
+    });
+    match result {
+        Err(Error::Message(ref msg)) if msg.contains("Square") => (),
+        _ => panic!("Expected an unknown constructor error, got {:?}", result),
+    }
+}
+
+#[test]
+fn format_nested_value() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let expr = r#"
+        let { Option } = import! std.types
+        {
+            name = "a \"quoted\" name",
+            shape = Some { x = 1, y = 2.5 },
+            values = [1, 2, 3, 4, 5],
+            inner = { empty = None, chars = ['a', 'b'] },
+        }
+    "#;
+    let (value, typ) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "value", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(
+        vm.format_value(&value.get_variant(), Some(&typ)),
+        r#"{
+    name: "a \"quoted\" name",
+    shape: Some { x: 1, y: 2.5, },
+    values: [1, 2, 3, 4, 5],
+    inner: { empty: None, chars: ['a', 'b'], },
+}"#
+    );
+
+    // Without a type the field names stored in the records are still available
+    assert_eq!(
+        format!("{:?}", value),
+        r#"{
+    name: "a \"quoted\" name",
+    shape: <tag 1> { x: 1, y: 2.5, },
+    values: [1, 2, 3, 4, 5],
+    inner: { empty: <tag 0>, chars: ['a', 'b'], },
+}"#
+    );
+}
+
+#[test]
+fn format_value_elides_long_arrays() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let (value, typ) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(
+            &vm,
+            "array",
+            "[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22]",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        vm.format_value(&value.get_variant(), Some(&typ)),
+        "[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, ..]"
+    );
+}
+
+#[derive(Debug)]
+struct Counter(VmInt);
+
+fn new_counter(_: ()) -> UserdataMut<Counter> {
+    UserdataMut::new(Counter(0))
+}
+
+fn increment(mut counter: UserdataMutGuard<Counter>) -> VmInt {
+    counter.0 += 1;
+    counter.0
+}
+
+fn make_counter_vm() -> RootedThread {
+    let vm = make_vm();
+    vm.register_type::<UserdataMut<Counter>>("Counter", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    add_extern_module(&vm, "counter", |thread| {
+        ExternModule::new(
+            thread,
+            record! {
+                new_counter => primitive!(1 new_counter),
+                increment => primitive!(1 increment)
+            },
+        )
+    });
+    vm
+}
+
+#[test]
+fn userdata_mut_persists_mutation() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_counter_vm();
+    let expr = r#"
+        let { new_counter, increment } = import! counter
+        let counter = new_counter ()
+        let _ = increment counter
+        let _ = increment counter
+        increment counter
+    "#;
+    let result = Compiler::new()
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, 3);
+}
+
+#[test]
+fn userdata_mut_errors_on_concurrent_access() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_counter_vm();
+    load_script(&vm, "shared_counter", "(import! counter).new_counter ()")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let counter: Root<UserdataMut<Counter>> = vm.get_global("shared_counter").unwrap();
+
+    let expr = r#"
+        let { increment } = import! counter
+        increment (import! shared_counter)
+    "#;
+    let thread = vm.new_thread().unwrap();
+    {
+        let _guard = counter.lock().unwrap();
+        let result = Compiler::new().run_expr::<VmInt>(&thread, "<top>", expr);
+        assert!(result.is_err(), "Expected an error, got {:?}", result);
+    }
+    let result = Compiler::new()
+        .run_expr::<VmInt>(&thread, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, 1);
+}
+
+#[derive(Debug)]
+struct Callbacks {
+    on_event: OwnedFunction<fn(VmInt) -> VmInt>,
+}
+
+impl_traverseable!(Callbacks { on_event });
+impl Userdata for Callbacks {}
+impl VmType for Callbacks {
+    type Type = Callbacks;
+}
+
+#[test]
+fn callback_in_userdata_survives_collection() {
+    let _ = ::env_logger::try_init();
+
+    fn callbacks(on_event: OwnedFunction<fn(VmInt) -> VmInt>) -> Callbacks {
+        Callbacks { on_event }
+    }
+    fn collect(thread: WithVM<()>) {
+        thread.vm.collect();
+    }
+    fn fire(callbacks: &Callbacks, x: VmInt) -> VmInt {
+        callbacks.on_event.clone().call(x).unwrap()
+    }
+
+    let vm = make_vm();
+    vm.register_type::<Callbacks>("Callbacks", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    add_extern_module(&vm, "callbacks", |thread| {
+        ExternModule::new(
+            thread,
+            record! {
+                callbacks => primitive!(1 callbacks),
+                collect => primitive!(1 collect),
+                fire => primitive!(2 fire)
+            },
+        )
+    });
+
+    let expr = r#"
+        let { callbacks, collect, fire } = import! callbacks
+        let cb =
+            let offset = 10
+            callbacks (\x -> x #Int+ offset)
+        collect ()
+        fire cb 1
+    "#;
+    let result = Compiler::new()
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, 11);
+}
+
+#[derive(Debug)]
+struct Large(Vec<u8>);
+
+impl Userdata for Large {}
+impl Traverseable for Large {}
+impl VmType for Large {
+    type Type = Large;
+}
+
+#[test]
+fn arc_userdata_is_shared_when_pushed() {
+    let _ = ::env_logger::try_init();
+
+    fn same(l: Arc<Large>, r: Arc<Large>) -> bool {
+        Arc::ptr_eq(&l, &r)
+    }
+    fn size(large: &Large) -> VmInt {
+        large.0.len() as VmInt
+    }
+
+    let vm = make_vm();
+    vm.register_type::<Large>("Large", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    add_extern_module(&vm, "large", |thread| {
+        ExternModule::new(
+            thread,
+            record! {
+                same => primitive!(2 same),
+                size => primitive!(1 size)
+            },
+        )
+    });
+    load_script(&vm, "same", "(import! large).same").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "size", "(import! large).size").unwrap_or_else(|err| panic!("{}", err));
+
+    let data = Arc::new(Large(vec![0; 1024]));
+    let _first = data.clone().marshal::<RootedThread>(&vm).unwrap();
+    let _second = data.clone().marshal::<RootedThread>(&vm).unwrap();
+    assert_eq!(Arc::strong_count(&data), 3);
+
+    let mut same: FunctionRef<fn(Arc<Large>, Arc<Large>) -> bool> =
+        vm.get_global("same").unwrap();
+    assert_eq!(same.call(data.clone(), data.clone()), Ok(true));
+    let mut size: FunctionRef<fn(Arc<Large>) -> VmInt> = vm.get_global("size").unwrap();
+    assert_eq!(size.call(data.clone()), Ok(1024));
+}
+
+#[test]
+fn int_conversions_are_checked() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let result = u64::max_value().marshal::<RootedThread>(&vm);
+    assert!(result.is_err(), "Expected an error, got {:?}", result.map(|_| ()));
+}
+
+#[test]
+fn int_too_large_for_target_type() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let result = Compiler::new().run_expr::<i32>(&vm, "<top>", "1099511627776");
+    match result {
+        Err(err) => assert!(
+            err.to_string().contains("does not fit in `i32`"),
+            "Unexpected error: {}",
+            err
+        ),
+        Ok((value, _)) => panic!("Expected an error, got {}", value),
+    }
+}
+
+#[test]
+fn int_too_large_for_extern_function_argument() {
+    let _ = ::env_logger::try_init();
+
+    fn add_one(x: i32) -> i32 {
+        x + 1
+    }
+
+    let vm = make_vm();
+    vm.define_global("add_one", primitive!(1 add_one)).unwrap();
+    let result = Compiler::new().run_expr::<i32>(&vm, "<top>", "add_one 1099511627776");
+    assert!(result.is_err(), "Expected an error, got {:?}", result.map(|(x, _)| x));
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Config {
+    name: String,
+}
+
+impl Userdata for Config {}
+impl Traverseable for Config {}
+impl VmType for Config {
+    type Type = Config;
+}
+
+#[test]
+fn userdata_value_is_an_owned_copy() {
+    let _ = ::env_logger::try_init();
+
+    fn rename(config: UserdataValue<Config>) -> UserdataValue<Config> {
+        let UserdataValue(mut config) = config;
+        config.name.push_str(" (renamed)");
+        UserdataValue(config)
+    }
+
+    let vm = make_vm();
+    vm.register_type::<Config>("Config", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    add_extern_module(&vm, "config", |thread| {
+        ExternModule::new(thread, primitive!(1 rename))
+    });
+    load_script(&vm, "rename", "import! config").unwrap_or_else(|err| panic!("{}", err));
+
+    let config = Config {
+        name: "test".to_string(),
+    };
+    let mut rename: FunctionRef<fn(UserdataValue<Config>) -> UserdataValue<Config>> =
+        vm.get_global("rename").unwrap();
+    let renamed = rename.call(UserdataValue(config.clone())).unwrap();
+    assert_eq!(
+        renamed,
+        UserdataValue(Config {
+            name: "test (renamed)".to_string(),
+        })
+    );
+}
+
+#[test]
+fn root_map_projects_into_userdata() {
+    let _ = ::env_logger::try_init();
+
+    // Shared so that the config can be deep cloned into the global
+    fn new_config(name: String) -> Arc<Config> {
+        Arc::new(Config { name })
+    }
+
+    let vm = make_vm();
+    vm.register_type::<Config>("Config", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    add_extern_module(&vm, "new_config", |thread| {
+        ExternModule::new(thread, primitive!(1 new_config))
+    });
+
+    load_script(&vm, "config", r#"(import! new_config) "test""#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let config: Root<Config> = vm.get_global("config").unwrap();
+    let name: Root<String> = Root::map(config, |config| &config.name);
+    let name2 = Root::clone(&name);
+    drop(name);
+
+    // Allocate some garbage so that the collection has something to free
+    let expr = r#"
+        let string = import! std.string
+        string.append (string.append "a" "b") "c"
+    "#;
+    for _ in 0..10 {
+        Compiler::new()
+            .run_expr::<String>(&vm, "<top>", expr)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+    vm.collect();
+
+    assert_eq!(*name2, "test");
+}
+
+#[test]
+fn opaque_value_equality_is_structural() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let _ = ::env_logger::try_init();
+
+    fn hash<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let vm = make_vm();
+    let run = |expr: &str| {
+        Compiler::new()
+            .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "<top>", expr)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .0
+    };
+    let expr = r#"
+        let string = import! std.string
+        { x = 1, inner = { name = string.append "a" "b", values = [1.0, 2.0] } }
+    "#;
+    let l = run(expr);
+    let r = run(expr);
+    let different = run(r#"{ x = 1, inner = { name = "ab", values = [1.0, 3.0] } }"#);
+
+    assert_eq!(l, r);
+    assert_eq!(hash(&l), hash(&r));
+    assert_ne!(l, different);
+
+    let set: HashSet<_> = vec![l, r, different].into_iter().collect();
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn runtime_result_panic_has_stacktrace() {
+    let _ = ::env_logger::try_init();
+
+    fn fail(x: VmInt) -> RuntimeResult<VmInt, String> {
+        RuntimeResult::Panic(format!("failed with {}", x))
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "fail", |thread| {
+        ExternModule::new(thread, primitive!(1 fail))
+    });
+
+    let expr = r#"
+        let fail = import! fail
+        let inner x = fail x #Int+ 1
+        let outer x = inner x #Int+ 1
+        outer 3
+    "#;
+    let err = Compiler::new()
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_err();
+    let (msg, stacktrace) = match err {
+        ::gluon::Error::VM(Error::Panic(msg, stacktrace)) => (msg, stacktrace),
+        err => panic!("Expected a panic, got {}", err),
+    };
+    assert_eq!(msg, "failed with 3");
+
+    let names: Vec<_> = stacktrace
+        .frames
+        .iter()
+        .filter_map(|frame| frame.as_ref())
+        .map(|frame| frame.name.declared_name().to_string())
+        .collect();
+    let position = |name: &str| {
+        names
+            .iter()
+            .position(|n| n == name)
+            .unwrap_or_else(|| panic!("`{}` not found in {:?}", name, names))
+    };
+    assert!(position("outer") < position("inner"), "{:?}", names);
+}
+
+#[test]
+fn value_builder_builds_nested_values() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let value: RootedValue<RootedThread> = {
+        let builder = ValueBuilder::new(&vm);
+        let name = builder.str("builder").unwrap();
+        let values = [builder.int(1), builder.int(2), builder.int(3)];
+        let values = builder.array(&values).unwrap();
+        let flags = [builder.str("a").unwrap(), builder.str("b").unwrap()];
+        let flags = builder.array(&flags).unwrap();
+        let nested = builder.record(&[("flags", flags)]).unwrap();
+        // Allocate garbage to check that the intermediate values are kept alive
+        vm.collect();
+        let record = builder
+            .record(&[("name", name), ("values", values), ("nested", nested)])
+            .unwrap();
+        builder.finish(record)
+    };
+
+    let expr = r#"
+        let array = import! std.array
+        let string = import! std.string
+        let check r : { name : String, values : Array Int, nested : { flags : Array String } } -> Int =
+            let flags = r.nested.flags
+            if r.name == "builder" && array.index flags 1 == "b"
+            then array.len r.values #Int+ array.index r.values 2
+            else 0
+        check
+    "#;
+    load_script(&vm, "check", expr).unwrap_or_else(|err| panic!("{}", err));
+    let mut check: FunctionRef<fn(OpaqueValue<RootedThread, Hole>) -> VmInt> =
+        vm.get_global("check").unwrap();
+    let result = check.call(OpaqueValue::from_value(value)).unwrap();
+    assert_eq!(result, 6);
+}
+
+#[test]
+fn io_exception_is_returned_from_function_call() {
+    let _ = ::env_logger::try_init();
+
+    fn checked_div(x: VmInt, y: VmInt) -> IO<VmInt> {
+        if y == 0 {
+            IO::Exception("Division by zero".to_string())
+        } else {
+            IO::Value(x / y)
+        }
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "checked_div", |thread| {
+        ExternModule::new(thread, primitive!(2 checked_div))
+    });
+    let expr = r#"
+        let checked_div = import! checked_div
+        \x y -> checked_div x y
+    "#;
+    load_script(&vm, "div", expr).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut div: FunctionRef<fn(VmInt, VmInt) -> IO<VmInt>> =
+        vm.get_global("div").unwrap();
+    assert_eq!(div.call(6, 2), Ok(IO::Value(3)));
+    assert_eq!(
+        div.call(1, 0),
+        Ok(IO::Exception("Division by zero".to_string()))
+    );
+    // The failed call must not leave anything behind on the stack
+    assert_eq!(div.call(6, 3), Ok(IO::Value(2)));
+    assert_eq!(
+        div.call_async(1, 0).wait(),
+        Ok(IO::Exception("Division by zero".to_string()))
+    );
+}
+
+#[test]
+fn function_call_arity_with_io() {
+    let _ = ::env_logger::try_init();
+
+    fn add_io(x: VmInt, y: VmInt) -> IO<VmInt> {
+        IO::Value(x + y)
+    }
+
+    fn get_add_one(_: ()) -> ClosurePrimitive<Fn(VmInt) -> IO<VmInt> + Send + Sync> {
+        primitive!(1, |x: VmInt| IO::Value(x + 1))
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "add_io", |thread| {
+        ExternModule::new(thread, primitive!(2 add_io))
+    });
+    add_extern_module(&vm, "get_add_one", |thread| {
+        ExternModule::new(thread, primitive!(1 get_add_one))
+    });
+    let expr = r#"
+        let io = import! std.io
+        let add_io = import! add_io
+        let get_add_one = import! get_add_one
+        let add x y = io.applicative.wrap (x #Int+ y)
+        let add_curried x = \y -> io.applicative.wrap (x #Int+ y)
+        {
+            pure = \x -> x #Int+ 1,
+            io_fn = \x -> io.applicative.wrap (x #Int+ 1),
+            io_value = io.applicative.wrap 1,
+            io_flat_map = io.monad.flat_map (\x -> io.applicative.wrap (x #Int+ 1)) (io.applicative.wrap 1),
+            partial_io_fn = add 1,
+            partial_curried_io_fn = add_curried 1,
+            partial_extern_io_fn = add_io 1,
+            extern_io_fn = add_io,
+            extern_returning_io_fn = get_add_one,
+        }
+    "#;
+    load_script(&vm, "arity", expr).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut pure: FunctionRef<fn(VmInt) -> VmInt> = vm.get_global("arity.pure").unwrap();
+    assert_eq!(pure.call(1), Ok(2));
+
+    let mut io_fn: FunctionRef<fn(VmInt) -> IO<VmInt>> = vm.get_global("arity.io_fn").unwrap();
+    assert_eq!(io_fn.call(1), Ok(IO::Value(2)));
+
+    let mut io_value: FunctionRef<fn() -> IO<VmInt>> = vm.get_global("arity.io_value").unwrap();
+    assert_eq!(io_value.call(), Ok(IO::Value(1)));
+
+    let mut io_flat_map: FunctionRef<fn() -> IO<VmInt>> =
+        vm.get_global("arity.io_flat_map").unwrap();
+    assert_eq!(io_flat_map.call(), Ok(IO::Value(2)));
+
+    for name in &[
+        "arity.partial_io_fn",
+        "arity.partial_curried_io_fn",
+        "arity.partial_extern_io_fn",
+    ] {
+        let mut f: FunctionRef<fn(VmInt) -> IO<VmInt>> = vm.get_global(name).unwrap();
+        assert_eq!(f.call(2), Ok(IO::Value(3)), "{}", name);
+        // Calling again must find the stack as it was left by the first call
+        assert_eq!(f.call(3), Ok(IO::Value(4)), "{}", name);
+    }
+
+    let mut extern_io_fn: FunctionRef<fn(VmInt, VmInt) -> IO<VmInt>> =
+        vm.get_global("arity.extern_io_fn").unwrap();
+    assert_eq!(extern_io_fn.call(1, 2), Ok(IO::Value(3)));
+
+    // The extern function only takes `()` so the remaining arguments (including the extra
+    // argument of `IO`) are passed to the function it returns
+    let mut extern_returning_io_fn: FunctionRef<fn((), VmInt) -> IO<VmInt>> =
+        vm.get_global("arity.extern_returning_io_fn").unwrap();
+    assert_eq!(extern_returning_io_fn.call((), 2), Ok(IO::Value(3)));
+
+    assert_eq!(vm.context().stack.len(), 0);
+}
+
+#[test]
+fn extern_function_called_with_excess_arguments() {
+    let _ = ::env_logger::try_init();
+
+    fn get_inc(_: ()) -> ClosurePrimitive<Fn(VmInt) -> VmInt + Send + Sync> {
+        primitive!(1, |x: VmInt| x + 1)
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "get_inc", |thread| {
+        ExternModule::new(thread, primitive!(1 get_inc))
+    });
+    let expr = r#"
+        let get_inc = import! get_inc
+        get_inc () 5 #Int+ 10
+    "#;
+    let result = Compiler::new()
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, 16);
+}
+
+/// An effect which (incorrectly) says that it takes the extra argument of `IO` but which pushes
+/// the action itself instead of running it
+struct UnappliedIO(OpaqueValue<RootedThread, Hole>);
+
+impl VmType for UnappliedIO {
+    type Type = IO<VmInt>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        IO::<VmInt>::make_type(vm)
+    }
+
+    fn extra_args() -> VmIndex {
+        1
+    }
+}
+
+impl<'vm> Pushable<'vm> for UnappliedIO {
+    fn push(self, vm: &'vm Thread, context: &mut Context) -> ::gluon::vm::Result<()> {
+        self.0.push(vm, context)
+    }
+}
+
+#[test]
+fn unapplied_io_action_is_run_for_compatibility() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let expr = r#"
+        let io = import! std.io
+        io.applicative.wrap 5
+    "#;
+    load_script(&vm, "action", expr).unwrap_or_else(|err| panic!("{}", err));
+    let action: OpaqueValue<RootedThread, Hole> = vm.get_global("action").unwrap();
+    vm.define_global(
+        "unapplied",
+        primitive!(1, move |_: VmInt| UnappliedIO(action.clone())),
+    ).unwrap();
+
+    let mut unapplied: FunctionRef<fn(VmInt) -> IO<VmInt>> = vm.get_global("unapplied").unwrap();
+    assert_eq!(unapplied.call(1), Ok(IO::Value(5)));
+    assert_eq!(vm.context().stack.len(), 0);
+}
+
+#[test]
+fn call_any_with_opaque_arguments() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let expr = r#"
+        let io = import! std.io
+        {
+            add = \x y -> x #Int+ y,
+            add_io = \x y -> io.applicative.wrap (x #Int+ y),
+        }
+    "#;
+    load_script(&vm, "functions", expr).unwrap_or_else(|err| panic!("{}", err));
+
+    let arg = |expr: &str| {
+        Compiler::new()
+            .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "arg", expr)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .0
+    };
+    let args = [arg("1"), arg("2")];
+
+    let add: FunctionRef<fn(VmInt, VmInt) -> VmInt> = vm.get_global("functions.add").unwrap();
+    let result = add.call_any(&args).unwrap();
+    assert_eq!(result.get_ref(), ValueRef::Int(3));
+    let result = add.call_any_async(&args).wait().unwrap();
+    assert_eq!(result.get_ref(), ValueRef::Int(3));
+
+    match add.call_any(&args[..1]) {
+        Err(Error::Message(ref msg)) if msg.contains("Expected 2 argument(s)") => (),
+        result => panic!("Expected an arity error, got {:?}", result),
+    }
+
+    // The action is run so its result is returned
+    let add_io: FunctionRef<fn(VmInt, VmInt) -> IO<VmInt>> =
+        vm.get_global("functions.add_io").unwrap();
+    let result = add_io.call_any(&args).unwrap();
+    assert_eq!(result.get_ref(), ValueRef::Int(3));
+
+    // Without a type the arity of the function value is used
+    let add_any: FunctionRef<Hole> = vm.get_global("functions.add").unwrap();
+    let result = add_any.call_any(&args).unwrap();
+    assert_eq!(result.get_ref(), ValueRef::Int(3));
+    assert!(add_any.call_any(&[]).is_err());
+
+    assert_eq!(vm.context().stack.len(), 0);
+}
+
+#[test]
+fn c_primitive_returning_io() {
+    let _ = ::env_logger::try_init();
+
+    extern "C" fn double_io(thread: &Thread) -> Status {
+        let x = {
+            let mut context = thread.context();
+            let stack = context.stack.current_frame();
+            VmInt::from_value(thread, stack.get_variant(0).unwrap())
+        };
+        Thread::push(thread, x * 2).unwrap();
+        Status::Ok
+    }
+
+    let vm = make_vm();
+    let typ = Type::function(vec![Type::int()], IO::<VmInt>::make_type(&vm));
+
+    let primitive = unsafe { CPrimitive::new(double_io, 1, "double_io") }.with_type(typ.clone());
+    assert!(Thread::push(&vm, primitive).is_err());
+
+    let primitive = unsafe { CPrimitive::new(double_io, 1, "double_io") }
+        .returns_io()
+        .with_type(typ.clone());
+    Thread::push(&vm, primitive).unwrap();
+    let value = vm.context().stack.pop();
+    vm.set_global(Symbol::from("@double_io"), typ, Metadata::default(), value)
+        .unwrap();
+
+    let expr = r#"
+        let double_io = import! double_io
+        double_io 21
+    "#;
+    let result = Compiler::new()
+        .run_io(true)
+        .run_expr::<IO<VmInt>>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, IO::Value(42));
+}
+
+/// A user defined effect which, just like `IO`, is represented by a function taking one extra
+/// argument
+#[derive(Debug, PartialEq)]
+struct Eff<T>(T);
+
+impl<T> VmType for Eff<T>
+where
+    T: VmType,
+    T::Type: Sized,
+{
+    type Type = Eff<T::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        let alias = vm.find_type_info("Eff").unwrap();
+        Type::app(alias.into_type(), vec![T::make_type(vm)].into_iter().collect())
+    }
+
+    fn extra_args() -> VmIndex {
+        1
+    }
+}
+
+impl<'vm, T: Getable<'vm>> Getable<'vm> for Eff<T> {
+    fn from_value(vm: &'vm Thread, value: Variants) -> Eff<T> {
+        Eff(T::from_value(vm, value))
+    }
+}
+
+impl<'vm, T: Pushable<'vm>> Pushable<'vm> for Eff<T> {
+    fn push(self, vm: &'vm Thread, context: &mut Context) -> ::gluon::vm::Result<()> {
+        self.0.push(vm, context)
+    }
+}
+
+#[test]
+fn user_defined_effect_with_extra_args() {
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+    static RUNS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    fn eff_double(x: VmInt) -> Eff<VmInt> {
+        RUNS.fetch_add(1, Ordering::SeqCst);
+        Eff(x * 2)
+    }
+
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    vm.register_type::<Eff<A>>("Eff", &["a"]).unwrap();
+    add_extern_module(&vm, "eff_double", |thread| {
+        ExternModule::new(thread, primitive!(1 eff_double))
+    });
+
+    let expr = r#"
+        let eff_double = import! eff_double
+        let action : Eff Int = eff_double 1
+        \x -> eff_double x
+    "#;
+    load_script(&vm, "eff", expr).unwrap_or_else(|err| panic!("{}", err));
+    // Creating the action does not run it
+    assert_eq!(RUNS.load(Ordering::SeqCst), 0);
+
+    let mut eff: FunctionRef<fn(VmInt) -> Eff<VmInt>> = vm.get_global("eff").unwrap();
+    assert_eq!(eff.call(21), Ok(Eff(42)));
+    assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn char_has_a_distinct_representation() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let (value, _) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, char>>(&vm, "test", "'a'")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(value.get_ref(), ValueRef::Char('a'));
+
+    let (value, _) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, VmInt>>(&vm, "test", "97")
+        .unwrap_or_else(|err| panic!("{}", err));
+    match value.get_ref() {
+        ValueRef::Int(97) => (),
+        value => panic!("Expected an Int, got {:?}", value),
+    }
+
+    let (mut last, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(Vec<char>) -> char>>(
+            &vm,
+            "last",
+            "let array = import! std.array in \\x -> array.index x (array.len x #Int- 1)",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(last.call(vec!['a', 'ö', '\u{10FFFF}']), Ok('\u{10FFFF}'));
+}
+
+#[test]
+fn char_accepts_the_legacy_int_representation() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let value: RootedValue<RootedThread> = {
+        let builder = ValueBuilder::new(&vm);
+        let c = builder.int('a' as VmInt);
+        builder.finish(c)
+    };
+    assert_eq!(value.get_variant().as_ref(), ValueRef::Char('a'));
+    assert_eq!(char::from_value(&vm, value.get_variant()), 'a');
+
+    let (mut is_a, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(OpaqueValue<RootedThread, char>) -> bool>>(
+            &vm,
+            "is_a",
+            "let is_a c : Char -> Bool = c #Char== 'a' in is_a",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(is_a.call(OpaqueValue::from_value(value)), Ok(true));
+}
+
+#[test]
+fn userdata_finalizer_runs_at_run_deferred() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use gluon::vm::deferred::DeferredCall;
+
+    let _ = ::env_logger::try_init();
+
+    static FINALIZED: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug)]
+    struct Finalized(Option<OwnedFunction<fn(()) -> ()>>);
+    impl Userdata for Finalized {
+        fn finalizer(&mut self) -> Option<DeferredCall> {
+            self.0.take().map(|finalizer| DeferredCall::new(finalizer, ()))
+        }
+    }
+    impl Traverseable for Finalized {}
+    impl VmType for Finalized {
+        type Type = Finalized;
+    }
+
+    fn finalized(finalizer: OwnedFunction<fn(()) -> ()>) -> Finalized {
+        Finalized(Some(finalizer))
+    }
+    fn record_finalized(_: ()) {
+        FINALIZED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let vm = make_vm();
+    vm.register_type::<Finalized>("Finalized", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    add_extern_module(&vm, "finalized", |thread| {
+        ExternModule::new(
+            thread,
+            record! {
+                finalized => primitive!(1 finalized),
+                record_finalized => primitive!(1 record_finalized)
+            },
+        )
+    });
+
+    let expr = r#"
+        let { finalized, record_finalized } = import! finalized
+        let _ = finalized (\_ -> record_finalized ())
+        ()
+    "#;
+    Compiler::new()
+        .run_expr::<()>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    vm.collect();
+    assert_eq!(FINALIZED.load(Ordering::SeqCst), 0);
+
+    vm.run_deferred().unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(FINALIZED.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn defer_call_during_panic_does_not_abort() {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let _ = ::env_logger::try_init();
+
+    static CALLED: AtomicUsize = AtomicUsize::new(0);
+
+    fn record_call(_: ()) {
+        CALLED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    struct Guard(RootedThread, Option<OwnedFunction<fn(()) -> ()>>);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            if let Some(function) = self.1.take() {
+                self.0.defer_call(function, ());
+            }
+            // Not a safe point while unwinding so nothing may run here
+            self.0.run_deferred().unwrap();
+        }
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "record_call", |thread| {
+        ExternModule::new(thread, primitive!(1 record_call))
+    });
+    let (function, _) = Compiler::new()
+        .run_expr::<OwnedFunction<fn(()) -> ()>>(
+            &vm,
+            "test",
+            r#" let record_call = import! record_call in \_ -> record_call () "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _guard = Guard(vm.clone(), Some(function));
+        panic!("Unwinding with a deferred call")
+    }));
+    assert!(result.is_err());
+    assert_eq!(CALLED.load(Ordering::SeqCst), 0);
+
+    vm.run_deferred().unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(CALLED.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn array_get_out_of_bounds_and_negative_index() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let (array, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<Array<i32>>(&vm, "test", "[1, 2, 3]")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(array.len(), 3);
+    assert_eq!(array.get(1), Some(2));
+    assert_eq!(array.get(3), None);
+    assert_eq!(array.get(-1), None);
+    assert_eq!(array.get_checked(2).unwrap(), Some(3));
+    assert_eq!(array.get_checked(3).unwrap(), None);
+}
+
+#[test]
+fn array_get_on_value_which_is_not_an_array() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let (value, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<&Thread, Hole>>(&vm, "test", "123")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let array = Array::<i32>::from_value(&vm, value.get_variant());
+    assert_eq!(array.len(), 0);
+    assert_eq!(array.get(0), None);
+    assert!(array.get_checked(0).is_err());
+}
+
+#[test]
+fn opaque_record_field_into_function() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let (record, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(
+            &vm,
+            "test",
+            r#" { callback = \x -> x #Int+ 1, value = 10 } "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let field = |name| match record.get_ref() {
+        ValueRef::Data(data) => {
+            let variant = data.lookup_field(&vm, name).unwrap();
+            <OpaqueValue<RootedThread, Hole> as Getable>::from_value(&vm, variant)
+        }
+        _ => panic!("Expected a record"),
+    };
+
+    let callback = field("callback");
+    assert!(callback.as_function::<fn(i32, i32) -> i32>().is_none());
+    let mut callback: OwnedFunction<fn(i32) -> i32> = callback
+        .into_function()
+        .unwrap_or_else(|value| panic!("Expected a function, found {:?}", value));
+    assert_eq!(callback.call(1), Ok(2));
+
+    let opaque = callback.into_opaque();
+    assert!(opaque.as_function::<fn(i32) -> i32>().is_some());
+}
+
+#[test]
+fn opaque_value_which_is_not_a_function_is_returned_unchanged() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let (value, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "test", "123")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let value = value
+        .into_function::<fn(i32) -> i32>()
+        .expect_err("Expected an `Int` to not be converted to a function");
+    assert_eq!(value.get_ref(), ValueRef::Int(123));
+}
+
+#[test]
+fn field_token_is_reused_across_records() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let expr = r#"
+        let array = import! std.array
+        let make n : Int -> Array { x : Int, y : String } =
+            if n #Int== 1000 then
+                []
+            else
+                array.append [{ x = n, y = "" }] (make (n #Int+ 1))
+        make 0
+    "#;
+    let (records, _) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let records = match records.get_ref() {
+        ValueRef::Array(array) => array,
+        _ => panic!("Expected an array"),
+    };
+    let data = |index| match records.get(index).unwrap().as_ref() {
+        ValueRef::Data(data) => data,
+        _ => panic!("Expected a record"),
+    };
+
+    // Resolve the name once, after that every lookup only indexes into the record
+    let token = data(0).field_token(&vm, "x").unwrap();
+    let sum: VmInt = (0..records.len())
+        .map(|i| match data(i).get_by_token(&token).unwrap().as_ref() {
+            ValueRef::Int(x) => x,
+            _ => panic!("Expected an Int"),
+        })
+        .sum();
+    assert_eq!(sum, (0..1000).sum());
+    assert!(data(0).field_token(&vm, "z").is_none());
+}
+
+#[test]
+fn field_token_on_record_with_different_fields() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let run = |expr| {
+        Compiler::new()
+            .implicit_prelude(false)
+            .run_expr::<OpaqueValue<RootedThread, Hole>>(&vm, "test", expr)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .0
+    };
+    fn data<'a>(value: &'a OpaqueValue<RootedThread, Hole>) -> Data<'a> {
+        match value.get_ref() {
+            ValueRef::Data(data) => data,
+            _ => panic!("Expected a record"),
+        }
+    }
+    let first = run("{ x = 1, y = 2 }");
+    let second = run("{ a = \"\", y = 3, x = 4 }");
+    let third = run("{ y = 5 }");
+
+    let token = data(&first).field_token(&vm, "x").unwrap();
+    assert_eq!(
+        data(&second).get_by_token(&token).map(|x| x.as_ref()),
+        Some(ValueRef::Int(4))
+    );
+    assert_eq!(data(&third).get_by_token(&token), None);
+}
+
+
+#[test]
+fn weak_map_entries_are_cleared_when_keys_are_collected() {
+    let _ = ::env_logger::try_init();
+
+    fn collect(thread: WithVM<()>) {
+        thread.vm.collect();
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "collect", |thread| {
+        ExternModule::new(thread, primitive!(1 collect))
+    });
+
+    let expr = r#"
+        let { new, insert, get } = import! std.weakmap
+        let collect = import! collect
+        let map = new ()
+        let insert_temporary x = insert map [x] { value = x }
+        insert_temporary 1
+        let live = [2]
+        insert map live { value = 2 }
+        collect ()
+        let value opt =
+            match opt with
+            | Some r -> Some r.value
+            | None -> None
+        (value (get map [1]), value (get map live))
+    "#;
+    let result = Compiler::new()
+        .run_expr::<(Option<VmInt>, Option<VmInt>)>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.0, (None, Some(2)));
+}
+
+#[test]
+fn resuming_a_dead_named_thread_reports_its_name() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let expr = r#"
+        let thread = import! std.thread
+        let { Result } = import! std.result
+        let worker = thread.spawn_named "worker" (\_ -> ())
+        match thread.resume worker with
+        | Ok _ -> thread.resume worker
+        | Err err -> error err
+    "#;
+    let result = Compiler::new()
+        .run_expr::<Result<(), String>>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        result.0,
+        Err("Attempted to resume the dead thread `worker`".to_string())
+    );
+}
+
+#[test]
+fn thread_names() {
+    let _ = ::env_logger::try_init();
+
+    fn fail(_: ()) -> RuntimeResult<VmInt, String> {
+        RuntimeResult::Panic("failed".to_string())
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "fail", |thread| {
+        ExternModule::new(thread, primitive!(1 fail))
+    });
+
+    let thread = vm.new_thread().unwrap();
+    assert!(thread.name().starts_with("thread-"), "{}", thread.name());
+    assert_ne!(thread.name(), vm.name());
+
+    thread.set_name("worker".to_string());
+    assert_eq!(&*thread.name(), "worker");
+    assert!(format!("{:?}", *thread).contains("worker"));
+
+    let err = Compiler::new()
+        .run_expr::<VmInt>(&thread, "<top>", "(import! fail) ()")
+        .unwrap_err();
+    match err {
+        ::gluon::Error::VM(Error::Panic(_, stacktrace)) => {
+            assert_eq!(
+                stacktrace.thread_name.as_ref().map(|name| &**name),
+                Some("worker")
+            );
+            assert!(
+                stacktrace.to_string().starts_with("Stacktrace of thread `worker`"),
+                "{}",
+                stacktrace
+            );
+        }
+        err => panic!("Expected a panic, got {}", err),
+    }
+}
+
+fn value_size(vm: &Thread, expr: &str) -> SizeReport {
+    let (value, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<&Thread, Hole>>(vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    vm.value_size(&value)
+}
+
+#[test]
+fn value_size_of_string() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let short = value_size(&vm, r#" "abc" "#);
+    let long = value_size(&vm, &format!("{:?}", "a".repeat(1000)));
+    assert_eq!(short.nodes, 1);
+    assert_eq!(short.shallow, short.deep);
+    assert_eq!(long.deep - short.deep, 1000 - 3);
+}
+
+#[test]
+fn value_size_of_unboxed_and_boxed_arrays() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let ints = (0..1000).map(|i| i.to_string()).collect::<Vec<_>>();
+    let unboxed = value_size(&vm, &format!("[{}]", ints.join(", ")));
+    let records = ints.iter()
+        .map(|i| format!("{{ x = {} }}", i))
+        .collect::<Vec<_>>();
+    let boxed = value_size(&vm, &format!("[{}]", records.join(", ")));
+
+    assert_eq!(unboxed.nodes, 1);
+    assert_eq!(unboxed.shallow, unboxed.deep);
+    assert!(unboxed.deep >= 1000 * 8, "{:?}", unboxed);
+
+    assert_eq!(boxed.nodes, 1001);
+    assert!(boxed.shallow > unboxed.shallow, "{:?} {:?}", boxed, unboxed);
+    assert!(boxed.deep > boxed.shallow + 1000 * 8, "{:?}", boxed);
+}
+
+#[test]
+fn value_size_counts_shared_values_once() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let big = format!("{:?}", "a".repeat(10000));
+    let other = format!("{:?}", "b".repeat(10000));
+    let shared = value_size(&vm, &format!("let s = {} in {{ a = s, b = s }}", big));
+    let distinct = value_size(
+        &vm,
+        &format!("let s = {} in {{ a = s, b = {} }}", big, other),
+    );
+
+    assert_eq!(shared.nodes, 2);
+    assert_eq!(distinct.nodes, 3);
+    assert!(shared.deep > 10000, "{:?}", shared);
+    assert!(distinct.deep - shared.deep >= 10000, "{:?} {:?}", shared, distinct);
+}
+
+#[test]
+fn debug_size_of_returns_the_deep_size() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let expr = r#"
+        let { size_of } = import! std.debug
+        size_of "abc"
+    "#;
+    let (size, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(size as usize, value_size(&vm, r#" "abc" "#).deep);
+}
+
+/// Creates a vm which can import `my_prelude` from a temporary directory
+fn make_vm_with_prelude(prelude: &str) -> RootedThread {
+    let dir = ::std::env::temp_dir().join(format!(
+        "gluon_implicit_prelude_{}",
+        prelude.len()
+    ));
+    ::std::fs::create_dir_all(&dir).unwrap();
+    ::std::fs::File::create(dir.join("my_prelude.glu"))
+        .and_then(|mut file| file.write_all(prelude.as_bytes()))
+        .unwrap();
+
+    let vm = make_vm();
+    vm.get_macros()
+        .get("import")
+        .as_ref()
+        .and_then(|import| import.downcast_ref::<Import>())
+        .expect("Import macro")
+        .add_path(dir);
+    vm
+}
+
+#[test]
+fn custom_implicit_prelude_is_opened_in_scripts() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm_with_prelude(
+        r#"
+        let double x : Int -> Int = x #Int+ x
+        { double }
+        "#,
+    );
+
+    let (result, _) = Compiler::new()
+        .implicit_prelude_module(Some("my_prelude".to_string()))
+        .run_expr::<VmInt>(&vm, "<top>", "double 21")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn custom_implicit_prelude_replaces_the_standard_prelude() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm_with_prelude(
+        r#"
+        let answer : Int = 42
+        { answer }
+        "#,
+    );
+
+    let result = Compiler::new()
+        .implicit_prelude_module(Some("my_prelude".to_string()))
+        .run_expr::<VmInt>(&vm, "<top>", "answer + 1");
+    assert!(result.is_err());
+
+    let (result, _) = Compiler::new()
+        .implicit_prelude_module(Some("my_prelude".to_string()))
+        .run_expr::<VmInt>(&vm, "<top>", "answer")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn custom_implicit_prelude_which_can_not_be_parsed_is_an_error() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let result = Compiler::new()
+        .implicit_prelude_module(Some("my_prelude)".to_string()))
+        .run_expr::<VmInt>(&vm, "<top>", "1");
+    assert!(result.is_err());
+
+    let (result, _) = Compiler::new()
+        .implicit_prelude_module(Some("my_prelude)".to_string()))
+        .implicit_prelude_module(None)
+        .run_expr::<VmInt>(&vm, "<top>", "1 + 1")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 2);
+}
+
+#[derive(Debug)]
+struct Plugin(VmInt);
+impl Userdata for Plugin {}
+impl Traverseable for Plugin {}
+impl VmType for Plugin {
+    type Type = Plugin;
+}
+
+#[test]
+fn register_type_again_returns_the_registered_type() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    assert_eq!(vm.lookup_registered_type::<Plugin>(), None);
+    let first = vm.register_type::<Plugin>("lib.Plugin", &[])
+        .unwrap_or_else(|err| panic!("{}", err));
+    let second = vm.register_type::<Plugin>("lib.Plugin", &[])
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(first, second);
+    assert_eq!(vm.lookup_registered_type::<Plugin>(), Some(first));
+}
+
+#[test]
+fn register_type_with_conflicting_parameters_lists_both() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.register_type::<Plugin>("lib.Plugin", &["a"])
+        .unwrap_or_else(|err| panic!("{}", err));
+    match vm.register_type::<Plugin>("lib.Plugin", &["a", "b"]) {
+        Err(err @ Error::ConflictingTypeRegistration(..)) => {
+            let message = err.to_string();
+            assert!(message.contains("[a]"), "{}", message);
+            assert!(message.contains("[a, b]"), "{}", message);
+        }
+        result => panic!("Expected a conflicting registration, found {:?}", result),
+    }
+}
+
+#[test]
+fn register_type_with_same_name_as_different_rust_type_errors() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.register_type::<Plugin>("lib.Plugin", &[])
+        .unwrap_or_else(|err| panic!("{}", err));
+    match vm.register_type::<Callbacks>("lib.Plugin", &[]) {
+        Err(Error::ConflictingTypeRegistration(_, _, _, false)) => (),
+        result => panic!("Expected a conflicting registration, found {:?}", result),
+    }
+}
+
+#[test]
+fn userdata_of_type_registered_twice_can_be_extracted() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    fn make_plugin(value: VmInt) -> Plugin {
+        Plugin(value)
+    }
+    fn plugin_value(plugin: &Plugin) -> VmInt {
+        plugin.0
+    }
+    for _ in 0..2 {
+        vm.register_type::<Plugin>("lib.Plugin", &[])
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+    add_extern_module(&vm, "plugin", |thread| {
+        ExternModule::new(
+            thread,
+            record!{
+                make_plugin => primitive!(1 make_plugin),
+                plugin_value => primitive!(1 plugin_value)
+            },
+        )
+    });
+
+    let expr = r#"
+        let { make_plugin, plugin_value } = import! plugin
+        plugin_value (make_plugin 42)
+    "#;
+    let (result, _) = Compiler::new()
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn record_field_names_are_shared_between_modules() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let fields = (0..20)
+        .map(|i| format!("field{} = {}", i, i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let load_module = |i: usize| {
+        load_script(&vm, &format!("fields{}", i), &format!("{{ {} }}", fields))
+            .unwrap_or_else(|err| panic!("{}", err));
+    };
+
+    load_module(0);
+    let interned_records = vm.global_env().interned_records();
+    for i in 1..100 {
+        load_module(i);
+    }
+    assert_eq!(vm.global_env().interned_records(), interned_records);
+
+    let expr = r#"
+        let { field3 } = import! fields0
+        let { field19 } = import! fields99
+        field3 #Int+ field19
+    "#;
+    let (result, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 22);
+}
+
+#[test]
+fn update_record_created_by_rust() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    fn scale(x: VmInt) -> VmInt {
+        x * 10
+    }
+    fn offset(x: VmInt) -> VmInt {
+        x + 10
+    }
+    add_extern_module(&vm, "ops", |thread| {
+        ExternModule::new(
+            thread,
+            record! {
+                scale => primitive!(1 scale),
+                offset => primitive!(1 offset)
+            },
+        )
+    });
+
+    let expr = r#"
+        let ops = import! ops
+        let updated = { scale = \x -> x #Int* 2, offset = ops.offset }
+        updated.scale 3 #Int+ updated.offset 1 #Int+ ops.scale 1
+    "#;
+    let (result, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 27);
+}
+
+#[test]
+fn unused_bindings_are_returned_as_warnings() {
+    use gluon::check::typecheck::Warning;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let expr = r#"
+        let unused = 1
+        let used = 2
+        used
+    "#;
+    let mut compiler = Compiler::new().implicit_prelude(false);
+    let (result, _) = compiler
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 2);
+
+    let warnings: Vec<_> = compiler
+        .take_warnings()
+        .into_iter()
+        .flat_map(|warnings| warnings.errors())
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    match warnings[0].value.error {
+        Warning::UnusedBinding(ref id) => assert_eq!(id.declared_name(), "unused"),
+        ref err => panic!("Unexpected warning: {}", err),
+    }
+    assert_eq!(warnings[0].span.start.line.to_usize(), 1);
+    assert!(compiler.take_warnings().is_empty());
+}
+
+#[test]
+fn warnings_in_imported_modules_are_returned_with_the_importers_lint_levels() {
+    use gluon::check::typecheck::{LintLevel, Warning};
+
+    let _ = ::env_logger::try_init();
+    let expr = r#"
+        let { used } = import! my_prelude
+        used
+    "#;
+
+    let vm = make_vm_with_prelude("//@NO-IMPLICIT-PRELUDE\nlet unused = 1\n{ used = 2 }\n");
+    let mut compiler = Compiler::new().implicit_prelude(false);
+    compiler
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let warnings = compiler.take_warnings();
+    assert_eq!(warnings.len(), 1);
+    let rendered = warnings[0].to_string();
+    assert!(rendered.contains("my_prelude:Line: 2"), "{}", rendered);
+    let warnings: Vec<_> = warnings
+        .into_iter()
+        .flat_map(|warnings| warnings.errors())
+        .collect();
+    match warnings[0].value.error {
+        Warning::UnusedBinding(ref id) => assert_eq!(id.declared_name(), "unused"),
+        ref err => panic!("Unexpected warning: {}", err),
+    }
+
+    let vm = make_vm_with_prelude("//@NO-IMPLICIT-PRELUDE\nlet unused = 1\n{ used = 3 }\n\n");
+    let mut compiler = Compiler::new()
+        .implicit_prelude(false)
+        .unused_bindings(LintLevel::Allow);
+    compiler
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(compiler.take_warnings().is_empty());
+}
@@ -0,0 +1,173 @@
+#![cfg(feature = "serialization")]
+extern crate gluon;
+extern crate log;
+#[macro_use]
+extern crate serde_derive;
+
+use std::fmt::Write as FmtWrite;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+
+use gluon::base::types::{ArcType, Field, Type};
+use gluon::base::symbol::Symbol;
+use gluon::import::Import;
+use gluon::vm::api::de::De;
+use gluon::vm::api::VmType;
+use gluon::vm::thread::{RootedThread, Thread};
+use gluon::vm::types::VmInt;
+use gluon::Compiler;
+
+static LARGE_MODULE_WARNED: AtomicBool = ATOMIC_BOOL_INIT;
+
+struct WarningLogger;
+
+impl log::Log for WarningLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.args().to_string().contains("large module threshold") {
+            LARGE_MODULE_WARNED.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: WarningLogger = WarningLogger;
+
+fn make_vm(dir: &PathBuf) -> RootedThread {
+    let vm = ::gluon::new_vm();
+    with_import(&vm, |import| import.add_path(dir.clone()));
+    vm
+}
+
+fn with_import<F>(vm: &Thread, f: F)
+where
+    F: FnOnce(&Import),
+{
+    let import = vm.get_macros().get("import");
+    f(import
+        .as_ref()
+        .and_then(|import| import.downcast_ref::<Import>())
+        .expect("Import macro"))
+}
+
+fn module_dir(name: &str) -> PathBuf {
+    let dir = ::std::env::temp_dir().join(format!("gluon_data_module_{}", name));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_module(dir: &PathBuf, name: &str, contents: &str) {
+    let mut file = File::create(dir.join(format!("{}.glu", name))).unwrap();
+    file.write_all(contents.as_bytes()).unwrap();
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Entry {
+    id: VmInt,
+    name: String,
+    weight: f64,
+    initial: char,
+    values: Vec<VmInt>,
+}
+
+impl VmType for Entry {
+    type Type = Self;
+
+    fn make_type(thread: &Thread) -> ArcType {
+        Type::record(
+            vec![],
+            vec![
+                Field::new(Symbol::from("id"), VmInt::make_type(thread)),
+                Field::new(Symbol::from("name"), String::make_type(thread)),
+                Field::new(Symbol::from("weight"), f64::make_type(thread)),
+                Field::new(Symbol::from("initial"), char::make_type(thread)),
+                Field::new(Symbol::from("values"), Vec::<VmInt>::make_type(thread)),
+            ],
+        )
+    }
+}
+
+fn entries(count: usize) -> String {
+    let mut text = String::from("[\n");
+    for i in 0..count {
+        writeln!(
+            text,
+            r#"    {{ id = {}, name = "item \"{}\"", weight = {}.5, initial = 'é', values = [{}, -{}] }},"#,
+            i, i, i, i, i
+        ).unwrap();
+    }
+    text.push_str("]\n");
+    text
+}
+
+#[test]
+fn data_module_equals_compiled_module() {
+    let dir = module_dir("equals");
+    write_module(
+        &dir,
+        "data_large",
+        &format!("//@DATA-MODULE\n// Generated\n{}", entries(10_000)),
+    );
+    write_module(&dir, "data_sample", &format!("//@DATA-MODULE\n{}", entries(20)));
+    write_module(&dir, "compiled_sample", &entries(20));
+
+    let vm = make_vm(&dir);
+    let (De(large), _) = Compiler::new()
+        .run_expr::<De<Vec<Entry>>>(&vm, "large", "import! data_large")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let (De(sample), _) = Compiler::new()
+        .run_expr::<De<Vec<Entry>>>(&vm, "sample", "import! data_sample")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let (De(expected), _) = Compiler::new()
+        .run_expr::<De<Vec<Entry>>>(&vm, "expected", "import! compiled_sample")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(large.len(), 10_000);
+    assert_eq!(sample, expected);
+    assert_eq!(large[..20], expected[..]);
+}
+
+#[test]
+fn data_module_fields_are_accessible() {
+    let dir = module_dir("fields");
+    write_module(
+        &dir,
+        "data_record",
+        "//@DATA-MODULE\n{ name = \"test\", numbers = [1, 2, 3] }\n",
+    );
+
+    let vm = make_vm(&dir);
+    let text = r#"
+        let array = import! std.array
+        let { name, numbers } = import! data_record
+        array.len numbers + array.index numbers 2
+    "#;
+    let (result, _) = Compiler::new()
+        .run_expr::<VmInt>(&vm, "test", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 6);
+}
+
+#[test]
+fn large_module_threshold_warns() {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(log::LevelFilter::Warn);
+
+    let dir = module_dir("threshold");
+    write_module(&dir, "data_threshold", "//@DATA-MODULE\n[1, 2, 3, 4, 5, 6, 7, 8]\n");
+
+    let vm = make_vm(&dir);
+    with_import(&vm, |import| import.set_large_module_threshold(16));
+    let (De(values), _) = Compiler::new()
+        .run_expr::<De<Vec<VmInt>>>(&vm, "test", "import! data_threshold")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(values, [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert!(LARGE_MODULE_WARNED.load(Ordering::SeqCst));
+}
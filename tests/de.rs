@@ -1,16 +1,27 @@
 #![cfg(feature = "serialization")]
 extern crate env_logger;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
 
 extern crate gluon;
+#[macro_use]
+extern crate gluon_vm;
 
 use gluon::base::types::{ArcType, Field, Type};
 use gluon::base::symbol::Symbol;
-use gluon::vm::api::{Getable, Hole, OpaqueValue, VmType};
+use gluon::vm::ExternModule;
+use gluon::vm::api::{Array, FunctionRef, Getable, Hole, OpaqueValue, SerdeGet, SerdePush, VmType};
 use gluon::vm::api::de::{self, De};
+use gluon::vm::api::ser::SerializeValue;
+use gluon::vm::api::typ;
 use gluon::vm::thread::Thread;
-use gluon::{new_vm, Compiler};
+use gluon::import::add_extern_module;
+use gluon::{new_vm, Compiler, RootedThread};
+
+use serde::de::{Deserialize, Deserializer, Error};
 
 #[test]
 fn bool() {
@@ -207,3 +218,348 @@ fn enum_() {
         .unwrap_or_else(|err| panic!("{}", err));
     assert_eq!(enum_, Enum::C(0, 1));
 }
+
+#[derive(Debug, PartialEq, Deserialize)]
+enum Event {
+    Click { x: i64, y: i64 },
+    Key(String),
+    Move(i64, i64),
+    Quit,
+}
+
+impl VmType for Event {
+    type Type = Self;
+
+    fn make_type(thread: &Thread) -> ArcType {
+        thread.find_type_info("test.Event").unwrap().into_type()
+    }
+}
+
+#[test]
+fn enum_variants() {
+    let _ = env_logger::try_init();
+
+    let thread = new_vm();
+    Compiler::new()
+        .implicit_prelude(false)
+        .load_script(
+            &thread,
+            "test",
+            r#"
+type Event =
+    | Click { y : Int, x : Int }
+    | Key String
+    | Move Int Int
+    | Quit
+    | Resize Int Int
+{ Event }
+"#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let run = |expr: &str| {
+        Compiler::new()
+            .implicit_prelude(false)
+            .run_expr::<De<Event>>(
+                &thread,
+                "test",
+                &format!(r#" let {{ Event }} = import! "test" in {} "#, expr),
+            )
+            .map(|(De(event), _)| event)
+    };
+
+    assert_eq!(
+        run("Click { y = 2, x = 1 }").unwrap_or_else(|err| panic!("{}", err)),
+        Event::Click { x: 1, y: 2 }
+    );
+    assert_eq!(
+        run(r#"Key "a""#).unwrap_or_else(|err| panic!("{}", err)),
+        Event::Key("a".to_string())
+    );
+    assert_eq!(
+        run("Move 3 4").unwrap_or_else(|err| panic!("{}", err)),
+        Event::Move(3, 4)
+    );
+    assert_eq!(
+        run("Quit").unwrap_or_else(|err| panic!("{}", err)),
+        Event::Quit
+    );
+
+    let (value, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<&Thread, Hole>>(
+            &thread,
+            "test",
+            r#" let { Event } = import! "test" in Resize 1 2 "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    let typ = Event::make_type(&thread);
+    let err = de::from_value::<Event>(&thread, value.get_variant(), &typ)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("Unknown constructor `Resize`, expected one of `Click`, `Key`, `Move`, `Quit`"),
+        "{}",
+        err
+    );
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Circle(f64),
+    Rect { width: f64, height: f64 },
+    Empty,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Tag(String);
+
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let tag = String::deserialize(deserializer)?;
+        if tag.contains(' ') {
+            Err(D::Error::custom("tags may not contain spaces"))
+        } else {
+            Ok(Tag(tag))
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Drawing {
+    name: String,
+    shape: Shape,
+    layer: Option<i32>,
+    tags: Vec<Tag>,
+}
+
+fn serde_bridge_vm() -> gluon::RootedThread {
+    fn scale(drawing: SerdeGet<Drawing>) -> SerdePush<Drawing> {
+        let SerdeGet(mut drawing) = drawing;
+        drawing.shape = match drawing.shape {
+            Shape::Circle(r) => Shape::Circle(r * 2.),
+            Shape::Rect { width, height } => Shape::Rect {
+                width: width * 2.,
+                height: height * 2.,
+            },
+            Shape::Empty => Shape::Empty,
+        };
+        drawing.layer = drawing.layer.map(|layer| layer + 1);
+        SerdePush(drawing)
+    }
+
+    let thread = new_vm();
+    typ::register_variant::<Shape>(&thread).unwrap_or_else(|err| panic!("{}", err));
+    add_extern_module(&thread, "scale", |thread| {
+        ExternModule::new(thread, primitive!(1 scale))
+    });
+    thread
+}
+
+#[test]
+fn serde_bridge_round_trip() {
+    let _ = env_logger::try_init();
+
+    let thread = serde_bridge_vm();
+    let (mut scale, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(SerdePush<Drawing>) -> SerdeGet<Drawing>>>(
+            &thread,
+            "test",
+            "import! scale",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let drawing = Drawing {
+        name: "box".to_string(),
+        shape: Shape::Rect {
+            width: 1.,
+            height: 2.5,
+        },
+        layer: Some(3),
+        tags: vec![Tag("a".to_string()), Tag("b".to_string())],
+    };
+    let SerdeGet(result) = scale
+        .call(SerdePush(drawing.clone()))
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        result,
+        Drawing {
+            shape: Shape::Rect {
+                width: 2.,
+                height: 5.,
+            },
+            layer: Some(4),
+            ..drawing
+        }
+    );
+
+    let drawing = Drawing {
+        name: "dot".to_string(),
+        shape: Shape::Circle(0.5),
+        layer: None,
+        tags: vec![],
+    };
+    let SerdeGet(result) = scale
+        .call(SerdePush(drawing.clone()))
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        result,
+        Drawing {
+            shape: Shape::Circle(1.),
+            ..drawing
+        }
+    );
+}
+
+#[test]
+fn serde_bridge_error_includes_the_field_path() {
+    let _ = env_logger::try_init();
+
+    let thread = serde_bridge_vm();
+    let (mut scale, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(SerdePush<Drawing>) -> SerdeGet<Drawing>>>(
+            &thread,
+            "test",
+            "import! scale",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let drawing = Drawing {
+        name: "box".to_string(),
+        shape: Shape::Empty,
+        layer: None,
+        tags: vec![Tag("a".to_string()), Tag("b c".to_string())],
+    };
+    let err = match scale.call(SerdePush(drawing)) {
+        Ok(_) => panic!("Expected the tag to be rejected"),
+        Err(err) => err.to_string(),
+    };
+    assert!(
+        err.contains("tags[1]: tags may not contain spaces"),
+        "Unexpected error: {}",
+        err
+    );
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+enum Mode {
+    Fast,
+    Slow(i32),
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Config {
+    port: i32,
+    hosts: Vec<String>,
+    mode: Mode,
+}
+
+#[test]
+fn generated_source_can_annotate_host_types() {
+    let _ = env_logger::try_init();
+
+    let thread = new_vm();
+    typ::register_variant::<Mode>(&thread).unwrap_or_else(|err| panic!("{}", err));
+    let source = typ::make_source::<Config>(&thread).unwrap_or_else(|err| panic!("{}", err));
+    Compiler::new()
+        .load_script(&thread, "config_types", &source)
+        .unwrap_or_else(|err| panic!("{}\n{}", err, source));
+
+    let (value, _) = Compiler::new()
+        .run_expr::<OpaqueValue<&Thread, Hole>>(
+            &thread,
+            "test",
+            r#"
+let { Config, Mode } = import! config_types
+let config : Config = { port = 80, hosts = ["a", "b"], mode = Slow 2 }
+config
+"#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    let typ = typ::make_type::<Config>(&thread);
+    assert_eq!(
+        de::from_value::<Config>(&thread, value.get_variant(), &typ)
+            .unwrap_or_else(|err| panic!("{}", err)),
+        Config {
+            port: 80,
+            hosts: vec!["a".to_string(), "b".to_string()],
+            mode: Mode::Slow(2),
+        }
+    );
+}
+
+#[test]
+fn serialize_value_to_json() {
+    let _ = env_logger::try_init();
+
+    let thread = new_vm();
+    Compiler::new()
+        .load_script(
+            &thread,
+            "shapes",
+            r#" type Shape = | Circle Float | Rect Int Int | Empty in { Shape } "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let expr = r#"
+        let { Shape } = import! shapes
+        {
+            name = "drawing",
+            layer = {
+                shapes = [Circle 1.5, Rect 2 3, Empty],
+                visible = True,
+                parent = Some 1,
+            },
+        }
+    "#;
+    let (value, typ) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(&thread, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let json = serde_json::to_value(SerializeValue::new(&thread, value.get_variant(), &typ))
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        json,
+        json!({
+            "name": "drawing",
+            "layer": {
+                "shapes": [{ "Circle": 1.5 }, { "Rect": [2, 3] }, "Empty"],
+                "visible": true,
+                "parent": 1,
+            },
+        })
+    );
+}
+
+#[test]
+fn serialize_value_without_type_is_positional() {
+    let _ = env_logger::try_init();
+
+    let thread = new_vm();
+    let (value, _) = Compiler::new()
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(&thread, "test", r#" { x = 1, y = "a" } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let json = serde_json::to_value(SerializeValue::untyped(&thread, value.get_variant()))
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(json, json!([1, "a"]));
+}
+
+#[test]
+fn array_get_checked_reports_conversion_errors() {
+    let _ = env_logger::try_init();
+
+    let thread = new_vm();
+    let (value, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<&Thread, Hole>>(&thread, "test", r#" ["a", "b"] "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let array = Array::<De<i32>>::from_value(&thread, value.get_variant());
+    assert!(array.get_checked(0).is_err());
+    assert!(array.get_checked(2).unwrap().is_none());
+    assert!(array.get(0).is_none());
+}
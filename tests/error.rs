@@ -3,8 +3,13 @@ extern crate gluon;
 
 mod support;
 
+use std::fs::{self, File};
+use std::io::Write;
+
 use gluon::{Compiler, Error};
 use gluon::check::typecheck::TypeError;
+use gluon::import::{Import, UTF8_BOM};
+use gluon::vm::thread::Thread;
 
 #[test]
 fn dont_panic_when_error_span_is_at_eof() {
@@ -50,3 +55,159 @@ fn dont_miss_errors_in_file_if_import_has_errors() {
         error => panic!("{}", error),
     }
 }
+
+#[test]
+fn private_module_fields_are_not_exported() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let module = r#"
+        let _helper x = x #Int+ 1
+        /// @private
+        let secret = 10
+        let pub_fn x = _helper x #Int* secret
+        { pub_fn, _helper, secret }
+    "#;
+    Compiler::new()
+        .load_script_async(&vm, "private_module", module)
+        .sync_or_error()
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let text = r#"
+        let { pub_fn } = import! private_module
+        pub_fn 2
+    "#;
+    let (result, _) = Compiler::new()
+        .run_expr::<i32>(&vm, "test", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 30);
+
+    let text = r#"
+        let m = import! private_module
+        m._helper 2
+    "#;
+    let error = Compiler::new()
+        .run_expr::<i32>(&vm, "test", text)
+        .unwrap_err();
+    let error_string = error.to_string();
+    match error {
+        Error::Typecheck(errors) => {
+            let errors: Vec<_> = errors.errors().into();
+            match errors[0].value.error {
+                TypeError::UndefinedField(..) => (),
+                _ => panic!("{}", error_string),
+            }
+        }
+        _ => panic!("{}", error_string),
+    }
+    assert!(error_string.contains("private"), "{}", error_string);
+
+    let text = r#"
+        let m = import! private_module
+        m.secret
+    "#;
+    assert!(
+        Compiler::new()
+            .run_expr::<i32>(&vm, "test", text)
+            .is_err()
+    );
+}
+
+#[test]
+fn same_named_aliases_show_where_they_were_defined() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let module = r#"
+        type Test = | A Int
+        { Test, a = A }
+    "#;
+    Compiler::new()
+        .load_script_async(&vm, "alias_module", module)
+        .sync_or_error()
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let text = r#"
+        let m = import! alias_module
+        type Test = | B Float
+        let x : Test = m.a 1
+        x
+    "#;
+    let error = Compiler::new()
+        .load_script_async(&vm, "test", text)
+        .sync_or_error()
+        .unwrap_err();
+    let error_string = error.to_string();
+    assert!(
+        error_string.contains("`Test` defined at 3:14"),
+        "{}",
+        error_string
+    );
+    assert!(
+        error_string.contains("`Test` defined in module alias_module"),
+        "{}",
+        error_string
+    );
+}
+
+/// Writes `contents` with a byte order mark and `\r\n` line endings to `<name>.glu` in a
+/// directory which `vm` imports from
+fn write_windows_module(vm: &Thread, name: &str, contents: &str) {
+    let dir = ::std::env::temp_dir().join("gluon_error_windows_modules");
+    fs::create_dir_all(&dir).unwrap();
+    let mut bytes = UTF8_BOM.to_vec();
+    bytes.extend(contents.replace('\n', "\r\n").into_bytes());
+    File::create(dir.join(format!("{}.glu", name)))
+        .and_then(|mut file| file.write_all(&bytes))
+        .unwrap();
+
+    vm.get_macros()
+        .get("import")
+        .as_ref()
+        .and_then(|import| import.downcast_ref::<Import>())
+        .expect("Import macro")
+        .add_path(dir);
+}
+
+#[test]
+fn attribute_is_detected_after_byte_order_mark() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    write_windows_module(
+        &vm,
+        "bom_no_prelude",
+        "//@NO-IMPLICIT-PRELUDE\nlet x = 1\nx #Int+ show\n",
+    );
+
+    let error = Compiler::new()
+        .load_script_async(&vm, "test", "import! bom_no_prelude")
+        .sync_or_error()
+        .unwrap_err();
+    let error_string = error.to_string();
+    assert!(
+        error_string.contains("bom_no_prelude:Line: 3, Column: 9: Undefined variable `show`"),
+        "{}",
+        error_string
+    );
+}
+
+#[test]
+fn errors_after_byte_order_mark_point_at_the_visual_column() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    write_windows_module(&vm, "bom_type_error", "let x : Int = \"\"\nx\n");
+
+    let error = Compiler::new()
+        .load_script_async(&vm, "test", "import! bom_type_error")
+        .sync_or_error()
+        .unwrap_err();
+    let error_string = error.to_string();
+    assert!(
+        error_string.contains("bom_type_error:Line: 1, Column: 15:"),
+        "{}",
+        error_string
+    );
+
+    let import = vm.get_macros().get("import").expect("Import macro");
+    let import = import.downcast_ref::<Import>().expect("Import macro");
+    assert!(import.stripped_byte_order_mark("bom_type_error"));
+    assert!(!import.stripped_byte_order_mark("std.prelude"));
+}
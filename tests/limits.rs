@@ -1,12 +1,16 @@
 extern crate env_logger;
 extern crate gluon;
+#[macro_use]
+extern crate gluon_vm;
 
 mod support;
 
 use gluon::{Compiler, Error, Thread};
-use gluon::vm::Error as VMError;
-use gluon::vm::api::{Hole, OpaqueValue};
+use gluon::import::add_extern_module;
+use gluon::vm::{Error as VMError, ExternModule};
+use gluon::vm::api::{FunctionRef, Hole, OpaqueValue, WithVM, IO};
 use gluon::vm::thread::ThreadInternal;
+use gluon::vm::types::VmInt;
 
 use support::make_vm;
 
@@ -50,3 +54,181 @@ fn stack_overflow() {
         Ok(_) => panic!("Expected an error"),
     }
 }
+
+/// Adds a `tick` module whose function cancels the thread once it is called with `10`
+fn add_cancelling_tick(vm: &Thread) {
+    fn tick(n: WithVM<VmInt>) {
+        if n.value == 10 {
+            n.vm.cancellation_token().cancel();
+        }
+    }
+    add_extern_module(vm, "tick", |thread| {
+        ExternModule::new(thread, primitive!(1 tick))
+    });
+}
+
+#[test]
+fn cancel_mid_loop() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    add_cancelling_tick(&vm);
+
+    let expr = r#"
+        let tick = import! tick
+        let loop n : Int -> Int =
+            if n #Int== 1000000 then n
+            else
+                let _ = tick n
+                loop (n #Int+ 1)
+        loop 0
+    "#;
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr_async::<VmInt>(&vm, "example", expr)
+        .sync_or_error();
+
+    match result {
+        Err(Error::VM(VMError::Cancelled)) => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn cancellation_is_raised_again_until_acknowledged() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    add_cancelling_tick(&vm);
+
+    // The handler ignores the error and keeps running without acknowledging the cancellation
+    let expr = r#"
+        let io = import! std.io
+        let tick = import! tick
+        let loop n : Int -> IO Int =
+            io.flat_map (\_ ->
+                let _ = tick n
+                loop (n #Int+ 1)) (io.applicative.wrap ())
+        io.catch (loop 0) (\_ -> loop 100)
+    "#;
+    let result = Compiler::new()
+        .run_io(true)
+        .run_expr::<IO<VmInt>>(&vm, "example", expr);
+
+    // Errors from running the action are only passed on as messages
+    match result {
+        Err(ref err) if err.to_string().contains("Thread was cancelled") => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn cancelling_a_function_returning_io_is_not_an_exception() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    add_cancelling_tick(&vm);
+
+    let expr = r#"
+        let io = import! std.io
+        let tick = import! tick
+        let loop n : Int -> IO Int =
+            io.flat_map (\_ ->
+                let _ = tick n
+                loop (n #Int+ 1)) (io.applicative.wrap ())
+        loop
+    "#;
+    Compiler::new()
+        .load_script(&vm, "looping", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let mut looping: FunctionRef<fn(VmInt) -> IO<VmInt>> = vm.get_global("looping").unwrap();
+
+    match looping.call(0) {
+        Err(VMError::Cancelled) => (),
+        result => panic!("Expected the call to be cancelled, found `{:?}`", result),
+    }
+}
+
+#[test]
+fn cancelled_script_polls_and_finishes() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    add_cancelling_tick(&vm);
+
+    let expr = r#"
+        let io = import! std.io
+        let cancel = import! std.cancel
+        let tick = import! tick
+        let loop n : Int -> IO Int =
+            io.flat_map (\requested ->
+                if requested then io.applicative.wrap n
+                else
+                    let _ = tick n
+                    loop (n #Int+ 1)) cancel.is_requested
+        loop 0
+    "#;
+    let (result, _) = Compiler::new()
+        .run_io(true)
+        .run_expr::<IO<VmInt>>(&vm, "example", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(result, IO::Value(11));
+}
+
+#[test]
+fn cancellation_check_is_catchable() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    add_cancelling_tick(&vm);
+
+    let expr = r#"
+        let io = import! std.io
+        let cancel = import! std.cancel
+        let tick = import! tick
+        let loop n : Int -> IO Int =
+            io.flat_map (\_ ->
+                let _ = tick n
+                loop (n #Int+ 1)) cancel.check
+        io.catch (io.functor.map (\_ -> "finished") (loop 0)) (\err -> io.applicative.wrap err)
+    "#;
+    let (result, _) = Compiler::new()
+        .run_io(true)
+        .run_expr::<IO<String>>(&vm, "example", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(result, IO::Value("Thread was cancelled".to_string()));
+}
+
+#[test]
+fn child_threads_share_the_cancellation_token() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let child = vm.new_thread().unwrap();
+    let grandchild = child.new_thread().unwrap();
+    let detached = vm.new_detached_thread().unwrap();
+
+    vm.cancellation_token().cancel();
+
+    assert!(child.cancellation_token().is_cancelled());
+    assert!(grandchild.cancellation_token().is_cancelled());
+    assert!(!detached.cancellation_token().is_cancelled());
+
+    let expr = r#"
+        let loop n : Int -> Int = if n #Int== 100000 then n else loop (n #Int+ 1)
+        loop 0
+    "#;
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr_async::<VmInt>(&child, "example", expr)
+        .sync_or_error();
+    match result {
+        Err(Error::VM(VMError::Cancelled)) => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
@@ -1,9 +1,11 @@
 extern crate env_logger;
 extern crate gluon;
 
+use gluon::base::metadata::{Fixity, OpInfo};
+use gluon::check::metadata::operator_info;
 use gluon::vm::thread::RootedThread;
 use gluon::import::Import;
-use gluon::Compiler;
+use gluon::{Compiler, Error};
 
 fn make_vm() -> RootedThread {
     let vm = ::gluon::new_vm();
@@ -33,3 +35,57 @@ let { List, of }  = import! std.list
     assert!(env.get_metadata("test.of").is_ok());
     assert!(env.get_metadata("test.List").is_ok());
 }
+
+#[test]
+fn operator_metadata_through_import() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let text = r#"
+/// @infix right 5
+let (+++) x y : Int -> Int -> Int = x + y
+{ (+++) }
+"#;
+    Compiler::new()
+        .load_script_async(&vm, "ops", text)
+        .sync_or_error()
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let text = r#"
+let { (+++) } = import! ops
+{ (+++) }
+"#;
+    Compiler::new()
+        .load_script_async(&vm, "test", text)
+        .sync_or_error()
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let env = vm.get_env();
+    let metadata = env.get_metadata("test").unwrap();
+    assert_eq!(
+        operator_info(metadata, "+++"),
+        Some(OpInfo {
+            precedence: 5,
+            fixity: Fixity::Right,
+        })
+    );
+}
+
+#[test]
+fn conflicting_operator_metadata_is_an_error() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let text = r#"
+/// @infix left 6
+let (+++) x y : Int -> Int -> Int = x + y
+/// @infix right 5
+let (+++) x y : Int -> Int -> Int = x + y
+{ (+++) }
+"#;
+    let result = Compiler::new()
+        .load_script_async(&vm, "test", text)
+        .sync_or_error();
+    match result {
+        Err(Error::Metadata(_)) => (),
+        _ => panic!("Expected a metadata error, got {:?}", result),
+    }
+}
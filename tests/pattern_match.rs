@@ -33,6 +33,58 @@ match A with
     assert!(result.is_err());
 }
 
+#[test]
+fn string_affix_patterns_are_not_compiled_yet() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+match "GET /" with
+| "GET " ++ path -> path
+| _ -> ""
+"#;
+    let mut vm = make_vm();
+    let result = Compiler::new()
+        .run_expr_async::<String>(&mut vm, "<top>", text)
+        .sync_or_error();
+    let err = result.unwrap_err().to_string();
+    assert!(
+        err.contains("String prefix and suffix patterns can't be compiled yet"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn or_patterns_are_not_compiled_yet() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Lookup = | Ok Int | Missing Int
+match Ok 1 with
+| Ok x | Missing x -> x
+"#;
+    let mut vm = make_vm();
+    let result = Compiler::new()
+        .run_expr_async::<i32>(&mut vm, "<top>", text)
+        .sync_or_error();
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("Or-patterns can't be compiled yet"), "{}", err);
+}
+
+#[test]
+fn pattern_guards_are_not_compiled_yet() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+match 1 with
+| x if x #Int== 1 -> x
+| _ -> 0
+"#;
+    let mut vm = make_vm();
+    let result = Compiler::new()
+        .run_expr_async::<i32>(&mut vm, "<top>", text)
+        .sync_or_error();
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("Pattern guards can't be compiled yet"), "{}", err);
+}
+
 test_expr!{ match_record_pattern,
 r#"
 let string_prim = import! std.string.prim
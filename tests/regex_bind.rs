@@ -50,3 +50,81 @@ fn regex_error() {
         "regex parse error:\n    )\n    ^\nerror: unopened group"
     );
 }
+
+#[cfg(feature = "regex-syntax")]
+#[test]
+fn regex_error_position() {
+    let _ = ::env_logger::try_init();
+
+    let thread = new_vm();
+    let text = r#"
+        let regex = import! std.regex
+        let { (|>) } = import! std.function
+        let { unwrap_err } = import! std.result
+
+        regex.new "ab(c" |> unwrap_err |> regex.error_position
+        "#;
+    let result = Compiler::new()
+        .run_expr_async::<Option<i32>>(&thread, "<top>", text)
+        .sync_or_error();
+
+    assert_eq!(result.unwrap_or_else(|err| panic!("{}", err)).0, Some(2));
+}
+
+#[test]
+fn regex_find() {
+    let _ = ::env_logger::try_init();
+
+    let thread = new_vm();
+    let text = r#"
+        let regex = import! std.regex
+        let { (|>) } = import! std.function
+        let { unwrap_ok } = import! std.result
+        let { assert }  = import! std.test
+
+        let re = regex.new "é+" |> unwrap_ok
+        let no_match =
+            match regex.find re "abc" with
+            | Some _ -> False
+            | None -> True
+        assert no_match
+        match regex.find re "cafééé!" with
+        | Some m -> (m.start, m.end, m.text)
+        | None -> error "Expected a match"
+        "#;
+    let result = Compiler::new()
+        .run_expr_async::<(i32, i32, String)>(&thread, "<top>", text)
+        .sync_or_error();
+
+    assert_eq!(
+        result.unwrap_or_else(|err| panic!("{}", err)).0,
+        (3, 9, "ééé".to_string())
+    );
+}
+
+#[test]
+fn regex_captures() {
+    let _ = ::env_logger::try_init();
+
+    let thread = new_vm();
+    let text = r#"
+        let regex = import! std.regex
+        let { (|>) } = import! std.function
+        let { unwrap_ok } = import! std.result
+        let array = import! std.array
+
+        let re = regex.new "(\\w+)(-(\\d+))?" |> unwrap_ok
+        match regex.captures re "  version" with
+        | Some captures ->
+            (array.len captures, array.index captures 1, array.index captures 3)
+        | None -> error "Expected a match"
+        "#;
+    let result = Compiler::new()
+        .run_expr_async::<(i32, Option<String>, Option<String>)>(&thread, "<top>", text)
+        .sync_or_error();
+
+    assert_eq!(
+        result.unwrap_or_else(|err| panic!("{}", err)).0,
+        (4, Some("version".to_string()), None)
+    );
+}
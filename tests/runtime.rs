@@ -0,0 +1,105 @@
+extern crate env_logger;
+#[macro_use]
+extern crate gluon_vm;
+extern crate gluon;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+use gluon::vm::{ExternModule, Result};
+use gluon::vm::api::{VmType, IO};
+use gluon::import::add_extern_module;
+use gluon::{new_vm, Compiler, Thread};
+
+field_decl! { name, version }
+
+type Plugin = record_type!{ name => String, version => i32 };
+
+fn load_plugins(vm: &Thread) -> Result<ExternModule> {
+    ExternModule::new(
+        vm,
+        record! {
+            load_plugin => primitive!(1 gluon::runtime::load_with_type::<Plugin>),
+            load_int => primitive!(1 gluon::runtime::load_with_type::<i32>)
+        },
+    )
+}
+
+fn plugin_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("gluon_runtime_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join("plugin.glu"))
+        .unwrap()
+        .write_all(br#"{ name = "test", version = 3 }"#)
+        .unwrap();
+    dir
+}
+
+/// Loads `path` with the `load` function of the `plugins` module, evaluating `ok` if it succeeds
+fn run_loader(vm: &Thread, load: &str, path: &str, ok: &str) -> String {
+    let _ = ::env_logger::try_init();
+
+    let expr = format!(
+        r#"
+        let io @ {{ ? }} = import! std.io
+        let {{ wrap }} = io.applicative
+        let {{ flat_map }} = import! std.prelude
+        let {{ Result }} = import! std.types
+        let plugins = import! plugins
+        do result = plugins.{} {:?}
+        wrap (match result with
+            | Ok plugin -> {}
+            | Err (NotFound path) -> path
+            | Err (Compile msg) -> msg
+            | Err (TypeMismatch expected actual) -> expected)
+        "#,
+        load, path, ok
+    );
+    Compiler::new()
+        .run_io(true)
+        .run_expr::<IO<String>>(vm, "<top>", &expr)
+        .map(|(value, _)| match value {
+            IO::Value(value) => value,
+            IO::Exception(err) => panic!("{}", err),
+        })
+        .unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[test]
+fn load_module_with_expected_type() {
+    let vm = new_vm();
+    add_extern_module(&vm, "plugins", load_plugins);
+    let path = plugin_dir("expected").join("plugin.glu");
+
+    assert_eq!(
+        run_loader(&vm, "load_plugin", path.to_str().unwrap(), "plugin.name"),
+        "test"
+    );
+}
+
+#[test]
+fn load_module_with_wrong_type() {
+    let vm = new_vm();
+    add_extern_module(&vm, "plugins", load_plugins);
+    let path = plugin_dir("wrong").join("plugin.glu");
+
+    assert_eq!(
+        run_loader(&vm, "load_int", path.to_str().unwrap(), r#""Ok""#),
+        i32::make_type(&vm).to_string()
+    );
+}
+
+#[test]
+fn load_missing_module() {
+    let vm = new_vm();
+    add_extern_module(&vm, "plugins", load_plugins);
+    let path = plugin_dir("missing").join("missing.glu");
+    let path = path.to_str().unwrap();
+
+    assert_eq!(
+        run_loader(&vm, "load_plugin", path, r#""Ok""#),
+        path
+    );
+}
@@ -4,8 +4,14 @@ extern crate gluon;
 
 mod support;
 
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+
+use gluon::import::Import;
 use gluon::vm::api::{FunctionRef, OpaqueValue};
 use gluon::vm::reference::Reference;
+use gluon::vm::thread::GlobalChange;
 use gluon::{Compiler, RootedThread, Thread};
 
 use support::*;
@@ -68,3 +74,80 @@ fn cant_transfer_opaque_value_between_disjoint_threads() {
     let vm2 = make_vm();
     verify_value_cloned(&vm1, &vm2);
 }
+
+/// Makes a vm which may only load modules from the standard library
+fn make_sandboxed_vm(dir: &str) -> RootedThread {
+    let vm = make_vm();
+    sandbox(&vm, dir);
+    vm
+}
+
+/// Only allows `vm` to load modules from the standard library
+fn sandbox(vm: &Thread, dir: &str) {
+    let dir = env::temp_dir().join(format!("gluon_safety_{}", dir));
+    fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join("sandboxed.glu"))
+        .unwrap()
+        .write_all(b"123")
+        .unwrap();
+    vm.get_macros()
+        .get("import")
+        .as_ref()
+        .and_then(|import| import.downcast_ref::<Import>())
+        .expect("Import macro")
+        .add_path(dir);
+    vm.set_global_policy(Box::new(|change| match *change {
+        GlobalChange::LoadModule { ref name } if !name.starts_with("std.") => {
+            Err(format!("Loading `{}` is not allowed", name))
+        }
+        _ => Ok(()),
+    }));
+}
+
+#[test]
+fn global_policy_vetoes_module_loads() {
+    let _ = ::env_logger::try_init();
+    let vm = make_sandboxed_vm("vetoes_module_loads");
+
+    let result = Compiler::new().run_expr::<i32>(&vm, "test", "import! sandboxed");
+    match result {
+        Ok(_) => panic!("Expected the import to be denied"),
+        Err(err) => assert!(
+            err.to_string().contains("Loading `sandboxed` is not allowed"),
+            "{}",
+            err
+        ),
+    }
+
+    Compiler::new()
+        .run_expr::<i32>(&vm, "test", "let { id } = import! std.function in id 1")
+        .unwrap_or_else(|err| panic!("{}", err));
+}
+
+#[test]
+fn global_policy_is_inherited_by_child_threads() {
+    let _ = ::env_logger::try_init();
+    let vm = make_sandboxed_vm("inherited_by_child_threads");
+    let child = vm.new_thread().unwrap();
+
+    let result = Compiler::new().run_expr::<i32>(&child, "test", "import! sandboxed");
+    assert!(result.is_err());
+}
+
+#[test]
+fn global_policy_applies_to_threads_spawned_before_it_was_set() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let child = vm.new_thread().unwrap();
+    sandbox(&vm, "spawned_before_it_was_set");
+
+    let result = Compiler::new().run_expr::<i32>(&child, "test", "import! sandboxed");
+    match result {
+        Ok(_) => panic!("Expected the import to be denied"),
+        Err(err) => assert!(
+            err.to_string().contains("Loading `sandboxed` is not allowed"),
+            "{}",
+            err
+        ),
+    }
+}
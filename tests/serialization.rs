@@ -3,6 +3,7 @@ extern crate futures;
 
 extern crate env_logger;
 
+extern crate bincode;
 extern crate serde_json;
 extern crate serde_state as serde;
 
@@ -20,6 +21,7 @@ use gluon::vm::api::{Hole, OpaqueValue};
 use gluon::vm::thread::{RootedThread, RootedValue, Thread, ThreadInternal};
 use gluon::vm::serialization::{DeSeed, SeSeed};
 use gluon::vm::internal::Value;
+use gluon::vm::types::Instruction;
 
 fn serialize_value(value: &Value) {
     let mut buffer = Vec::new();
@@ -168,6 +170,47 @@ fn precompile() {
     );
 }
 
+#[test]
+fn precompiled_module_keeps_implicit_instances() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type Add a = { (+) : a -> a -> a }
+/// @implicit
+let add_int : Add Int = { (+) = \x y -> x #Int+ y }
+/// @implicit
+let add_float : Add Float = { (+) = \x y -> x #Float+ y }
+let (+) ?add : [Add a] -> a -> a -> a = add.(+)
+{ Add, add_int, add_float, (+) }
+"#;
+    let thread = new_vm();
+    let mut buffer = Vec::new();
+    {
+        let mut serializer = serde_json::Serializer::new(&mut buffer);
+        Compiler::new()
+            .compile_to_bytecode(&thread, "overload", text, &mut serializer)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    // Load into the same thread so that the modules imported by `text` are already available
+    {
+        let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+        Compiler::new()
+            .load_bytecode(&thread, "overload", &mut deserializer)
+            .wait()
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    let expr = r#"
+let { (+), ? } = import! overload
+if 1 + 2 #Int== 3 then 1.5 + 2.0 else 0.0
+"#;
+    let (result, _) = Compiler::new()
+        .run_expr::<f64>(&thread, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 3.5);
+}
+
 #[test]
 fn roundtrip_reference() {
     let thread = new_vm();
@@ -197,3 +240,34 @@ fn roundtrip_thread() {
         .unwrap_or_else(|err| panic!("{}", err));
     roundtrip(&thread, &value);
 }
+
+// The serialized forms must not depend on the target so they are compared against blobs recorded
+// on a 64-bit host (which is also what the `vm64` feature gives every other target)
+#[cfg(any(feature = "vm64", target_pointer_width = "64"))]
+#[test]
+fn push_int_serialization_is_target_independent() {
+    let instruction = Instruction::PushInt(1 << 40);
+    let blob = bincode::serialize(&instruction, bincode::Infinite).unwrap();
+    assert_eq!(blob, [0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0]);
+}
+
+#[cfg(any(feature = "vm64", target_pointer_width = "64"))]
+#[test]
+fn int_value_serialization_is_target_independent() {
+    let thread = new_vm();
+    let (value, _) = Compiler::new()
+        .run_expr::<OpaqueValue<&Thread, Hole>>(&thread, "test", "[1099511627776]")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let value = unsafe { value.get_value() };
+
+    let mut buffer = Vec::new();
+    {
+        let mut ser = serde_json::Serializer::new(&mut buffer);
+        let ser_state = SeSeed::new();
+        value.serialize_state(&mut ser, &ser_state).unwrap();
+    }
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        r#"{"Array":{"Marked":[0,[{"Int":1099511627776}]]}}"#
+    );
+}
@@ -0,0 +1,49 @@
+extern crate env_logger;
+extern crate gluon;
+
+mod support;
+
+use gluon::vm::testing::TestResult;
+use gluon::Compiler;
+
+#[test]
+fn test_report_contains_passing_and_failing_cases() {
+    let _ = ::env_logger::try_init();
+
+    let vm = support::make_vm();
+    let text = r#"
+let { assert, assert_eq, group, case } = import! std.test.prim
+
+group "arithmetic" (\_ ->
+    case "addition" (\_ -> assert (1 #Int+ 1 #Int== 2))
+    case "records" (\_ ->
+        let _ = assert_eq { x = 1, y = "a" } { x = 1, y = "b" }
+        ())
+)
+"#;
+    Compiler::new()
+        .load_script_async(&vm, "test", text)
+        .sync_or_error()
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let results = vm.test_report().results();
+    assert_eq!(
+        results[0],
+        TestResult {
+            path: vec!["arithmetic".to_string(), "addition".to_string()],
+            error: None,
+        }
+    );
+    assert_eq!(
+        results[1],
+        TestResult {
+            path: vec!["arithmetic".to_string(), "records".to_string()],
+            error: Some(
+                "Assertion failed at test:Line 7\n  left: { x: 1, y: \"a\", }\n right: { x: 1, y: \"b\", }"
+                    .to_string()
+            ),
+        }
+    );
+    assert_eq!(results.len(), 2);
+    assert_eq!(vm.test_report().failures(), &results[1..]);
+}
@@ -155,6 +155,42 @@ in g 3
 -1
 }
 
+test_expr!{ recursive_value_guarded_by_constructor,
+r"
+type Stream = | Cons Int (() -> Stream)
+let ones = Cons 1 (\_ -> ones)
+let take n s =
+    match s with
+    | Cons x rest -> if n #Int== 0 then 0 else x #Int+ take (n #Int- 1) (rest ())
+take 5 ones
+",
+5i32
+}
+
+test_expr!{ recursive_record_of_functions,
+r"
+let parity = {
+    even = \n -> if n #Int== 0 then 1 else parity.odd (n #Int- 1),
+    odd = \n -> if n #Int== 0 then 0 else parity.even (n #Int- 1),
+}
+parity.even 10
+",
+1i32
+}
+
+test_expr!{ recursive_value_and_function,
+r"
+type Stream = | Cons Int (() -> Stream)
+let count n = Cons n (\_ -> count (n #Int+ 1))
+and naturals = Cons 0 (\_ -> count 1)
+let nth n s =
+    match s with
+    | Cons x rest -> if n #Int== 0 then x else nth (n #Int- 1) (rest ())
+nth 4 naturals
+",
+4i32
+}
+
 test_expr!{ no_capture_self_function,
 r"
 let x = 2 in
@@ -434,6 +470,27 @@ r#"
 2
 }
 
+test_expr!{ record_update_leaves_base_unmodified,
+r#"
+let r = { x = 1, y = 2, z = 3 }
+let r2 = { y = 20, .. r }
+let r3 = { x = 400, z = 500, .. r2 }
+r.x #Int+ r.y #Int+ r.z #Int+ r2.x #Int+ r2.y #Int+ r2.z #Int+ r3.x #Int+ r3.y #Int+ r3.z
+"#,
+6 + 24 + 920
+}
+
+test_expr!{ record_update_keeps_type_fields,
+r#"
+type Test = Int
+let r = { Test, x = 1, y = 2 }
+let { Test, x, y } = { x = 10, .. r }
+let z : Test = x #Int+ y
+z
+"#,
+12
+}
+
 test_expr!{ prelude do_expression_option_some,
 r#"
 let { monad = { flat_map } } = import! std.option
@@ -886,3 +943,42 @@ fn deep_clone_partial_application() {
         global_memory_with_closures
     );
 }
+
+#[test]
+fn concat_array_allocates_the_result_once() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let (mut concat_array, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(OpaqueValue<RootedThread, Vec<String>>) -> String>>(
+            &vm,
+            "concat_array",
+            "let string = import! std.string in string.concat_array",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    let (mut id, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(Vec<String>) -> OpaqueValue<RootedThread, Vec<String>>>>(
+            &vm,
+            "id",
+            "let id : Array String -> Array String = \\x -> x in id",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let parts: Vec<String> = (0..10_000).map(|i| i.to_string()).collect();
+    let total: usize = parts.iter().map(|part| part.len()).sum();
+    let parts = id.call(parts).unwrap();
+
+    vm.collect();
+    let before = vm.context().gc.allocated_memory();
+    let result = concat_array.call(parts).unwrap();
+    let allocated = vm.context().gc.allocated_memory() - before;
+
+    assert_eq!(result.len(), total);
+    // A single allocation only adds the header and the array length to the bytes themselves
+    assert!(
+        allocated >= total && allocated < total + 256,
+        "Expected a single allocation of about {} bytes, allocated {}",
+        total,
+        allocated
+    );
+}
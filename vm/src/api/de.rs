@@ -1,8 +1,9 @@
 //! Gluon -> Rust value conversion via the `serde::Deserialize` trait
 
 use std::cell::RefCell;
-use std::fmt;
+use std::fmt::{self, Write};
 use std::marker::PhantomData;
+use std::mem;
 use std::result::Result as StdResult;
 
 use base::resolve;
@@ -10,7 +11,7 @@ use base::types::{arg_iter, ArcType, BuiltinType, Type, TypeEnv};
 use base::symbol::Symbol;
 
 use {Error as VmError, Result, Variants};
-use api::{Getable, ValueRef, VmType};
+use api::{typ, Getable, ValueRef, VmType};
 use thread::{RootedThread, RootedValue, Thread, ThreadInternal};
 
 use serde::de::{self, DeserializeOwned, DeserializeSeed, EnumAccess, Error, IntoDeserializer,
@@ -135,7 +136,9 @@ let (De(enum_), _) = Compiler::new()
     .unwrap_or_else(|err| panic!("{}", err));
 assert_eq!(enum_, Enum::A(123));
 
-// The field names of record variants are ignored so make sure the fields are declared correctly
+// Struct variants are matched by field name when the constructor takes a single record.
+// Otherwise the constructor arguments are taken in order and the field names are ignored so make
+// sure the fields are declared correctly
 let (De(enum_), _) = Compiler::new()
 #   .implicit_prelude(false)
     .run_expr::<De<Enum>>(
@@ -175,12 +178,61 @@ where
     T: DeserializeOwned,
 {
     fn from_value(thread: &'vm Thread, value: Variants) -> Self {
-        let typ = T::make_type(thread);
-        match from_value(thread, value, &typ).map(De) {
+        match Self::try_from_value(thread, value) {
             Ok(v) => v,
             Err(err) => ice!("Getable::from_value for De: {}", err),
         }
     }
+
+    unsafe fn try_from_value_unsafe(thread: &'vm Thread, value: Variants) -> Result<Self> {
+        Self::try_from_value(thread, value)
+    }
+
+    fn try_from_value(thread: &'vm Thread, value: Variants) -> Result<Self> {
+        let typ = T::make_type(thread);
+        from_value(thread, value, &typ).map(De)
+    }
+}
+
+/// `Getable` wrapper which extracts `T` by deserializing it, deriving the gluon type of `T` from
+/// its `Deserialize` implementation with `typ::make_type`.
+///
+/// Since the type is derived any `Deserialize` type can be used as an argument of a rust function
+/// without further glue. Enums must first be registered with `typ::register_variant` however.
+/// If the value can't be deserialized the function is not called and the error (which includes
+/// the path to the field that failed) is returned instead.
+pub struct SerdeGet<T>(pub T);
+
+impl<T> VmType for SerdeGet<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Type = SerdeGet<T>;
+
+    fn make_type(thread: &Thread) -> ArcType {
+        typ::make_type::<T>(thread)
+    }
+}
+
+impl<'vm, T> Getable<'vm> for SerdeGet<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    fn from_value(thread: &'vm Thread, value: Variants) -> Self {
+        match Self::try_from_value(thread, value) {
+            Ok(v) => v,
+            Err(err) => ice!("Getable::from_value for SerdeGet: {}", err),
+        }
+    }
+
+    unsafe fn try_from_value_unsafe(thread: &'vm Thread, value: Variants) -> Result<Self> {
+        Self::try_from_value(thread, value)
+    }
+
+    fn try_from_value(thread: &'vm Thread, value: Variants) -> Result<Self> {
+        let typ = Self::make_type(thread);
+        from_value(thread, value, &typ).map(SerdeGet)
+    }
 }
 
 /// Deserializes `T` from a gluon value assuming that `value` is of type `typ`.
@@ -189,14 +241,66 @@ where
     T: DeserializeOwned,
 {
     let env = thread.global_env().get_env();
-    let mut deserializer = Deserializer::from_value(thread, &*env, value, typ);
-    T::deserialize(&mut deserializer)
+    let path = ErrorPath::default();
+    let mut deserializer = Deserializer::from_value(thread, &*env, &path, value, typ);
+    T::deserialize(&mut deserializer).map_err(|err| path.attach(err))
+}
+
+/// Records the path to the value which failed to (de)serialize while the error unwinds so that
+/// the path can be added to the error message
+#[derive(Default)]
+pub(crate) struct ErrorPath(RefCell<Vec<PathSegment>>);
+
+enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl ErrorPath {
+    pub(crate) fn field<T>(&self, name: &str, result: Result<T>) -> Result<T> {
+        if result.is_err() {
+            self.0.borrow_mut().push(PathSegment::Field(name.to_string()));
+        }
+        result
+    }
+
+    pub(crate) fn index<T>(&self, index: usize, result: Result<T>) -> Result<T> {
+        if result.is_err() {
+            self.0.borrow_mut().push(PathSegment::Index(index));
+        }
+        result
+    }
+
+    /// Adds the recorded path to the message of `err`
+    pub(crate) fn attach(&self, err: VmError) -> VmError {
+        let segments = mem::replace(&mut *self.0.borrow_mut(), Vec::new());
+        if segments.is_empty() {
+            return err;
+        }
+        let mut path = String::new();
+        for segment in segments.iter().rev() {
+            match *segment {
+                PathSegment::Field(ref name) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(name);
+                }
+                PathSegment::Index(index) => write!(path, "[{}]", index).unwrap(),
+            }
+        }
+        match err {
+            VmError::Message(msg) => VmError::Message(format!("{}: {}", path, msg)),
+            err => err,
+        }
+    }
 }
 
 #[derive(Clone)]
 struct State<'de> {
     thread: &'de Thread,
     env: &'de TypeEnv,
+    path: &'de ErrorPath,
 }
 
 #[derive(Clone)]
@@ -210,6 +314,7 @@ impl<'de, 't> Deserializer<'de, 't> {
     fn from_value(
         thread: &'de Thread,
         env: &'de TypeEnv,
+        path: &'de ErrorPath,
         input: Variants<'de>,
         typ: &'t ArcType,
     ) -> Self {
@@ -217,6 +322,7 @@ impl<'de, 't> Deserializer<'de, 't> {
             state: State {
                 thread: thread,
                 env: env,
+                path: path,
             },
             input: input,
             typ: typ,
@@ -303,14 +409,18 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de, 't> {
                     typ: &typ,
                     ..self.clone()
                 };
-                if let Type::Record(_) = **typ {
-                    deserializer.deserialize_enum("", &[], visitor)
-                } else {
-                    deserializer.deserialize_map(visitor)
+                match **typ {
+                    Type::Record(_) => deserializer.deserialize_map(visitor),
+                    Type::Variant(_) => deserializer.deserialize_enum("", &[], visitor),
+                    _ => Err(VmError::Message(format!(
+                        "Unable to deserialize `{}`",
+                        self.typ
+                    ))),
                 }
             }
             ValueRef::Float(_) => self.deserialize_f64(visitor),
             ValueRef::Int(_) => self.deserialize_i64(visitor),
+            ValueRef::Char(c) => visitor.visit_char(c),
             ValueRef::String(ref s) => visitor.visit_borrowed_str(s),
             ValueRef::Userdata(_) | ValueRef::Thread(_) | ValueRef::Internal => Err(
                 VmError::Message(format!("Unable to deserialize `{}`", self.typ)),
@@ -434,10 +544,11 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de, 't> {
     where
         V: Visitor<'de>,
     {
-        use std::char::from_u32;
+        use api::char_from_int;
         let typ = resolve::remove_aliases_cow(self.state.env, self.typ);
         match (self.input.as_ref(), &**typ) {
-            (ValueRef::Int(c), &Type::Builtin(BuiltinType::Char)) => match from_u32(c as u32) {
+            (ValueRef::Char(c), &Type::Builtin(BuiltinType::Char)) => visitor.visit_char(c),
+            (ValueRef::Int(c), &Type::Builtin(BuiltinType::Char)) => match char_from_int(c) {
                 Some(c) => visitor.visit_char(c),
                 None => self.deserialize_any(visitor),
             },
@@ -623,12 +734,37 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de, 't> {
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        // Constructors are mapped onto the variants by their position so any constructor past the
+        // last variant has no counterpart in the rust enum
+        if !variants.is_empty() {
+            let typ = resolve::remove_aliases_cow(self.state.env, self.typ);
+            if let (ValueRef::Data(data), &Type::Variant(ref row)) = (self.input.as_ref(), &**typ)
+            {
+                let tag = data.tag() as usize;
+                if tag >= variants.len() {
+                    let mut expected = String::new();
+                    for field in row.row_iter().take(variants.len()) {
+                        if !expected.is_empty() {
+                            expected.push_str(", ");
+                        }
+                        write!(expected, "`{}`", field.name.declared_name()).unwrap();
+                    }
+                    let constructor = row.row_iter()
+                        .nth(tag)
+                        .map_or("<unknown>", |field| field.name.declared_name());
+                    return Err(VmError::Message(format!(
+                        "Unknown constructor `{}`, expected one of {}",
+                        constructor, expected
+                    )));
+                }
+            }
+        }
         visitor.visit_enum(Enum::new(self))
     }
 
@@ -650,6 +786,7 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de, 't> {
 struct SeqDeserializer<'de, 't, I> {
     state: State<'de>,
     iter: I,
+    index: usize,
     _marker: PhantomData<&'t ()>,
 }
 
@@ -658,6 +795,7 @@ impl<'de, 't, I> SeqDeserializer<'de, 't, I> {
         SeqDeserializer {
             state: state,
             iter: iter,
+            index: 0,
             _marker: PhantomData,
         }
     }
@@ -674,11 +812,16 @@ where
         T: DeserializeSeed<'de>,
     {
         match self.iter.next() {
-            Some((value, typ)) => seed.deserialize(&mut Deserializer {
-                state: self.state.clone(),
-                input: value,
-                typ: typ,
-            }).map(Some),
+            Some((value, typ)) => {
+                let index = self.index;
+                self.index += 1;
+                let result = seed.deserialize(&mut Deserializer {
+                    state: self.state.clone(),
+                    input: value,
+                    typ: typ,
+                });
+                self.state.path.index(index, result).map(Some)
+            }
             None => Ok(None),
         }
     }
@@ -687,7 +830,7 @@ where
 struct MapDeserializer<'de, 't, I> {
     state: State<'de>,
     iter: I,
-    value: Option<(Variants<'de>, &'t ArcType)>,
+    value: Option<(Variants<'de>, &'t Symbol, &'t ArcType)>,
 }
 
 impl<'de, 't, I> MapDeserializer<'de, 't, I> {
@@ -712,7 +855,7 @@ where
     {
         match self.iter.next() {
             Some((value, field, typ)) => {
-                self.value = Some((value, typ));
+                self.value = Some((value, field, typ));
                 seed.deserialize(field.as_ref().into_deserializer())
                     .map(Some)
             }
@@ -725,11 +868,14 @@ where
         V: DeserializeSeed<'de>,
     {
         match self.value.take() {
-            Some((value, typ)) => seed.deserialize(&mut Deserializer {
-                state: self.state.clone(),
-                input: value,
-                typ: typ,
-            }),
+            Some((value, field, typ)) => {
+                let result = seed.deserialize(&mut Deserializer {
+                    state: self.state.clone(),
+                    input: value,
+                    typ: typ,
+                });
+                self.state.path.field(field.as_ref(), result)
+            }
             None => Err(Self::Error::custom("Unable to deserialize value")),
         }
     }
@@ -847,6 +993,32 @@ impl<'de, 'a, 't> VariantAccess<'de> for Enum<'a, 'de, 't> {
     where
         V: Visitor<'de>,
     {
+        let typ = resolve::remove_aliases_cow(self.de.state.env, self.de.typ);
+        if let (ValueRef::Data(data), &Type::Variant(ref row)) = (self.de.input.as_ref(), &**typ) {
+            // A constructor taking a single record is matched against the fields by name
+            if let Some(field) = row.row_iter().nth(data.tag() as usize) {
+                let mut args = arg_iter(&field.typ);
+                if let (Some(arg), None, Some(value)) =
+                    (args.next(), args.next(), data.get_variant(0))
+                {
+                    let is_record = match **resolve::remove_aliases_cow(self.de.state.env, arg) {
+                        Type::Record(_) => true,
+                        _ => false,
+                    };
+                    if is_record {
+                        return de::Deserializer::deserialize_map(
+                            &mut Deserializer {
+                                input: value,
+                                typ: arg,
+                                ..self.de.clone()
+                            },
+                            visitor,
+                        );
+                    }
+                }
+            }
+        }
+        // Otherwise the arguments of the constructor are the fields in declaration order
         de::Deserializer::deserialize_seq(self.de, visitor)
     }
 }
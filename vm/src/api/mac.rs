@@ -11,6 +11,19 @@ macro_rules! primitive_cast {
     (7, $name: expr) => { $name as fn (_, _, _, _, _, _, _) -> _ };
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! primitive_box {
+    (0, $func: expr) => { Box::new($func) as Box<Fn () -> _ + Send + Sync> };
+    (1, $func: expr) => { Box::new($func) as Box<Fn (_) -> _ + Send + Sync> };
+    (2, $func: expr) => { Box::new($func) as Box<Fn (_, _) -> _ + Send + Sync> };
+    (3, $func: expr) => { Box::new($func) as Box<Fn (_, _, _) -> _ + Send + Sync> };
+    (4, $func: expr) => { Box::new($func) as Box<Fn (_, _, _, _) -> _ + Send + Sync> };
+    (5, $func: expr) => { Box::new($func) as Box<Fn (_, _, _, _, _) -> _ + Send + Sync> };
+    (6, $func: expr) => { Box::new($func) as Box<Fn (_, _, _, _, _, _) -> _ + Send + Sync> };
+    (7, $func: expr) => { Box::new($func) as Box<Fn (_, _, _, _, _, _, _) -> _ + Send + Sync> };
+}
+
 /// Creates a `GluonFunction` from a function implementing `VMFunction`
 ///
 /// ```rust
@@ -24,8 +37,31 @@ macro_rules! primitive_cast {
 ///     primitive!(2 test);
 /// }
 /// ```
+///
+/// Closures, which may capture state, are passed with a comma after the number of arguments.
+/// As the closure can be called with any thread its arguments can not borrow from the thread.
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate gluon_vm;
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// fn main() {
+///     let counter = Arc::new(AtomicUsize::new(0));
+///     primitive!(0, move || counter.fetch_add(1, Ordering::SeqCst));
+/// }
+/// ```
 #[macro_export]
 macro_rules! primitive {
+    (0, $func: expr) => { $crate::api::closure_primitive("<closure>", primitive_box!(0, $func)) };
+    (1, $func: expr) => { $crate::api::closure_primitive("<closure>", primitive_box!(1, $func)) };
+    (2, $func: expr) => { $crate::api::closure_primitive("<closure>", primitive_box!(2, $func)) };
+    (3, $func: expr) => { $crate::api::closure_primitive("<closure>", primitive_box!(3, $func)) };
+    (4, $func: expr) => { $crate::api::closure_primitive("<closure>", primitive_box!(4, $func)) };
+    (5, $func: expr) => { $crate::api::closure_primitive("<closure>", primitive_box!(5, $func)) };
+    (6, $func: expr) => { $crate::api::closure_primitive("<closure>", primitive_box!(6, $func)) };
+    (7, $func: expr) => { $crate::api::closure_primitive("<closure>", primitive_box!(7, $func)) };
     (0 $name: expr) => { named_primitive!(0, stringify!($name), $name) };
     (1 $name: expr) => { named_primitive!(1, stringify!($name), $name) };
     (2 $name: expr) => { named_primitive!(2, stringify!($name), $name) };
@@ -260,3 +296,279 @@ macro_rules! record_p {
         }
     }
 }
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! marshal_variant_impl {
+    // All fields of the current constructor have been bound, continue with the next constructor
+    (
+        $name: ident [$($done: tt)*] ($tag: expr)
+        ($variant: ident ($args: expr) [$($bound: tt)*] [] [$($pool: tt)*])
+        $($rest: tt)*
+    ) => {
+        marshal_variant_impl!{
+            $name [$($done)* ($variant ($tag) ($args) $($bound)*)] ($tag + 1)
+            $($rest)*
+        }
+    };
+    // Bind the next field of the current constructor to a name from the pool
+    (
+        $name: ident [$($done: tt)*] ($tag: expr)
+        ($variant: ident ($args: expr) [$($bound: tt)*] [$field: ty $(, $fields: ty)*]
+            [($index: tt $binding: ident) $($pool: tt)*])
+        $($rest: tt)*
+    ) => {
+        marshal_variant_impl!{
+            $name [$($done)*] ($tag)
+            ($variant ($args + 1) [$($bound)* ($index $binding $field)] [$($fields),*] [$($pool)*])
+            $($rest)*
+        }
+    };
+    (
+        $name: ident
+        [$(($variant: ident ($tag: expr) ($args: expr) $(($index: tt $binding: ident $field: ty))*))*]
+        ($next_tag: expr)
+    ) => {
+        impl $name {
+            /// Registers this type and its constructors with the virtual machine. Must be called
+            /// before the type is marshalled.
+            pub fn register_type(vm: &$crate::thread::Thread) -> $crate::Result<()> {
+                vm.register_variant::<$name>(
+                    stringify!($name),
+                    &[$(
+                        (
+                            stringify!($variant),
+                            vec![$(<$field as $crate::api::VmType>::make_type(vm)),*],
+                        ),
+                    )*],
+                ).map(|_| ())
+            }
+        }
+
+        impl $crate::api::VmType for $name {
+            type Type = $name;
+        }
+
+        impl<'vm> $crate::api::Pushable<'vm> for $name {
+            fn push(
+                self,
+                thread: &'vm $crate::thread::Thread,
+                context: &mut $crate::thread::Context,
+            ) -> $crate::Result<()> {
+                match self {
+                    $(
+                        $name::$variant { $($index: $binding),* } => {
+                            $( $crate::api::Pushable::push($binding, thread, context)?; )*
+                            $crate::api::push_variant(thread, context, $tag, $args)
+                        }
+                    )*
+                }
+            }
+        }
+
+        impl<'vm> $crate::api::Getable<'vm> for $name {
+            #[allow(unused_variables)]
+            fn from_value(vm: &'vm $crate::thread::Thread, value: $crate::Variants) -> Self {
+                let data = match value.as_ref() {
+                    $crate::api::ValueRef::Data(data) => data,
+                    _ => panic!("ValueRef is not a {}", stringify!($name)),
+                };
+                $(
+                    if data.tag() == $tag {
+                        return $name::$variant {
+                            $(
+                                $index: $crate::api::Getable::from_value(
+                                    vm,
+                                    data.get_variant($index).unwrap(),
+                                )
+                            ),*
+                        };
+                    }
+                )*
+                panic!("ValueRef has a wrong tag: {}", data.tag())
+            }
+        }
+    };
+}
+
+/// Defines a rust enum which is marshalled to and from a gluon variant type with the same name
+/// and constructors. Each constructor is given a tag in the order it is declared and may have at
+/// most 7 fields.
+///
+/// The type must be registered with `register_type` before it is used, after which its
+/// constructors can be used in gluon code both to construct values and in pattern matches.
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate gluon_vm;
+///
+/// marshal_variant! {
+///     #[derive(Debug, PartialEq)]
+///     pub enum Shape {
+///         Circle(f64),
+///         Rectangle(f64, f64),
+///         Empty,
+///     }
+/// }
+///
+/// fn main() {
+///     let vm = gluon_vm::thread::RootedThread::new();
+///     Shape::register_type(&vm).unwrap();
+/// }
+/// ```
+#[macro_export]
+macro_rules! marshal_variant {
+    (
+        $(#[$attr: meta])*
+        pub enum $name: ident {
+            $($variant: ident $(($($field: ty),*))*),* $(,)*
+        }
+    ) => {
+        $(#[$attr])*
+        pub enum $name {
+            $($variant $(($($field),*))*),*
+        }
+        marshal_variant!{ impl $name $($variant $(($($field),*))*),* }
+    };
+    (
+        $(#[$attr: meta])*
+        enum $name: ident {
+            $($variant: ident $(($($field: ty),*))*),* $(,)*
+        }
+    ) => {
+        $(#[$attr])*
+        enum $name {
+            $($variant $(($($field),*))*),*
+        }
+        marshal_variant!{ impl $name $($variant $(($($field),*))*),* }
+    };
+    (impl $name: ident $($variant: ident $(($($field: ty),*))*),*) => {
+        marshal_variant_impl!{
+            $name [] (0)
+            $((
+                $variant (0) [] [$($($field),*)*]
+                [(0 _0) (1 _1) (2 _2) (3 _3) (4 _4) (5 _5) (6 _6)]
+            ))*
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! match_variant_arm {
+    ($vm: ident, $value: ident, $ctor: ident, $body: expr $(, $arg: ident : $arg_ty: ty)*) => {{
+        #[allow(unused_mut)]
+        let mut _index = 0..;
+        let args = (|| -> $crate::Result<_> {
+            $(
+                let $arg: $arg_ty = $crate::api::variant_argument(
+                    $vm,
+                    &$value,
+                    _index.next().unwrap(),
+                )?;
+            )*
+            Ok(($($arg,)*))
+        })();
+        match args {
+            Ok(($($arg,)*)) => Ok($body),
+            Err(err) => Err(err),
+        }
+    }};
+}
+
+/// Matches on a value of a gluon variant type by the names of its constructors, binding and
+/// converting the arguments of the matched constructor. The type is looked up by its name (as
+/// passed to `find_type_info`) when the match is run so the constructors are always resolved to
+/// the tags they currently have.
+///
+/// Evaluates to a `Result` which is an error if the value is not of the variant type, if an arm
+/// names a constructor which the type does not have or if an argument could not be converted.
+/// Constructors which do not have an arm are matched by the optional `_` arm (and are reported
+/// as an error if there is none).
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate gluon_vm;
+///
+/// use gluon_vm::Variants;
+/// use gluon_vm::thread::Thread;
+///
+/// fn describe(vm: &Thread, value: Variants) -> gluon_vm::Result<String> {
+///     match_variant!(vm, value, "std.types.Option", {
+///         Some(x: i32) => format!("Some {}", x),
+///         None => "None".to_string(),
+///     })
+/// }
+///
+/// fn main() { }
+/// ```
+#[macro_export]
+macro_rules! match_variant {
+    ($vm: expr, $value: expr, $type_name: expr, {
+        $($ctor: ident $(($($arg: ident : $arg_ty: ty),*))* => $body: expr,)*
+        _ => $default: expr $(,)*
+    }) => {{
+        let vm: &$crate::thread::Thread = $vm;
+        let value: $crate::Variants = $value;
+        let constructors = [$(stringify!($ctor)),*];
+        match $crate::api::match_constructor(vm, $type_name, &value, &constructors) {
+            $(
+                Ok(Some(stringify!($ctor))) => {
+                    match_variant_arm!(vm, value, $ctor, $body $($(, $arg: $arg_ty)*)*)
+                }
+            )*
+            Ok(_) => Ok($default),
+            Err(err) => Err(err),
+        }
+    }};
+    ($vm: expr, $value: expr, $type_name: expr, {
+        $($ctor: ident $(($($arg: ident : $arg_ty: ty),*))* => $body: expr),* $(,)*
+    }) => {{
+        let vm: &$crate::thread::Thread = $vm;
+        let value: $crate::Variants = $value;
+        let constructors = [$(stringify!($ctor)),*];
+        match $crate::api::match_constructor(vm, $type_name, &value, &constructors) {
+            $(
+                Ok(Some(stringify!($ctor))) => {
+                    match_variant_arm!(vm, value, $ctor, $body $($(, $arg: $arg_ty)*)*)
+                }
+            )*
+            Ok(_) => Err($crate::Error::Message(format!(
+                "The constructor of the `{}` value is not handled",
+                $type_name
+            ))),
+            Err(err) => Err(err),
+        }
+    }};
+}
+
+/// Implements `Traverseable` for a struct by traversing each of the listed fields. Userdata which
+/// stores gluon values (`OpaqueValue`, `OwnedFunction`, `RootedValue` etc) must traverse them so
+/// that they stay alive for as long as the userdata itself.
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate gluon_vm;
+///
+/// use gluon_vm::api::OwnedFunction;
+///
+/// #[derive(Debug)]
+/// struct Callbacks {
+///     on_event: OwnedFunction<fn(i32) -> i32>,
+///     name: String,
+/// }
+///
+/// impl_traverseable!(Callbacks { on_event });
+///
+/// fn main() { }
+/// ```
+#[macro_export]
+macro_rules! impl_traverseable {
+    ($name: ident { $($field: ident),* $(,)* }) => {
+        impl $crate::thread::Traverseable for $name {
+            fn traverse(&self, gc: &mut $crate::gc::Gc) {
+                $( $crate::thread::Traverseable::traverse(&self.$field, gc); )*
+            }
+        }
+    };
+}
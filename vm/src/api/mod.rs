@@ -1,13 +1,15 @@
 //! The marshalling api
 use {forget_lifetime, Error, Result, Variants};
+use deferred::DeferredCall;
 use future::FutureValue;
 use gc::{DataDef, Gc, GcPtr, Move, Traverseable};
+use interner::InternedStr;
 use base::symbol::{Symbol, Symbols};
 use base::scoped_map::ScopedMap;
 use stack::{Lock, StackFrame};
 use vm::{self, Root, RootStr, RootedValue, Status, Thread};
-use value::{ArrayDef, ArrayRepr, Cloner, DataStruct, Def, ExternFunction, GcStr, Value,
-            ValueArray, ValueRepr};
+use value::{ArrayDef, ArrayRepr, Cloner, DataStruct, Def, ExternCall, ExternFunction, GcStr,
+            RecordDef, Value, ValueArray, ValueRepr};
 use thread::{self, Context, RootedThread, VmRoot};
 use thread::ThreadInternal;
 use base::types::{self, ArcType, Type};
@@ -15,12 +17,15 @@ use types::{Instruction, VmIndex, VmInt, VmTag};
 use compiler::{CompiledFunction, CompiledModule};
 
 use std::any::Any;
-use std::cell::Ref;
+use std::cell::{Ref, RefCell};
 use std::cmp::Ordering;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
-use std::ops::Deref;
+use std::mem;
+use std::ops::{Deref, DerefMut};
 use std::result::Result as StdResult;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
 
 use futures::{Async, Future};
 
@@ -38,6 +43,11 @@ pub mod de;
 #[cfg(feature = "serde")]
 pub mod typ;
 
+#[cfg(feature = "serde")]
+pub use self::de::SerdeGet;
+#[cfg(feature = "serde")]
+pub use self::ser::SerdePush;
+
 macro_rules! count {
     () => { 0 };
     ($_e: ident) => { 1 };
@@ -49,6 +59,7 @@ pub enum ValueRef<'a> {
     Byte(u8),
     Int(VmInt),
     Float(f64),
+    Char(char),
     String(&'a str),
     Data(Data<'a>),
     Array(ArrayRef<'a>),
@@ -66,6 +77,9 @@ impl<'a, 'b> PartialEq<ValueRef<'b>> for ValueRef<'a> {
             (&Byte(l), &Byte(r)) => l == r,
             (&Int(l), &Int(r)) => l == r,
             (&Float(l), &Float(r)) => l == r,
+            (&Char(l), &Char(r)) => l == r,
+            // Characters used to be represented as integers so they still compare equal to them
+            (&Char(c), &Int(i)) | (&Int(i), &Char(c)) => c as VmInt == i,
             (&String(l), &String(r)) => l == r,
             (&Data(l), &Data(r)) => l == r,
             _ => false,
@@ -89,6 +103,7 @@ impl<'a> ValueRef<'a> {
             ValueRepr::Byte(i) => ValueRef::Byte(i),
             ValueRepr::Int(i) => ValueRef::Int(i),
             ValueRepr::Float(f) => ValueRef::Float(f),
+            ValueRepr::Char(c) => ValueRef::Char(c),
             ValueRepr::String(s) => ValueRef::String(forget_lifetime(&*s)),
             ValueRepr::Data(data) => ValueRef::Data(Data(DataInner::Data(forget_lifetime(&*data)))),
             ValueRepr::Tag(tag) => ValueRef::Data(Data(DataInner::Tag(tag))),
@@ -161,6 +176,52 @@ impl<'a> Data<'a> {
             },
         }
     }
+
+    /// Resolves the field `name` of this record to a `FieldToken` which can be used to retrieve
+    /// the same field from many records with `get_by_token`, without resolving the name again
+    pub fn field_token(&self, thread: &Thread, name: &str) -> Option<FieldToken> {
+        match self.0 {
+            DataInner::Tag(_) => None,
+            DataInner::Data(data) => {
+                let data = unsafe { GcPtr::from_raw(data) };
+                let name = thread.global_env().intern(name).ok()?;
+                let offset = *data.field_map().get(&name)?;
+                Some(FieldToken {
+                    name: name,
+                    fields: data.field_names().clone(),
+                    offset: offset,
+                })
+            }
+        }
+    }
+
+    /// Retrieves the field referred to by `token` from this record.
+    ///
+    /// Records with the same fields as the record the token was created from only need to index
+    /// into the record, other records fall back to looking up the field by its name.
+    pub fn get_by_token(&self, token: &FieldToken) -> Option<Variants<'a>> {
+        match self.0 {
+            DataInner::Tag(_) => None,
+            DataInner::Data(data) => {
+                let data = unsafe { GcPtr::from_raw(data) };
+                let offset = if Arc::ptr_eq(data.field_names(), &token.fields) {
+                    token.offset
+                } else {
+                    *data.field_map().get(&token.name)?
+                };
+                self.get_variant(offset as usize)
+            }
+        }
+    }
+}
+
+/// A record field resolved with `Data::field_token`
+#[derive(Clone, Debug)]
+pub struct FieldToken {
+    name: InternedStr,
+    /// The fields of the records which have the field at `offset`
+    fields: Arc<Vec<InternedStr>>,
+    offset: VmIndex,
 }
 
 /// Marker type representing a hole
@@ -207,6 +268,21 @@ pub struct RefPrimitive<'vm, F> {
     _typ: PhantomData<F>,
 }
 
+/// A primitive function backed by a boxed closure (usually `Box<Fn(..) -> R + Send + Sync>`).
+/// Created by `primitive!` when it is passed a closure.
+pub struct ClosurePrimitive<F: ?Sized> {
+    name: &'static str,
+    function: Box<F>,
+}
+
+#[inline]
+pub fn closure_primitive<F: ?Sized>(name: &'static str, function: Box<F>) -> ClosurePrimitive<F> {
+    ClosurePrimitive {
+        name: name,
+        function: function,
+    }
+}
+
 #[inline]
 pub fn primitive<F>(
     name: &'static str,
@@ -373,10 +449,46 @@ pub trait VmType {
 
     /// How many extra arguments a function returning this type requires.
     /// Used for abstract types which when used in return position should act like they still need
-    /// more arguments before they are called
+    /// more arguments before they are called.
+    ///
+    /// This is how `IO` represents suspended actions but any type can make use of it. A rust
+    /// function returning such a type gets `extra_args` added to its arity, so applying it to its
+    /// arguments from gluon only creates the action. The function itself runs once the action is
+    /// applied to the extra (unused) arguments which is what `Function::call` does when its
+    /// return type has extra arguments. A user defined effect therefore only needs a type
+    /// registered with `Thread::register_type` and a `VmType` implementation whose `make_type`
+    /// applies that type and whose `extra_args` returns `1`.
+    ///
+    /// `Function::call` always passes the arguments and the extra arguments in a single call. If
+    /// the called value takes fewer arguments than that (a gluon function returning an action or
+    /// a partially applied function) the result is called with the remaining arguments, the same
+    /// for closures and extern functions, so the extra arguments always reach the action no
+    /// matter how the function was written.
     fn extra_args() -> VmIndex {
         0
     }
+
+    /// Converts an error raised by a gluon function returning this type into a value.
+    /// By default the error is returned as is.
+    fn from_error(err: Error) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(err)
+    }
+}
+
+/// Pushes the message of `err` so that it can be returned along with `Status::Error`
+fn push_error(context: &mut Context, err: Error) -> Status {
+    // Only the message is passed on as the stacktrace is captured again when the error status is
+    // handled
+    let msg = match err {
+        Error::Panic(msg, _) => msg,
+        err => format!("{}", err),
+    };
+    let msg = unsafe { GcStr::from_utf8_unchecked(context.alloc_ignore_limit(msg.as_bytes())) };
+    context.stack.push(ValueRepr::String(msg));
+    Status::Error
 }
 
 /// Trait which allows a possibly asynchronous rust value to be pushed to the virtual machine
@@ -396,15 +508,7 @@ pub trait AsyncPushable<'vm> {
         match self.async_push(vm, context, lock) {
             Ok(Async::Ready(())) => Status::Ok,
             Ok(Async::NotReady) => Status::Yield,
-            Err(err) => {
-                let msg = unsafe {
-                    GcStr::from_utf8_unchecked(
-                        context.alloc_ignore_limit(format!("{}", err).as_bytes()),
-                    )
-                };
-                context.stack.push(ValueRepr::String(msg));
-                Status::Error
-            }
+            Err(err) => push_error(context, err),
         }
     }
 }
@@ -432,15 +536,7 @@ pub trait Pushable<'vm>: AsyncPushable<'vm> {
     {
         match self.push(vm, context) {
             Ok(()) => Status::Ok,
-            Err(err) => {
-                let msg = unsafe {
-                    GcStr::from_utf8_unchecked(
-                        context.alloc_ignore_limit(format!("{}", err).as_bytes()),
-                    )
-                };
-                context.stack.push(ValueRepr::String(msg));
-                Status::Error
-            }
+            Err(err) => push_error(context, err),
         }
     }
 
@@ -472,6 +568,18 @@ pub trait Getable<'vm>: Sized {
         Self::from_value(vm, value)
     }
     fn from_value(vm: &'vm Thread, value: Variants) -> Self;
+
+    /// Fallible version of `from_value_unsafe`
+    unsafe fn try_from_value_unsafe(vm: &'vm Thread, value: Variants) -> Result<Self> {
+        Ok(Self::from_value_unsafe(vm, value))
+    }
+
+    /// Fallible version of `from_value` for types which can only be extracted from some of the
+    /// values of their gluon type. Rust functions called from gluon return the error instead of
+    /// calling the function
+    fn try_from_value(vm: &'vm Thread, value: Variants) -> Result<Self> {
+        Ok(Self::from_value(vm, value))
+    }
 }
 
 pub fn convert<'vm, T, U>(thread: &'vm Thread, t: T) -> Result<U>
@@ -483,7 +591,277 @@ where
     t.push(thread, &mut context)?;
     unsafe {
         let value = context.stack.pop();
-        Ok(U::from_value(thread, Variants::new(&value)))
+        U::try_from_value(thread, Variants::new(&value))
+    }
+}
+
+/// Replaces the top `len` values of the stack with an array containing them.
+#[doc(hidden)]
+pub fn push_array(thread: &Thread, context: &mut Context, len: VmIndex) -> Result<()> {
+    let result = {
+        let Context {
+            ref mut gc,
+            ref stack,
+            ..
+        } = *context;
+        let values = &stack[stack.len() - len..];
+        thread::alloc(gc, thread, stack, ArrayDef(values))?
+    };
+    for _ in 0..len {
+        context.stack.pop();
+    }
+    context.stack.push(ValueRepr::Array(result));
+    Ok(())
+}
+
+/// Replaces the top `fields.len()` values of the stack with a record using `fields` as its field
+/// names.
+#[doc(hidden)]
+pub fn push_record(thread: &Thread, context: &mut Context, fields: &[&str]) -> Result<()> {
+    if fields.is_empty() {
+        context.stack.push(ValueRepr::Tag(0));
+        return Ok(());
+    }
+    let fields = fields
+        .iter()
+        .map(|field| thread.global_env().intern(field))
+        .collect::<Result<Vec<_>>>()?;
+    let len = fields.len() as VmIndex;
+    let value = {
+        let offset = context.stack.len() - len;
+        thread::alloc(
+            &mut context.gc,
+            thread,
+            &context.stack,
+            RecordDef {
+                elems: &context.stack[offset..],
+                fields: &fields,
+            },
+        )?
+    };
+    for _ in 0..len {
+        context.stack.pop();
+    }
+    context.stack.push(ValueRepr::Data(value));
+    Ok(())
+}
+
+/// A value created by a `ValueBuilder`. It may only be used with the builder which created it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BuiltValue(usize);
+
+/// Builds values directly on the heap of a thread without going through its stack.
+///
+/// Every value created by the builder stays rooted until the builder is finished (or dropped) so
+/// the values can be freely combined into larger values. Each allocation only borrows the context
+/// of the thread for the duration of the allocation.
+///
+/// ```ignore
+/// let builder = ValueBuilder::new(&thread);
+/// let name = builder.str("gluon")?;
+/// let version = builder.int(1);
+/// let record = builder.record(&[("name", name), ("version", version)])?;
+/// let value = builder.finish(record);
+/// ```
+pub struct ValueBuilder<'vm> {
+    thread: &'vm Thread,
+    values: RefCell<Vec<RootedValue<&'vm Thread>>>,
+}
+
+impl<'vm> ValueBuilder<'vm> {
+    pub fn new(thread: &'vm Thread) -> ValueBuilder<'vm> {
+        ValueBuilder {
+            thread,
+            values: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn root(&self, value: ValueRepr) -> BuiltValue {
+        let mut values = self.values.borrow_mut();
+        values.push(self.thread.root_value(value.into()));
+        BuiltValue(values.len() - 1)
+    }
+
+    fn get_values(&self, built: &[BuiltValue]) -> Vec<Value> {
+        let values = self.values.borrow();
+        built.iter().map(|value| values[value.0].get_value()).collect()
+    }
+
+    fn alloc<D>(&self, def: D) -> Result<GcPtr<D::Value>>
+    where
+        D: DataDef + Traverseable,
+        D::Value: Sized + Any,
+    {
+        self.thread.context().alloc_with(self.thread, def)
+    }
+
+    pub fn int(&self, i: VmInt) -> BuiltValue {
+        self.root(ValueRepr::Int(i))
+    }
+
+    pub fn float(&self, f: f64) -> BuiltValue {
+        self.root(ValueRepr::Float(f))
+    }
+
+    pub fn char(&self, c: char) -> BuiltValue {
+        self.root(ValueRepr::Char(c))
+    }
+
+    pub fn str(&self, s: &str) -> Result<BuiltValue> {
+        let s = unsafe { GcStr::from_utf8_unchecked(self.alloc(s.as_bytes())?) };
+        Ok(self.root(ValueRepr::String(s)))
+    }
+
+    /// Creates a variant with the tag `tag`. As in compiled code, variants without any fields are
+    /// represented by just their tag.
+    pub fn data(&self, tag: VmTag, fields: &[BuiltValue]) -> Result<BuiltValue> {
+        if fields.is_empty() {
+            return Ok(self.root(ValueRepr::Tag(tag)));
+        }
+        let elems = self.get_values(fields);
+        let data = self.alloc(Def {
+            tag: tag,
+            elems: &elems,
+        })?;
+        Ok(self.root(ValueRepr::Data(data)))
+    }
+
+    /// Creates a record. The fields must be given in the same order as they appear in the type of
+    /// the record.
+    pub fn record(&self, fields: &[(&str, BuiltValue)]) -> Result<BuiltValue> {
+        if fields.is_empty() {
+            return Ok(self.root(ValueRepr::Tag(0)));
+        }
+        let names = fields
+            .iter()
+            .map(|&(name, _)| self.thread.global_env().intern(name))
+            .collect::<Result<Vec<_>>>()?;
+        let elems = self.get_values(&fields.iter().map(|&(_, value)| value).collect::<Vec<_>>());
+        let data = self.alloc(RecordDef {
+            elems: &elems,
+            fields: &names,
+        })?;
+        Ok(self.root(ValueRepr::Data(data)))
+    }
+
+    pub fn array(&self, elems: &[BuiltValue]) -> Result<BuiltValue> {
+        let elems = self.get_values(elems);
+        let array = self.alloc(ArrayDef(&elems))?;
+        Ok(self.root(ValueRepr::Array(array)))
+    }
+
+    /// Releases all intermediate values, returning `value` rooted on its own
+    pub fn finish<T>(self, value: BuiltValue) -> RootedValue<T>
+    where
+        T: VmRoot<'vm>,
+    {
+        let ValueBuilder { thread, values } = self;
+        let value = values.borrow()[value.0].get_value();
+        // Roots are released in a stack like order so the intermediate values must be released
+        // before the result is rooted
+        drop(values);
+        thread.root_value(value)
+    }
+}
+
+/// Replaces the top `args` values of the stack with a variant constructed from them.
+/// Constructors without arguments are pushed as a plain tag just as the compiler does.
+#[doc(hidden)]
+pub fn push_variant(
+    thread: &Thread,
+    context: &mut Context,
+    tag: VmTag,
+    args: VmIndex,
+) -> Result<()> {
+    let value = if args == 0 {
+        ValueRepr::Tag(tag)
+    } else {
+        let offset = context.stack.len() - args;
+        let data = thread::alloc(
+            &mut context.gc,
+            thread,
+            &context.stack,
+            Def {
+                tag: tag,
+                elems: &context.stack[offset..],
+            },
+        )?;
+        for _ in 0..args {
+            context.stack.pop();
+        }
+        ValueRepr::Data(data)
+    };
+    context.stack.push(value);
+    Ok(())
+}
+
+/// Returns the name of the constructor of the variant type `type_name` which `value` were created
+/// with, or `None` if the constructor is not one of `constructors`. Errors if any of
+/// `constructors` is not a constructor of `type_name`, which happens if the type has changed
+/// since the caller was written.
+#[doc(hidden)]
+pub fn match_constructor<'c>(
+    thread: &Thread,
+    type_name: &str,
+    value: &Variants,
+    constructors: &[&'c str],
+) -> Result<Option<&'c str>> {
+    let env = thread.global_env().get_env();
+    let alias = env.find_type_info(type_name)?;
+    let type_constructors: Vec<&str> = match **alias.unresolved_type().remove_forall() {
+        Type::Variant(ref row) => row.row_iter().map(|field| field.name.as_ref()).collect(),
+        _ => {
+            return Err(Error::Message(format!(
+                "`{}` is not a variant type",
+                type_name
+            )))
+        }
+    };
+    if let Some(constructor) = constructors
+        .iter()
+        .find(|constructor| !type_constructors.contains(constructor))
+    {
+        return Err(Error::Message(format!(
+            "`{}` is not a constructor of `{}`, the constructors are: {}",
+            constructor,
+            type_name,
+            type_constructors.join(", ")
+        )));
+    }
+    let tag = match value.as_ref() {
+        ValueRef::Data(data) => data.tag(),
+        _ => {
+            return Err(Error::Message(format!(
+                "Expected a value of type `{}`",
+                type_name
+            )))
+        }
+    };
+    let name = type_constructors.get(tag as usize).ok_or_else(|| {
+        Error::Message(format!("`{}` does not have a constructor with tag {}", type_name, tag))
+    })?;
+    Ok(constructors
+        .iter()
+        .find(|constructor| *constructor == name)
+        .cloned())
+}
+
+/// Converts the argument at `index` of the variant `value`. Used by `match_variant!`.
+#[doc(hidden)]
+pub fn variant_argument<'vm, T>(thread: &'vm Thread, value: &Variants, index: usize) -> Result<T>
+where
+    T: Getable<'vm>,
+{
+    let arg = match value.as_ref() {
+        ValueRef::Data(data) => data.get_variant(index),
+        _ => None,
+    };
+    match arg {
+        Some(arg) => T::try_from_value(thread, arg),
+        None => Err(Error::Message(format!(
+            "The constructor does not have an argument at index {}",
+            index
+        ))),
     }
 }
 
@@ -496,6 +874,65 @@ impl<'vm, T: vm::Userdata> Pushable<'vm> for T {
     }
 }
 
+/// Userdata which was pushed as an `Arc<T>`. Pushing the same `Arc` multiple times, or copying
+/// the value to another thread, shares the `T` instead of boxing a new copy of it.
+#[derive(Debug)]
+pub(crate) struct SharedUserdata<T>(Arc<T>);
+
+impl<T: Traverseable> Traverseable for SharedUserdata<T> {
+    fn traverse(&self, gc: &mut Gc) {
+        self.0.traverse(gc)
+    }
+}
+
+impl<T: vm::Userdata> vm::Userdata for SharedUserdata<T> {
+    fn deep_clone(&self, deep_cloner: &mut Cloner) -> Result<GcPtr<Box<vm::Userdata>>> {
+        let data: Box<vm::Userdata> = Box::new(SharedUserdata(self.0.clone()));
+        deep_cloner.gc().alloc(Move(data))
+    }
+
+    fn finalizer(&mut self) -> Option<DeferredCall> {
+        // Only the last copy of the value may finalize it
+        Arc::get_mut(&mut self.0).and_then(|data| data.finalizer())
+    }
+}
+
+/// Downcasts `data` to `T`, looking through userdata which was pushed as an `Arc<T>`
+pub(crate) fn downcast_userdata<T: vm::Userdata>(data: &vm::Userdata) -> Option<&T> {
+    data.downcast_ref::<T>().or_else(|| {
+        data.downcast_ref::<SharedUserdata<T>>()
+            .map(|shared| &*shared.0)
+    })
+}
+
+impl<T: VmType> VmType for Arc<T> {
+    type Type = T::Type;
+    fn make_type(vm: &Thread) -> ArcType {
+        T::make_type(vm)
+    }
+}
+
+impl<'vm, T: vm::Userdata> Pushable<'vm> for Arc<T> {
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        let data: Box<vm::Userdata> = Box::new(SharedUserdata(self));
+        let userdata = context.alloc_with(thread, Move(data))?;
+        context.stack.push(ValueRepr::Userdata(userdata));
+        Ok(())
+    }
+}
+
+impl<'vm, T: vm::Userdata> Getable<'vm> for Arc<T> {
+    fn from_value(_: &'vm Thread, value: Variants) -> Arc<T> {
+        match value.as_ref() {
+            ValueRef::Userdata(data) => match data.downcast_ref::<SharedUserdata<T>>() {
+                Some(shared) => shared.0.clone(),
+                None => ice!("Userdata was not pushed as an `Arc`"),
+            },
+            _ => ice!("ValueRef is not an Userdata"),
+        }
+    }
+}
+
 impl<'vm> Getable<'vm> for Value {
     fn from_value(_vm: &'vm Thread, value: Variants) -> Self {
         value.get_value()
@@ -523,6 +960,130 @@ where
     }
 }
 
+/// Userdata which can be mutated by rust functions. The value is guarded by a lock so any attempt
+/// to access it while it is already locked, be it from another gluon thread or a recursive call,
+/// results in an error instead of a data race.
+///
+/// Rust functions take a `UserdataMutGuard` argument to get mutable access to the value for the
+/// duration of the call.
+///
+/// ```rust,ignore
+/// fn increment(mut counter: UserdataMutGuard<Counter>) -> VmInt {
+///     counter.0 += 1;
+///     counter.0
+/// }
+/// ```
+///
+/// Copying a `UserdataMut` to another thread or into a global shares the value and its lock.
+/// The garbage collector does not look inside a `UserdataMut` so the value it contains must not
+/// refer to any gluon values.
+#[derive(Debug)]
+pub struct UserdataMut<T>(Arc<Mutex<T>>);
+
+impl<T> UserdataMut<T> {
+    pub fn new(value: T) -> UserdataMut<T> {
+        UserdataMut(Arc::new(Mutex::new(value)))
+    }
+
+    /// Acquires mutable access to the value. Returns an error if the value is already locked.
+    pub fn lock(&self) -> Result<MutexGuard<T>> {
+        self.0.try_lock().map_err(|err| match err {
+            TryLockError::WouldBlock => {
+                Error::Message("Userdata is already borrowed mutably".to_string())
+            }
+            TryLockError::Poisoned(_) => {
+                Error::Message("Userdata was poisoned by a panic".to_string())
+            }
+        })
+    }
+}
+
+impl<T> Traverseable for UserdataMut<T> {}
+
+impl<T: fmt::Debug + Send + Any> vm::Userdata for UserdataMut<T> {
+    fn deep_clone(&self, deep_cloner: &mut Cloner) -> Result<GcPtr<Box<vm::Userdata>>> {
+        let data: Box<vm::Userdata> = Box::new(UserdataMut(self.0.clone()));
+        deep_cloner.gc().alloc(Move(data))
+    }
+}
+
+impl<T: Any> VmType for UserdataMut<T> {
+    type Type = Self;
+}
+
+/// Mutable access to the value of a `UserdataMut<T>` passed to a rust function. The lock is
+/// acquired before the function is called, failing the call if the value is already locked, and
+/// is released once the guard is dropped.
+pub struct UserdataMutGuard<'vm, T: 'vm>(MutexGuard<'vm, T>);
+
+impl<'vm, T> Deref for UserdataMutGuard<'vm, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<'vm, T> DerefMut for UserdataMutGuard<'vm, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<'vm, T: Any> VmType for UserdataMutGuard<'vm, T> {
+    type Type = UserdataMut<T>;
+}
+
+impl<'vm, T> Getable<'vm> for UserdataMutGuard<'vm, T>
+where
+    T: fmt::Debug + Send + Any,
+{
+    unsafe fn from_value_unsafe(vm: &'vm Thread, value: Variants) -> Self {
+        match Self::try_from_value_unsafe(vm, value) {
+            Ok(guard) => guard,
+            Err(err) => panic!("{}", err),
+        }
+    }
+    unsafe fn try_from_value_unsafe(vm: &'vm Thread, value: Variants) -> Result<Self> {
+        let data = <&'vm UserdataMut<T> as Getable<'vm>>::from_value_unsafe(vm, value);
+        data.lock().map(UserdataMutGuard)
+    }
+    // Only allow the unsafe version to be used
+    fn from_value(_vm: &'vm Thread, _value: Variants) -> Self {
+        ice!("Getable::from_value usage")
+    }
+}
+
+/// Wrapper which retrieves an owned copy of userdata by cloning it, letting the value be kept
+/// after the call which received it has returned. Pushing a `UserdataValue` pushes the contained
+/// value as new userdata.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserdataValue<T>(pub T);
+
+impl<T: VmType> VmType for UserdataValue<T> {
+    type Type = T::Type;
+    fn make_type(vm: &Thread) -> ArcType {
+        T::make_type(vm)
+    }
+}
+
+impl<'vm, T: vm::Userdata> Pushable<'vm> for UserdataValue<T> {
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        self.0.push(thread, context)
+    }
+}
+
+impl<'vm, T: vm::Userdata + Clone> Getable<'vm> for UserdataValue<T> {
+    fn from_value(_: &'vm Thread, value: Variants) -> UserdataValue<T> {
+        match value.as_ref() {
+            ValueRef::Userdata(data) => match downcast_userdata::<T>(data) {
+                Some(data) => UserdataValue(data.clone()),
+                None => ice!("Userdata does not have the expected type"),
+            },
+            _ => ice!("ValueRef is not an Userdata"),
+        }
+    }
+}
+
 impl<'vm> Getable<'vm> for &'vm str {
     fn from_value(_vm: &'vm Thread, value: Variants) -> Self {
         unsafe {
@@ -534,6 +1095,59 @@ impl<'vm> Getable<'vm> for &'vm str {
     }
 }
 
+/// Borrowed bytes of an `Array Byte` or `String` value. Unlike `Vec<u8>` no copy is made when
+/// retrieving the bytes, the slice refers directly to the value in the virtual machine the same
+/// way `&str` does. Pushing `Bytes` pushes an `Array Byte`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bytes<'vm>(pub &'vm [u8]);
+
+impl<'vm> Deref for Bytes<'vm> {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl<'vm> VmType for Bytes<'vm> {
+    type Type = &'static [u8];
+
+    fn make_type(vm: &Thread) -> ArcType {
+        <&[u8]>::make_type(vm)
+    }
+}
+
+impl<'vm, 's> Pushable<'vm> for Bytes<'s> {
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        self.0.push(thread, context)
+    }
+}
+
+impl<'vm> Getable<'vm> for Bytes<'vm> {
+    fn from_value(vm: &'vm Thread, value: Variants) -> Self {
+        match Self::try_from_value(vm, value) {
+            Ok(bytes) => bytes,
+            Err(err) => ice!("{}", err),
+        }
+    }
+
+    fn try_from_value(_vm: &'vm Thread, value: Variants) -> Result<Self> {
+        unsafe {
+            match value.as_ref() {
+                ValueRef::Array(array) => match array.as_slice::<u8>() {
+                    Some(bytes) => Ok(Bytes(forget_lifetime(bytes))),
+                    None if array.len() == 0 => Ok(Bytes(&[])),
+                    None => Err(Error::Message("Array does not contain bytes".to_string())),
+                },
+                ValueRef::String(s) => Ok(Bytes(forget_lifetime(s.as_bytes()))),
+                _ => Err(Error::Message(format!(
+                    "Expected an `Array Byte` or a `String`, found {:?}",
+                    value.as_ref()
+                ))),
+            }
+        }
+    }
+}
+
 /// Wrapper type which passes acts as the type `T` but also passes the `VM` to the called function
 pub struct WithVM<'vm, T> {
     pub vm: &'vm Thread,
@@ -577,6 +1191,16 @@ where
         let t = T::from_value(vm, value);
         WithVM { vm, value: t }
     }
+
+    unsafe fn try_from_value_unsafe(vm: &'vm Thread, value: Variants) -> Result<WithVM<'vm, T>> {
+        let t = T::try_from_value_unsafe(vm, value)?;
+        Ok(WithVM { vm, value: t })
+    }
+
+    fn try_from_value(vm: &'vm Thread, value: Variants) -> Result<WithVM<'vm, T>> {
+        let t = T::try_from_value(vm, value)?;
+        Ok(WithVM { vm, value: t })
+    }
 }
 
 impl VmType for () {
@@ -612,22 +1236,74 @@ impl<'vm> Getable<'vm> for u8 {
     }
 }
 
+// The conversions are checked as the width of `VmInt` depends on the target (and the `vm64`
+// feature) so a value which fits on one target may not fit on another
+/// Conversions between `VmInt` and the other integer types which fail if the value does not fit
+trait IntConversion: Sized {
+    fn from_vm_int(i: VmInt) -> Option<Self>;
+    fn to_vm_int(self) -> Option<VmInt>;
+}
+
 macro_rules! int_impls {
     ($($id: ident)*) => {
         $(
+        impl IntConversion for $id {
+            fn from_vm_int(i: VmInt) -> Option<Self> {
+                // The value fits if it survives a round trip without changing its sign
+                let converted = i as $id;
+                if converted as VmInt == i && (i < 0) == (converted < (0 as $id)) {
+                    Some(converted)
+                } else {
+                    None
+                }
+            }
+
+            fn to_vm_int(self) -> Option<VmInt> {
+                let converted = self as VmInt;
+                if converted as $id == self && (self < (0 as $id)) == (converted < 0) {
+                    Some(converted)
+                } else {
+                    None
+                }
+            }
+        }
         impl VmType for $id {
             type Type = VmInt;
         }
         impl<'vm> Pushable<'vm> for $id {
             fn push(self, _: &'vm Thread, context: &mut Context) -> Result<()> {
-                context.stack.push(ValueRepr::Int(self as VmInt));
+                let i = self.to_vm_int().ok_or_else(|| {
+                    Error::Message(format!(
+                        "`{}` does not fit in an Int ({} bits)",
+                        self,
+                        mem::size_of::<VmInt>() * 8
+                    ))
+                })?;
+                context.stack.push(ValueRepr::Int(i));
                 Ok(())
             }
         }
         impl<'vm> Getable<'vm> for $id {
-            fn from_value(_: &'vm Thread, value: Variants) -> Self {
+            fn from_value(vm: &'vm Thread, value: Variants) -> Self {
+                match Self::try_from_value(vm, value) {
+                    Ok(i) => i,
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            unsafe fn try_from_value_unsafe(vm: &'vm Thread, value: Variants) -> Result<Self> {
+                Self::try_from_value(vm, value)
+            }
+
+            fn try_from_value(_: &'vm Thread, value: Variants) -> Result<Self> {
                 match value.as_ref() {
-                    ValueRef::Int(i) => i as $id,
+                    ValueRef::Int(i) => $id::from_vm_int(i).ok_or_else(|| {
+                        Error::Message(format!(
+                            "Int `{}` does not fit in `{}`",
+                            i,
+                            stringify!($id)
+                        ))
+                    }),
                     _ => ice!("expected ValueRef to be an Int, got {:?}", value.as_ref()),
                 }
             }
@@ -638,6 +1314,124 @@ macro_rules! int_impls {
 
 int_impls!{ i16 i32 i64 u16 u32 u64 usize isize }
 
+/// Wrapper which lets an integer be retrieved from a `Float` as well as from an `Int`, as long as
+/// the float is integral and fits in the integer type (so `3.0` is accepted but `3.5` and `1e20`
+/// are not for `i32`). On the gluon side `Lossy<T>` has the same type as `T`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Lossy<T>(pub T);
+
+/// Wrapper which documents that an integer may only be retrieved from an `Int`. This is the
+/// behaviour of the integer types themselves, `Strict<T>` only makes it explicit in signatures.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Strict<T>(pub T);
+
+impl<T> Deref for Lossy<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> Deref for Strict<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: VmType> VmType for Strict<T> {
+    type Type = T::Type;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        T::make_type(vm)
+    }
+}
+
+impl<'vm, T: Pushable<'vm>> Pushable<'vm> for Strict<T> {
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        self.0.push(thread, context)
+    }
+}
+
+impl<'vm, T: Getable<'vm>> Getable<'vm> for Strict<T> {
+    fn from_value(vm: &'vm Thread, value: Variants) -> Self {
+        Strict(T::from_value(vm, value))
+    }
+
+    unsafe fn try_from_value_unsafe(vm: &'vm Thread, value: Variants) -> Result<Self> {
+        T::try_from_value_unsafe(vm, value).map(Strict)
+    }
+
+    fn try_from_value(vm: &'vm Thread, value: Variants) -> Result<Self> {
+        T::try_from_value(vm, value).map(Strict)
+    }
+}
+
+macro_rules! lossy_int_impls {
+    ($($id: ident)*) => {
+        $(
+        impl Lossy<$id> {
+            /// Converts `f` if it is integral and fits in the integer type
+            pub fn try_from_float(f: f64) -> Result<Self> {
+                // `min_value()` and `max_value() + 1` are (zero or) powers of two so they are
+                // represented exactly as floats, unlike `max_value()`
+                let min = $id::min_value() as f64;
+                let end = ($id::max_value() / 2 + 1) as f64 * 2.0;
+                if f.fract() == 0.0 && min <= f && f < end {
+                    Ok(Lossy(f as $id))
+                } else {
+                    Err(Error::Message(format!(
+                        "Float `{}` can't be converted to `{}` without loss",
+                        f,
+                        stringify!($id)
+                    )))
+                }
+            }
+
+            /// Converts `i` if it fits in the integer type
+            pub fn try_from_int(i: VmInt) -> Result<Self> {
+                $id::from_vm_int(i).map(Lossy).ok_or_else(|| {
+                    Error::Message(format!("Int `{}` does not fit in `{}`", i, stringify!($id)))
+                })
+            }
+        }
+        impl VmType for Lossy<$id> {
+            type Type = VmInt;
+        }
+        impl<'vm> Pushable<'vm> for Lossy<$id> {
+            fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+                self.0.push(thread, context)
+            }
+        }
+        impl<'vm> Getable<'vm> for Lossy<$id> {
+            fn from_value(vm: &'vm Thread, value: Variants) -> Self {
+                match Self::try_from_value(vm, value) {
+                    Ok(i) => i,
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            unsafe fn try_from_value_unsafe(vm: &'vm Thread, value: Variants) -> Result<Self> {
+                Self::try_from_value(vm, value)
+            }
+
+            fn try_from_value(_: &'vm Thread, value: Variants) -> Result<Self> {
+                match value.as_ref() {
+                    ValueRef::Int(i) => Lossy::<$id>::try_from_int(i),
+                    ValueRef::Float(f) => Lossy::<$id>::try_from_float(f),
+                    _ => Err(Error::Message(format!(
+                        "Expected an `Int` or a `Float`, found {:?}",
+                        value.as_ref()
+                    ))),
+                }
+            }
+        }
+        )*
+    };
+}
+
+lossy_int_impls!{ i16 i32 i64 u16 u32 u64 usize isize }
+
 impl VmType for f64 {
     type Type = Self;
 }
@@ -753,25 +1547,34 @@ impl VmType for char {
 }
 impl<'vm> Pushable<'vm> for char {
     fn push(self, _: &'vm Thread, context: &mut Context) -> Result<()> {
-        context.stack.push(ValueRepr::Int(self as VmInt));
+        context.stack.push(ValueRepr::Char(self));
         Ok(())
     }
 }
 impl<'vm> Getable<'vm> for char {
     fn from_value(_: &'vm Thread, value: Variants) -> char {
         match value.as_ref() {
-            ValueRef::Int(x) => match ::std::char::from_u32(x as u32) {
+            ValueRef::Char(ch) => ch,
+            // Characters used to be pushed as integers, accept those as long as they are valid
+            // code points
+            ValueRef::Int(x) => match char_from_int(x) {
                 Some(ch) => ch,
                 None => ice!("Failed conversion from Int to char for: {}", x),
             },
             _ => ice!(
-                "expected ValueRef to be an Int (char), got {:?}",
+                "expected ValueRef to be a Char, got {:?}",
                 value.as_ref()
             ),
         }
     }
 }
 
+/// Converts an integer to the character with that code point. Unlike `char::from_u32` this does
+/// not truncate integers which are out of range before converting them.
+pub(crate) fn char_from_int(i: VmInt) -> Option<char> {
+    u32::from_vm_int(i).and_then(::std::char::from_u32)
+}
+
 impl<'s, T: VmType> VmType for Ref<'s, T> {
     type Type = T::Type;
     fn make_type(vm: &Thread) -> ArcType {
@@ -850,20 +1653,7 @@ where
                 return Err(Error::Message("Push error".into()));
             }
         }
-        let result = {
-            let Context {
-                ref mut gc,
-                ref stack,
-                ..
-            } = *context;
-            let values = &stack[stack.len() - len..];
-            thread::alloc(gc, thread, stack, ArrayDef(values))?
-        };
-        for _ in 0..len {
-            context.stack.pop();
-        }
-        context.stack.push(ValueRepr::Array(result));
-        Ok(())
+        push_array(thread, context, len)
     }
 }
 
@@ -878,7 +1668,7 @@ impl<'vm, T: vm::Userdata> Getable<'vm> for *const T {
     fn from_value(_: &'vm Thread, value: Variants) -> *const T {
         match value.as_ref() {
             ValueRef::Userdata(data) => {
-                let x = data.downcast_ref::<T>().unwrap();
+                let x = downcast_userdata::<T>(data).unwrap();
                 x as *const T
             }
             _ => ice!("ValueRef is not an Userdata"),
@@ -1091,7 +1881,10 @@ impl<'vm, T: Pushable<'vm>, E: fmt::Display> Pushable<'vm> for RuntimeResult<T,
     fn push(self, vm: &'vm Thread, context: &mut Context) -> Result<()> {
         match self {
             RuntimeResult::Return(value) => value.push(vm, context),
-            RuntimeResult::Panic(err) => Err(Error::Message(format!("{}", err))),
+            RuntimeResult::Panic(err) => Err(Error::Panic(
+                format!("{}", err),
+                context.stack.stacktrace(0).with_thread_name(vm.name()),
+            )),
         }
     }
 }
@@ -1110,6 +1903,15 @@ where
     fn extra_args() -> VmIndex {
         1
     }
+    /// Exceptions thrown by an action are returned as `IO::Exception`. Interrupting or cancelling
+    /// the thread is not an exception and is returned as an error
+    fn from_error(err: Error) -> Result<Self> {
+        Ok(IO::Exception(match err {
+            Error::Interrupted | Error::Cancelled => return Err(err),
+            Error::Panic(msg, _) => msg,
+            err => err.to_string(),
+        }))
+    }
 }
 
 impl<'vm, T: Getable<'vm>> Getable<'vm> for IO<T> {
@@ -1151,7 +1953,7 @@ where
     T: Deref<Target = Thread>,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self.0)
+        write!(f, "{}", self.vm().format_value(&self.get_variant(), None))
     }
 }
 
@@ -1164,6 +1966,35 @@ where
     }
 }
 
+/// Compares the contents of the values. Userdata, threads and functions are only equal to
+/// themselves.
+impl<T, V> PartialEq for OpaqueValue<T, V>
+where
+    T: Deref<Target = Thread>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        ::value::structural_eq(self.get_variant().0, other.get_variant().0)
+    }
+}
+
+impl<T, V> Eq for OpaqueValue<T, V>
+where
+    T: Deref<Target = Thread>,
+{
+}
+
+impl<T, V> Hash for OpaqueValue<T, V>
+where
+    T: Deref<Target = Thread>,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        ::value::structural_hash(self.get_variant().0, state)
+    }
+}
+
 impl<T, V> OpaqueValue<T, V>
 where
     T: Deref<Target = Thread>,
@@ -1192,6 +2023,54 @@ where
     pub fn get_ref(&self) -> ValueRef {
         self.0.get_variant().as_ref()
     }
+
+    /// Converts the value into a `Function` if it is a function which takes as many arguments as
+    /// `F`, otherwise the value is returned unchanged
+    pub fn into_function<F>(self) -> StdResult<Function<T, F>, Self>
+    where
+        F: FunctionType,
+    {
+        if function_arity(&self.0.get_value()) == Some(F::arguments()) {
+            Ok(Function {
+                value: self.0,
+                _marker: PhantomData,
+            })
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Returns a `Function` referring to the value if it is a function which takes as many
+    /// arguments as `F`
+    pub fn as_function<F>(&self) -> Option<Function<T, F>>
+    where
+        T: Clone,
+        F: FunctionType,
+    {
+        self.clone().into_function().ok()
+    }
+}
+
+/// Returns how many more arguments `value` needs before it is called, or `None` if it is not a
+/// function
+fn function_arity(value: &Value) -> Option<VmIndex> {
+    match value.get_repr() {
+        ValueRepr::Function(function) => Some(function.args),
+        ValueRepr::Closure(closure) => Some(closure.function.args),
+        ValueRepr::PartialApplication(app) => {
+            Some(app.function.args() - app.args.len() as VmIndex)
+        }
+        _ => None,
+    }
+}
+
+impl<T, V> Traverseable for OpaqueValue<T, V>
+where
+    T: Deref<Target = Thread>,
+{
+    fn traverse(&self, gc: &mut Gc) {
+        self.0.traverse(gc);
+    }
 }
 
 impl<T, V> VmType for OpaqueValue<T, V>
@@ -1243,7 +2122,11 @@ impl<'vm, V> Getable<'vm> for OpaqueValue<RootedThread, V> {
 pub struct ArrayRef<'vm>(&'vm ValueArray);
 
 impl<'vm> ArrayRef<'vm> {
-    pub fn get(&self, index: usize) -> Option<Variants> {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<Variants<'vm>> {
         if index < self.0.len() {
             Some(self.0.get(index))
         } else {
@@ -1271,27 +2154,45 @@ impl<'vm, T> Array<'vm, T> {
         self.0.vm_()
     }
 
+    /// Returns the length of the array or 0 if the value is not an array
     pub fn len(&self) -> usize {
-        self.get_value_array().len()
+        self.get_value_array().map(|array| array.len()).unwrap_or(0)
     }
 
     #[doc(hidden)]
-    pub fn get_value_array(&self) -> &ValueArray {
+    pub fn get_value_array(&self) -> Result<&ValueArray> {
         match self.0.get_variant().as_ref() {
-            ValueRef::Array(ref array) => &array.0,
-            _ => ice!("Expected an array found {:?}", self.0),
+            ValueRef::Array(ref array) => Ok(&array.0),
+            _ => Err(Error::Message(format!(
+                "Expected an array found {:?}",
+                self.0
+            ))),
         }
     }
 }
 
 impl<'vm, T: for<'vm2> Getable<'vm2>> Array<'vm, T> {
+    /// Returns the element at `index` or `None` if `index` is out of bounds or the element could
+    /// not be converted to `T`
     pub fn get(&self, index: VmInt) -> Option<T> {
+        let index = usize::from_vm_int(index)?;
+        self.get_checked(index).ok().and_then(|value| value)
+    }
+
+    /// Returns the element at `index` or `Ok(None)` if `index` is out of bounds.
+    ///
+    /// Returns an error if the value is not an array or if the element could not be converted to
+    /// `T`.
+    pub fn get_checked(&self, index: usize) -> Result<Option<T>> {
         match self.0.get_variant().as_ref() {
-            ValueRef::Array(data) => {
-                let v = data.get(index as usize).unwrap();
-                Some(T::from_value(self.0.vm(), v))
-            }
-            _ => None,
+            ValueRef::Array(data) => match data.get(index) {
+                Some(value) => T::try_from_value(self.0.vm(), value).map(Some),
+                None => Ok(None),
+            },
+            _ => Err(Error::Message(format!(
+                "Expected an array found {:?}",
+                self.0
+            ))),
         }
     }
 }
@@ -1379,6 +2280,20 @@ where
     }
 }
 
+/// Retrieves the field at `index` of a tuple.
+///
+/// Tuples created in gluon are records with the fields `_0`, `_1`, ... which are not guaranteed
+/// to be stored in that order (`{ _1 = "x", _0 = 1 }`, `{ .. base }`), so records have their
+/// fields looked up by name. Values without field names are accessed by position.
+fn tuple_field<'a>(vm: &Thread, data: &Data<'a>, index: usize) -> Option<Variants<'a>> {
+    match data.0 {
+        DataInner::Data(inner) if inner.is_record() => data
+            .lookup_field(vm, &format!("_{}", index))
+            .or_else(|| data.get_variant(index)),
+        _ => data.get_variant(index),
+    }
+}
+
 macro_rules! define_tuple {
     ($($id: ident)+) => {
         impl<$($id),+> VmType for ($($id),+)
@@ -1405,7 +2320,11 @@ macro_rules! define_tuple {
                         assert!(v.len() == count!($($id),+));
                         let mut i = 0;
                         ( $(
-                            { let a = $id::from_value(vm, v.get_variant(i).unwrap()); i += 1; a }
+                            {
+                                let a = $id::from_value(vm, tuple_field(vm, &v, i).unwrap());
+                                i += 1;
+                                a
+                            }
                         ),+ )
                     }
                     _ => ice!("ValueRef is not a Tuple"),
@@ -1611,7 +2530,7 @@ where
             Move(ExternFunction {
                 id: id,
                 args: F::arguments(),
-                function: self.function,
+                function: ExternCall::Fn(self.function),
             }),
         )?);
         context.stack.push(value);
@@ -1647,10 +2566,41 @@ where
     }
 }
 
+impl<F: ?Sized + VmType> VmType for ClosurePrimitive<F> {
+    type Type = F::Type;
+    fn make_type(vm: &Thread) -> ArcType {
+        F::make_type(vm)
+    }
+}
+
+impl<'vm, F: ?Sized> Pushable<'vm> for ClosurePrimitive<F>
+where
+    F: for<'t> VmFunction<'t> + FunctionType + Send + Sync + 'static,
+{
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        let id = Symbol::from(self.name);
+        let function = self.function;
+        let value = ValueRepr::Function(context.alloc_with(
+            thread,
+            Move(ExternFunction {
+                id: id,
+                args: F::arguments(),
+                function: ExternCall::Closure(Arc::new(move |thread: &Thread| {
+                    function.unpack_and_call(thread)
+                })),
+            }),
+        )?);
+        context.stack.push(value);
+        Ok(())
+    }
+}
+
 pub struct CPrimitive {
     function: GluonFunction,
     args: VmIndex,
+    extra_args: VmIndex,
     id: Symbol,
+    typ: Option<ArcType>,
 }
 
 impl CPrimitive {
@@ -1659,13 +2609,53 @@ impl CPrimitive {
             id: Symbol::from(id),
             function: function,
             args: args,
+            extra_args: 0,
+            typ: None,
         }
     }
+
+    /// Marks the function as returning an `IO` action. As with rust functions returning `IO` the
+    /// function is then called with an extra argument after the `args` it was created with.
+    pub fn returns_io(mut self) -> CPrimitive {
+        self.extra_args = IO::<()>::extra_args();
+        self
+    }
+
+    /// Sets the type the function is declared with. Pushing the function fails if its number of
+    /// arguments does not match what the type requires.
+    pub fn with_type(mut self, typ: ArcType) -> CPrimitive {
+        self.typ = Some(typ);
+        self
+    }
+}
+
+/// Returns the number of arguments an extern function with the type `typ` is called with
+fn declared_arguments(typ: &ArcType) -> VmIndex {
+    let mut iter = types::arg_iter(typ.remove_forall());
+    let args = iter.by_ref().count() as VmIndex;
+    let returns_io = iter.typ
+        .name()
+        .map_or(false, |name| name.definition_name() == "IO");
+    if returns_io {
+        args + IO::<()>::extra_args()
+    } else {
+        args
+    }
 }
 
 impl<'vm> Pushable<'vm> for CPrimitive {
     fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
         use std::mem::transmute;
+        let args = self.args + self.extra_args;
+        if let Some(ref typ) = self.typ {
+            let expected = declared_arguments(typ);
+            if args != expected {
+                return Err(Error::Message(format!(
+                    "`{}` takes {} arguments but its type `{}` requires {}",
+                    self.id, args, typ, expected
+                )));
+            }
+        }
         let function = self.function;
         let extern_function = unsafe {
             // The VM guarantess that it only ever calls this function with itself which should
@@ -1678,8 +2668,8 @@ impl<'vm> Pushable<'vm> for CPrimitive {
             thread,
             Move(ExternFunction {
                 id: self.id,
-                args: self.args,
-                function: extern_function,
+                args: args,
+                function: ExternCall::Fn(extern_function),
             }),
         )?;
         context.stack.push(ValueRepr::Function(value));
@@ -1729,6 +2719,153 @@ where
     pub fn vm(&self) -> &Thread {
         self.value.vm()
     }
+
+    /// Forgets that the value is a function, the inverse of `OpaqueValue::into_function`
+    pub fn into_opaque(self) -> OpaqueValue<T, F> {
+        OpaqueValue::from_value(self.value)
+    }
+}
+
+impl<T, F> Function<T, F>
+where
+    T: Deref<Target = Thread>,
+    F: VmType,
+{
+    /// Calls the function with arguments which have already been converted to gluon values and
+    /// returns the result without converting it.
+    ///
+    /// The number of arguments is checked against the type of the function, `F`. If `F` is not a
+    /// function type (such as `Hole`) the number of arguments the function value takes is used
+    /// instead. A function returning `IO` is passed the extra argument needed to run the action
+    /// (see `VmType::extra_args`) so the result of the action is returned.
+    pub fn call_any(
+        &self,
+        args: &[OpaqueValue<RootedThread, Hole>],
+    ) -> Result<OpaqueValue<RootedThread, Hole>> {
+        match self.call_any_first(args)? {
+            Async::Ready(value) => Ok(value),
+            Async::NotReady => Err(Error::Message("Unexpected async".into())),
+        }
+    }
+
+    /// Like `call_any` but the function may yield, in which case the returned future resolves
+    /// once the call has finished
+    pub fn call_any_async(
+        &self,
+        args: &[OpaqueValue<RootedThread, Hole>],
+    ) -> Box<Future<Item = OpaqueValue<RootedThread, Hole>, Error = Error> + Send + Sync + 'static>
+    {
+        use futures::IntoFuture;
+        use thread::Execute;
+
+        let vm = self.value.vm().root_thread();
+        let frame_level = vm.context().stack.get_frames().len();
+        match self.call_any_first(args) {
+            Ok(Async::Ready(value)) => Box::new(Ok(value).into_future()),
+            Ok(Async::NotReady) => Box::new(Execute::new(vm.clone()).then(move |result| {
+                match result {
+                    Ok((vm, value)) => Ok(OpaqueValue::from_value(vm.root_value(value))),
+                    Err(err) => {
+                        exit_frames(&vm, frame_level);
+                        Err(err)
+                    }
+                }
+            })),
+            Err(err) => Box::new(Err(err).into_future()),
+        }
+    }
+
+    fn call_any_first(
+        &self,
+        args: &[OpaqueValue<RootedThread, Hole>],
+    ) -> Result<Async<OpaqueValue<RootedThread, Hole>>> {
+        let vm = self.value.vm();
+        let (arity, extra_args) = self.any_arguments(vm);
+        if args.len() != arity as usize {
+            return Err(Error::Message(format!(
+                "Expected {} argument(s) but {} were given",
+                arity,
+                args.len()
+            )));
+        }
+        let mut context = vm.context();
+        let frame_level = context.stack.get_frames().len();
+        context.stack.push(self.value.get_variant());
+        for arg in args {
+            arg.clone().push(vm, &mut context)?;
+        }
+        for _ in 0..extra_args {
+            0.push(vm, &mut context)?;
+        }
+        match vm.call_function(context, arity + extra_args) {
+            Ok(Async::Ready(context)) => {
+                let value = context.unwrap().stack.pop();
+                Ok(Async::Ready(OpaqueValue::from_value(vm.root_value(value))))
+            }
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(err) => {
+                exit_frames(vm, frame_level);
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the number of arguments the function takes and the number of extra arguments its
+    /// result needs
+    fn any_arguments(&self, vm: &Thread) -> (VmIndex, VmIndex) {
+        use base::resolve::remove_aliases_cow;
+
+        let env = vm.get_env();
+        let mut arity = 0;
+        let mut ret = F::make_type(vm);
+        loop {
+            let next = match remove_aliases_cow(&*env, types::remove_forall(&ret)).as_function() {
+                Some((_, next)) => next.clone(),
+                None => break,
+            };
+            arity += 1;
+            ret = next;
+        }
+
+        match **types::remove_forall(&ret) {
+            Type::Hole | Type::Generic(_) | Type::Variable(_) if arity == 0 => {
+                return (function_arity(&self.value.get_value()).unwrap_or(0), 0)
+            }
+            Type::App(ref f, _) => {
+                let io = env.find_type_info("IO").ok();
+                let name = match **f {
+                    Type::Alias(ref alias) => Some(&alias.name),
+                    Type::Ident(ref id) => Some(id),
+                    _ => None,
+                };
+                match (io, name) {
+                    (Some(ref io), Some(name)) if io.name == *name => {
+                        (arity, IO::<Hole>::extra_args())
+                    }
+                    _ => (arity, 0),
+                }
+            }
+            _ => (arity, 0),
+        }
+    }
+}
+
+impl<T, F> fmt::Debug for Function<T, F>
+where
+    T: Deref<Target = Thread>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.value)
+    }
+}
+
+impl<T, F> Traverseable for Function<T, F>
+where
+    T: Deref<Target = Thread>,
+{
+    fn traverse(&self, gc: &mut Gc) {
+        self.value.traverse(gc);
+    }
 }
 
 impl<T, F> Clone for Function<T, F>
@@ -1828,6 +2965,66 @@ where
     }
 }
 
+/// Removes the frames above `frame_level` which were left behind by a call which failed part way
+/// through so that the thread can be used again
+fn exit_frames(vm: &Thread, frame_level: usize) {
+    let mut context = vm.context();
+    let mut stack = StackFrame::current(&mut context.stack);
+    while stack.stack.get_frames().len() > frame_level {
+        if stack.exit_scope().is_err() {
+            break;
+        }
+    }
+}
+
+/// Returns true if the value wrapped by an effect type such as `IO` (the last argument of the
+/// type application) could itself be a function
+fn effect_may_wrap_function(typ: &ArcType) -> bool {
+    match **typ {
+        Type::App(_, ref args) => match args.last() {
+            Some(arg) => match **arg {
+                Type::Hole | Type::Generic(_) | Type::Variable(_) | Type::Skolem(_) => true,
+                _ => arg.as_function().is_some(),
+            },
+            None => true,
+        },
+        _ => true,
+    }
+}
+
+/// The extra arguments of an effect (see `VmType::extra_args`) are supplied by the caller of the
+/// function returning it. A function which returns the effect without applying it to those
+/// arguments (usually an extern function pushing a gluon action from a type which claims to have
+/// extra arguments) therefore returns an action which has not been run. For compatibility such an
+/// action is run here, but this is deprecated and only done if the result of the action cannot be
+/// a function itself.
+fn run_unapplied_action<R>(vm: &Thread, value: Value) -> Result<Value>
+where
+    R: VmType,
+{
+    let extra_args = R::extra_args();
+    if extra_args == 0 || function_arity(&value) != Some(extra_args)
+        || effect_may_wrap_function(&R::make_type(vm))
+    {
+        return Ok(value);
+    }
+    warn!(
+        "Deprecated: A function returning `{}` returned an action which was not applied to its \
+         {} extra argument(s). The action is run but the function should be changed to run it",
+        R::make_type(vm),
+        extra_args
+    );
+    let mut context = vm.context();
+    context.stack.push(value);
+    for _ in 0..extra_args {
+        0.push(vm, &mut context)?;
+    }
+    match vm.call_function(context, extra_args)? {
+        Async::Ready(context) => Ok(context.unwrap().stack.pop()),
+        Async::NotReady => Err(Error::Message("Unexpected async".into())),
+    }
+}
+
 macro_rules! make_vm_function {
     ($($args:ident),*) => (
 impl <$($args: VmType,)* R: VmType> VmType for fn ($($args),*) -> R {
@@ -1861,7 +3058,13 @@ where $($args: Getable<'vm> + 'vm,)*
             let ($($args,)*) = {
                 let stack = StackFrame::current(&mut context.stack);
                 $(let $args = {
-                    let x = $args::from_value_unsafe(vm, Variants::new(&stack[i]));
+                    let x = match $args::try_from_value_unsafe(vm, Variants::new(&stack[i])) {
+                        Ok(x) => x,
+                        Err(err) => {
+                            drop(stack);
+                            return push_error(&mut context, err);
+                        }
+                    };
                     i += 1;
                     x
                 });*;
@@ -1908,7 +3111,13 @@ where $($args: Getable<'vm> + 'vm,)*
             let ($($args,)*) = {
                 let stack = StackFrame::current(&mut context.stack);
                 $(let $args = {
-                    let x = $args::from_value_unsafe(vm, Variants::new(&stack[i]));
+                    let x = match $args::try_from_value_unsafe(vm, Variants::new(&stack[i])) {
+                        Ok(x) => x,
+                        Err(err) => {
+                            drop(stack);
+                            return push_error(&mut context, err);
+                        }
+                    };
                     i += 1;
                     x
                 });*;
@@ -1926,6 +3135,31 @@ where $($args: Getable<'vm> + 'vm,)*
     }
 }
 
+impl <'s, $($args,)* R: VmType> FunctionType for Fn($($args),*) -> R + Send + Sync + 's {
+    fn arguments() -> VmIndex {
+        count!($($args),*) + R::extra_args()
+    }
+}
+
+impl <'s, $($args: VmType,)* R: VmType> VmType for Fn($($args),*) -> R + Send + Sync + 's {
+    type Type = fn ($($args::Type),*) -> R::Type;
+
+    #[allow(non_snake_case)]
+    fn make_type(vm: &Thread) -> ArcType {
+        <fn ($($args),*) -> R>::make_type(vm)
+    }
+}
+
+impl <'vm, 's: 'vm, $($args,)* R> VmFunction<'vm> for Fn($($args),*) -> R + Send + Sync + 's
+where $($args: Getable<'vm> + 'vm,)*
+      R: AsyncPushable<'vm> + VmType + 'vm
+{
+    fn unpack_and_call(&self, vm: &'vm Thread) -> Status {
+        let function: &(Fn($($args),*) -> R + 'vm) = self;
+        function.unpack_and_call(vm)
+    }
+}
+
 impl<T, $($args,)* R> Function<T, fn($($args),*) -> R>
     where $($args: for<'vm> Pushable<'vm>,)*
           T: Deref<Target = Thread>,
@@ -1943,6 +3177,7 @@ impl<T, $($args,)* R> Function<T, fn($($args),*) -> R>
     fn call_first(&self $(, $args: $args)*) -> Result<Async<R>> {
         let vm = self.value.vm();
         let mut context = vm.context();
+        let frame_level = context.stack.get_frames().len();
         context.stack.push(self.value.get_variant());
         $(
             $args.push(&vm, &mut context)?;
@@ -1960,13 +3195,21 @@ impl<T, $($args,)* R> Function<T, fn($($args),*) -> R>
                 Async::NotReady => Ok(Async::NotReady),
             }
         })
+        .or_else(|err| Self::catch_error(vm, frame_level, err).map(Async::Ready))
     }
 
     fn return_value(vm: &Thread, value: Value) -> Result<R> {
+        let value = run_unapplied_action::<R>(vm, value)?;
         unsafe {
-            Ok(R::from_value(vm, Variants::new(&value)))
+            R::try_from_value(vm, Variants::new(&value))
         }
     }
+
+    fn catch_error(vm: &Thread, frame_level: usize, err: Error) -> Result<R> {
+        let value = R::from_error(err)?;
+        exit_frames(vm, frame_level);
+        Ok(value)
+    }
 }
 
 impl<T, $($args,)* R> Function<T, fn($($args),*) -> R>
@@ -1983,14 +3226,19 @@ impl<T, $($args,)* R> Function<T, fn($($args),*) -> R>
         use thread::Execute;
         use futures::IntoFuture;
 
+        let vm = self.value.vm().root_thread();
+        let frame_level = vm.context().stack.get_frames().len();
         match self.call_first($($args),*) {
             Ok(ok) => {
                 match ok {
                     Async::Ready(value) => Box::new(Ok(value).into_future()),
                     Async::NotReady => {
                         Box::new(
-                            Execute::new(self.value.vm().root_thread())
-                                .and_then(|(vm, value)| Self::return_value(&vm, value))
+                            Execute::new(vm.clone())
+                                .then(move |result| match result {
+                                    Ok((vm, value)) => Self::return_value(&vm, value),
+                                    Err(err) => Self::catch_error(&vm, frame_level, err),
+                                })
                         )
                     }
                 }
@@ -2009,14 +3257,19 @@ impl<T, $($args,)* R> Function<T, fn($($args),*) -> R>
     {
         use thread::Execute;
 
+        let vm = self.value.vm().root_thread();
+        let frame_level = vm.context().stack.get_frames().len();
         match self.call_first($($args),*) {
             Ok(ok) => {
                 match ok {
                     Async::Ready(value) => FutureValue::Value(Ok(value)),
                     Async::NotReady => {
                         FutureValue::Future(Box::new(
-                            Execute::new(self.value.vm().root_thread())
-                                .and_then(|(vm, value)| Self::return_value(&vm, value))
+                            Execute::new(vm.clone())
+                                .then(move |result| match result {
+                                    Ok((vm, value)) => Self::return_value(&vm, value),
+                                    Err(err) => Self::catch_error(&vm, frame_level, err),
+                                })
                         ))
                     }
                 }
@@ -1,16 +1,21 @@
-//! Rust -> Gluon value conversion via the `serde::Serialize` trait
+//! Rust -> Gluon value conversion via the `serde::Serialize` trait, along with `SerializeValue`
+//! which serializes gluon values back out through any `serde::Serializer`
 
 use std::fmt;
 use std::ops::{Deref, DerefMut};
+use std::result::Result as StdResult;
 
-use base::types::ArcType;
-use {Error, Result};
-use api::{Pushable, VmType};
+use base::resolve;
+use base::types::{arg_iter, ArcType, Type, TypeEnv};
+use {Error, Result, Variants};
+use api::{push_array, typ, Data, OpaqueValue, Pushable, ValueRef, VmType};
+use api::de::ErrorPath;
 use interner::InternedStr;
 use thread::{Context, Thread, ThreadInternal};
 use types::{VmIndex, VmTag};
 use value::{Def, RecordDef, ValueRepr};
-use serde::ser::{self, Serialize};
+use serde::de::DeserializeOwned;
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq};
 
 /**
 `Pushable` wrapper which pushes `T` by serializing it.
@@ -149,11 +154,42 @@ where
     T: Serialize,
 {
     fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        let path = ErrorPath::default();
         let mut serializer = Serializer {
             thread: thread,
             context: context,
+            path: &path,
         };
-        self.0.serialize(&mut serializer)
+        self.0
+            .serialize(&mut serializer)
+            .map_err(|err| path.attach(err))
+    }
+}
+
+/// `Pushable` wrapper which pushes `T` by serializing it, deriving the gluon type of `T` from its
+/// `Deserialize` implementation with `typ::make_type`.
+///
+/// The counterpart of `SerdeGet`, allowing any type implementing both `Serialize` and
+/// `Deserialize` to be returned from a rust function without further glue.
+pub struct SerdePush<T>(pub T);
+
+impl<T> VmType for SerdePush<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Type = SerdePush<T>;
+
+    fn make_type(thread: &Thread) -> ArcType {
+        typ::make_type::<T>(thread)
+    }
+}
+
+impl<'vm, T> Pushable<'vm> for SerdePush<T>
+where
+    T: Serialize,
+{
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        Ser(self.0).push(thread, context)
     }
 }
 
@@ -169,6 +205,7 @@ impl ser::Error for Error {
 struct Serializer<'t> {
     thread: &'t Thread,
     context: &'t mut Context,
+    path: &'t ErrorPath,
 }
 
 impl<'t> Serializer<'t> {
@@ -279,7 +316,7 @@ impl<'a, 'vm> ser::Serializer for &'a mut Serializer<'vm> {
     // Not particularly efficient but this is example code anyway. A more
     // performant approach would be to use the `itoa` crate.
     fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
-        self.to_value(v as isize)
+        self.to_value(v)
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
@@ -295,7 +332,7 @@ impl<'a, 'vm> ser::Serializer for &'a mut Serializer<'vm> {
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
-        self.to_value(v as isize)
+        self.to_value(v)
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
@@ -307,7 +344,7 @@ impl<'a, 'vm> ser::Serializer for &'a mut Serializer<'vm> {
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok> {
-        self.serialize_str(&v.to_string())
+        self.to_value(v)
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok> {
@@ -318,21 +355,17 @@ impl<'a, 'vm> ser::Serializer for &'a mut Serializer<'vm> {
         self.to_value(v)
     }
 
-    // An absent optional is represented as the JSON `null`.
+    // Optionals are represented as `std.types.Option`
     fn serialize_none(self) -> Result<Self::Ok> {
         self.serialize_unit()
     }
 
-    // A present optional is represented as just the contained value. Note that
-    // this is a lossy representation. For example the values `Some(())` and
-    // `None` both serialize as just `null`. Unfortunately this is typically
-    // what people expect when working with JSON. Other formats are encouraged
-    // to behave more intelligently if possible.
     fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        value.serialize(&mut *self)?;
+        self.alloc(1, 1)
     }
 
     fn serialize_unit(self) -> Result<Self::Ok> {
@@ -435,13 +468,14 @@ impl<'a, 'vm> ser::SerializeSeq for RecordSerializer<'a, 'vm> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)?;
+        let result = value.serialize(&mut **self);
+        self.path.index(self.values as usize, result)?;
         self.values += 1;
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok> {
-        self.serializer.alloc(self.variant_index, self.values)
+        push_array(self.serializer.thread, self.serializer.context, self.values)
     }
 }
 
@@ -453,7 +487,8 @@ impl<'a, 'vm> ser::SerializeTuple for RecordSerializer<'a, 'vm> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)?;
+        let result = value.serialize(&mut **self);
+        self.path.index(self.values as usize, result)?;
         self.values += 1;
         Ok(())
     }
@@ -471,7 +506,8 @@ impl<'a, 'vm> ser::SerializeTupleStruct for RecordSerializer<'a, 'vm> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)?;
+        let result = value.serialize(&mut **self);
+        self.path.index(self.values as usize, result)?;
         self.values += 1;
         Ok(())
     }
@@ -489,7 +525,8 @@ impl<'a, 'vm> ser::SerializeTupleVariant for RecordSerializer<'a, 'vm> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)?;
+        let result = value.serialize(&mut **self);
+        self.path.index(self.values as usize, result)?;
         self.values += 1;
         Ok(())
     }
@@ -514,7 +551,8 @@ impl<'a, 'vm> ser::SerializeMap for RecordSerializer<'a, 'vm> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)?;
+        let result = value.serialize(&mut **self);
+        self.path.index(self.values as usize, result)?;
         self.values += 1;
         Ok(())
     }
@@ -534,7 +572,8 @@ impl<'a, 'vm> ser::SerializeStruct for RecordSerializer<'a, 'vm> {
     {
         let field = self.thread.global_env().intern(key)?;
         self.fields.push(field);
-        value.serialize(&mut **self)?;
+        let result = value.serialize(&mut **self);
+        self.path.field(key, result)?;
         self.values += 1;
         Ok(())
     }
@@ -550,11 +589,12 @@ impl<'a, 'vm> ser::SerializeStructVariant for RecordSerializer<'a, 'vm> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)?;
+        let result = value.serialize(&mut **self);
+        self.path.field(key, result)?;
         self.values += 1;
         Ok(())
     }
@@ -564,6 +604,260 @@ impl<'a, 'vm> ser::SerializeStructVariant for RecordSerializer<'a, 'vm> {
     }
 }
 
+/// `Serialize` wrapper which serializes a gluon value, using the type of the value to recover the
+/// names of record fields and constructors.
+///
+/// Records are serialized as maps and variants as externally tagged enums, that is `"A"` for a
+/// constructor without arguments, `{ "B": value }` for a constructor with one argument and
+/// `{ "C": [values..] }` otherwise. `Bool` and `Option` are serialized as `bool` and `Option`.
+/// When no type information is available records and variants are serialized as arrays of their
+/// fields. Userdata, functions and threads can't be serialized and result in an error.
+///
+/// ```
+/// extern crate gluon;
+/// extern crate serde_json;
+///
+/// use gluon::{Compiler, new_vm};
+/// use gluon::vm::api::{Hole, OpaqueValue};
+/// use gluon::vm::api::ser::SerializeValue;
+/// use gluon::vm::thread::RootedThread;
+/// # fn main() {
+///
+/// # if ::std::env::var("GLUON_PATH").is_err() {
+/// #     ::std::env::set_var("GLUON_PATH", "..");
+/// # }
+///
+/// let thread = new_vm();
+///
+/// let (value, typ) = Compiler::new()
+/// #   .implicit_prelude(false)
+///     .run_expr::<OpaqueValue<RootedThread, Hole>>(
+///         &thread,
+///         "test",
+///         r#" { x = 1, name = "abc" } "#,
+///     )
+///     .unwrap_or_else(|err| panic!("{}", err));
+///
+/// let json = serde_json::to_string(&SerializeValue::new(&thread, value.get_variant(), &typ))
+///     .unwrap_or_else(|err| panic!("{}", err));
+/// assert_eq!(json, r#"{"x":1,"name":"abc"}"#);
+/// # }
+/// ```
+pub struct SerializeValue<'a> {
+    thread: &'a Thread,
+    value: Variants<'a>,
+    typ: Option<ArcType>,
+}
+
+impl<'a> SerializeValue<'a> {
+    /// Serializes `value` assuming that it is of type `typ`
+    pub fn new(thread: &'a Thread, value: Variants<'a>, typ: &ArcType) -> Self {
+        SerializeValue {
+            thread: thread,
+            value: value,
+            typ: Some(typ.clone()),
+        }
+    }
+
+    /// Serializes `value` without any type information
+    pub fn untyped(thread: &'a Thread, value: Variants<'a>) -> Self {
+        SerializeValue {
+            thread: thread,
+            value: value,
+            typ: None,
+        }
+    }
+
+    /// Serializes `value` using the type of `V`
+    pub fn from_opaque<T, V>(value: &'a OpaqueValue<T, V>) -> Self
+    where
+        T: Deref<Target = Thread>,
+        V: VmType,
+        V::Type: Sized,
+    {
+        let typ = V::make_type(value.vm());
+        SerializeValue::new(value.vm(), value.get_variant(), &typ)
+    }
+}
+
+impl<'a> Serialize for SerializeValue<'a> {
+    fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let env = self.thread.global_env().get_env();
+        ValueSerializer {
+            thread: self.thread,
+            env: &*env,
+            value: self.value,
+            typ: self.typ.as_ref(),
+        }.serialize(serializer)
+    }
+}
+
+struct ValueSerializer<'a, 't> {
+    thread: &'a Thread,
+    env: &'a TypeEnv,
+    value: Variants<'a>,
+    typ: Option<&'t ArcType>,
+}
+
+impl<'a, 't> ValueSerializer<'a, 't> {
+    fn with<'u>(&self, value: Variants<'a>, typ: Option<&'u ArcType>) -> ValueSerializer<'a, 'u> {
+        ValueSerializer {
+            thread: self.thread,
+            env: self.env,
+            value: value,
+            typ: typ,
+        }
+    }
+
+    fn serialize_data<S>(&self, data: Data<'a>, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        if let Some(typ) = self.typ {
+            let canonical = resolve::canonical_alias(self.env, typ, |alias| {
+                let name = alias.name.name().as_str();
+                name == "std.types.Bool" || name == "std.types.Option"
+            });
+            match **canonical {
+                Type::Alias(ref alias) if alias.name.name().as_str() == "std.types.Bool" => {
+                    return serializer.serialize_bool(data.tag() != 0);
+                }
+                Type::App(ref func, ref args) if args.len() == 1 => match **func {
+                    Type::Alias(ref alias) if alias.name.name().as_str() == "std.types.Option" => {
+                        return match data.get_variant(0) {
+                            Some(value) => {
+                                serializer.serialize_some(&self.with(value, Some(&args[0])))
+                            }
+                            None => serializer.serialize_none(),
+                        };
+                    }
+                    _ => (),
+                },
+                _ => (),
+            }
+
+            let typ = resolve::remove_aliases_cow(self.env, typ);
+            match **typ {
+                Type::Record(_) => {
+                    let mut map = serializer.serialize_map(Some(data.len()))?;
+                    for field in typ.row_iter() {
+                        if let Some(value) = data.lookup_field(self.thread, field.name.as_ref()) {
+                            map.serialize_entry(
+                                field.name.declared_name(),
+                                &self.with(value, Some(&field.typ)),
+                            )?;
+                        }
+                    }
+                    return map.end();
+                }
+                Type::Variant(ref row) => {
+                    let constructor = match row.row_iter().nth(data.tag() as usize) {
+                        Some(constructor) => constructor,
+                        None => {
+                            return Err(<S::Error as ser::Error>::custom(format!(
+                                "Unable to serialize tag {} of `{}`",
+                                data.tag(),
+                                typ
+                            )))
+                        }
+                    };
+                    let name = constructor.name.declared_name();
+                    let arg_types = arg_iter(&constructor.typ).collect::<Vec<_>>();
+                    return match data.len() {
+                        0 => serializer.serialize_str(name),
+                        1 => {
+                            let mut map = serializer.serialize_map(Some(1))?;
+                            let value = data.get_variant(0).unwrap();
+                            let typ = arg_types.get(0).cloned();
+                            map.serialize_entry(name, &self.with(value, typ))?;
+                            map.end()
+                        }
+                        _ => {
+                            let mut map = serializer.serialize_map(Some(1))?;
+                            map.serialize_entry(
+                                name,
+                                &FieldsSerializer {
+                                    parent: self,
+                                    data: data,
+                                    types: arg_types,
+                                },
+                            )?;
+                            map.end()
+                        }
+                    };
+                }
+                _ => (),
+            }
+        }
+        FieldsSerializer {
+            parent: self,
+            data: data,
+            types: Vec::new(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'a, 't> Serialize for ValueSerializer<'a, 't> {
+    fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self.value.as_ref() {
+            ValueRef::Byte(b) => serializer.serialize_u8(b),
+            ValueRef::Int(i) => serializer.serialize_i64(i as i64),
+            ValueRef::Float(f) => serializer.serialize_f64(f),
+            ValueRef::Char(c) => serializer.serialize_char(c),
+            ValueRef::String(s) => serializer.serialize_str(s),
+            ValueRef::Data(data) => self.serialize_data(data, serializer),
+            ValueRef::Array(array) => {
+                let typ = self.typ.map(|typ| resolve::remove_aliases_cow(self.env, typ));
+                let element_type = typ.as_ref().and_then(|typ| match ***typ {
+                    Type::App(_, ref args) if args.len() == 1 => Some(&args[0]),
+                    _ => None,
+                });
+                let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                for value in array.iter() {
+                    seq.serialize_element(&self.with(value, element_type))?;
+                }
+                seq.end()
+            }
+            ValueRef::Userdata(data) => Err(<S::Error as ser::Error>::custom(format!(
+                "Unable to serialize userdata `{:?}`",
+                data
+            ))),
+            ValueRef::Thread(_) | ValueRef::Internal => {
+                Err(<S::Error as ser::Error>::custom(match self.typ {
+                    Some(typ) => format!("Unable to serialize `{}`", typ),
+                    None => "Unable to serialize functions or threads".to_string(),
+                }))
+            }
+        }
+    }
+}
+
+/// Serializes the fields of a record or constructor as an array
+struct FieldsSerializer<'s, 'a: 's, 't: 's, 'u> {
+    parent: &'s ValueSerializer<'a, 't>,
+    data: Data<'a>,
+    types: Vec<&'u ArcType>,
+}
+
+impl<'s, 'a, 't, 'u> Serialize for FieldsSerializer<'s, 'a, 't, 'u> {
+    fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.data.len()))?;
+        for (i, value) in self.data.iter().enumerate() {
+            seq.serialize_element(&self.parent.with(value, self.types.get(i).cloned()))?;
+        }
+        seq.end()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
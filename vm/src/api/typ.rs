@@ -1,6 +1,8 @@
 //! Rust type to gluon type conversion
 
-use base::types::{ArcType, Field, Type, TypeCache};
+use std::any::Any;
+
+use base::types::{arg_iter, walk_type, ArcType, Field, Generic, Type, TypeCache};
 use base::symbol::{Symbol, Symbols};
 
 use {Error as VmError, Result};
@@ -10,18 +12,139 @@ use thread::Thread;
 use serde::de::{self, DeserializeOwned, DeserializeSeed, EnumAccess, Error, IntoDeserializer,
                 MapAccess, SeqAccess, VariantAccess, Visitor};
 
+/// Returns gluon source code which defines `T` as a type named after `T` and exports it in a
+/// record. Variant and record types registered in `thread` which `T` refers to are defined as well,
+/// with mutually recursive types being defined together in a `type ... and ...` group.
 pub fn make_source<T>(thread: &Thread) -> Result<String>
 where
     T: DeserializeOwned,
 {
     let (name, typ) = from_rust::<T>(thread)?;
-    Ok(format!(
-        r#"
-type {0} = {1}
-{{ {0} }}
-"#,
-        name, typ
-    ))
+    if name.is_empty() {
+        return Err(VmError::Message(
+            "Only named structs and enums can be defined in gluon source".to_string(),
+        ));
+    }
+
+    let mut definitions = vec![Definition::new(name, Vec::new(), typ)];
+    let mut i = 0;
+    while i < definitions.len() {
+        for reference in definitions[i].references.clone() {
+            if definitions.iter().any(|def| def.name == reference) {
+                continue;
+            }
+            // Types from gluon modules are referred to by their module path and are left out
+            if reference.contains('.') {
+                continue;
+            }
+            if let Ok(alias) = thread.find_type_info(&reference) {
+                match **alias.unresolved_type() {
+                    Type::Record(_) | Type::Variant(_) => definitions.push(Definition::new(
+                        reference,
+                        alias.params().to_owned(),
+                        alias.unresolved_type().clone(),
+                    )),
+                    _ => (),
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let mut source = String::from("\n");
+    for group in recursive_groups(&definitions) {
+        for (j, &index) in group.iter().enumerate() {
+            let def = &definitions[index];
+            source.push_str(if j == 0 { "type " } else { "and " });
+            source.push_str(&def.name);
+            for param in &def.params {
+                source.push(' ');
+                source.push_str(param.id.as_ref());
+            }
+            source.push_str(&format!(" = {}\n", def.typ));
+        }
+    }
+    source.push_str("{ ");
+    for (j, def) in definitions.iter().enumerate() {
+        if j != 0 {
+            source.push_str(", ");
+        }
+        source.push_str(&def.name);
+    }
+    source.push_str(" }\n");
+    Ok(source)
+}
+
+/// A type definition emitted by `make_source`
+struct Definition {
+    name: String,
+    params: Vec<Generic<Symbol>>,
+    typ: ArcType,
+    /// Names of the types referred to by `typ`
+    references: Vec<String>,
+}
+
+impl Definition {
+    fn new(name: String, params: Vec<Generic<Symbol>>, typ: ArcType) -> Definition {
+        let mut references = Vec::new();
+        walk_type(&typ, |typ: &ArcType| {
+            let name = match **typ {
+                Type::Alias(ref alias) => alias.name.as_ref(),
+                Type::Ident(ref id) => id.as_ref(),
+                _ => return,
+            };
+            if !references.iter().any(|reference| reference == name) {
+                references.push(name.to_string());
+            }
+        });
+        Definition {
+            name,
+            params,
+            typ,
+            references,
+        }
+    }
+}
+
+/// Splits `definitions` into groups of mutually recursive definitions. The groups are ordered so
+/// that each group only refers to itself and the groups before it.
+fn recursive_groups(definitions: &[Definition]) -> Vec<Vec<usize>> {
+    let len = definitions.len();
+    // `reaches[i][j]` is true if definition `i` refers to `j`, directly or indirectly
+    let mut reaches = vec![vec![false; len]; len];
+    for (i, def) in definitions.iter().enumerate() {
+        for (j, other) in definitions.iter().enumerate() {
+            reaches[i][j] = def.references.contains(&other.name);
+        }
+    }
+    for k in 0..len {
+        for i in 0..len {
+            for j in 0..len {
+                if reaches[i][k] && reaches[k][j] {
+                    reaches[i][j] = true;
+                }
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut emitted = vec![false; len];
+    while groups.iter().map(|group| group.len()).sum::<usize>() < len {
+        let next = (0..len)
+            .filter(|&i| !emitted[i])
+            .find(|&i| {
+                (0..len).all(|j| !reaches[i][j] || emitted[j] || reaches[j][i])
+            })
+            .expect("A definition without unemitted dependencies");
+        let group: Vec<_> = (0..len)
+            .filter(|&j| j == next || (reaches[next][j] && reaches[j][next]))
+            .collect();
+        for &j in &group {
+            emitted[j] = true;
+        }
+        groups.push(group);
+    }
+    groups
 }
 
 /// Deserializes `T` from a gluon value assuming that `value` is of type `typ`.
@@ -33,6 +156,43 @@ where
     from_rust_::<T>(&mut symbols, thread)
 }
 
+/// Returns the gluon type of `T`. If a type with the same name as `T` has been registered that
+/// type is returned instead of the one derived from `T` as variant types can only refer to
+/// themselves through a registered name.
+pub fn make_type<T>(thread: &Thread) -> ArcType
+where
+    T: DeserializeOwned,
+{
+    match from_rust::<T>(thread) {
+        Ok((name, typ)) => match thread.find_type_info(&name) {
+            Ok(alias) if !name.is_empty() => alias.into_type(),
+            _ => typ,
+        },
+        Err(err) => ice!("Unable to derive a gluon type: {}", err),
+    }
+}
+
+/// Registers the enum `T` as a variant type named after `T`, allowing it to be used inside other
+/// types derived by `from_rust`.
+pub fn register_variant<T>(thread: &Thread) -> Result<ArcType>
+where
+    T: DeserializeOwned + Any,
+{
+    let (name, typ) = from_rust::<T>(thread)?;
+    let constructors: Vec<_> = match *typ {
+        Type::Variant(ref row) => row.row_iter()
+            .map(|field| (field.name.as_ref(), arg_iter(&field.typ).cloned().collect()))
+            .collect(),
+        _ => {
+            return Err(VmError::Message(format!(
+                "`{}` is not an enum and can't be registered as a variant",
+                name
+            )))
+        }
+    };
+    thread.register_variant::<T>(&name, &constructors)
+}
+
 fn from_rust_<T>(symbols: &mut Symbols, thread: &Thread) -> Result<(String, ArcType)>
 where
     T: DeserializeOwned,
@@ -70,6 +230,8 @@ struct Deserializer<'de> {
     variant: Option<Field<Symbol, ArcType>>,
     variant_index: usize,
     name: &'static str,
+    /// How many structures deep into the top-level type the deserializer currently is
+    depth: usize,
 }
 
 impl<'de> Deserializer<'de> {
@@ -88,8 +250,25 @@ impl<'de> Deserializer<'de> {
             variant: None,
             variant_index: 0,
             name: "",
+            depth: 0,
         }
     }
+
+    fn set_name(&mut self, name: &'static str) {
+        if self.depth == 0 {
+            self.name = name;
+        }
+    }
+
+    fn nested<F, R>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Self) -> Result<R>,
+    {
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
 }
 
 impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
@@ -234,7 +413,7 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let value = visitor.visit_some(&mut *self)?;
+        let value = self.nested(|this| visitor.visit_some(this))?;
         let option_alias = self.state
             .thread
             .find_type_info("std.types.Option")
@@ -260,7 +439,7 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.name = name;
+        self.set_name(name);
         self.deserialize_unit(visitor)
     }
 
@@ -268,19 +447,23 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.name = name;
-        visitor.visit_newtype_struct(self)
+        self.set_name(name);
+        self.nested(|this| visitor.visit_newtype_struct(this))
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let value = {
+        let (value, mut types) = {
             let mut seq_deserializer = SeqDeserializer::new(&mut *self, 1);
-            visitor.visit_seq(&mut seq_deserializer)?
+            (
+                visitor.visit_seq(&mut seq_deserializer)?,
+                seq_deserializer.types,
+            )
         };
-        self.typ = Some(Type::array(self.state.cache.byte()));
+        let element_type = types.pop().expect("element type");
+        self.typ = Some(Type::array(element_type));
         Ok(value)
     }
 
@@ -329,7 +512,7 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.name = name;
+        self.set_name(name);
         let (value, types) = {
             let mut map_deserializer = MapDeserializer::new(&mut *self, fields.iter().cloned());
             (
@@ -350,9 +533,29 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        if self.depth != 0 {
+            // Only the variants of the top-level enum are enumerated so nested enums need to
+            // be registered beforehand
+            let typ = self.state
+                .thread
+                .find_type_info(name)
+                .map(|alias| alias.into_type())
+                .map_err(|_| {
+                    VmError::Message(format!(
+                        "The nested enum `{}` must be registered before its type can be derived",
+                        name
+                    ))
+                })?;
+            let variant = variants
+                .first()
+                .ok_or_else(|| VmError::Message(format!("The enum `{}` has no variants", name)))?;
+            let value = visitor.visit_enum(Enum::new(self, name, variant, false))?;
+            self.typ = Some(typ);
+            return Ok(value);
+        }
         self.name = name;
         match variants.get(self.variant_index) {
-            Some(variant) => visitor.visit_enum(Enum::new(self, name, variant)),
+            Some(variant) => visitor.visit_enum(Enum::new(self, name, variant, true)),
             None => Err(VmError::Message("".to_string())),
         }
     }
@@ -399,7 +602,8 @@ impl<'de, 'a, 't> SeqAccess<'de> for &'a mut SeqDeserializer<'de, 't> {
             Ok(None)
         } else {
             self.len -= 1;
-            let value = seed.deserialize(&mut *self.deserializer)?;
+            let value = self.deserializer
+                .nested(|deserializer| seed.deserialize(deserializer))?;
             self.types.push(self.deserializer.typ.take().expect("typ"));
             Ok(Some(value))
         }
@@ -444,7 +648,8 @@ where
     {
         match self.iter.next() {
             Some(field) => {
-                let value = seed.deserialize(&mut *self.deserializer)?;
+                let value = self.deserializer
+                    .nested(|deserializer| seed.deserialize(deserializer))?;
                 self.types.push(Field::new(
                     self.deserializer.state.symbols.symbol(field),
                     self.deserializer.typ.take().expect("typ"),
@@ -460,14 +665,32 @@ struct Enum<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
     enum_name: &'static str,
     variant: &'static str,
+    /// If the type of the variant should be recorded (only done for the top-level enum)
+    record: bool,
 }
 
 impl<'a, 'de> Enum<'a, 'de> {
-    fn new(de: &'a mut Deserializer<'de>, enum_name: &'static str, variant: &'static str) -> Self {
+    fn new(
+        de: &'a mut Deserializer<'de>,
+        enum_name: &'static str,
+        variant: &'static str,
+        record: bool,
+    ) -> Self {
         Enum {
             de,
             enum_name,
             variant,
+            record,
+        }
+    }
+
+    fn set_variant(&mut self, args: Vec<ArcType>) {
+        if self.record {
+            let enum_type = Type::ident(self.de.state.symbols.symbol(self.enum_name));
+            self.de.variant = Some(Field::new(
+                self.de.state.symbols.symbol(self.variant),
+                Type::function(args, enum_type),
+            ));
         }
     }
 }
@@ -488,29 +711,22 @@ impl<'a, 'de> EnumAccess<'de> for Enum<'a, 'de> {
 impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
     type Error = VmError;
 
-    fn unit_variant(self) -> Result<()> {
-        let enum_type = Type::ident(self.de.state.symbols.symbol(self.enum_name));
-        self.de.variant = Some(Field::new(
-            self.de.state.symbols.symbol(self.variant),
-            enum_type,
-        ));
+    fn unit_variant(mut self) -> Result<()> {
+        self.set_variant(vec![]);
         Ok(())
     }
 
-    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value>
     where
         T: DeserializeSeed<'de>,
     {
-        let value = seed.deserialize(&mut *self.de)?;
-        let enum_type = Type::ident(self.de.state.symbols.symbol(self.enum_name));
-        self.de.variant = Some(Field::new(
-            self.de.state.symbols.symbol(self.variant),
-            Type::function(collect![self.de.typ.take().expect("typ")], enum_type),
-        ));
+        let value = self.de.nested(|de| seed.deserialize(de))?;
+        let typ = self.de.typ.take().expect("typ");
+        self.set_variant(collect![typ]);
         Ok(value)
     }
 
-    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    fn tuple_variant<V>(mut self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -521,11 +737,7 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
                 seq_deserializer.types,
             )
         };
-        let enum_type = Type::ident(self.de.state.symbols.symbol(self.enum_name));
-        self.de.variant = Some(Field::new(
-            self.de.state.symbols.symbol(self.variant),
-            Type::function(types, enum_type),
-        ));
+        self.set_variant(types);
         Ok(value)
     }
 
@@ -567,6 +779,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn seq_type() {
+        let mut symbols = Symbols::new();
+        let (name, typ) = from_rust_::<Vec<Test>>(&mut symbols, &RootedThread::new()).unwrap();
+        assert_eq!(name, "");
+        assert_eq!(
+            typ,
+            Type::array(Type::record(
+                vec![],
+                vec![
+                    Field::new(symbols.symbol("x"), Type::int()),
+                    Field::new(symbols.symbol("name"), Type::string()),
+                ]
+            ))
+        );
+    }
+
     #[allow(dead_code)]
     #[derive(Deserialize)]
     enum Enum {
@@ -598,4 +827,77 @@ mod tests {
             ])
         );
     }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    enum Mode {
+        Fast,
+        Slow(i32),
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Config {
+        port: i32,
+        hosts: Vec<String>,
+        mode: Mode,
+    }
+
+    #[test]
+    fn source_includes_registered_variants() {
+        let thread = RootedThread::new();
+        register_variant::<Mode>(&thread).unwrap();
+        assert_eq!(
+            make_source::<Config>(&thread).unwrap(),
+            r#"
+type Mode = | Fast | Slow Int
+type Config = { port : Int, hosts : Array String, mode : Mode }
+{ Config, Mode }
+"#
+        );
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    enum Expr {
+        Int(i32),
+        Block(Vec<Stmt>),
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    enum Stmt {
+        Expr(Expr),
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Program {
+        body: Expr,
+    }
+
+    #[test]
+    fn source_groups_mutually_recursive_types() {
+        let thread = RootedThread::new();
+        let stmt = Type::ident(Symbol::from("Stmt"));
+        let expr = Type::ident(Symbol::from("Expr"));
+        thread
+            .register_variant::<Expr>(
+                "Expr",
+                &[("Int", vec![Type::int()]), ("Block", vec![Type::array(stmt)])],
+            )
+            .unwrap();
+        thread
+            .register_variant::<Stmt>("Stmt", &[("Expr", vec![expr])])
+            .unwrap();
+        assert_eq!(
+            make_source::<Program>(&thread).unwrap(),
+            r#"
+type Expr = | Int Int | Block (Array Stmt)
+and Stmt = | Expr Expr
+type Program = { body : Expr }
+{ Program, Expr, Stmt }
+"#
+        );
+    }
 }
@@ -0,0 +1,132 @@
+//! Cooperative cancellation of gluon threads.
+//!
+//! The host requests cancellation through the `CancellationToken` of a thread (see
+//! `Thread::cancellation_token`). Scripts can poll the token with `std.cancel.is_requested` or
+//! `std.cancel.check` and exit on their own. Code which does not poll the token is stopped by the
+//! interpreter with `Error::Cancelled` once the token has been cancelled for a while. The error is
+//! raised again periodically until the script acknowledges the cancellation.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use {Error, ExternModule, Result};
+use api::{Userdata, VmType, WithVM, IO};
+use gc::{Gc, Traverseable};
+use thread::{Thread, ThreadInternal};
+
+/// How many times the interpreter sees a cancelled token before it raises `Error::Cancelled`, both
+/// the first time and after each time it has been raised. Gives scripts polling the token a chance
+/// to notice the cancellation first and handlers of the error a chance to acknowledge it.
+const GRACE_CHECKS: usize = 1024;
+
+/// Shared flag which requests that the threads holding it stop running.
+///
+/// Cloning the token returns a handle to the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Requests cancellation of every thread holding this token
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Userdata for CancellationToken {}
+
+impl VmType for CancellationToken {
+    type Type = CancellationToken;
+}
+
+impl Traverseable for CancellationToken {
+    fn traverse(&self, _: &mut Gc) {}
+}
+
+/// Per thread state tracking how the thread has been told about a cancelled token
+#[derive(Debug, Default)]
+pub(crate) struct CancellationState {
+    checks: usize,
+    acknowledged: bool,
+}
+
+impl CancellationState {
+    /// Called by the interpreter whenever it sees that the token of the thread is cancelled.
+    /// Raises the error every `GRACE_CHECKS` checks until the cancellation is acknowledged, which
+    /// leaves cleanup code in a handler of the error time to run (and to acknowledge it).
+    pub(crate) fn check(&mut self) -> Result<()> {
+        if self.acknowledged {
+            return Ok(());
+        }
+        self.checks += 1;
+        if self.checks >= GRACE_CHECKS {
+            self.checks = 0;
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Marks the cancellation as seen by the script which is then trusted to exit on its own
+    fn acknowledge(&mut self) {
+        self.acknowledged = true;
+    }
+
+    /// Makes the next check raise `Error::Cancelled`
+    fn raise(&mut self) {
+        self.acknowledged = false;
+        self.checks = GRACE_CHECKS;
+    }
+}
+
+fn is_requested(WithVM { vm, .. }: WithVM<()>) -> IO<bool> {
+    let requested = vm.cancellation_token().is_cancelled();
+    if requested {
+        vm.context().cancellation.acknowledge();
+    }
+    IO::Value(requested)
+}
+
+fn check(WithVM { vm, .. }: WithVM<()>) -> IO<()> {
+    // The error is raised by the interpreter when it returns from this function
+    if vm.cancellation_token().is_cancelled() {
+        vm.context().cancellation.raise();
+    }
+    IO::Value(())
+}
+
+fn token(WithVM { vm, .. }: WithVM<()>) -> IO<CancellationToken> {
+    IO::Value(vm.cancellation_token().clone())
+}
+
+fn request(token: &CancellationToken) -> IO<()> {
+    token.cancel();
+    IO::Value(())
+}
+
+mod std {
+    pub mod cancel {
+        pub use cancel as prim;
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    let _ = vm.register_type::<CancellationToken>("CancellationToken", &[]);
+    ExternModule::new(
+        vm,
+        record!{
+            is_requested => primitive!(1 std::cancel::prim::is_requested),
+            check => primitive!(1 std::cancel::prim::check),
+            token => primitive!(1 std::cancel::prim::token),
+            request => primitive!(1 std::cancel::prim::request)
+        },
+    )
+}
@@ -12,10 +12,10 @@ use {Error, ExternModule, Result as VmResult};
 use api::{primitive, AsyncPushable, Function, FunctionRef, FutureResult, Generic, Getable,
           OpaqueValue, OwnedFunction, Pushable, RuntimeResult, VmType, WithVM, IO};
 use api::generic::A;
-use gc::{Gc, GcPtr, Traverseable};
+use gc::{DataDef, Gc, GcPtr, Traverseable, WriteOnly};
 use vm::{RootedThread, Status, Thread};
 use thread::{OwnedContext, ThreadInternal};
-use value::{Callable, GcStr, Userdata, ValueRepr};
+use value::{Callable, Def, GcStr, Repr, Userdata, ValueArray, ValueRepr};
 use stack::{StackFrame, State};
 use types::VmInt;
 
@@ -149,6 +149,52 @@ fn send(sender: &Sender<Generic<A>>, value: Generic<A>) -> Result<(), ()> {
     }
 }
 
+/// A string made out of `parts` which is written directly into the gluon heap
+struct MessageDef<'b> {
+    parts: &'b [&'b str],
+}
+
+impl<'b> MessageDef<'b> {
+    fn len(&self) -> usize {
+        self.parts.iter().map(|part| part.len()).sum()
+    }
+}
+
+impl<'b> Traverseable for MessageDef<'b> {
+    fn traverse(&self, _: &mut Gc) {}
+}
+
+unsafe impl<'b> DataDef for MessageDef<'b> {
+    type Value = ValueArray;
+    fn size(&self) -> usize {
+        use std::mem::size_of;
+        size_of::<ValueArray>() + self.len() * size_of::<u8>()
+    }
+    fn initialize<'w>(self, mut result: WriteOnly<'w, ValueArray>) -> &'w mut ValueArray {
+        unsafe {
+            let result = &mut *result.as_mut_ptr();
+            result.set_repr(Repr::Byte);
+            let bytes = result.unsafe_array_mut::<u8>();
+            bytes.set_len(self.len());
+            let mut start = 0;
+            for part in self.parts {
+                bytes[start..start + part.len()].copy_from_slice(part.as_bytes());
+                start += part.len();
+            }
+            result
+        }
+    }
+}
+
+/// Pushes the concatenation of `parts` as a string. The message is allocated ignoring the memory
+/// limit and without formatting it into a `String` first since it is used to report errors.
+fn push_message(context: &mut OwnedContext, parts: &[&str]) {
+    let message = unsafe {
+        GcStr::from_utf8_unchecked(context.alloc_ignore_limit(MessageDef { parts }))
+    };
+    context.stack.push(ValueRepr::String(message));
+}
+
 extern "C" fn resume(vm: &Thread) -> Status {
     let mut context = vm.context();
     let value = StackFrame::current(&mut context.stack)[0].get_repr();
@@ -168,17 +214,27 @@ extern "C" fn resume(vm: &Thread) -> Status {
                     value.status_push(vm, &mut context)
                 }
                 Err(Error::Dead) => {
-                    let value: Result<(), &str> = Err("Attempted to resume a dead thread");
-                    value.status_push(vm, &mut context)
+                    let name = child.name();
+                    push_message(
+                        &mut context,
+                        &["Attempted to resume the dead thread `", &name, "`"],
+                    );
+                    // Wrap the message in `Err`
+                    let message = context.stack.get_values().last().cloned().unwrap();
+                    let err = context.alloc_ignore_limit(Def {
+                        tag: 0,
+                        elems: &[message],
+                    });
+                    context.stack.pop();
+                    context.stack.push(ValueRepr::Data(err));
+                    Status::Ok
                 }
                 Err(err) => {
-                    let fmt = format!("{}", err);
-                    let result = unsafe {
-                        ValueRepr::String(GcStr::from_utf8_unchecked(
-                            context.alloc_ignore_limit(fmt.as_bytes()),
-                        ))
-                    };
-                    context.stack.push(result);
+                    let name = child.name();
+                    push_message(
+                        &mut context,
+                        &["Error in thread `", &name, "`: ", &err.to_string()],
+                    );
                     Status::Error
                 }
             }
@@ -194,10 +250,38 @@ extern "C" fn yield_(_vm: &Thread) -> Status {
 fn spawn<'vm>(
     value: WithVM<'vm, Function<&'vm Thread, fn(())>>,
 ) -> RuntimeResult<RootedThread, Error> {
-    spawn_(value).into()
+    spawn_(value, false).into()
+}
+
+/// Like `spawn` but gives the spawned thread a name which is used to refer to it in errors
+fn spawn_named<'vm>(
+    name: String,
+    value: WithVM<'vm, Function<&'vm Thread, fn(())>>,
+) -> RuntimeResult<RootedThread, Error> {
+    spawn_(value, false)
+        .map(|thread| {
+            thread.set_name(name);
+            thread
+        })
+        .into()
 }
-fn spawn_<'vm>(value: WithVM<'vm, Function<&'vm Thread, fn(())>>) -> VmResult<RootedThread> {
-    let thread = value.vm.new_thread()?;
+
+/// Like `spawn` but the spawned thread does not share the cancellation token of its parent
+fn spawn_detached<'vm>(
+    value: WithVM<'vm, Function<&'vm Thread, fn(())>>,
+) -> RuntimeResult<RootedThread, Error> {
+    spawn_(value, true).into()
+}
+
+fn spawn_<'vm>(
+    value: WithVM<'vm, Function<&'vm Thread, fn(())>>,
+    detached: bool,
+) -> VmResult<RootedThread> {
+    let thread = if detached {
+        value.vm.new_detached_thread()?
+    } else {
+        value.vm.new_thread()?
+    };
     {
         let mut context = thread.context();
         let callable = match value.value.get_variant().0 {
@@ -361,6 +445,8 @@ pub fn load_thread<'vm>(vm: &'vm Thread) -> VmResult<ExternModule> {
             resume => primitive::<fn(&'vm Thread) -> Result<(), String>>("std.thread.prim.resume", resume),
             (yield_ "yield") => primitive::<fn(())>("std.thread.prim.yield", yield_),
             spawn => primitive!(1 std::thread::prim::spawn),
+            spawn_named => primitive!(2 std::thread::prim::spawn_named),
+            spawn_detached => primitive!(1 std::thread::prim::spawn_detached),
             spawn_on => primitive!(2 std::thread::prim::spawn_on),
             new_thread => primitive!(1 std::thread::prim::new_thread),
             interrupt => primitive!(1 std::thread::prim::interrupt),
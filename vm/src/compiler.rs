@@ -408,7 +408,9 @@ impl<'a> TypeEnv for Compiler<'a> {
     }
 
     fn find_type_info(&self, id: &SymbolRef) -> Option<&Alias<Symbol, ArcType>> {
-        self.stack_types.get(id)
+        self.stack_types
+            .get(id)
+            .or_else(|| self.globals.find_type_info(id))
     }
 
     fn find_record(
@@ -515,12 +517,70 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    /// Checks if the record of type `typ` constructed from `exprs` is a copy of another record
+    /// with some of its fields replaced (`{ x = 1, .. record }`). Returns the record being copied
+    /// along with the indexes of the fields which are replaced.
+    ///
+    /// The record must have exactly the fields of `typ`, in the same order, so the new record can
+    /// reuse its layout and all fields which are not replaced must be projections from it.
+    fn find_record_update<'e>(
+        &self,
+        typ: &ArcType,
+        exprs: &'e [Expr<'e>],
+    ) -> Option<(&'e TypedIdent<Symbol>, Vec<usize>)> {
+        fn projection<'e>(expr: &'e Expr<'e>) -> Option<(&'e TypedIdent<Symbol>, &'e Symbol)> {
+            match *expr {
+                Expr::Match(&Expr::Ident(ref base, _), alts) if alts.len() == 1 => {
+                    match (&alts[0].pattern, alts[0].expr) {
+                        (&Pattern::Record(ref fields), &Expr::Ident(ref id, _))
+                            if fields.len() == 1 && fields[0].1.is_none()
+                                && fields[0].0.name == id.name =>
+                        {
+                            Some((base, &id.name))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+
+        match **typ.remove_forall() {
+            Type::Record(_) => (),
+            _ => return None,
+        }
+        let base = exprs.iter().filter_map(projection).next()?.0;
+        let base_type = resolve::remove_aliases_cow(self, &base.typ);
+        let mut base_fields = base_type.remove_forall().row_iter();
+        let mut fields = typ.row_iter();
+
+        let mut updated = Vec::new();
+        for (i, expr) in exprs.iter().enumerate() {
+            let field = fields.next()?;
+            if !base_fields.next()?.name.name_eq(&field.name) {
+                return None;
+            }
+            match projection(expr) {
+                Some((id, name)) if id.name == base.name && name.name_eq(&field.name) => (),
+                _ => updated.push(i),
+            }
+        }
+        if fields.next().is_some() || base_fields.next().is_some()
+            || **base_fields.current_type() != Type::EmptyRow
+        {
+            return None;
+        }
+        Some((base, updated))
+    }
+
     fn find_tag(&self, typ: &ArcType, constructor: &Symbol) -> Option<VmTag> {
         let x = resolve::remove_aliases_cow(self, typ);
         match **x {
             Type::Variant(ref row) => row.row_iter()
                 .enumerate()
-                .find(|&(_, field)| field.name == *constructor)
+                .find(|&(_, field)| {
+                    field.name.definition_name() == constructor.definition_name()
+                })
                 .map(|(tag, _)| tag as VmTag),
             _ => None,
         }
@@ -609,11 +669,11 @@ impl<'a> Compiler<'a> {
     ) -> Result<Option<CExpr<'e>>> {
         match *expr {
             Expr::Const(ref lit, _) => match *lit {
-                Literal::Int(i) => function.emit(PushInt(i as isize)),
+                Literal::Int(i) => function.emit(PushInt(i as VmInt)),
                 Literal::Byte(b) => function.emit(PushByte(b)),
                 Literal::Float(f) => function.emit(PushFloat(f.into_inner())),
                 Literal::String(ref s) => function.emit_string(self.intern(&s)?),
-                Literal::Char(c) => function.emit(PushInt(c as isize)),
+                Literal::Char(c) => function.emit(PushChar(c)),
             },
             Expr::Ident(ref id, _) => self.load_identifier(&id.name, function)?,
             Expr::Let(ref let_binding, ref body) => {
@@ -739,13 +799,13 @@ impl<'a> Compiler<'a> {
                                 }
                                 ast::Literal::Int(i) => {
                                     function.emit(Push(lhs_i));
-                                    function.emit(PushInt(i as isize));
+                                    function.emit(PushInt(i as VmInt));
                                     function.emit(IntEQ);
                                 }
                                 ast::Literal::Char(ch) => {
                                     function.emit(Push(lhs_i));
-                                    function.emit(PushInt(ch as isize));
-                                    function.emit(IntEQ);
+                                    function.emit(PushChar(ch));
+                                    function.emit(CharEQ);
                                 }
                                 ast::Literal::Float(f) => {
                                     function.emit(Push(lhs_i));
@@ -809,10 +869,28 @@ impl<'a> Compiler<'a> {
                 }
             }
             Expr::Data(ref id, exprs, _, _) => {
+                let typ = resolve::remove_aliases_cow(self, &id.typ.remove_forall());
+                if let Some((base, updated)) = self.find_record_update(&typ, exprs) {
+                    self.load_identifier(&base.name, function)?;
+                    for &i in &updated {
+                        self.compile(&exprs[i], function, false)?;
+                    }
+                    // The specification lists every field of the record followed by the fields
+                    // which are replaced
+                    let mut fields: Vec<_> =
+                        typ.row_iter().map(|field| field.name.clone()).collect();
+                    let replaced: Vec<_> = updated.iter().map(|&i| fields[i].clone()).collect();
+                    fields.extend(replaced);
+                    let index = function.add_record_map(fields);
+                    function.emit(UpdateRecord {
+                        record: index,
+                        args: updated.len() as VmIndex,
+                    });
+                    return Ok(None);
+                }
                 for expr in exprs {
                     self.compile(expr, function, false)?;
                 }
-                let typ = resolve::remove_aliases_cow(self, &id.typ.remove_forall());
                 match **typ.remove_forall() {
                     Type::Record(_) => {
                         let index = function.add_record_map(
@@ -830,7 +908,9 @@ impl<'a> Compiler<'a> {
                         function.emit(Construct {
                             tag: variants
                                 .row_iter()
-                                .position(|field| field.name == id.name)
+                                .position(|field| {
+                                    field.name.definition_name() == id.name.definition_name()
+                                })
                                 .unwrap() as VmTag,
                             args: exprs.len() as u32,
                         });
@@ -884,8 +964,10 @@ impl<'a> Compiler<'a> {
                 "#Int-" => SubtractInt,
                 "#Int*" => MultiplyInt,
                 "#Int/" => DivideInt,
-                "#Int<" | "#Char<" => IntLT,
-                "#Int==" | "#Char==" => IntEQ,
+                "#Int<" => IntLT,
+                "#Int==" => IntEQ,
+                "#Char<" => CharLT,
+                "#Char==" => CharEQ,
                 "#Byte+" => AddByte,
                 "#Byte-" => SubtractByte,
                 "#Byte*" => MultiplyByte,
@@ -898,7 +980,15 @@ impl<'a> Compiler<'a> {
                 "#Float/" => DivideFloat,
                 "#Float<" => FloatLT,
                 "#Float==" => FloatEQ,
+                // Primitive operators for types registered with `register_primitive_operand`
+                // are implemented by a global with the same name as the operator
                 _ => {
+                    if self.find(op, function).is_none() {
+                        return Err(Error::Message(format!(
+                            "No implementation is registered for the primitive operator `{}`",
+                            self.symbols.string(op)
+                        )));
+                    }
                     self.load_identifier(op, function)?;
                     Call(2)
                 }
@@ -34,6 +34,7 @@ pub mod optimize;
 pub mod interpreter;
 
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
 use std::iter::once;
@@ -48,7 +49,6 @@ use self::smallvec::SmallVec;
 use pretty::{self, DocAllocator};
 
 use base::ast::{self, Literal, SpannedExpr, SpannedPattern, Typed, TypedIdent};
-use base::fnv::FnvSet;
 use base::pos::{spanned, BytePos, ExpansionId, Span};
 use base::resolve::remove_aliases_cow;
 use base::symbol::Symbol;
@@ -460,6 +460,9 @@ pub struct Translator<'a, 'e> {
     pub allocator: Allocator<'a>,
     env: &'e PrimitiveEnv,
     dummy_symbol: TypedIdent<Symbol>,
+    /// Values of the recursive binding group currently being translated mapped to the closures
+    /// which construct them
+    recursive_values: RefCell<HashMap<Symbol, TypedIdent<Symbol>>>,
 }
 
 impl<'a, 'e> Translator<'a, 'e> {
@@ -468,6 +471,7 @@ impl<'a, 'e> Translator<'a, 'e> {
             allocator: Allocator::new(),
             env: env,
             dummy_symbol: TypedIdent::new(Symbol::from("")),
+            recursive_values: RefCell::new(HashMap::new()),
         }
     }
 
@@ -547,7 +551,7 @@ impl<'a, 'e> Translator<'a, 'e> {
             ast::Expr::Ident(ref id) => if is_constructor(&id.name) {
                 self.new_data_constructor(id.typ.clone(), id, SmallVec::new(), expr.span)
             } else {
-                Expr::Ident(id.clone(), expr.span)
+                self.variable(id.clone(), expr.span)
             },
             ast::Expr::IfElse(ref pred, ref if_true, ref if_false) => {
                 let alts: SmallVec<[_; 2]> = collect![
@@ -626,30 +630,50 @@ impl<'a, 'e> Translator<'a, 'e> {
             } => {
                 let mut binder = Binder::default();
 
-                // If `base` exists and is non-trivial we need to introduce bindings for each
-                // value to ensure that the expressions are evaluated in the correct order
+                // The fields of the constructed record are ordered as in its type, which may
+                // differ from the order they were written in when fields of `base` are updated
+                let record_type = remove_aliases_cow(&self.env, typ.remove_forall());
+                let field_index = |name: &Symbol| {
+                    record_type
+                        .row_iter()
+                        .position(|field| field.name.declared_name() == name.declared_name())
+                };
+                let is_reordered = exprs
+                    .iter()
+                    .map(|field| field_index(&field.name.value))
+                    .tuple_windows()
+                    .any(|(l, r)| l > r);
+
+                // If `base` exists and is non-trivial or the fields are reordered we need to
+                // introduce bindings for each value to ensure that the expressions are evaluated
+                // in the correct order
                 let needs_bindings = base.as_ref().map_or(false, |base| match base.value {
-                    ast::Expr::Ident(_) => false,
+                    ast::Expr::Ident(_) => is_reordered,
                     _ => true,
                 });
 
                 let mut last_span = expr.span;
-                let mut args = SmallVec::<[_; 16]>::new();
-                args.extend(exprs.iter().map(|field| {
-                    let expr = match field.value {
-                        Some(ref expr) => {
-                            last_span = expr.span;
-                            self.translate(expr)
-                        }
-                        None => Expr::Ident(TypedIdent::new(field.name.value.clone()), last_span),
-                    };
-                    if needs_bindings {
-                        let typ = expr.env_type_of(&self.env);
-                        binder.bind(arena.alloc(expr), typ)
-                    } else {
-                        expr
-                    }
-                }));
+                let mut fields = exprs
+                    .iter()
+                    .map(|field| {
+                        let expr = match field.value {
+                            Some(ref expr) => {
+                                last_span = expr.span;
+                                self.translate(expr)
+                            }
+                            None => {
+                                self.variable(TypedIdent::new(field.name.value.clone()), last_span)
+                            }
+                        };
+                        let expr = if needs_bindings {
+                            let typ = expr.env_type_of(&self.env);
+                            binder.bind(arena.alloc(expr), typ)
+                        } else {
+                            expr
+                        };
+                        (field.name.value.declared_name(), Some(expr))
+                    })
+                    .collect::<SmallVec<[_; 16]>>();
 
                 let base_binding = base.as_ref().map(|base_expr| {
                     let core_base = self.translate_alloc(base_expr);
@@ -664,26 +688,38 @@ impl<'a, 'e> Translator<'a, 'e> {
                     (core_base, typ)
                 });
 
-                let defined_fields: FnvSet<&str> = exprs
-                    .iter()
-                    .map(|field| field.name.value.declared_name())
-                    .collect();
-                args.extend(base_binding.as_ref().into_iter().flat_map(
-                    |&(base_ident_expr, ref base_type)| {
-                        base_type
-                            .row_iter()
-                            // Only load fields that aren't named in this record constructor
-                            .filter(|field| !defined_fields.contains(field.name.declared_name()))
-                            .map(move |field| {
-                                self.project_expr(
-                                    base_ident_expr.span(),
-                                    base_ident_expr,
-                                    &field.name,
-                                    &field.typ
-                                )
-                            })
-                    },
-                ));
+                let mut args = SmallVec::<[_; 16]>::new();
+                match base_binding {
+                    // Without a base the fields are already in the order of the record's type
+                    None => args.extend(fields.into_iter().filter_map(|(_, expr)| expr)),
+                    Some((base_ident_expr, ref base_type)) => args.extend(
+                        record_type.row_iter().map(|field| {
+                            let name = field.name.declared_name();
+                            match fields.iter_mut().find(|&&mut (field, _)| field == name) {
+                                Some(&mut (_, ref mut expr)) => {
+                                    expr.take().expect("Duplicate field")
+                                }
+                                // Only load fields that aren't named in this record constructor
+                                None => {
+                                    let base_field = base_type
+                                        .row_iter()
+                                        .find(|base_field| {
+                                            base_field.name.declared_name() == name
+                                        })
+                                        .unwrap_or_else(|| {
+                                            ice!("Undefined field `{}` in record", name)
+                                        });
+                                    self.project_expr(
+                                        base_ident_expr.span(),
+                                        base_ident_expr,
+                                        &base_field.name,
+                                        &base_field.typ,
+                                    )
+                                }
+                            }
+                        }),
+                    ),
+                }
 
                 let record_constructor = Expr::Data(
                     TypedIdent {
@@ -786,20 +822,84 @@ impl<'a, 'e> Translator<'a, 'e> {
         span_start: BytePos,
     ) -> Expr<'a> {
         let arena = &self.allocator.arena;
-        let is_recursive = binds.iter().all(|bind| bind.args.len() > 0);
+        let is_recursive = ast::is_recursive_binding_group(binds);
         if is_recursive {
+            let binding_name = |bind: &ast::ValueBinding<Symbol>| match bind.name.value {
+                ast::Pattern::Ident(ref id) => id.clone(),
+                _ => unreachable!(),
+            };
+
+            // Values can't be allocated before their fields are known so each value in the group
+            // is instead constructed by a closure taking `()`. Any reference to the value inside
+            // the group calls that closure which delays the evaluation until it is actually used.
+            //
+            // This loses sharing: every such reference constructs a new copy of the value, only
+            // the value bound after the group is built once. Building the value once and patching
+            // the references afterwards is not possible as closures copy their upvars when they
+            // are closed and the lambdas inside the value are closed before the value exists.
+            let values: Vec<_> = binds
+                .iter()
+                .filter(|bind| bind.args.is_empty())
+                .map(|bind| {
+                    let name = binding_name(bind);
+                    let constructor = TypedIdent {
+                        name: Symbol::from(format!("${}", name.name)),
+                        typ: Type::function(vec![Type::unit()], name.typ.clone()),
+                    };
+                    self.recursive_values
+                        .borrow_mut()
+                        .insert(name.name.clone(), constructor.clone());
+                    (name, constructor, bind.expr.span)
+                })
+                .collect();
+
             let closures = binds
                 .iter()
-                .map(|bind| Closure {
-                    pos: bind.name.span.start,
-                    name: match bind.name.value {
-                        ast::Pattern::Ident(ref id) => id.clone(),
-                        _ => unreachable!(),
-                    },
-                    args: bind.args.iter().map(|arg| arg.name.value.clone()).collect(),
-                    expr: self.translate_alloc(&bind.expr),
+                .map(|bind| {
+                    let (name, args) = if bind.args.is_empty() {
+                        let unit = TypedIdent {
+                            name: Symbol::from("_"),
+                            typ: Type::unit(),
+                        };
+                        let name = self.recursive_values.borrow()[&binding_name(bind).name].clone();
+                        (name, vec![unit])
+                    } else {
+                        (
+                            binding_name(bind),
+                            bind.args.iter().map(|arg| arg.name.value.clone()).collect(),
+                        )
+                    };
+                    Closure {
+                        pos: bind.name.span.start,
+                        name,
+                        args,
+                        expr: self.translate_alloc(&bind.expr),
+                    }
                 })
                 .collect();
+
+            {
+                let mut recursive_values = self.recursive_values.borrow_mut();
+                for &(ref name, _, _) in &values {
+                    recursive_values.remove(&name.name);
+                }
+            }
+
+            let tail = values
+                .into_iter()
+                .rev()
+                .fold(tail, |tail, (name, constructor, span)| {
+                    Expr::Let(
+                        LetBinding {
+                            name,
+                            expr: Named::Expr(
+                                arena.alloc(self.call_value_constructor(constructor, span)),
+                            ),
+                            span_start: span.start,
+                        },
+                        arena.alloc(tail),
+                    )
+                });
             Expr::Let(
                 LetBinding {
                     // TODO
@@ -851,6 +951,37 @@ impl<'a, 'e> Translator<'a, 'e> {
         }
     }
 
+    /// Translates a reference to `id`. References to a value of the recursive group being
+    /// translated construct a new copy of the value (see `translate_let`)
+    fn variable(&'a self, id: TypedIdent<Symbol>, span: Span<BytePos>) -> Expr<'a> {
+        let constructor = self.recursive_values.borrow().get(&id.name).cloned();
+        match constructor {
+            Some(constructor) => self.call_value_constructor(constructor, span),
+            None => Expr::Ident(id, span),
+        }
+    }
+
+    fn call_value_constructor(
+        &'a self,
+        constructor: TypedIdent<Symbol>,
+        span: Span<BytePos>,
+    ) -> Expr<'a> {
+        let arena = &self.allocator.arena;
+        let unit = Expr::Data(
+            TypedIdent {
+                name: self.dummy_symbol.name.clone(),
+                typ: Type::unit(),
+            },
+            &[],
+            span.start,
+            span.expansion_id,
+        );
+        Expr::Call(
+            arena.alloc(Expr::Ident(constructor, span)),
+            arena.alloc_extend(once(unit)),
+        )
+    }
+
     fn bool_constructor(&self, variant: bool) -> TypedIdent<Symbol> {
         let b = self.env.get_bool();
         match **b {
@@ -955,7 +1086,7 @@ impl<'a, 'e> Translator<'a, 'e> {
 impl<'a> Typed for Expr<'a> {
     type Ident = Symbol;
 
-    fn try_type_of(&self, env: &TypeEnv) -> Result<ArcType<Self::Ident>, String> {
+    fn try_type_of(&self, env: &TypeEnv) -> Result<ArcType<Symbol>, String> {
         match *self {
             Expr::Call(expr, args) => get_return_type(env, &expr.try_type_of(env)?, args.len()),
             Expr::Const(ref literal, _) => literal.try_type_of(env),
@@ -1212,6 +1343,8 @@ impl<'a, 'e> PatternTranslator<'a, 'e> {
                 | ast::Pattern::Record { .. }
                 | ast::Pattern::Ident(_)
                 | ast::Pattern::Literal(_)
+                | ast::Pattern::StringAffix { .. }
+                | ast::Pattern::Or(_)
                 | ast::Pattern::Error => unreachable!(),
             }
         }
@@ -1375,6 +1508,8 @@ impl<'a, 'e> PatternTranslator<'a, 'e> {
                 | ast::Pattern::Tuple { .. }
                 | ast::Pattern::Record { .. }
                 | ast::Pattern::Ident(_)
+                | ast::Pattern::StringAffix { .. }
+                | ast::Pattern::Or(_)
                 | ast::Pattern::Error => unreachable!(),
             }
         }
@@ -1487,6 +1622,10 @@ impl<'a, 'e> PatternTranslator<'a, 'e> {
                 ast::Pattern::Record { .. } | ast::Pattern::Tuple { .. } => CType::Record,
                 ast::Pattern::Constructor(_, _) => CType::Constructor,
                 ast::Pattern::Literal(_) => CType::Literal,
+                ast::Pattern::StringAffix { .. } => {
+                    ice!("ICE: String affix patterns are rejected before translation")
+                }
+                ast::Pattern::Or(_) => ice!("ICE: Or-patterns are rejected before translation"),
                 ast::Pattern::Error => ice!("ICE: Error pattern survived typechecking"),
             }
         }
@@ -1695,7 +1834,10 @@ impl<'a, 'e> PatternTranslator<'a, 'e> {
                         ));
                     }
                 },
-                ast::Pattern::Literal(_) | ast::Pattern::Error => (),
+                ast::Pattern::Literal(_)
+                | ast::Pattern::StringAffix { .. }
+                | ast::Pattern::Or(_)
+                | ast::Pattern::Error => (),
             }
         }
         let pattern = match core_pattern {
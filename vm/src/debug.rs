@@ -1,12 +1,19 @@
 use api::generic::A;
 use api::Generic;
 use thread::Thread;
+use types::VmInt;
+use value::value_size;
 use {ExternModule, Result};
 
 fn trace(a: Generic<A>) {
     println!("{:?}", a);
 }
 
+/// Returns the estimated number of bytes used by `a` (see `Thread::value_size`)
+fn size_of(a: Generic<A>) -> VmInt {
+    value_size(unsafe { a.get_value() }.get_repr()).deep as VmInt
+}
+
 mod std {
     pub use debug;
 }
@@ -17,7 +24,8 @@ pub fn load(vm: &Thread) -> Result<ExternModule> {
     ExternModule::new(
         vm,
         record!{
-            trace => primitive!(1 std::debug::trace)
+            trace => primitive!(1 std::debug::trace),
+            size_of => primitive!(1 std::debug::size_of)
         },
     )
 }
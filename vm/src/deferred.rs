@@ -0,0 +1,38 @@
+//! Calls into gluon which are queued instead of being run immediately.
+//!
+//! Code running in a `Drop` impl or while the garbage collector frees a value can't safely call
+//! back into gluon as the context of the thread may already be locked or the thread may be
+//! unwinding from a panic. Such calls are queued with `Thread::defer_call` (or returned from
+//! `Userdata::finalizer`) and run later by `Thread::run_deferred`.
+use std::fmt;
+
+use Result;
+use api::{Getable, OwnedFunction, Pushable, VmType};
+
+/// A call to a gluon function which has been deferred until the next `Thread::run_deferred`
+pub struct DeferredCall(Box<FnMut() -> Result<()> + Send>);
+
+impl fmt::Debug for DeferredCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DeferredCall")
+    }
+}
+
+impl DeferredCall {
+    /// Creates a call of `function` with `arg`. The result of the call is discarded.
+    pub fn new<A, R>(function: OwnedFunction<fn(A) -> R>, arg: A) -> DeferredCall
+    where
+        A: for<'vm> Pushable<'vm> + Send + 'static,
+        R: VmType + for<'x> Getable<'x> + 'static,
+    {
+        let mut call = Some((function, arg));
+        DeferredCall(Box::new(move || match call.take() {
+            Some((mut function, arg)) => function.call(arg).map(|_| ()),
+            None => Ok(()),
+        }))
+    }
+
+    pub(crate) fn run(mut self) -> Result<()> {
+        (self.0)()
+    }
+}
@@ -12,8 +12,10 @@ use std::marker::PhantomData;
 use std::sync::Arc;
 
 use base::fnv::FnvMap;
+use deferred::DeferredCall;
 use interner::InternedStr;
 use types::VmIndex;
+use value::Userdata;
 use {Error, Result};
 
 #[inline]
@@ -148,6 +150,12 @@ pub struct Gc {
     type_infos: FnvMap<TypeId, Box<TypeInfo>>,
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     record_infos: FnvMap<Vec<InternedStr>, Box<TypeInfo>>,
+    /// Finalizers of the userdata freed by the last collections which have not been run yet
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    finalizers: Vec<DeferredCall>,
+    /// Weak tables reached by the collection which is currently running
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    weak_tables: WeakTables,
     /// The generation of a gc determines what values it needs to copy and what values it can
     /// share. A gc can share values generated by itself (the same generation) and those in an
     /// earlier (lower) generation. It is important to note that two garbage collectors can have
@@ -170,6 +178,35 @@ pub struct Gc {
     generation: Generation,
 }
 
+/// A table whose entries are only kept alive as long as their keys are reachable from outside of
+/// the table.
+///
+/// Instead of traversing its entries the `Traverseable` implementation of a weak table must call
+/// `Gc::register_weak_table`. Once all other reachable values have been marked the collector
+/// calls `traverse_live_entries` until no more entries are traversed and then
+/// `clear_dead_entries`.
+pub trait WeakTable {
+    /// Traverses the values of each entry whose key is marked and which has not been traversed
+    /// yet during this collection. Returns `true` if any entry were traversed.
+    fn traverse_live_entries(&self, gc: &mut Gc) -> bool;
+
+    /// Removes the entries whose keys were not marked during this collection.
+    fn clear_dead_entries(&self, gc: &Gc);
+}
+
+#[derive(Default)]
+struct WeakTables(Vec<*const WeakTable>);
+
+// The pointers are only stored for the duration of a collection
+unsafe impl Send for WeakTables {}
+unsafe impl Sync for WeakTables {}
+
+impl fmt::Debug for WeakTables {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WeakTables({})", self.0.len())
+    }
+}
+
 /// Trait which creates a typed pointer from a *mut () pointer.
 /// For `Sized` types this is just a cast but for unsized types some more metadata must be taken
 /// from the provided `D` value to make it initialize correctly.
@@ -221,6 +258,7 @@ unsafe impl<T> DataDef for Move<T> {
 #[derive(Debug)]
 struct TypeInfo {
     drop: unsafe fn(*mut ()),
+    finalize: Option<unsafe fn(*mut ()) -> Option<DeferredCall>>,
     generation: Generation,
     fields: FnvMap<InternedStr, VmIndex>,
     fields_key: Arc<Vec<InternedStr>>,
@@ -413,6 +451,12 @@ impl<T: ?Sized> GcPtr<T> {
         unsafe { &(*self.header().type_info).fields_key }
     }
 
+    /// The number of bytes allocated for the value, including the header used by the garbage
+    /// collector
+    pub fn allocation_size(&self) -> usize {
+        GcHeader::value_offset() + self.header().value_size
+    }
+
     fn header(&self) -> &GcHeader {
         // Use of transmute_copy allows us to get the pointer
         // to the data regardless of wether T is unsized or not
@@ -526,7 +570,7 @@ macro_rules! empty_traverse {
     }
 }
 
-empty_traverse! { () Any u8 u16 u32 u64 usize i8 i16 i32 i64 isize f32 f64 str }
+empty_traverse! { () Any u8 u16 u32 u64 usize i8 i16 i32 i64 isize f32 f64 char str }
 
 impl<T: ?Sized> Traverseable for *const T {
     fn traverse(&self, _: &mut Gc) {}
@@ -597,6 +641,8 @@ impl Gc {
             memory_limit: memory_limit,
             type_infos: FnvMap::default(),
             record_infos: FnvMap::default(),
+            finalizers: Vec::new(),
+            weak_tables: WeakTables::default(),
             generation: generation,
         }
     }
@@ -688,6 +734,9 @@ impl Gc {
         unsafe fn drop<T>(t: *mut ()) {
             ptr::drop_in_place(t as *mut T);
         }
+        unsafe fn finalize_userdata(t: *mut ()) -> Option<DeferredCall> {
+            (*(t as *mut Box<Userdata>)).finalizer()
+        }
         let type_info: *const TypeInfo = match def.fields() {
             Some(fields) => match self.record_infos
                 .get(fields)
@@ -698,6 +747,7 @@ impl Gc {
                     .entry(fields.to_owned())
                     .or_insert(Box::new(TypeInfo {
                         drop: drop::<D::Value>,
+                        finalize: None,
                         generation: self.generation,
                         fields: fields
                             .iter()
@@ -711,6 +761,11 @@ impl Gc {
                 Entry::Occupied(entry) => &**entry.get(),
                 Entry::Vacant(entry) => &**entry.insert(Box::new(TypeInfo {
                     drop: drop::<D::Value>,
+                    finalize: if TypeId::of::<D::Value>() == TypeId::of::<Box<Userdata>>() {
+                        Some(finalize_userdata)
+                    } else {
+                        None
+                    },
                     generation: self.generation,
                     fields: FnvMap::default(),
                     fields_key: Arc::new(Vec::new()),
@@ -751,12 +806,52 @@ impl Gc {
     {
         info!("Start collect {:?}", self.generation);
         roots.scope(self, |self_| {
+            self_.weak_tables.0.clear();
             roots.traverse(self_);
+            self_.traverse_weak_tables();
             self_.sweep();
             self_.collect_limit = 2 * self_.allocated_memory;
         })
     }
 
+    /// Traverses the entries of the registered weak tables whose keys are alive until no more
+    /// entries are found, after which the entries with unreachable keys are removed.
+    unsafe fn traverse_weak_tables(&mut self) {
+        loop {
+            let mut traversed = false;
+            // Traversing an entry may reach and register additional tables
+            let mut i = 0;
+            while i < self.weak_tables.0.len() {
+                let table = self.weak_tables.0[i];
+                traversed |= (*table).traverse_live_entries(self);
+                i += 1;
+            }
+            if !traversed {
+                break;
+            }
+        }
+        for table in mem::replace(&mut self.weak_tables.0, Vec::new()) {
+            (*table).clear_dead_entries(self);
+        }
+    }
+
+    /// Registers `table` so that its entries are processed once every value reachable from the
+    /// roots have been marked.
+    ///
+    /// Unsafe as `table` must be kept alive until the collection finishes, which is always the
+    /// case if it is called from the `Traverseable` implementation of a gc allocated value.
+    pub unsafe fn register_weak_table<'a>(&mut self, table: &'a (WeakTable + 'a)) {
+        let table: *const (WeakTable + 'a) = table;
+        self.weak_tables.0.push(mem::transmute(table));
+    }
+
+    /// Returns true if `value` will survive the collection that is currently running, either
+    /// because it has been marked or because it is owned by a parent generation.
+    pub fn is_marked<T: ?Sized>(&self, value: GcPtr<T>) -> bool {
+        let header = value.header();
+        header.generation().is_parent_of(self.generation()) || header.marked.get()
+    }
+
     /// Marks the GcPtr
     /// Returns true if the pointer was already marked
     pub fn mark<T: ?Sized>(&mut self, value: GcPtr<T>) -> bool {
@@ -818,9 +913,18 @@ impl Gc {
         self.values = first;
     }
 
-    fn free(&mut self, header: Option<AllocPtr>) {
-        if let Some(ref ptr) = header {
+    /// Takes the finalizers of the userdata which have been freed since the last call
+    pub(crate) fn take_finalizers(&mut self) -> Vec<DeferredCall> {
+        mem::replace(&mut self.finalizers, Vec::new())
+    }
+
+    fn free(&mut self, mut header: Option<AllocPtr>) {
+        if let Some(ref mut ptr) = header {
             self.allocated_memory -= ptr.size();
+            let finalize = unsafe { (*ptr.type_info).finalize };
+            if let Some(call) = finalize.and_then(|finalize| unsafe { finalize(ptr.value()) }) {
+                self.finalizers.push(call);
+            }
         }
         debug!("FREE: {:?}", header);
         drop(header);
@@ -2,8 +2,9 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
+use std::sync::Arc;
 use Result;
-use base::fnv::FnvMap;
+use base::fnv::{FnvMap, FnvSet};
 
 use gc::{Gc, Traverseable};
 use value::GcStr;
@@ -77,6 +78,10 @@ pub struct Interner {
     // have the keys as strings without any unsafety as the keys do not escape the interner and they
     // live as long as their values
     indexes: FnvMap<&'static str, InternedStr>,
+    // The field names of records are shared between every function which constructs the same
+    // kind of record
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    records: FnvSet<Arc<Vec<InternedStr>>>,
 }
 
 impl Traverseable for Interner {
@@ -91,6 +96,7 @@ impl Interner {
     pub fn new() -> Interner {
         Interner {
             indexes: FnvMap::default(),
+            records: FnvSet::default(),
         }
     }
 
@@ -106,6 +112,22 @@ impl Interner {
         self.indexes.insert(key, gc_str);
         Ok(gc_str)
     }
+
+    /// Returns a shared copy of the field names of a record
+    pub fn intern_fields(&mut self, fields: Vec<InternedStr>) -> Arc<Vec<InternedStr>> {
+        match self.records.get(&fields) {
+            Some(fields) => return fields.clone(),
+            None => (),
+        }
+        let fields = Arc::new(fields);
+        self.records.insert(fields.clone());
+        fields
+    }
+
+    /// The number of distinct field name lists which are interned
+    pub fn interned_records(&self) -> usize {
+        self.records.len()
+    }
 }
 
 impl fmt::Debug for InternedStr {
@@ -44,10 +44,12 @@ pub mod serialization;
 
 #[macro_use]
 pub mod api;
+pub mod cancel;
 pub mod channel;
 pub mod core;
 pub mod compiler;
 pub mod debug;
+pub mod deferred;
 pub mod dynamic;
 #[macro_use]
 pub mod future;
@@ -58,8 +60,11 @@ pub mod thread;
 pub mod primitives;
 pub mod reference;
 pub mod stack;
+pub mod string_builder;
+pub mod testing;
 pub mod types;
 pub mod vm;
+pub mod weakmap;
 
 mod array;
 mod interner;
@@ -74,6 +79,7 @@ use types::VmIndex;
 use base::types::ArcType;
 use base::symbol::Symbol;
 use base::metadata::Metadata;
+use stack::Stacktrace;
 use thread::{RootedThread, RootedValue, Thread};
 
 unsafe fn forget_lifetime<'a, 'b, T: ?Sized>(x: &'a T) -> &'b T {
@@ -103,6 +109,47 @@ impl<'a> Variants<'a> {
     pub fn as_ref(&self) -> ValueRef<'a> {
         unsafe { ValueRef::rooted_new(self.0) }
     }
+
+    /// Returns the field `name` if this value is a record with that field.
+    ///
+    /// The field is rooted for as long as this value is so nested values can be traversed
+    /// without any `unsafe`.
+    pub fn field(&self, thread: &Thread, name: &str) -> Option<Variants<'a>> {
+        match self.as_ref() {
+            ValueRef::Data(data) => data.lookup_field(thread, name),
+            _ => None,
+        }
+    }
+
+    /// Returns the field at `index` of a record or variant, or the element at `index` of an
+    /// array
+    pub fn index(&self, index: usize) -> Option<Variants<'a>> {
+        match self.as_ref() {
+            ValueRef::Data(data) => data.get_variant(index),
+            ValueRef::Array(array) => array.get(index),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the constructor of this value if it is a value of the variant type
+    /// `type_name` (such as `std.types.Option`). Values do not store the names of their
+    /// constructors so the type must be known.
+    pub fn tag_name(&self, thread: &Thread, type_name: &str) -> Option<String> {
+        use base::types::Type;
+
+        let tag = match self.as_ref() {
+            ValueRef::Data(data) => data.tag(),
+            _ => return None,
+        };
+        let alias = thread.find_type_info(type_name).ok()?;
+        let name = match **alias.unresolved_type().remove_forall() {
+            Type::Variant(ref row) => row.row_iter()
+                .nth(tag as usize)
+                .map(|field| field.name.declared_name().to_string()),
+            _ => None,
+        };
+        name
+    }
 }
 
 /// Type returned from vm functions which may fail
@@ -123,6 +170,21 @@ quick_error! {
         TypeAlreadyExists(symbol: String) {
             display("Type `{}` already exists", symbol)
         }
+        ConflictingTypeRegistration(
+            symbol: String,
+            registered: Vec<String>,
+            conflicting: Vec<String>,
+            same_rust_type: bool
+        ) {
+            display(
+                "Type `{}` is already registered with the parameters [{}]{} and can't be \
+                 registered again with the parameters [{}]",
+                symbol,
+                registered.join(", "),
+                if *same_rust_type { "" } else { " by a different Rust type" },
+                conflicting.join(", ")
+            )
+        }
         GlobalAlreadyExists(symbol: Symbol) {
             display("Global `{}` already exists", symbol)
         }
@@ -133,6 +195,10 @@ quick_error! {
             display("Expected a value of type `{}` but the returned type was `{}`",
                     expected, actual)
         }
+        GlobalType(err: GlobalTypeError) {
+            display("{}", err)
+            from()
+        }
         OutOfMemory { limit: usize, needed: usize } {
             display("Thread is out of memory: Limit {}, needed {}", limit, needed)
         }
@@ -143,11 +209,32 @@ quick_error! {
             display("{}", err)
             from()
         }
+        GlobalChangeDenied(message: String) {
+            display("{}", message)
+        }
         Interrupted {
             display("Thread was interrupted")
         }
-        Panic(err: String) {
-            display("{}", err)
+        Cancelled {
+            display("Thread was cancelled")
+        }
+        Panic(err: String, stacktrace: Stacktrace) {
+            display("{}\n{}", err, stacktrace)
+        }
+    }
+}
+
+quick_error! {
+    /// Error returned when a global does not have the type it was expected to have
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum GlobalTypeError {
+        UndefinedGlobal(name: String) {
+            display("Global `{}` is not defined", name)
+        }
+        Mismatch { name: String, expected: ArcType, actual: ArcType, errors: Vec<String> } {
+            display("Global `{}` has the type `{}` which does not match the expected type `{}`{}",
+                    name, actual, expected,
+                    errors.iter().map(|err| format!("\n{}", err)).collect::<String>())
         }
     }
 }
@@ -76,11 +76,12 @@ pub mod array {
         }
         let vm = lhs.vm();
         let value = {
+            let (lhs, rhs) = match (lhs.get_value_array(), rhs.get_value_array()) {
+                (Ok(lhs), Ok(rhs)) => (lhs, rhs),
+                (Err(err), _) | (_, Err(err)) => return RuntimeResult::Panic(err),
+            };
             let mut context = vm.context();
-            let result = context.alloc(Append {
-                lhs: lhs.get_value_array(),
-                rhs: rhs.get_value_array(),
-            });
+            let result = context.alloc(Append { lhs: lhs, rhs: rhs });
             match result {
                 Ok(x) => x,
                 Err(err) => return RuntimeResult::Panic(err),
@@ -148,6 +149,68 @@ mod string {
         }
     }
 
+    /// Concatenates all strings in an array with a single allocation
+    pub extern "C" fn concat_array(thread: &Thread) -> Status {
+        struct StrConcat<'b> {
+            parts: &'b [GcStr],
+        }
+
+        impl<'b> StrConcat<'b> {
+            fn len(&self) -> usize {
+                self.parts.iter().map(|part| part.len()).sum()
+            }
+        }
+
+        impl<'b> Traverseable for StrConcat<'b> {
+            fn traverse(&self, gc: &mut Gc) {
+                self.parts.traverse(gc);
+            }
+        }
+
+        unsafe impl<'b> DataDef for StrConcat<'b> {
+            type Value = ValueArray;
+            fn size(&self) -> usize {
+                use std::mem::size_of;
+                size_of::<ValueArray>() + self.len() * size_of::<u8>()
+            }
+            fn initialize<'w>(self, mut result: WriteOnly<'w, ValueArray>) -> &'w mut ValueArray {
+                unsafe {
+                    let result = &mut *result.as_mut_ptr();
+                    result.set_repr(Repr::Byte);
+                    let bytes = result.unsafe_array_mut::<u8>();
+                    bytes.set_len(self.len());
+                    let mut start = 0;
+                    for part in self.parts {
+                        bytes[start..start + part.len()].copy_from_slice(part.as_bytes());
+                        start += part.len();
+                    }
+                    result
+                }
+            }
+        }
+
+        let mut context = thread.context();
+        let value = StackFrame::current(&mut context.stack)[0].get_repr();
+        match value {
+            ValueRepr::Array(array) => {
+                // The array is kept alive by the stack for the duration of the allocation
+                let parts = array.as_slice::<GcStr>().unwrap_or(&[]);
+                match context.alloc_with(thread, StrConcat { parts }) {
+                    Ok(string) => {
+                        let string = unsafe { GcStr::from_utf8_unchecked(string) };
+                        context.stack.push(ValueRepr::String(string));
+                        Status::Ok
+                    }
+                    Err(err) => {
+                        let result: RuntimeResult<(), _> = RuntimeResult::Panic(err);
+                        result.status_push(thread, &mut context)
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
     pub fn slice(s: &str, start: usize, end: usize) -> RuntimeResult<&str, String> {
         if s.is_char_boundary(start) && s.is_char_boundary(end) {
             RuntimeResult::Return(&s[start..end])
@@ -411,6 +474,10 @@ pub fn load_string(vm: &Thread) -> Result<ExternModule> {
             trim_right => primitive!(1 std::string::prim::trim_right),
             append => named_primitive!(2, "std.string.prim.append", string::append),
             slice => named_primitive!(3, "std.string.prim.slice", string::slice),
+            concat_array => primitive::<fn(Vec<String>) -> String>(
+                "std.string.prim.concat_array",
+                string::concat_array
+            ),
             from_utf8 => primitive::<fn(Vec<u8>) -> StdResult<String, ()>>(
                 "std.string.prim.from_utf8",
                 string::from_utf8
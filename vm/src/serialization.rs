@@ -370,6 +370,40 @@ pub mod intern {
     }
 }
 
+/// Serializes the field names of records as lists of strings, sharing them with the other
+/// functions in the vm when deserializing
+pub mod records {
+    use super::*;
+    use interner::InternedStr;
+    use thread::ThreadInternal;
+
+    pub fn deserialize<'de, D>(
+        seed: &mut DeSeed,
+        deserializer: D,
+    ) -> Result<Vec<Arc<Vec<InternedStr>>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let records = Vec::<Vec<InternedStr>>::deserialize_state(seed, deserializer)?;
+        let env = seed.thread.global_env();
+        Ok(records
+            .into_iter()
+            .map(|fields| env.intern_fields(fields))
+            .collect())
+    }
+
+    pub fn serialize<S>(
+        records: &[Arc<Vec<InternedStr>>],
+        serializer: S,
+        state: &SeSeed,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(records.iter().map(|fields| Seeded::new(state, &**fields)))
+    }
+}
+
 pub mod borrow {
     use super::*;
     use std::borrow::{Borrow, BorrowMut};
@@ -711,7 +745,7 @@ impl<'de> DeserializeState<'de, DeSeed> for ExternFunction {
                     Ok(ExternFunction {
                         id: function.id.clone(),
                         args: function.args,
-                        function: function.function,
+                        function: function.function.clone(),
                     })
                 }
                 _ => Err(D::Error::custom("Invalid type for extern function")),
@@ -1,5 +1,6 @@
 use std::fmt;
 use std::ops::{Deref, DerefMut, Index, IndexMut, Range, RangeFrom, RangeFull, RangeTo};
+use std::sync::Arc;
 
 use base::symbol::Symbol;
 use base::pos::Line;
@@ -205,7 +206,10 @@ impl Stack {
                 State::Lock | State::Excess => None,
             })
             .collect();
-        Stacktrace { frames: frames }
+        Stacktrace {
+            frames: frames,
+            thread_name: None,
+        }
     }
 }
 
@@ -259,8 +263,9 @@ impl<'b> fmt::Debug for StackFrame<'b> {
 
 impl<'a: 'b, 'b> StackFrame<'b> {
     pub fn take_stack(self) -> &'b mut Stack {
-        *self.stack.frames.last_mut().unwrap() = self.frame;
-        self.stack
+        let mut this = ::std::mem::ManuallyDrop::new(self);
+        *this.stack.frames.last_mut().unwrap() = this.frame;
+        unsafe { ::std::ptr::read(&this.stack) }
     }
 
     pub fn len(&self) -> VmIndex {
@@ -490,11 +495,23 @@ pub struct StacktraceFrame {
 #[derive(Debug, PartialEq)]
 pub struct Stacktrace {
     pub frames: Vec<Option<StacktraceFrame>>,
+    /// The name of the thread which the stacktrace was taken from, if it is known
+    pub thread_name: Option<Arc<str>>,
+}
+
+impl Stacktrace {
+    pub fn with_thread_name(mut self, name: Arc<str>) -> Stacktrace {
+        self.thread_name = Some(name);
+        self
+    }
 }
 
 impl fmt::Display for Stacktrace {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "Stacktrace:\n")?;
+        match self.thread_name {
+            Some(ref name) => writeln!(f, "Stacktrace of thread `{}`:\n", name)?,
+            None => writeln!(f, "Stacktrace:\n")?,
+        }
         for (i, frame) in self.frames.iter().enumerate() {
             match *frame {
                 Some(ref frame) => writeln!(
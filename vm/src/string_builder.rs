@@ -0,0 +1,80 @@
+//! Module containing a mutable string builder which avoids the repeated allocations of `++` chains.
+//!
+//! `build` takes the contents of the builder and resets it to empty, so a builder can be reused
+//! after it has been built.
+use std::fmt::Write;
+use std::mem;
+use std::sync::Mutex;
+
+use {ExternModule, Result};
+use api::{Userdata, VmType};
+use gc::{Gc, GcPtr, Move, Traverseable};
+use thread::Thread;
+use types::VmInt;
+use value::Cloner;
+
+#[derive(Debug)]
+pub struct Builder(Mutex<String>);
+
+impl Userdata for Builder {
+    fn deep_clone(&self, deep_cloner: &mut Cloner) -> Result<GcPtr<Box<Userdata>>> {
+        let data: Box<Userdata> = Box::new(Builder(Mutex::new(self.0.lock().unwrap().clone())));
+        deep_cloner.gc().alloc(Move(data))
+    }
+}
+
+impl VmType for Builder {
+    type Type = Builder;
+}
+
+impl Traverseable for Builder {
+    fn traverse(&self, _: &mut Gc) {}
+}
+
+fn new(_: ()) -> Builder {
+    Builder(Mutex::new(String::new()))
+}
+
+fn push_str(builder: &Builder, s: &str) {
+    builder.0.lock().unwrap().push_str(s);
+}
+
+fn push_int(builder: &Builder, i: VmInt) {
+    write!(builder.0.lock().unwrap(), "{}", i).unwrap();
+}
+
+fn push_float(builder: &Builder, f: f64) {
+    write!(builder.0.lock().unwrap(), "{}", f).unwrap();
+}
+
+fn push_char(builder: &Builder, c: char) {
+    builder.0.lock().unwrap().push(c);
+}
+
+/// Returns the contents of the builder, leaving it empty
+fn build(builder: &Builder) -> String {
+    mem::replace(&mut *builder.0.lock().unwrap(), String::new())
+}
+
+mod std {
+    pub mod string {
+        pub use string_builder as builder;
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    let _ = vm.register_type::<Builder>("Builder", &[]);
+    ExternModule::new(
+        vm,
+        record!{
+            new => primitive!(1 std::string::builder::new),
+            push_str => primitive!(2 std::string::builder::push_str),
+            push_int => primitive!(2 std::string::builder::push_int),
+            push_float => primitive!(2 std::string::builder::push_float),
+            push_char => primitive!(2 std::string::builder::push_char),
+            build => primitive!(1 std::string::builder::build)
+        },
+    )
+}
@@ -0,0 +1,178 @@
+//! Primitives for writing test suites in gluon.
+//!
+//! The results of the test cases are collected into a `TestReport` which the host can retrieve
+//! with `Thread::test_report` once the suite has been run.
+use std::result::Result as StdResult;
+use std::sync::Mutex;
+
+use base::types::Type;
+
+use api::generic::A;
+use api::{OpaqueValue, OwnedFunction, RuntimeResult, WithVM};
+use stack::{StackFrame, State};
+use thread::{Thread, ThreadInternal};
+use value::ValuePrinter;
+use {Error, ExternModule, Result, Variants};
+
+/// The outcome of a single test case
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestResult {
+    /// The names of the groups enclosing the test case followed by the name of the case itself
+    pub path: Vec<String>,
+    /// The error the test case failed with or `None` if it passed
+    pub error: Option<String>,
+}
+
+/// Collects the results of the test cases run by `std.test.prim.case`
+#[derive(Debug, Default)]
+pub struct TestReport {
+    groups: Mutex<Vec<String>>,
+    results: Mutex<Vec<TestResult>>,
+}
+
+impl TestReport {
+    /// Returns the results of all test cases run so far, in the order they were run
+    pub fn results(&self) -> Vec<TestResult> {
+        self.results.lock().unwrap().clone()
+    }
+
+    /// Returns the results of the test cases which failed
+    pub fn failures(&self) -> Vec<TestResult> {
+        self.results
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|result| result.error.is_some())
+            .cloned()
+            .collect()
+    }
+
+    /// Removes all collected results
+    pub fn clear(&self) {
+        self.results.lock().unwrap().clear();
+    }
+}
+
+/// Returns the location in the closest gluon function on the stack.
+///
+/// Tail calls replace the frame of the caller so for an assertion in tail position this is the
+/// location of the function which called the function containing the assertion.
+fn caller_location(thread: &Thread) -> String {
+    let context = thread.context();
+    context
+        .stack
+        .get_frames()
+        .iter()
+        .rev()
+        .filter_map(|frame| match frame.state {
+            State::Closure(ref closure) => {
+                let debug_info = &closure.function.debug_info;
+                // Subtract one to account for the `Call` instruction adding one
+                debug_info
+                    .source_map
+                    .line(frame.instruction_index.saturating_sub(1))
+                    .map(|line| format!("{}:Line {}", debug_info.source_name, line))
+            }
+            _ => None,
+        })
+        .next()
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+fn show(thread: &Thread, value: Variants) -> String {
+    let env = thread.global_env().get_env();
+    ValuePrinter::new(&*env, &Type::hole(), value).to_string()
+}
+
+fn assert(condition: WithVM<bool>) -> RuntimeResult<(), String> {
+    if condition.value {
+        RuntimeResult::Return(())
+    } else {
+        RuntimeResult::Panic(format!(
+            "Assertion failed at {}",
+            caller_location(condition.vm)
+        ))
+    }
+}
+
+fn assert_msg(message: WithVM<&str>, condition: bool) -> RuntimeResult<(), String> {
+    if condition {
+        RuntimeResult::Return(())
+    } else {
+        RuntimeResult::Panic(format!(
+            "Assertion failed at {}: {}",
+            caller_location(message.vm),
+            message.value
+        ))
+    }
+}
+
+fn assert_eq<'vm>(
+    l: WithVM<'vm, OpaqueValue<&'vm Thread, A>>,
+    r: OpaqueValue<&'vm Thread, A>,
+) -> RuntimeResult<(), String> {
+    let thread = l.vm;
+    let l = l.value;
+    if l == r {
+        RuntimeResult::Return(())
+    } else {
+        RuntimeResult::Panic(format!(
+            "Assertion failed at {}\n  left: {}\n right: {}",
+            caller_location(thread),
+            show(thread, l.get_variant()),
+            show(thread, r.get_variant())
+        ))
+    }
+}
+
+/// Calls `f`, removing the frames of the failed calls from the stack if it returns an error
+fn call_test(thread: &Thread, mut f: OwnedFunction<fn(()) -> ()>) -> StdResult<(), String> {
+    let frame_level = thread.context().stack.get_frames().len();
+    f.call(()).map_err(|err| {
+        let mut context = thread.context();
+        let mut stack = StackFrame::current(&mut context.stack);
+        while stack.stack.get_frames().len() > frame_level {
+            if stack.exit_scope().is_err() {
+                break;
+            }
+        }
+        match err {
+            // The location of the assertion is already part of the message
+            Error::Panic(msg, _) => msg,
+            err => err.to_string(),
+        }
+    })
+}
+
+fn group(name: WithVM<&str>, tests: OwnedFunction<fn(()) -> ()>) -> RuntimeResult<(), String> {
+    let report = name.vm.global_env().test_report();
+    report.groups.lock().unwrap().push(name.value.to_string());
+    let result = call_test(name.vm, tests);
+    report.groups.lock().unwrap().pop();
+    result.into()
+}
+
+fn case(name: WithVM<&str>, test: OwnedFunction<fn(()) -> ()>) {
+    let report = name.vm.global_env().test_report();
+    let mut path = report.groups.lock().unwrap().clone();
+    path.push(name.value.to_string());
+    let error = call_test(name.vm, test).err();
+    report
+        .results
+        .lock()
+        .unwrap()
+        .push(TestResult { path, error });
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    ExternModule::new(
+        vm,
+        record!{
+            assert => named_primitive!(1, "std.test.prim.assert", assert),
+            assert_msg => named_primitive!(2, "std.test.prim.assert_msg", assert_msg),
+            assert_eq => named_primitive!(2, "std.test.prim.assert_eq", assert_eq),
+            group => named_primitive!(2, "std.test.prim.group", group),
+            case => named_primitive!(2, "std.test.prim.case", case)
+        },
+    )
+}
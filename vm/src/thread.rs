@@ -8,7 +8,7 @@ use std::ops::{Add, Deref, DerefMut, Div, Mul, Sub};
 use std::string::String as StdString;
 use std::result::Result as StdResult;
 use std::sync::Arc;
-use std::sync::atomic::{self, AtomicBool};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
 use std::usize;
 
 use futures::{Async, Future, Poll};
@@ -17,25 +17,29 @@ use future::FutureValue;
 use base::metadata::Metadata;
 use base::pos::Line;
 use base::symbol::Symbol;
-use base::types::ArcType;
+use base::types::{ArcType, Type};
 use base::types;
 
-use {Error, Result, Variants};
+use {Error, GlobalTypeError, Result, Variants};
 use macros::MacroEnv;
-use api::{Getable, Pushable, ValueRef, VmType};
+use api::{Getable, OpaqueValue, OwnedFunction, Pushable, ValueRef, VmType};
+use cancel::{CancellationState, CancellationToken};
 use compiler::UpvarInfo;
+use deferred::DeferredCall;
 use gc::{DataDef, Gc, GcPtr, Generation, Move};
 use source_map::LocalIter;
+use testing::TestReport;
 use stack::{Frame, Lock, Stack, StackFrame, State};
 use types::*;
 use vm::{GlobalVmState, GlobalVmStateBuilder, VmEnv};
 use value::{BytecodeFunction, Callable, ClosureData, ClosureDataDef, ClosureInitDef, Def,
-            ExternFunction, GcStr, PartialApplicationDataDef, RecordDef, Userdata, Value,
-            ValueRepr};
+            ExternFunction, GcStr, PartialApplicationDataDef, RecordDef, UpdateRecordDef, Userdata,
+            Value, ValuePrinter, ValueRepr};
 
 use value::ValueRepr::{Closure, Data, Float, Function, Int, PartialApplication, String};
 
 pub use gc::Traverseable;
+pub use value::SizeReport;
 
 pub struct Execute<T> {
     thread: Option<T>,
@@ -124,6 +128,15 @@ where
     }
 }
 
+impl<T> Traverseable for RootedValue<T>
+where
+    T: Deref<Target = Thread>,
+{
+    fn traverse(&self, gc: &mut Gc) {
+        self.value.traverse(gc);
+    }
+}
+
 impl<T> fmt::Debug for RootedValue<T>
 where
     T: Deref<Target = Thread>,
@@ -181,9 +194,53 @@ impl<'vm> RootedValue<&'vm Thread> {
 /// A rooted userdata value
 pub struct Root<'vm, T: ?Sized + 'vm> {
     roots: &'vm RwLock<Vec<GcPtr<Traverseable + Send + Sync>>>,
+    /// The allocation which is kept alive by this root, `ptr` always points into it
+    root: GcPtr<Traverseable + Send + Sync>,
     ptr: *const T,
 }
 
+impl<'vm, T: ?Sized> Root<'vm, T> {
+    /// Narrows the root to a part of the rooted value, similar to `std::cell::Ref::map`.
+    ///
+    /// The returned `Root` keeps the entire allocation rooted so the projected reference stays
+    /// valid for as long as it is alive.
+    ///
+    /// ```ignore
+    /// let name: Root<String> = Root::map(person, |person| &person.name);
+    /// ```
+    pub fn map<U: ?Sized, F>(root: Root<'vm, T>, f: F) -> Root<'vm, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        // The reference passed to `f` only lives as long as `root` so the compiler can't check
+        // that the returned pointer stays valid after the borrow ends. It does however point into
+        // the rooted allocation (or be `'static`) and since the garbage collector does not move
+        // values, the pointer is valid for as long as the allocation is rooted.
+        let ptr = f(&root) as *const U;
+        let mapped = Root {
+            roots: root.roots,
+            root: root.root.clone(),
+            ptr,
+        };
+        // Transfer the entry in `roots` to `mapped` instead of popping it
+        mem::forget(root);
+        mapped
+    }
+
+    /// Roots the value a second time, returning a new `Root` which can outlive `root`.
+    ///
+    /// This is an associated function (like `Root::map`) so that it does not shadow
+    /// a `clone` method on the rooted value.
+    pub fn clone(root: &Root<'vm, T>) -> Root<'vm, T> {
+        root.roots.write().unwrap().push(root.root.clone());
+        Root {
+            roots: root.roots,
+            root: root.root.clone(),
+            ptr: root.ptr,
+        }
+    }
+}
+
 impl<'vm, T: ?Sized> Drop for Root<'vm, T> {
     fn drop(&mut self) {
         // TODO not safe if the root changes order of being dropped with another root
@@ -305,14 +362,46 @@ pub struct Thread {
     context: Mutex<Context>,
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     interrupt: AtomicBool,
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    cancellation_token: CancellationToken,
+    /// Calls queued by `defer_call` which are run by `run_deferred`
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    deferred: Mutex<Vec<DeferredCall>>,
+    /// The name used for the thread in error messages. Threads which have not been given a name
+    /// get a generated name the first time it is needed.
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    name: Mutex<Option<Arc<str>>>,
 }
 
 impl fmt::Debug for Thread {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Thread({:p})", self)
+        write!(f, "Thread({} {:p})", self.name(), self)
     }
 }
 
+/// A modification of the global environment which is checked against the policy set with
+/// `Thread::set_global_policy` before it is performed
+#[derive(Clone, Debug, PartialEq)]
+pub enum GlobalChange {
+    /// A global value called `name` is defined
+    DefineGlobal { name: StdString },
+    /// A type called `name` is registered
+    RegisterType { name: StdString },
+    /// The module `name` is loaded by the `import!` macro
+    LoadModule { name: StdString },
+}
+
+/// Function deciding whether a `GlobalChange` is allowed. Returning `Err` vetoes the change with
+/// the returned message
+pub type GlobalPolicy = Fn(&GlobalChange) -> StdResult<(), StdString> + Send + Sync;
+
+/// Generates the name of a thread which has not been given one (`thread-0`, `thread-1`, ...)
+fn generate_thread_name() -> Arc<str> {
+    static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(0);
+    let id = NEXT_THREAD_ID.fetch_add(1, atomic::Ordering::SeqCst);
+    Arc::from(format!("thread-{}", id))
+}
+
 impl Userdata for Thread {}
 
 impl VmType for Thread {
@@ -420,6 +509,9 @@ impl RootedThread {
             rooted_values: RwLock::new(Vec::new()),
             child_threads: RwLock::new(Vec::new()),
             interrupt: AtomicBool::new(false),
+            cancellation_token: CancellationToken::new(),
+            deferred: Mutex::new(Vec::new()),
+            name: Mutex::new(None),
         };
         let mut gc = Gc::new(Generation::default(), usize::MAX);
         let vm = gc.alloc(Move(thread))
@@ -452,8 +544,17 @@ impl RootedThread {
 
 impl Thread {
     /// Spawns a new gluon thread with its own stack and heap but while still sharing the same
-    /// global environment
+    /// global environment. The new thread shares the cancellation token of `self`.
     pub fn new_thread(&self) -> Result<RootedThread> {
+        self.new_thread_with_token(self.cancellation_token.clone())
+    }
+
+    /// Spawns a new gluon thread like `new_thread` but with its own cancellation token
+    pub fn new_detached_thread(&self) -> Result<RootedThread> {
+        self.new_thread_with_token(CancellationToken::new())
+    }
+
+    fn new_thread_with_token(&self, cancellation_token: CancellationToken) -> Result<RootedThread> {
         let vm = Thread {
             global_state: self.global_state.clone(),
             parent: Some(self.root_thread()),
@@ -462,6 +563,9 @@ impl Thread {
             rooted_values: RwLock::new(Vec::new()),
             child_threads: RwLock::new(Vec::new()),
             interrupt: AtomicBool::new(false),
+            cancellation_token,
+            deferred: Mutex::new(Vec::new()),
+            name: Mutex::new(None),
         };
         // Enter the top level scope
         {
@@ -473,6 +577,21 @@ impl Thread {
         Ok(ptr.root_thread())
     }
 
+    /// Returns the name of the thread. Threads which have not been given a name with `set_name`
+    /// have a generated name such as `thread-3`.
+    pub fn name(&self) -> Arc<str> {
+        self.name
+            .lock()
+            .unwrap()
+            .get_or_insert_with(generate_thread_name)
+            .clone()
+    }
+
+    /// Sets the name which is used to refer to the thread in error messages and stacktraces
+    pub fn set_name(&self, name: StdString) {
+        *self.name.lock().unwrap() = Some(Arc::from(name));
+    }
+
     /// Roots `self`, extending the lifetime of this thread until at least the returned
     /// `RootedThread` is droppped
     pub fn root_thread(&self) -> RootedThread {
@@ -569,22 +688,45 @@ impl Thread {
     ///
     /// # Errors
     ///
-    /// if the global does not exist or it does not have the correct type.
+    /// if the global does not exist or it does not have the correct type. A global with the wrong
+    /// type is reported as `Error::GlobalType` (not `Error::WrongType`) so that the error names
+    /// the global.
     ///
     pub fn get_global<'vm, T>(&'vm self, name: &str) -> Result<T>
     where
         T: Getable<'vm> + VmType,
     {
-        use check::check_signature;
         let env = self.get_env();
         let (value, actual) = env.get_binding(name)?;
         // Finally check that type of the returned value is correct
-        let expected = T::make_type(self);
-        if check_signature(&*env, &expected, &actual) {
-            unsafe { Ok(T::from_value(self, Variants::new(&value))) }
-        } else {
-            Err(Error::WrongType(expected, actual.into_owned()))
-        }
+        Thread::check_global_type(&env, name, &T::make_type(self), &actual)?;
+        unsafe { T::try_from_value(self, Variants::new(&value)) }
+    }
+
+    /// Checks that the global called `name` can be used as a value of the type `expected`. If it
+    /// can't the error contains the type of the global and the errors found when unifying it with
+    /// `expected`.
+    pub fn verify_global(&self, name: &str, expected: &ArcType) -> StdResult<(), GlobalTypeError> {
+        let env = self.get_env();
+        let (_, actual) = env.get_binding(name)
+            .map_err(|_| GlobalTypeError::UndefinedGlobal(name.into()))?;
+        Thread::check_global_type(&env, name, expected, &actual)
+    }
+
+    fn check_global_type(
+        env: &VmEnv,
+        name: &str,
+        expected: &ArcType,
+        actual: &ArcType,
+    ) -> StdResult<(), GlobalTypeError> {
+        use check::check_signature_result;
+
+        check_signature_result(env, expected, actual).map_err(|errors| GlobalTypeError::Mismatch {
+            name: name.into(),
+            expected: expected.clone(),
+            actual: actual.clone(),
+            errors: errors.into_iter().map(|err| err.to_string()).collect(),
+        })
     }
 
     /// Retrieves type information about the type `name`. Types inside records can be accessed
@@ -594,16 +736,78 @@ impl Thread {
         env.find_type_info(name).map(|alias| alias.into_owned())
     }
 
+    /// Formats `value` for debugging purposes. If `typ` is given it is used to print the names of
+    /// record fields and variant constructors (resolving aliases through the global environment),
+    /// otherwise only the field names stored in records are available. Arrays longer than 20
+    /// elements are elided, `vm::internal::ValuePrinter` can be used directly for more control.
+    pub fn format_value(&self, value: &Variants, typ: Option<&ArcType>) -> StdString {
+        let env = self.get_env();
+        let hole = Type::hole();
+        ValuePrinter::new(&*env, typ.unwrap_or(&hole), *value)
+            .max_array_len(20)
+            .to_string()
+    }
+
+    /// Estimates how much memory `value` uses. Values which are shared by several parts of
+    /// `value` are only counted once.
+    pub fn value_size<T, V>(&self, value: &OpaqueValue<T, V>) -> SizeReport
+    where
+        T: Deref<Target = Thread>,
+    {
+        ::value::value_size(value.get_variant().0)
+    }
+
     /// Returns the gluon type that was bound to `T`
     pub fn get_type<T: ?Sized + Any>(&self) -> ArcType {
         self.global_env().get_type::<T>()
     }
 
-    /// Registers the type `T` as being a gluon type called `name` with generic arguments `args`
+    /// Returns the type which `T` were registered as, if it has been registered
+    pub fn lookup_registered_type<T: ?Sized + Any>(&self) -> Option<ArcType> {
+        self.global_env().lookup_registered_type::<T>()
+    }
+
+    /// Registers the type `T` as being a gluon type called `name` with generic arguments `args`.
+    /// Registering `T` again with the same name and arguments returns the existing type.
     pub fn register_type<T: ?Sized + Any>(&self, name: &str, args: &[&str]) -> Result<ArcType> {
+        self.check_global_change(&GlobalChange::RegisterType {
+            name: name.into(),
+        })?;
         self.global_env().register_type::<T>(name, args)
     }
 
+    /// Registers the type `T` as being a gluon variant type called `name` with the constructors
+    /// `constructors`
+    pub fn register_variant<T: ?Sized + Any>(
+        &self,
+        name: &str,
+        constructors: &[(&str, Vec<ArcType>)],
+    ) -> Result<ArcType> {
+        self.check_global_change(&GlobalChange::RegisterType {
+            name: name.into(),
+        })?;
+        self.global_env().register_variant::<T>(name, constructors)
+    }
+
+    /// Sets the policy which is consulted before any global is defined, type is registered or
+    /// module is loaded. Vetoed changes fail with `Error::GlobalChangeDenied`. The policy is
+    /// stored in the global environment so it applies to every thread of the vm.
+    pub fn set_global_policy(&self, policy: Box<GlobalPolicy>) {
+        self.global_env().set_global_policy(policy)
+    }
+
+    /// Checks `change` against the policy set with `set_global_policy`, allowing everything if no
+    /// policy has been set
+    pub fn check_global_change(&self, change: &GlobalChange) -> Result<()> {
+        self.global_env().check_global_change(change)
+    }
+
+    /// Registers `typ` as the operand type of the primitive operators `#<name>+` etc.
+    /// The operators themselves are implemented by globals with the same name as the operator.
+    pub fn register_primitive_operand(&self, name: &str, typ: ArcType) -> Result<()> {
+        self.global_env().register_primitive_operand(name, typ)
+    }
+
     /// Locks and retrieves the global environment of the vm
     pub fn get_env<'b>(&'b self) -> RwLockReadGuard<'b, VmEnv> {
         self.global_env().get_env()
@@ -614,6 +818,11 @@ impl Thread {
         self.global_env().get_macros()
     }
 
+    /// Retrieves the results of the test cases run through `std.test.prim`
+    pub fn test_report(&self) -> &TestReport {
+        self.global_env().test_report()
+    }
+
     /// Runs a garbage collection.
     pub fn collect(&self) {
         let mut context = self.current_context();
@@ -648,6 +857,57 @@ impl Thread {
         self.interrupt.load(atomic::Ordering::Relaxed)
     }
 
+    /// Returns the token which the host can use to request that this thread (and the threads
+    /// spawned from it) stop running
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    /// Queues a call of `function` with `arg` to be run by the next call to `run_deferred`.
+    ///
+    /// Unlike calling `function` directly this is safe to do from a `Drop` impl, even while the
+    /// thread is unwinding from a panic or while the context of the thread is locked.
+    pub fn defer_call<A, R>(&self, function: OwnedFunction<fn(A) -> R>, arg: A)
+    where
+        A: for<'vm> Pushable<'vm> + Send + 'static,
+        R: VmType + for<'x> Getable<'x> + 'static,
+    {
+        self.deferred
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(DeferredCall::new(function, arg));
+    }
+
+    /// Runs the calls queued by `defer_call` and the finalizers of the userdata freed by this
+    /// thread. Returns the first error of the calls after all of them have been run.
+    ///
+    /// Nothing is run if the thread is unwinding from a panic or if the context of the thread is
+    /// already locked, the calls are then left in the queue until the next call to `run_deferred`.
+    pub fn run_deferred(&self) -> Result<()> {
+        if ::std::thread::panicking() {
+            return Ok(());
+        }
+        let mut calls = match self.context.try_lock() {
+            Ok(mut context) => context.gc.take_finalizers(),
+            Err(_) => return Ok(()),
+        };
+        calls.extend(
+            self.deferred
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .drain(..),
+        );
+        let mut result = Ok(());
+        for call in calls {
+            if let Err(err) = call.run() {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+
     fn current_context(&self) -> OwnedContext {
         self.context()
     }
@@ -778,10 +1038,12 @@ impl ThreadInternal for Thread {
     }
     /// Roots a userdata
     fn root<'vm, T: Userdata>(&'vm self, v: GcPtr<Box<Userdata>>) -> Option<Root<'vm, T>> {
-        v.downcast_ref::<T>().map(|ptr| {
-            self.roots.write().unwrap().push(v.as_traverseable());
+        ::api::downcast_userdata::<T>(&**v).map(|ptr| {
+            let root = v.as_traverseable();
+            self.roots.write().unwrap().push(root.clone());
             Root {
                 roots: &self.roots,
+                root,
                 ptr: ptr,
             }
         })
@@ -789,12 +1051,11 @@ impl ThreadInternal for Thread {
 
     /// Roots a string
     fn root_string<'vm>(&'vm self, ptr: GcStr) -> RootStr<'vm> {
-        self.roots
-            .write()
-            .unwrap()
-            .push(ptr.into_inner().as_traverseable());
+        let root = ptr.into_inner().as_traverseable();
+        self.roots.write().unwrap().push(root.clone());
         RootStr(Root {
             roots: &self.roots,
+            root,
             ptr: &*ptr,
         })
     }
@@ -881,6 +1142,9 @@ impl ThreadInternal for Thread {
         metadata: Metadata,
         value: Value,
     ) -> Result<()> {
+        self.check_global_change(&GlobalChange::DefineGlobal {
+            name: name.definition_name().into(),
+        })?;
         let value = ::value::Cloner::new(self, &mut self.global_env().gc.lock().unwrap())
             .deep_clone(&value)?;
         self.global_env().set_global(name, typ, metadata, value)
@@ -1070,6 +1334,9 @@ pub struct Context {
             Box<for<'vm> FnMut(&'vm Thread) -> Result<Async<OwnedContext<'vm>>> + Send>,
         ),
     >,
+
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    pub(crate) cancellation: CancellationState,
 }
 
 impl Context {
@@ -1084,6 +1351,7 @@ impl Context {
             },
             max_stack_size: VmIndex::max_value(),
             poll_fns: Vec::new(),
+            cancellation: CancellationState::default(),
         }
     }
 
@@ -1166,12 +1434,13 @@ impl<'b> OwnedContext<'b> {
         D: DataDef + Traverseable,
         D::Value: Sized + Any,
     {
+        let thread = self.thread;
         let Context {
             ref mut gc,
             ref stack,
             ..
         } = **self;
-        alloc(gc, self.thread, &stack, data)
+        alloc(gc, thread, &stack, data)
     }
 
     pub fn debug_info(&self) -> DebugInfo {
@@ -1235,6 +1504,9 @@ impl<'b> OwnedContext<'b> {
             if context.thread.interrupted() {
                 return Err(Error::Interrupted);
             }
+            if context.thread.cancellation_token.is_cancelled() {
+                context.cancellation.check()?;
+            }
             debug!("STACK\n{:?}", context.stack.get_frames());
             let state = context.borrow_mut().stack.frame.state;
 
@@ -1345,7 +1617,7 @@ impl<'b> OwnedContext<'b> {
             // Necessary since we do not know what will happen during the function call
             let thread = self.thread;
             drop(self);
-            status = (function.function)(thread);
+            status = function.function.call(thread);
 
             if status == Status::Yield {
                 return Ok(Async::NotReady);
@@ -1355,7 +1627,10 @@ impl<'b> OwnedContext<'b> {
 
             if status == Status::Error {
                 return match self.stack.pop().get_repr() {
-                    String(s) => Err(Error::Panic(s.to_string())),
+                    String(s) => Err(Error::Panic(
+                        s.to_string(),
+                        self.stack.stacktrace(0).with_thread_name(thread.name()),
+                    )),
                     _ => Err(Error::Message(format!(
                         "Unexpected error calling function `{}`",
                         function.id
@@ -1405,8 +1680,10 @@ impl<'b> OwnedContext<'b> {
         // The function call is done at this point so remove any extra values from the frame and
         // return the value at the top of the stack
         let result = self.stack.pop();
+        let frame_has_excess;
         {
             let mut stack = self.stack.current_frame();
+            frame_has_excess = stack.frame.excess;
             while stack.len() > 0 {
                 debug!("{} {:?}", stack.len(), &*stack);
                 stack.pop();
@@ -1431,7 +1708,6 @@ impl<'b> OwnedContext<'b> {
             Error::Message(StdString::from("Poped the last frame in execute_function"))
         })?;
         self.stack.pop(); // Pop function
-        self.stack.push(result);
 
         info!(
             "EXIT EXTERN {} {:?}",
@@ -1439,11 +1715,34 @@ impl<'b> OwnedContext<'b> {
             &self.stack.current_frame()[..]
         );
 
+        if frame_has_excess && status == Status::Ok {
+            // Just as for closures, the result of an extern function called with more arguments
+            // than it takes is called with the remaining arguments
+            match self.stack.pop().get_repr() {
+                Data(excess) => {
+                    let mut context = self.borrow_mut();
+                    context.enter_scope(0, State::Excess);
+                    debug!("Push excess args {:?}", &excess.fields);
+                    context.stack.push(result);
+                    for value in &excess.fields {
+                        context.stack.push(value);
+                    }
+                    context.do_call(excess.fields.len() as VmIndex)?;
+                }
+                x => ice!("Expected excess arguments found {:?}", x),
+            }
+            return Ok(Async::Ready(self));
+        }
+        self.stack.push(result);
+
         match status {
             Status::Ok => Ok(Async::Ready(self)),
             Status::Yield => Ok(Async::NotReady),
             Status::Error => match self.stack.pop().get_repr() {
-                String(s) => Err(Error::Panic(s.to_string())),
+                String(s) => Err(Error::Panic(
+                    s.to_string(),
+                    self.stack.stacktrace(0).with_thread_name(self.thread.name()),
+                )),
                 _ => Err(Error::Message(format!(
                     "Unexpected error calling function `{}`",
                     function.id
@@ -1453,9 +1752,10 @@ impl<'b> OwnedContext<'b> {
     }
 
     fn borrow_mut(&mut self) -> ExecuteContext {
+        let thread = self.thread;
         let context = &mut **self;
         ExecuteContext {
-            thread: self.thread,
+            thread,
             gc: &mut context.gc,
             stack: StackFrame::current(&mut context.stack),
             hook: &mut context.hook,
@@ -1503,6 +1803,7 @@ impl<'b> ExecuteContext<'b> {
                 let function_index = self.stack.len() - ext.args - 1;
                 debug!("------- {} {:?}", function_index, &self.stack[..]);
                 self.enter_scope(ext.args, State::Extern(*ext));
+                self.stack.frame.excess = excess;
                 Ok(())
             }
         }
@@ -1637,6 +1938,9 @@ impl<'b> ExecuteContext<'b> {
                 PushByte(b) => {
                     self.stack.push(ValueRepr::Byte(b));
                 }
+                PushChar(c) => {
+                    self.stack.push(ValueRepr::Char(c));
+                }
                 PushString(string_index) => {
                     self.stack
                         .push(String(function.strings[string_index as usize].inner()));
@@ -1736,6 +2040,36 @@ impl<'b> ExecuteContext<'b> {
                     }
                     self.stack.push(d);
                 }
+                UpdateRecord { record, args } => {
+                    let d = {
+                        let base = match self.stack[self.stack.len() - args - 1].get_repr() {
+                            Data(base) => base,
+                            x => return Err(Error::Message(format!("UpdateRecord on {:?}", x))),
+                        };
+                        let elems = &self.stack[self.stack.len() - args..];
+                        unsafe {
+                            let roots = Roots {
+                                vm: GcPtr::from_raw(self.thread),
+                                stack: &self.stack.stack,
+                            };
+                            let spec = &function.records[record as usize];
+                            let (record_fields, fields) = spec.split_at(spec.len() - args as usize);
+                            Data(self.gc.alloc_and_collect(
+                                roots,
+                                UpdateRecordDef {
+                                    base,
+                                    elems,
+                                    record_fields,
+                                    fields,
+                                },
+                            )?)
+                        }
+                    };
+                    for _ in 0..(args + 1) {
+                        self.stack.pop();
+                    }
+                    self.stack.push(d);
+                }
                 ConstructArray(args) => {
                     let d = {
                         let fields = &self.stack[self.stack.len() - args..];
@@ -1879,6 +2213,8 @@ impl<'b> ExecuteContext<'b> {
                 DivideInt => binop_int(self.thread, &mut self.stack, VmInt::div),
                 IntLT => binop_bool(self.thread, &mut self.stack, |l: VmInt, r| l < r),
                 IntEQ => binop_bool(self.thread, &mut self.stack, |l: VmInt, r| l == r),
+                CharLT => binop_bool(self.thread, &mut self.stack, |l: char, r| l < r),
+                CharEQ => binop_bool(self.thread, &mut self.stack, |l: char, r| l == r),
 
                 AddByte => binop_byte(self.thread, &mut self.stack, u8::add),
                 SubtractByte => binop_byte(self.thread, &mut self.stack, u8::sub),
@@ -2009,7 +2345,7 @@ fn debug_instruction(stack: &StackFrame, index: usize, instr: Instruction) {
                 }
                 x
             }
-            NewClosure { .. } | MakeClosure { .. } => Some(Value::from(Int(stack.len() as isize))),
+            NewClosure { .. } | MakeClosure { .. } => Some(Value::from(Int(stack.len() as VmInt))),
             _ => None,
         }
     );
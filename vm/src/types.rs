@@ -7,7 +7,12 @@ pub use self::Instruction::*;
 
 pub type VmIndex = u32;
 pub type VmTag = u32;
+/// The integer type used by gluon's `Int`. Uses the native word size unless the `vm64` feature is
+/// enabled, in which case it is always 64 bits so that values are the same on every target.
+#[cfg(not(feature = "vm64"))]
 pub type VmInt = isize;
+#[cfg(feature = "vm64")]
+pub type VmInt = i64;
 
 /// Enum which represent the instructions executed by the virtual machine.
 ///
@@ -16,11 +21,13 @@ pub type VmInt = isize;
 #[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
 pub enum Instruction {
     /// Push an integer to the stack
-    PushInt(isize),
+    PushInt(VmInt),
     /// Push a byte to the stack
     PushByte(u8),
     /// Push a float to the stack
     PushFloat(f64),
+    /// Push a character to the stack
+    PushChar(char),
     /// Push a string to the stack by loading the string at `index` in the currently executing
     /// function
     PushString(VmIndex),
@@ -49,6 +56,16 @@ pub enum Instruction {
         /// How many arguments that is taken from the stack to construct the data.
         args: VmIndex,
     },
+    /// Constructs a copy of the record just below the top `args` values of the stack where the
+    /// fields named by the specification at `record` are replaced by those values. The record and
+    /// the values are replaced by the new record while the original record is left untouched.
+    UpdateRecord {
+        /// Index to the specification describing which fields are replaced. It lists every field
+        /// of the record, in order, followed by the `args` fields which are replaced
+        record: VmIndex,
+        /// How many values that is taken from the stack to replace the fields with.
+        args: VmIndex,
+    },
     /// Constructs an array containing `args` values.
     ConstructArray(VmIndex),
     /// Retrieves the field at `offset` of an object at the top of the stack. The result of the
@@ -108,6 +125,9 @@ pub enum Instruction {
     ByteLT,
     ByteEQ,
 
+    CharLT,
+    CharEQ,
+
     AddFloat,
     SubtractFloat,
     MultiplyFloat,
@@ -120,12 +140,13 @@ impl Instruction {
     /// Returns by how much the stack is adjusted when executing the instruction `self`.
     pub fn adjust(&self) -> i32 {
         match *self {
-            PushInt(_) | PushByte(_) | PushFloat(_) | PushString(_) | Push(_) => 1,
+            PushInt(_) | PushByte(_) | PushFloat(_) | PushChar(_) | PushString(_) | Push(_) => 1,
             Call(n) => -(n as i32),
             TailCall(n) => -(n as i32),
             Construct { args, .. } | ConstructRecord { args, .. } | ConstructArray(args) => {
                 1 - args as i32
             }
+            UpdateRecord { args, .. } => -(args as i32),
             GetField(_) | GetOffset(_) => 0,
             // The number of added stack slots are handled separately as the type is needed to
             // calculate the number of slots needed
@@ -140,8 +161,8 @@ impl Instruction {
             CloseClosure(_) => -1,
             PushUpVar(_) => 1,
             AddInt | SubtractInt | MultiplyInt | DivideInt | IntLT | IntEQ | AddFloat | AddByte
-            | SubtractByte | MultiplyByte | DivideByte | ByteLT | ByteEQ | SubtractFloat
-            | MultiplyFloat | DivideFloat | FloatLT | FloatEQ => -1,
+            | SubtractByte | MultiplyByte | DivideByte | ByteLT | ByteEQ | CharLT | CharEQ
+            | SubtractFloat | MultiplyFloat | DivideFloat | FloatLT | FloatEQ => -1,
         }
     }
 }
@@ -190,6 +211,12 @@ impl TypeEnv for TypeInfos {
     ) -> Option<(ArcType, ArcType)> {
         None
     }
+
+    fn each_type_info(&self, f: &mut FnMut(&Symbol, &Alias<Symbol, ArcType>)) {
+        for alias in self.id_to_type.values() {
+            f(&alias.name, alias);
+        }
+    }
 }
 
 impl TypeInfos {
@@ -1,7 +1,10 @@
 use std::collections::hash_map::Entry;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::mem::size_of;
 use std::result::Result as StdResult;
+use std::string::String as StdString;
+use std::sync::Arc;
 
 use itertools::Itertools;
 
@@ -10,11 +13,12 @@ use pretty::{Arena, DocAllocator, DocBuilder};
 use base::symbol::Symbol;
 use base::types::{ArcType, Type, TypeEnv};
 use types::*;
-use base::fnv::FnvMap;
+use base::fnv::{FnvMap, FnvSet};
 use base::types::pretty_print::ident as pretty_ident;
 
 use interner::InternedStr;
 use compiler::DebugInfo;
+use deferred::DeferredCall;
 use gc::{DataDef, Gc, GcPtr, Generation, Move, Traverseable, WriteOnly};
 use array::Array;
 use thread::{Status, Thread};
@@ -28,6 +32,18 @@ pub trait Userdata: ::mopa::Any + Traverseable + fmt::Debug + Send + Sync {
         let _ = deep_cloner;
         Err(Error::Message("Userdata cannot be cloned".into()))
     }
+
+    /// Called when the garbage collector frees the value. The returned call is run by the next
+    /// `Thread::run_deferred` of the thread which collected the value.
+    fn finalizer(&mut self) -> Option<DeferredCall> {
+        None
+    }
+
+    /// The number of bytes used by the value, counted by `Thread::value_size`. Userdata which
+    /// own memory outside of themselves (such as a `Vec`) should include it.
+    fn size_hint(&self) -> usize {
+        ::std::mem::size_of_val(self)
+    }
 }
 
 impl PartialEq for Userdata {
@@ -130,8 +146,8 @@ pub struct BytecodeFunction {
     pub inner_functions: Vec<GcPtr<BytecodeFunction>>,
     #[cfg_attr(feature = "serde_derive", serde(state))]
     pub strings: Vec<InternedStr>,
-    #[cfg_attr(feature = "serde_derive", serde(state))]
-    pub records: Vec<Vec<InternedStr>>,
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::records"))]
+    pub records: Vec<Arc<Vec<InternedStr>>>,
     #[cfg_attr(feature = "serde_derive", serde(state))]
     pub debug_info: DebugInfo,
 }
@@ -245,6 +261,49 @@ impl<'b> Traverseable for RecordDef<'b> {
     }
 }
 
+/// Definition for a copy of the record `base` where the fields in `fields` are replaced by the
+/// values in `elems`. `record_fields` are the names of every field of the record which are given
+/// to the copy, as `base` may have been created without any names (by a Rust function for
+/// instance)
+pub(crate) struct UpdateRecordDef<'b> {
+    pub base: GcPtr<DataStruct>,
+    pub elems: &'b [Value],
+    pub record_fields: &'b [InternedStr],
+    pub fields: &'b [InternedStr],
+}
+
+unsafe impl<'b> DataDef for UpdateRecordDef<'b> {
+    type Value = DataStruct;
+    fn size(&self) -> usize {
+        size_of::<DataStruct>() + size_of::<Value>() * self.base.fields.len()
+    }
+    fn initialize<'w>(self, mut result: WriteOnly<'w, DataStruct>) -> &'w mut DataStruct {
+        unsafe {
+            let result = &mut *result.as_mut_ptr();
+            result.tag = self.base.tag;
+            result.fields.initialize(self.base.fields.iter().cloned());
+            for (field, elem) in self.fields.iter().zip(self.elems) {
+                let offset = self.record_fields
+                    .iter()
+                    .position(|record_field| record_field == field)
+                    .expect("Replaced field is not a field of the record");
+                result.fields[offset] = elem.clone();
+            }
+            result
+        }
+    }
+    fn fields(&self) -> Option<&[InternedStr]> {
+        Some(self.record_fields)
+    }
+}
+
+impl<'b> Traverseable for UpdateRecordDef<'b> {
+    fn traverse(&self, gc: &mut Gc) {
+        self.base.traverse(gc);
+        self.elems.traverse(gc);
+    }
+}
+
 mod gc_str {
     use super::ValueArray;
     use gc::{Gc, GcPtr, Generation, Traverseable};
@@ -315,6 +374,7 @@ pub(crate) enum ValueRepr {
     Byte(u8),
     Int(VmInt),
     Float(f64),
+    Char(char),
     String(#[cfg_attr(feature = "serde_derive", serde(deserialize_state))] GcStr),
     Tag(VmTag),
     Data(
@@ -392,7 +452,25 @@ impl Value {
             PartialApplication(p) => p.generation(),
             ValueRepr::Userdata(p) => p.generation(),
             ValueRepr::Thread(p) => p.generation(),
-            ValueRepr::Tag(_) | ValueRepr::Byte(_) | Int(_) | Float(_) => Generation::default(),
+            ValueRepr::Tag(_) | ValueRepr::Byte(_) | Int(_) | Float(_) | ValueRepr::Char(_) => {
+                Generation::default()
+            }
+        }
+    }
+
+    /// Returns true if `self` survives the collection which `gc` is currently running. Values
+    /// which are not allocated are always considered to survive.
+    pub(crate) fn is_marked(&self, gc: &Gc) -> bool {
+        match self.get_repr() {
+            String(p) => gc.is_marked(p.into_inner()),
+            ValueRepr::Data(p) => gc.is_marked(p),
+            Function(p) => gc.is_marked(p),
+            Closure(p) => gc.is_marked(p),
+            ValueRepr::Array(p) => gc.is_marked(p),
+            PartialApplication(p) => gc.is_marked(p),
+            ValueRepr::Userdata(p) => gc.is_marked(p),
+            ValueRepr::Thread(p) => gc.is_marked(p),
+            ValueRepr::Tag(_) | ValueRepr::Byte(_) | Int(_) | Float(_) | ValueRepr::Char(_) => true,
         }
     }
 }
@@ -404,12 +482,16 @@ enum Prec {
 }
 use self::Prec::*;
 
+/// Pretty prints values using their gluon type to name record fields and variant constructors.
+/// Values whose type is not known (such as values under a `Type::hole()`) are still printed but
+/// variants are only printed with their tag as the constructor name is not available.
 pub struct ValuePrinter<'a> {
     pub typ: &'a ArcType,
     pub env: &'a TypeEnv,
     pub value: Variants<'a>,
     pub max_level: i32,
     pub width: usize,
+    pub max_array_len: usize,
 }
 
 impl<'t> ValuePrinter<'t> {
@@ -420,6 +502,7 @@ impl<'t> ValuePrinter<'t> {
             value,
             max_level: 50,
             width: 80,
+            max_array_len: usize::max_value(),
         }
     }
 
@@ -432,6 +515,12 @@ impl<'t> ValuePrinter<'t> {
         self.width = width;
         self
     }
+
+    /// Only prints the first `max_array_len` elements of arrays, the rest are elided with `..`
+    pub fn max_array_len(&mut self, max_array_len: usize) -> &mut ValuePrinter<'t> {
+        self.max_array_len = max_array_len;
+        self
+    }
 }
 
 const INDENT: usize = 4;
@@ -442,6 +531,14 @@ struct InternalPrinter<'a, 't> {
     arena: &'a Arena<'a>,
     prec: Prec,
     level: i32,
+    max_array_len: usize,
+}
+
+fn pretty_char<'a>(arena: &'a Arena<'a>, c: char) -> DocBuilder<'a, Arena<'a>> {
+    match c {
+        '"' => arena.text(format!("'{}'", '"')),
+        c => arena.text(format!("'{}'", c.escape_default())),
+    }
 }
 
 impl<'a> fmt::Display for ValuePrinter<'a> {
@@ -454,6 +551,7 @@ impl<'a> fmt::Display for ValuePrinter<'a> {
             arena: &arena,
             prec: Top,
             level: self.max_level,
+            max_array_len: self.max_array_len,
         }.pretty(self.value)
             .group()
             .1
@@ -471,8 +569,15 @@ impl<'a, 't> InternalPrinter<'a, 't> {
         match value.0 {
             _ if self.level == 0 => arena.text(".."),
             ValueRepr::String(s) => arena.text(format!("{:?}", &s[..])),
-            ValueRepr::Data(ref data) => self.pretty_data(data.tag(), variant_iter(&data.fields)),
-            ValueRepr::Tag(tag) => self.pretty_data(tag, iter::empty()),
+            ValueRepr::Data(ref data) => {
+                let field_names = if data.is_record() {
+                    Some(&data.field_names()[..])
+                } else {
+                    None
+                };
+                self.pretty_data(data.tag(), field_names, variant_iter(&data.fields))
+            }
+            ValueRepr::Tag(tag) => self.pretty_data(tag, None, iter::empty()),
             ValueRepr::Function(ref function) => chain![arena;
                     "<extern ",
                     function.id.declared_name().to_string(),
@@ -493,17 +598,26 @@ impl<'a, 't> InternalPrinter<'a, 't> {
                         }).intersperse(arena.text(","))).nest(INDENT),
                     ">"
                 ],
-            ValueRepr::Array(ref array) => chain![arena;
+            ValueRepr::Array(ref array) => {
+                let hole = Type::hole();
+                let element_type = match **::base::types::remove_forall(self.typ) {
+                    Type::App(_, ref args) => &args[0],
+                    _ => &hole,
+                };
+                let elided = if array.len() > self.max_array_len {
+                    Some(arena.text(".."))
+                } else {
+                    None
+                };
+                chain![arena;
                     "[",
-                    arena.concat(array.iter().map(|field| {
-                        match **self.typ {
-                            Type::App(_, ref args) => self.p(&args[0], Top).pretty(field),
-                            _ => arena.text(format!("{:?}", field)),
-                        }
-                    }).intersperse(arena.text(",").append(arena.space())))
+                    arena.concat(array.iter().take(self.max_array_len).map(|field| {
+                        self.p(element_type, Top).pretty(field)
+                    }).chain(elided).intersperse(arena.text(",").append(arena.space())))
                         .nest(INDENT),
                     "]"
-                ],
+                ]
+            }
             ValueRepr::PartialApplication(p) => arena.text(format!("{:?}", p)),
             ValueRepr::Userdata(ref data) => arena.text(format!("{:?}", data)),
             ValueRepr::Thread(thread) => arena.text(format!("{:?}", thread)),
@@ -512,9 +626,9 @@ impl<'a, 't> InternalPrinter<'a, 't> {
                 use base::types::BuiltinType;
                 match **self.typ {
                     Type::Builtin(BuiltinType::Int) => arena.text(format!("{}", i)),
+                    // Characters which were pushed as integers
                     Type::Builtin(BuiltinType::Char) => match ::std::char::from_u32(i as u32) {
-                        Some('"') => arena.text(format!("'{}'", '"')),
-                        Some(c) => arena.text(format!("'{}'", c.escape_default())),
+                        Some(c) => pretty_char(arena, c),
                         None => ice!(
                             "Invalid character (code point {}) passed to pretty printing",
                             i
@@ -524,95 +638,126 @@ impl<'a, 't> InternalPrinter<'a, 't> {
                 }
             }
             ValueRepr::Float(f) => arena.text(format!("{}", f)),
+            ValueRepr::Char(c) => pretty_char(arena, c),
         }
     }
 
-    fn pretty_data<'b, I>(&self, tag: VmTag, fields: I) -> DocBuilder<'a, Arena<'a>>
+    fn pretty_data<'b, I>(
+        &self,
+        tag: VmTag,
+        field_names: Option<&[InternedStr]>,
+        fields: I,
+    ) -> DocBuilder<'a, Arena<'a>>
     where
         I: IntoIterator<Item = Variants<'b>>,
     {
-        fn enclose<'a>(
-            p: Prec,
-            limit: Prec,
-            arena: &'a Arena<'a>,
-            doc: DocBuilder<'a, Arena<'a>>,
-        ) -> DocBuilder<'a, Arena<'a>> {
-            if p >= limit {
-                chain![arena; "(", doc, ")"]
-            } else {
-                doc
-            }
-        }
-
         use base::resolve::remove_aliases_cow;
-        use base::types::arg_iter;
-
-        let typ = remove_aliases_cow(self.env, self.typ);
-        let arena = self.arena;
-        match **typ {
-            Type::Record(ref row) => {
-                let mut is_empty = true;
-                let fields_doc = arena.concat(
-                    fields
-                        .into_iter()
-                        .zip(row.row_iter())
-                        .map(|(field, type_field)| {
-                            is_empty = false;
-                            chain![arena;
-                                pretty_ident(arena, type_field.name.declared_name().to_string()),
-                                ":",
-                                chain![arena;
-                                    arena.space(),
-                                    self.p(&type_field.typ, Top).pretty(field),
-                                    arena.text(",")
-                                ].nest(INDENT)
-                            ].group()
-                        })
-                        .intersperse(arena.space()),
-                );
-                chain![arena;
-                            "{",
-                            chain![arena;
-                                arena.space(),
-                                fields_doc
-                            ].nest(INDENT),
-                            if is_empty {
-                                arena.nil()
-                            } else {
-                                arena.space()
-                            },
-                            "}"
-                        ]
-            }
+        use base::types::{arg_iter, remove_forall};
+
+        let typ = remove_aliases_cow(self.env, remove_forall(self.typ));
+        match **remove_forall(&*typ) {
+            Type::Record(ref row) => self.pretty_record(
+                fields
+                    .into_iter()
+                    .zip(row.row_iter())
+                    .map(|(field, type_field)| {
+                        (
+                            type_field.name.declared_name().to_string(),
+                            self.p(&type_field.typ, Top).pretty(field),
+                        )
+                    }),
+            ),
             Type::Variant(ref row) => {
                 let type_field = row.row_iter()
                     .nth(tag as usize)
                     .expect("Variant tag is out of bounds");
-                let mut empty = true;
-                let doc = chain![arena;
-                            type_field.name.declared_name().to_string(),
-                            arena.concat(fields.into_iter().zip(arg_iter(&type_field.typ))
-                                .map(|(field, typ)| {
-                                    empty = false;
-                                    arena.space().append(self.p(typ, Constructor).pretty(field))
-                                }))
-                                .nest(INDENT)
-                        ];
-                if empty {
-                    doc
-                } else {
-                    enclose(self.prec, Constructor, arena, doc)
+                self.pretty_constructor(
+                    type_field.name.declared_name().to_string(),
+                    fields
+                        .into_iter()
+                        .zip(arg_iter(&type_field.typ))
+                        .map(|(field, typ)| self.p(typ, Constructor).pretty(field)),
+                )
+            }
+            // The type is not known (or is not a record or variant) so only the names stored in
+            // records are available
+            _ => {
+                let hole = Type::hole();
+                match field_names {
+                    Some(field_names) => self.pretty_record(
+                        field_names
+                            .iter()
+                            .zip(fields)
+                            .map(|(name, field)| {
+                                (name.to_string(), self.p(&hole, Top).pretty(field))
+                            }),
+                    ),
+                    None => self.pretty_constructor(
+                        format!("<tag {}>", tag),
+                        fields
+                            .into_iter()
+                            .map(|field| self.p(&hole, Constructor).pretty(field)),
+                    ),
                 }
             }
-            _ => chain![arena;
-                        "{",
-                        arena.concat(fields.into_iter().map(|field| {
-                            arena.space().append(self.p(&Type::hole(), Top).pretty(field))
-                        }).intersperse(arena.text(",")))
-                            .nest(INDENT),
-                        arena.space(),
-                        "}"
-                    ],
+        }
+    }
+
+    fn pretty_record<I>(&self, fields: I) -> DocBuilder<'a, Arena<'a>>
+    where
+        I: IntoIterator<Item = (StdString, DocBuilder<'a, Arena<'a>>)>,
+    {
+        let arena = self.arena;
+        let mut is_empty = true;
+        let fields_doc = arena.concat(
+            fields
+                .into_iter()
+                .map(|(name, field)| {
+                    is_empty = false;
+                    chain![arena;
+                        pretty_ident(arena, name),
+                        ":",
+                        chain![arena;
+                            arena.space(),
+                            field,
+                            arena.text(",")
+                        ].nest(INDENT)
+                    ].group()
+                })
+                .intersperse(arena.space()),
+        );
+        chain![arena;
+            "{",
+            chain![arena;
+                arena.space(),
+                fields_doc
+            ].nest(INDENT),
+            if is_empty {
+                arena.nil()
+            } else {
+                arena.space()
+            },
+            "}"
+        ]
+    }
+
+    fn pretty_constructor<I>(&self, name: StdString, args: I) -> DocBuilder<'a, Arena<'a>>
+    where
+        I: IntoIterator<Item = DocBuilder<'a, Arena<'a>>>,
+    {
+        let arena = self.arena;
+        let mut empty = true;
+        let doc = chain![arena;
+            name,
+            arena.concat(args.into_iter().map(|arg| {
+                empty = false;
+                arena.space().append(arg)
+            })).nest(INDENT)
+        ];
+        if empty || self.prec < Constructor {
+            doc
+        } else {
+            chain![arena; "(", doc, ")"]
         }
     }
 
@@ -623,6 +768,7 @@ impl<'a, 't> InternalPrinter<'a, 't> {
             arena: self.arena,
             prec: prec,
             level: self.level - 1,
+            max_array_len: self.max_array_len,
         }
     }
 }
@@ -729,7 +875,7 @@ impl Traverseable for ValueRepr {
             ValueRepr::Userdata(ref data) => data.traverse(gc),
             PartialApplication(ref data) => data.traverse(gc),
             ValueRepr::Thread(ref thread) => thread.traverse(gc),
-            ValueRepr::Tag(_) | ValueRepr::Byte(_) | Int(_) | Float(_) => (),
+            ValueRepr::Tag(_) | ValueRepr::Byte(_) | Int(_) | Float(_) | ValueRepr::Char(_) => (),
         }
     }
 }
@@ -773,6 +919,7 @@ impl fmt::Debug for ValueRepr {
                     ValueRepr::Byte(i) => write!(f, "{:?}b", i),
                     ValueRepr::Int(i) => write!(f, "{:?}", i),
                     ValueRepr::Float(x) => write!(f, "{:?}f", x),
+                    ValueRepr::Char(c) => write!(f, "{:?}", c),
                     ValueRepr::String(x) => write!(f, "{:?}", &*x),
                     ValueRepr::Tag(tag) => write!(f, "{{{:?}: }}", tag),
                     ValueRepr::Data(ref data) => write!(
@@ -819,6 +966,51 @@ impl fmt::Debug for ValueRepr {
     }
 }
 
+/// The rust code which is run when an `ExternFunction` is called
+#[derive(Clone)]
+pub enum ExternCall {
+    /// A plain function pointer, used by functions which do not capture any state
+    Fn(extern "C" fn(&Thread) -> Status),
+    /// A closure which is shared between the clones of the function along with any state it
+    /// captures
+    Closure(Arc<Fn(&Thread) -> Status + Send + Sync>),
+}
+
+impl ExternCall {
+    pub fn call(&self, thread: &Thread) -> Status {
+        match *self {
+            ExternCall::Fn(function) => function(thread),
+            ExternCall::Closure(ref function) => function(thread),
+        }
+    }
+
+    /// Returns the address of the function (or closure) which gets called
+    fn address(&self) -> *const () {
+        match *self {
+            ExternCall::Fn(function) => function as *const (),
+            ExternCall::Closure(ref function) => {
+                &**function as *const (Fn(&Thread) -> Status + Send + Sync) as *const ()
+            }
+        }
+    }
+}
+
+impl PartialEq for ExternCall {
+    fn eq(&self, other: &ExternCall) -> bool {
+        match (self, other) {
+            (&ExternCall::Fn(l), &ExternCall::Fn(r)) => l as usize == r as usize,
+            (&ExternCall::Closure(ref l), &ExternCall::Closure(ref r)) => Arc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for ExternCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.address())
+    }
+}
+
 #[cfg_attr(feature = "serde_derive", derive(SerializeState))]
 #[cfg_attr(feature = "serde_derive", serde(serialize_state = "::serialization::SeSeed"))]
 pub struct ExternFunction {
@@ -827,7 +1019,7 @@ pub struct ExternFunction {
     pub id: Symbol,
     pub args: VmIndex,
     #[cfg_attr(feature = "serde_derive", serde(skip_serializing))]
-    pub function: extern "C" fn(&Thread) -> Status,
+    pub function: ExternCall,
 }
 
 impl Clone for ExternFunction {
@@ -835,23 +1027,20 @@ impl Clone for ExternFunction {
         ExternFunction {
             id: self.id.clone(),
             args: self.args,
-            function: self.function,
+            function: self.function.clone(),
         }
     }
 }
 
 impl PartialEq for ExternFunction {
     fn eq(&self, other: &ExternFunction) -> bool {
-        self.id == other.id && self.args == other.args
-            && self.function as usize == other.function as usize
+        self.id == other.id && self.args == other.args && self.function == other.function
     }
 }
 
 impl fmt::Debug for ExternFunction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // read the v-table pointer of the Fn(..) type and print that
-        let p: *const () = unsafe { ::std::mem::transmute(self.function) };
-        write!(f, "{} {:?}", self.id, p)
+        write!(f, "{} {:?}", self.id, self.function)
     }
 }
 
@@ -865,6 +1054,7 @@ pub enum Repr {
     Byte,
     Int,
     Float,
+    Char,
     String,
     Array,
     Unknown,
@@ -926,6 +1116,7 @@ impl_repr! {
     u8, Repr::Byte,
     VmInt, Repr::Int,
     f64, Repr::Float,
+    char, Repr::Char,
     GcStr, Repr::String,
     GcPtr<ValueArray>, Repr::Array,
     Value, Repr::Unknown,
@@ -939,6 +1130,7 @@ impl Repr {
             ValueRepr::Byte(_) => Repr::Byte,
             ValueRepr::Int(_) => Repr::Int,
             ValueRepr::Float(_) => Repr::Float,
+            ValueRepr::Char(_) => Repr::Char,
             ValueRepr::String(_) => Repr::String,
             ValueRepr::Array(_) => Repr::Array,
             ValueRepr::Data(_)
@@ -961,6 +1153,7 @@ macro_rules! on_array {
                     Repr::Byte => $f(array.unsafe_array::<u8>()),
                     Repr::Int => $f(array.unsafe_array::<VmInt>()),
                     Repr::Float => $f(array.unsafe_array::<f64>()),
+                    Repr::Char => $f(array.unsafe_array::<char>()),
                     Repr::String => $f(array.unsafe_array::<GcStr>()),
                     Repr::Array => $f(array.unsafe_array::<GcPtr<ValueArray>>()),
                     Repr::Unknown => $f(array.unsafe_array::<Value>()),
@@ -1029,6 +1222,7 @@ impl ValueArray {
                 Repr::Byte => ValueRepr::Byte(self.unsafe_get(index)),
                 Repr::Int => ValueRepr::Int(self.unsafe_get(index)),
                 Repr::Float => ValueRepr::Float(self.unsafe_get(index)),
+                Repr::Char => ValueRepr::Char(self.unsafe_get(index)),
                 Repr::String => ValueRepr::String(self.unsafe_get(index)),
                 Repr::Array => ValueRepr::Array(self.unsafe_get(index)),
                 Repr::Unknown => self.unsafe_get(index),
@@ -1089,7 +1283,7 @@ impl ValueArray {
             }
         }
 
-        initialize_variants! { Byte Int Float String Array Userdata Thread }
+        initialize_variants! { Byte Int Float Char String Array Userdata Thread }
     }
 
     pub fn as_slice<T: ArrayRepr>(&self) -> Option<&[T]> {
@@ -1225,6 +1419,7 @@ impl<'t> Cloner<'t> {
             ValueRepr::Byte(i) => Ok(ValueRepr::Byte(i)),
             Int(i) => Ok(Int(i)),
             Float(f) => Ok(Float(f)),
+            ValueRepr::Char(c) => Ok(ValueRepr::Char(c)),
             ValueRepr::Userdata(userdata) => userdata.deep_clone(self).map(ValueRepr::Userdata),
             ValueRepr::Thread(_) => Err(Error::Message("Threads cannot be deep cloned yet".into())),
         };
@@ -1320,7 +1515,9 @@ impl<'t> Cloner<'t> {
             Err(new_array) => {
                 unsafe {
                     match new_array.repr() {
-                        Repr::Byte | Repr::Int | Repr::Float | Repr::String => Ok(()),
+                        Repr::Byte | Repr::Int | Repr::Float | Repr::Char | Repr::String => {
+                            Ok(())
+                        }
                         Repr::Array => deep_clone_elems(new_array, |e| self.deep_clone_array(*e)),
                         Repr::Unknown => deep_clone_elems(new_array, |e| self.deep_clone(e)),
                         Repr::Userdata => {
@@ -1388,6 +1585,153 @@ impl<'t> Cloner<'t> {
     }
 }
 
+fn address<T: ?Sized>(ptr: GcPtr<T>) -> *const () {
+    &*ptr as *const T as *const ()
+}
+
+/// Compares two values by their contents, recursing through data and arrays.
+///
+/// Userdata, threads and functions have no structure to compare so they are only equal to
+/// themselves. Floats are compared by their bit patterns to stay consistent with
+/// `structural_hash`.
+pub(crate) fn structural_eq(l: ValueRepr, r: ValueRepr) -> bool {
+    match (l, r) {
+        (ValueRepr::Byte(l), ValueRepr::Byte(r)) => l == r,
+        (Int(l), Int(r)) => l == r,
+        (Float(l), Float(r)) => l.to_bits() == r.to_bits(),
+        (ValueRepr::Char(l), ValueRepr::Char(r)) => l == r,
+        (String(l), String(r)) => *l == *r,
+        (ValueRepr::Tag(l), ValueRepr::Tag(r)) => l == r,
+        (ValueRepr::Data(l), ValueRepr::Data(r)) => {
+            l.tag == r.tag && l.fields.len() == r.fields.len()
+                && l.fields
+                    .iter()
+                    .zip(r.fields.iter())
+                    .all(|(l, r)| structural_eq(l.0, r.0))
+        }
+        (ValueRepr::Array(l), ValueRepr::Array(r)) => {
+            l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| structural_eq(l.0, r.0))
+        }
+        (Function(l), Function(r)) => address(l) == address(r),
+        (Closure(l), Closure(r)) => address(l) == address(r),
+        (PartialApplication(l), PartialApplication(r)) => address(l) == address(r),
+        (ValueRepr::Userdata(l), ValueRepr::Userdata(r)) => address(l) == address(r),
+        (ValueRepr::Thread(l), ValueRepr::Thread(r)) => address(l) == address(r),
+        _ => false,
+    }
+}
+
+/// Hashes a value such that values which are `structural_eq` get the same hash
+pub(crate) fn structural_hash<H>(value: ValueRepr, state: &mut H)
+where
+    H: Hasher,
+{
+    match value {
+        ValueRepr::Byte(b) => (0u8, b).hash(state),
+        Int(i) => (1u8, i).hash(state),
+        Float(f) => (2u8, f.to_bits()).hash(state),
+        String(s) => (3u8, &*s).hash(state),
+        ValueRepr::Tag(tag) => (4u8, tag).hash(state),
+        ValueRepr::Data(data) => {
+            (5u8, data.tag, data.fields.len()).hash(state);
+            for field in data.fields.iter() {
+                structural_hash(field.0, state);
+            }
+        }
+        ValueRepr::Array(array) => {
+            (6u8, array.len()).hash(state);
+            for elem in array.iter() {
+                structural_hash(elem.0, state);
+            }
+        }
+        Function(ptr) => (7u8, address(ptr)).hash(state),
+        Closure(ptr) => (8u8, address(ptr)).hash(state),
+        PartialApplication(ptr) => (9u8, address(ptr)).hash(state),
+        ValueRepr::Userdata(ptr) => (10u8, address(ptr)).hash(state),
+        ValueRepr::Thread(ptr) => (11u8, address(ptr)).hash(state),
+        ValueRepr::Char(c) => (12u8, c).hash(state),
+    }
+}
+
+/// An estimate of how much memory a value uses. Returned by `Thread::value_size`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SizeReport {
+    /// The size of the value and of the allocation it refers to directly
+    pub shallow: usize,
+    /// The size of the value and of every allocation reachable from it. Allocations which are
+    /// reachable through several paths are only counted once
+    pub deep: usize,
+    /// The number of allocations counted in `deep`
+    pub nodes: usize,
+}
+
+/// Returns the size of the allocation `value` refers to directly (if any)
+fn allocation_size(value: ValueRepr) -> usize {
+    match value {
+        ValueRepr::Byte(_) | Int(_) | Float(_) | ValueRepr::Char(_) | ValueRepr::Tag(_) => 0,
+        String(s) => s.into_inner().allocation_size(),
+        ValueRepr::Data(ptr) => ptr.allocation_size(),
+        ValueRepr::Array(ptr) => ptr.allocation_size(),
+        Function(ptr) => ptr.allocation_size(),
+        Closure(ptr) => ptr.allocation_size(),
+        PartialApplication(ptr) => ptr.allocation_size(),
+        ValueRepr::Userdata(ptr) => ptr.allocation_size() + ptr.size_hint(),
+        ValueRepr::Thread(ptr) => ptr.allocation_size(),
+    }
+}
+
+fn value_address(value: ValueRepr) -> Option<*const ()> {
+    match value {
+        ValueRepr::Byte(_) | Int(_) | Float(_) | ValueRepr::Char(_) | ValueRepr::Tag(_) => None,
+        String(s) => Some(address(s.into_inner())),
+        ValueRepr::Data(ptr) => Some(address(ptr)),
+        ValueRepr::Array(ptr) => Some(address(ptr)),
+        Function(ptr) => Some(address(ptr)),
+        Closure(ptr) => Some(address(ptr)),
+        PartialApplication(ptr) => Some(address(ptr)),
+        ValueRepr::Userdata(ptr) => Some(address(ptr)),
+        ValueRepr::Thread(ptr) => Some(address(ptr)),
+    }
+}
+
+/// Estimates the memory used by `value` by walking every allocation reachable from it. The code
+/// of functions and the contents of threads are not counted as they are not owned by the value.
+pub(crate) fn value_size(value: ValueRepr) -> SizeReport {
+    let mut report = SizeReport {
+        shallow: size_of::<Value>() + allocation_size(value),
+        deep: size_of::<Value>(),
+        nodes: 0,
+    };
+    let mut visited = FnvSet::default();
+    // Walk the value with an explicit stack as long lists would otherwise overflow the stack
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value_address(value) {
+            Some(address) => if !visited.insert(address) {
+                continue;
+            },
+            None => continue,
+        }
+        report.deep += allocation_size(value);
+        report.nodes += 1;
+
+        match value {
+            ValueRepr::Data(data) => stack.extend(data.fields.iter().map(|field| field.0)),
+            ValueRepr::Array(array) => stack.extend(array.iter().map(|elem| elem.0)),
+            Closure(closure) => stack.extend(closure.upvars.iter().map(|upvar| upvar.0)),
+            PartialApplication(app) => {
+                stack.push(match app.function {
+                    Callable::Closure(closure) => Closure(closure),
+                    Callable::Extern(function) => Function(function),
+                });
+                stack.extend(app.args.iter().map(|arg| arg.0));
+            }
+            _ => (),
+        }
+    }
+    report
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1422,6 +1766,12 @@ mod tests {
         ) -> Option<(ArcType, ArcType)> {
             None
         }
+
+        fn each_type_info(&self, f: &mut FnMut(&Symbol, &Alias<Symbol, ArcType>)) {
+            if let Some(ref alias) = self.0 {
+                f(&alias.name, alias)
+            }
+        }
     }
 
     #[test]
@@ -1495,6 +1845,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn pretty_char() {
+        let mut gc = Gc::new(Generation::default(), usize::max_value());
+
+        let typ = Type::array(Type::char());
+
+        let env = MockEnv(None);
+
+        let chars = Value::array(gc.alloc(&['a', '"', '\n'][..]).unwrap());
+        assert_eq!(
+            format!(
+                "{}",
+                ValuePrinter::new(&env, &typ, unsafe { Variants::new(&chars) })
+            ),
+            r#"['a', '"', '\n']"#
+        );
+
+        // Characters which were pushed as integers print the same way
+        let ints = Value::array(gc.alloc(&['a' as VmInt, '"' as VmInt, '\n' as VmInt][..]).unwrap());
+        assert_eq!(
+            format!(
+                "{}",
+                ValuePrinter::new(&env, &typ, unsafe { Variants::new(&ints) })
+            ),
+            r#"['a', '"', '\n']"#
+        );
+    }
+
     #[test]
     fn closure_data_upvars_location() {
         use std::mem;
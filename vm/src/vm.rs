@@ -1,5 +1,5 @@
 use std::borrow::Cow;
-use std::sync::{Mutex, RwLock, RwLockReadGuard};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 use std::any::{Any, TypeId};
 use std::result::Result as StdResult;
 use std::string::String as StdString;
@@ -10,8 +10,8 @@ use base::fnv::FnvMap;
 use base::kind::{ArcKind, Kind, KindEnv};
 use base::metadata::{Metadata, MetadataEnv};
 use base::symbol::{Name, Symbol, SymbolRef};
-use base::types::{Alias, AliasData, AppVec, ArcType, Generic, PrimitiveEnv, RecordSelector, Type,
-                  TypeCache, TypeEnv};
+use base::types::{Alias, AliasData, AppVec, ArcType, BuiltinType, Field, Generic, PrimitiveEnv,
+                  RecordSelector, Type, TypeCache, TypeEnv};
 
 use macros::MacroEnv;
 use {Error, Result, Variants};
@@ -21,8 +21,10 @@ use gc::{Gc, GcPtr, Generation, Move, Traverseable};
 use compiler::{CompiledFunction, CompiledModule, CompilerEnv, Variable};
 use api::{ValueRef, IO};
 use lazy::Lazy;
+use testing::TestReport;
 
 use value::{BytecodeFunction, ClosureData, ClosureDataDef, Value};
+use thread::{GlobalChange, GlobalPolicy};
 
 pub use value::Userdata;
 pub use thread::{Root, RootStr, RootedThread, RootedValue, Status, Thread};
@@ -71,12 +73,13 @@ fn new_bytecode_function(
         .map(|inner| new_bytecode_function(interner, gc, vm, inner))
         .collect();
 
-    let records: StdResult<_, _> = records
+    let records: Result<_> = records
         .into_iter()
         .map(|vec| {
-            vec.into_iter()
+            let fields = vec.into_iter()
                 .map(|field| Ok(interner.intern(gc, field.as_ref())?))
-                .collect::<Result<_>>()
+                .collect::<Result<_>>()?;
+            Ok(interner.intern_fields(fields))
         })
         .collect();
 
@@ -144,6 +147,13 @@ pub struct GlobalVmState {
     #[cfg_attr(feature = "serde_derive", serde(state))]
     pub generation_0_threads: RwLock<Vec<GcPtr<Thread>>>,
 
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    test_report: TestReport,
+
+    /// Policy consulted before the global environment is modified
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    global_policy: RwLock<Option<Arc<GlobalPolicy>>>,
+
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     #[cfg(not(target_arch = "wasm32"))]
     event_loop: Option<::std::panic::AssertUnwindSafe<::tokio_core::reactor::Remote>>,
@@ -171,6 +181,9 @@ pub struct VmEnv {
     pub type_infos: TypeInfos,
     #[cfg_attr(feature = "serde_derive", serde(state))]
     pub globals: FnvMap<StdString, Global>,
+    /// Types which have primitive operators (`#Decimal+`) registered for them
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
+    pub primitive_operands: FnvMap<StdString, ArcType>,
 }
 
 impl CompilerEnv for VmEnv {
@@ -192,19 +205,7 @@ impl TypeEnv for VmEnv {
         self.globals
             .get(id.definition_name())
             .map(|g| &g.typ)
-            .or_else(|| {
-                self.type_infos
-                    .id_to_type
-                    .values()
-                    .filter_map(|alias| match **alias.unresolved_type() {
-                        Type::Variant(ref row) => row.row_iter()
-                            .find(|field| *field.name == *id)
-                            .map(|field| &field.typ),
-                        _ => None,
-                    })
-                    .next()
-                    .map(|ctor| ctor)
-            })
+            .or_else(|| self.type_infos.find_type(id))
     }
 
     fn find_type_info(&self, id: &SymbolRef) -> Option<&Alias<Symbol, ArcType>> {
@@ -218,6 +219,10 @@ impl TypeEnv for VmEnv {
     ) -> Option<(ArcType, ArcType)> {
         self.type_infos.find_record(fields, selector)
     }
+
+    fn each_type_info(&self, f: &mut FnMut(&Symbol, &Alias<Symbol, ArcType>)) {
+        self.type_infos.each_type_info(f)
+    }
 }
 
 impl PrimitiveEnv for VmEnv {
@@ -229,6 +234,10 @@ impl PrimitiveEnv for VmEnv {
             })
             .expect("std.types.Bool")
     }
+
+    fn find_primitive_operand(&self, name: &str) -> Option<&ArcType> {
+        self.primitive_operands.get(name)
+    }
 }
 
 impl MetadataEnv for VmEnv {
@@ -396,6 +405,7 @@ impl GlobalVmStateBuilder {
             env: RwLock::new(VmEnv {
                 globals: FnvMap::default(),
                 type_infos: TypeInfos::new(),
+                primitive_operands: FnvMap::default(),
             }),
             generics: RwLock::new(FnvMap::default()),
             typeids: RwLock::new(FnvMap::default()),
@@ -404,6 +414,8 @@ impl GlobalVmStateBuilder {
             macros: MacroEnv::new(),
             type_cache: TypeCache::new(),
             generation_0_threads: RwLock::new(Vec::new()),
+            test_report: TestReport::default(),
+            global_policy: RwLock::new(None),
 
             #[cfg(not(target_arch = "wasm32"))]
             event_loop: self.event_loop.map(::std::panic::AssertUnwindSafe),
@@ -415,7 +427,6 @@ impl GlobalVmStateBuilder {
 
 impl GlobalVmState {
     fn add_types(&mut self) -> StdResult<(), (TypeId, ArcType)> {
-        use base::types::BuiltinType;
         use api::generic::A;
         use api::Generic;
         fn add_builtin_type<T: Any>(self_: &mut GlobalVmState, b: BuiltinType) {
@@ -534,44 +545,146 @@ impl GlobalVmState {
         g
     }
 
-    /// Registers a new type called `name`
+    /// Returns the type which `T` were registered as, if it has been registered
+    pub fn lookup_registered_type<T: ?Sized + Any>(&self) -> Option<ArcType> {
+        self.typeids
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .cloned()
+    }
+
+    /// Registers a new type called `name`. Registering the same type with the same name and
+    /// arguments again returns the type from the first registration.
     pub fn register_type<T: ?Sized + Any>(&self, name: &str, args: &[&str]) -> Result<ArcType> {
         let mut env = self.env.write().unwrap();
         let type_infos = &mut env.type_infos;
-        if type_infos.id_to_type.contains_key(name) {
-            Err(Error::TypeAlreadyExists(name.into()))
-        } else {
-            let id = TypeId::of::<T>();
-            let arg_types: AppVec<_> = args.iter().map(|g| self.get_generic(g)).collect();
-            let args = arg_types
+        let id = TypeId::of::<T>();
+        if let Some(alias) = type_infos.id_to_type.get(name) {
+            let registered_args: Vec<String> = alias
+                .params()
                 .iter()
-                .map(|g| match **g {
-                    Type::Generic(ref g) => g.clone(),
-                    _ => unreachable!(),
-                })
+                .map(|g| g.id.declared_name().to_string())
                 .collect();
-            let n = Symbol::from(name);
-            let typ: ArcType = Type::app(Type::ident(n.clone()), arg_types);
-            self.typeids.write().unwrap().insert(id, typ.clone());
-            let t = self.typeids.read().unwrap().get(&id).unwrap().clone();
-            type_infos.id_to_type.insert(
-                name.into(),
-                Alias::from(AliasData::new(n, args, self.type_cache.opaque())),
-            );
-            Ok(t)
+            let registered = self.lookup_registered_type::<T>();
+            let same_rust_type = registered
+                .as_ref()
+                .and_then(|typ| typ.alias_ident())
+                .map_or(false, |id| id.as_ref() == name);
+            return match registered {
+                Some(typ) if same_rust_type && registered_args.iter().eq(args) => Ok(typ),
+                _ => Err(Error::ConflictingTypeRegistration(
+                    name.into(),
+                    registered_args,
+                    args.iter().map(|arg| arg.to_string()).collect(),
+                    same_rust_type,
+                )),
+            };
         }
+        let arg_types: AppVec<_> = args.iter().map(|g| self.get_generic(g)).collect();
+        let args = arg_types
+            .iter()
+            .map(|g| match **g {
+                Type::Generic(ref g) => g.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        let n = Symbol::from(name);
+        let typ: ArcType = Type::app(Type::ident(n.clone()), arg_types);
+        self.typeids.write().unwrap().insert(id, typ.clone());
+        let t = self.typeids.read().unwrap().get(&id).unwrap().clone();
+        type_infos.id_to_type.insert(
+            name.into(),
+            Alias::from(AliasData::new(n, args, self.type_cache.opaque())),
+        );
+        Ok(t)
+    }
+
+    /// Registers the type `T` as being a gluon variant type called `name`. Each constructor is
+    /// given by its name and the types of its arguments and is assigned a tag in the order it
+    /// appears in `constructors`.
+    pub fn register_variant<T: ?Sized + Any>(
+        &self,
+        name: &str,
+        constructors: &[(&str, Vec<ArcType>)],
+    ) -> Result<ArcType> {
+        let mut env = self.env.write().unwrap();
+        let type_infos = &mut env.type_infos;
+        if type_infos.id_to_type.contains_key(name) {
+            return Err(Error::TypeAlreadyExists(name.into()));
+        }
+        let n = Symbol::from(name);
+        let ret: ArcType = Type::ident(n.clone());
+        let variants = constructors
+            .iter()
+            .map(|&(ctor, ref args)| {
+                Field::new(Symbol::from(ctor), Type::function(args.clone(), ret.clone()))
+            })
+            .collect();
+        let alias = Alias::new(n, Type::variant(variants));
+        let typ = alias.clone().into_type();
+        self.typeids
+            .write()
+            .unwrap()
+            .insert(TypeId::of::<T>(), typ.clone());
+        type_infos.id_to_type.insert(name.into(), alias);
+        Ok(typ)
+    }
+
+    /// Registers `typ` as the operand type of the primitive operators `#<name>+`, `#<name>-`,
+    /// `#<name>*`, `#<name>/`, `#<name>==` and `#<name><`.
+    ///
+    /// Each operator which is used must also be defined as a global with the same name as the
+    /// operator (`vm.define_global("#Decimal+", ...)`) which the compiler will call.
+    pub fn register_primitive_operand(&self, name: &str, typ: ArcType) -> Result<()> {
+        let mut env = self.env.write().unwrap();
+        if env.primitive_operands.contains_key(name) || name.parse::<BuiltinType>().is_ok() {
+            return Err(Error::TypeAlreadyExists(name.into()));
+        }
+        env.primitive_operands.insert(name.into(), typ);
+        Ok(())
     }
 
     pub fn get_macros(&self) -> &MacroEnv {
         &self.macros
     }
 
+    pub fn test_report(&self) -> &TestReport {
+        &self.test_report
+    }
+
+    /// Sets the policy which is consulted before any global is defined, type is registered or
+    /// module is loaded, replacing any earlier policy
+    pub fn set_global_policy(&self, policy: Box<GlobalPolicy>) {
+        *self.global_policy.write().unwrap() = Some(Arc::from(policy));
+    }
+
+    /// Checks `change` against the policy set with `set_global_policy`, allowing everything if no
+    /// policy has been set
+    pub fn check_global_change(&self, change: &GlobalChange) -> Result<()> {
+        match *self.global_policy.read().unwrap() {
+            Some(ref policy) => policy(change).map_err(Error::GlobalChangeDenied),
+            None => Ok(()),
+        }
+    }
+
     pub fn intern(&self, s: &str) -> Result<InternedStr> {
         let mut interner = self.interner.write().unwrap();
         let mut gc = self.gc.lock().unwrap();
         interner.intern(&mut *gc, s)
     }
 
+    /// Returns a copy of the record field names `fields` which is shared with every other function
+    /// which uses the same field names
+    pub fn intern_fields(&self, fields: Vec<InternedStr>) -> Arc<Vec<InternedStr>> {
+        self.interner.write().unwrap().intern_fields(fields)
+    }
+
+    /// The number of distinct lists of record field names used by the loaded functions
+    pub fn interned_records(&self) -> usize {
+        self.interner.read().unwrap().interned_records()
+    }
+
     /// Returns a borrowed structure which implements `CompilerEnv`
     pub fn get_env<'b>(&'b self) -> RwLockReadGuard<'b, VmEnv> {
         self.env.read().unwrap()
@@ -0,0 +1,212 @@
+//! Maps which hold their keys weakly.
+//!
+//! An entry in a `WeakMap` is removed by the garbage collector once its key is no longer
+//! reachable from anywhere except the map itself, which makes weak maps useful for caches and
+//! metadata which should not keep their keys alive.
+//!
+//! Keys are compared structurally in the same way as `OpaqueValue`, except that closures,
+//! functions, userdata and threads are only equal to themselves. Liveness is tracked for the
+//! key object which was inserted, so looking up an entry with a different but structurally
+//! equal key does not keep the entry alive. Keys which are not allocated (such as `Int`, `Float`
+//! or `Char`) are never collected and entries with such keys stay in the map until it is freed.
+//!
+//! A key inserted from a thread which can't share values with the thread that created the map is
+//! copied, so the entry is only kept alive by the copy and is removed at the next collection.
+use std::any::Any;
+use std::fmt;
+use std::hash::Hasher;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use base::fnv::{FnvHasher, FnvMap};
+use base::types::{ArcType, Type};
+use {ExternModule, Result};
+use gc::{Gc, GcPtr, Move, Traverseable, WeakTable};
+use vm::Thread;
+use thread::ThreadInternal;
+use value::{structural_eq, structural_hash, Cloner, Value};
+use api::{Generic, RuntimeResult, Userdata, VmType, WithVM};
+use api::generic::{A, B};
+
+struct Entry {
+    key: Value,
+    value: Value,
+    /// Set once `value` has been traversed by the collection currently running
+    traversed: bool,
+}
+
+pub struct WeakMap<K, V> {
+    entries: Mutex<FnvMap<u64, Vec<Entry>>>,
+    thread: GcPtr<Thread>,
+    _marker: PhantomData<(K, V)>,
+}
+
+fn hash_key(key: &Value) -> u64 {
+    let mut hasher = FnvHasher::default();
+    structural_hash(key.get_repr(), &mut hasher);
+    hasher.finish()
+}
+
+impl<K, V> Userdata for WeakMap<K, V>
+where
+    K: Any + Send + Sync,
+    V: Any + Send + Sync,
+{
+    fn deep_clone(&self, deep_cloner: &mut Cloner) -> Result<GcPtr<Box<Userdata>>> {
+        let entries = self.entries.lock().unwrap();
+        let mut cloned_entries = FnvMap::default();
+        for entry in entries.values().flat_map(|bucket| bucket) {
+            let key = deep_cloner.deep_clone(&entry.key)?;
+            let value = deep_cloner.deep_clone(&entry.value)?;
+            // Keys which are compared by identity get a new hash when they are cloned
+            cloned_entries
+                .entry(hash_key(&key))
+                .or_insert_with(Vec::new)
+                .push(Entry {
+                    key,
+                    value,
+                    traversed: false,
+                });
+        }
+        let data: Box<Userdata> = Box::new(WeakMap {
+            entries: Mutex::new(cloned_entries),
+            thread: unsafe { GcPtr::from_raw(deep_cloner.thread()) },
+            _marker: PhantomData::<(A, B)>,
+        });
+        deep_cloner.gc().alloc(Move(data))
+    }
+}
+
+impl<K, V> fmt::Debug for WeakMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entries = self.entries.lock().unwrap();
+        f.debug_map()
+            .entries(
+                entries
+                    .values()
+                    .flat_map(|bucket| bucket)
+                    .map(|entry| (&entry.key, &entry.value)),
+            )
+            .finish()
+    }
+}
+
+impl<K, V> Traverseable for WeakMap<K, V>
+where
+    K: 'static,
+    V: 'static,
+{
+    fn traverse(&self, gc: &mut Gc) {
+        unsafe { gc.register_weak_table(self) }
+    }
+}
+
+impl<K, V> WeakTable for WeakMap<K, V> {
+    fn traverse_live_entries(&self, gc: &mut Gc) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let mut traversed = false;
+        for entry in entries.values_mut().flat_map(|bucket| bucket) {
+            if !entry.traversed && entry.key.is_marked(gc) {
+                entry.traversed = true;
+                entry.value.traverse(gc);
+                traversed = true;
+            }
+        }
+        traversed
+    }
+
+    fn clear_dead_entries(&self, gc: &Gc) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, bucket| {
+            bucket.retain(|entry| entry.key.is_marked(gc));
+            for entry in bucket.iter_mut() {
+                entry.traversed = false;
+            }
+            !bucket.is_empty()
+        });
+    }
+}
+
+impl<K, V> VmType for WeakMap<K, V>
+where
+    K: VmType,
+    K::Type: Sized,
+    V: VmType,
+    V::Type: Sized,
+{
+    type Type = WeakMap<K::Type, V::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        let env = vm.global_env().get_env();
+        let symbol = env.find_type_info("WeakMap").unwrap().name.clone();
+        let ctor = Type::ident(symbol);
+        Type::app(ctor, collect![K::make_type(vm), V::make_type(vm)])
+    }
+}
+
+fn new_map(vm: WithVM<()>) -> WeakMap<A, B> {
+    WeakMap {
+        entries: Mutex::new(FnvMap::default()),
+        thread: unsafe { GcPtr::from_raw(vm.vm) },
+        _marker: PhantomData,
+    }
+}
+
+fn insert(map: &WeakMap<A, B>, key: Generic<A>, value: Generic<B>) -> RuntimeResult<(), String> {
+    let cloned = unsafe {
+        map.thread
+            .deep_clone_value(&map.thread, key.get_value())
+            .and_then(|key| {
+                map.thread
+                    .deep_clone_value(&map.thread, value.get_value())
+                    .map(|value| (key, value))
+            })
+    };
+    let (key, value) = match cloned {
+        Ok(x) => x,
+        Err(err) => return RuntimeResult::Panic(format!("{}", err)),
+    };
+    let mut entries = map.entries.lock().unwrap();
+    let bucket = entries.entry(hash_key(&key)).or_insert_with(Vec::new);
+    match bucket
+        .iter_mut()
+        .find(|entry| structural_eq(entry.key.get_repr(), key.get_repr()))
+    {
+        Some(entry) => entry.value = value,
+        None => bucket.push(Entry {
+            key,
+            value,
+            traversed: false,
+        }),
+    }
+    RuntimeResult::Return(())
+}
+
+fn get(map: &WeakMap<A, B>, key: Generic<A>) -> Option<Generic<B>> {
+    let key = unsafe { key.get_value() };
+    let entries = map.entries.lock().unwrap();
+    entries.get(&hash_key(&key)).and_then(|bucket| {
+        bucket
+            .iter()
+            .find(|entry| structural_eq(entry.key.get_repr(), key.get_repr()))
+            .map(|entry| Generic::from(entry.value.clone()))
+    })
+}
+
+mod std {
+    pub use weakmap;
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    let _ = vm.register_type::<WeakMap<A, B>>("WeakMap", &["k", "v"]);
+    ExternModule::new(
+        vm,
+        record!{
+            new => named_primitive!(1, "std.weakmap.new", std::weakmap::new_map),
+            insert => named_primitive!(3, "std.weakmap.insert", std::weakmap::insert),
+            get => named_primitive!(2, "std.weakmap.get", std::weakmap::get),
+        },
+    )
+}